@@ -17,6 +17,9 @@ else
   export SIGNER_CANISTER_WASM_FILE="/target/wasm32-unknown-unknown/release/$SIGNER_CANISTER_WASM"
 fi
 
+echo "Building the stand-in ckBTC minter canister."
+cargo build --locked --target wasm32-unknown-unknown --release -p test_ckbtc_minter
+
 if [ -f "./$BITCON_CANISTER_WASM" ]; then
   echo "Use existing $BITCON_CANISTER_WASM canister."
 else
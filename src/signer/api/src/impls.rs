@@ -2,7 +2,12 @@ use ic_canister_sig_creation::{extract_raw_root_pk_from_der, IC_ROOT_PK_DER};
 use ic_papi_api::PaymentError;
 
 use crate::types::{
-    bitcoin::{GetAddressError, GetBalanceError, SendBtcError},
+    bitcoin::{
+        BtcBumpFeeError, BtcFeeLimits, BtcFeePercentiles, BtcFeePercentilesError, BtcHtlcError,
+        BtcInscriptionError, BtcMultisigAddressError, BtcRebroadcastError, BtcSendRunesError,
+        BtcSignPsbtError, BtcTaprootError, BtcTxOutput, BuildP2wpkhTxError, GetAddressError,
+        GetBalanceError, SendBtcError,
+    },
     Config, InitArg,
 };
 
@@ -11,11 +16,16 @@ impl From<InitArg> for Config {
     ///
     /// # Panics
     /// - If the root key cannot be parsed.
+    /// - If `btc_fee_percentiles` are invalid; see `BtcFeePercentiles::check`.
     fn from(arg: InitArg) -> Self {
         let InitArg {
             ecdsa_key_name,
             ic_root_key_der,
             cycles_ledger,
+            btc_fee_percentiles,
+            btc_fee_limits,
+            max_concurrent_signatures,
+            ckbtc_minter,
         } = arg;
         let ic_root_key_raw = match extract_raw_root_pk_from_der(
             &ic_root_key_der.unwrap_or_else(|| IC_ROOT_PK_DER.to_vec()),
@@ -23,12 +33,82 @@ impl From<InitArg> for Config {
             Ok(root_key) => root_key,
             Err(msg) => panic!("{}", format!("Error parsing root key: {msg}")),
         };
+        if let Some(Err(err)) = btc_fee_percentiles.as_ref().map(BtcFeePercentiles::check) {
+            panic!("Invalid btc_fee_percentiles: {err:?}");
+        }
         let cycles_ledger =
             cycles_ledger.unwrap_or_else(ic_papi_api::cycles::cycles_ledger_canister_id);
         Config {
             ecdsa_key_name,
             ic_root_key_raw: Some(ic_root_key_raw),
             cycles_ledger,
+            btc_fee_percentiles,
+            btc_fee_limits,
+            max_concurrent_signatures,
+            ckbtc_minter,
+        }
+    }
+}
+
+impl Default for BtcFeePercentiles {
+    /// The 25th, 50th and 75th percentiles of recent network fee rates.
+    fn default() -> Self {
+        BtcFeePercentiles {
+            low: 25,
+            medium: 50,
+            high: 75,
+        }
+    }
+}
+
+impl BtcFeePercentiles {
+    /// The highest percentile that the Bitcoin API returns.
+    pub const MAX_PERCENTILE: u8 = 100;
+
+    /// Checks that every percentile is one that the Bitcoin API returns, and that a higher priority
+    /// never maps to a lower percentile.
+    pub fn check(&self) -> Result<(), BtcFeePercentilesError> {
+        let BtcFeePercentiles { low, medium, high } = *self;
+        if let Some(percentile) = [low, medium, high]
+            .into_iter()
+            .find(|percentile| *percentile > Self::MAX_PERCENTILE)
+        {
+            return Err(BtcFeePercentilesError::PercentileTooHigh { percentile });
+        }
+        if low > medium || medium > high {
+            return Err(BtcFeePercentilesError::NotOrdered { low, medium, high });
+        }
+        Ok(())
+    }
+}
+
+impl Default for BtcFeeLimits {
+    /// At most 0.01 BTC, half of the sent value and ten times the `High` network fee rate.
+    fn default() -> Self {
+        BtcFeeLimits {
+            max_fee_satoshis: 1_000_000,
+            max_fee_percent_of_sent: 50,
+            max_fee_rate_multiple: 10,
+        }
+    }
+}
+
+impl BtcTxOutput {
+    /// The value that the output sends: `sent_satoshis` of a payment, zero for data.
+    #[must_use]
+    pub fn sent_satoshis(&self) -> u64 {
+        match self {
+            BtcTxOutput::Payment { sent_satoshis, .. } => *sent_satoshis,
+            BtcTxOutput::Data { .. } => 0,
+        }
+    }
+
+    /// The `OP_RETURN` data of a data output.
+    #[must_use]
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            BtcTxOutput::Payment { .. } => None,
+            BtcTxOutput::Data { data } => Some(data),
         }
     }
 }
@@ -50,3 +130,264 @@ impl From<PaymentError> for SendBtcError {
         SendBtcError::PaymentError(e)
     }
 }
+
+impl From<PaymentError> for BtcBumpFeeError {
+    fn from(e: PaymentError) -> Self {
+        BtcBumpFeeError::PaymentError(e)
+    }
+}
+
+impl From<BuildP2wpkhTxError> for BtcBumpFeeError {
+    fn from(e: BuildP2wpkhTxError) -> Self {
+        BtcBumpFeeError::BuildP2wpkhError(e)
+    }
+}
+
+impl From<SendBtcError> for BtcBumpFeeError {
+    fn from(e: SendBtcError) -> Self {
+        match e {
+            // Fee bumps do not spend from vaults, so the vault policy is never invalid.
+            SendBtcError::InternalError { msg } | SendBtcError::InvalidVaultPolicy { msg } => {
+                BtcBumpFeeError::InternalError { msg }
+            }
+            SendBtcError::PaymentError(e) => BtcBumpFeeError::PaymentError(e),
+            SendBtcError::BuildP2wpkhError(e) => BtcBumpFeeError::BuildP2wpkhError(e),
+            SendBtcError::BroadcastFailed { txid, msg } => {
+                BtcBumpFeeError::BroadcastFailed { txid, msg }
+            }
+            SendBtcError::NoBitcoinCanister { network } => {
+                BtcBumpFeeError::NoBitcoinCanister { network }
+            }
+            SendBtcError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            } => BtcBumpFeeError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            },
+            // Only `btc_caller_sign`, `btc_caller_send` and `btc_deposit_to_ckbtc` return these.
+            e @ (SendBtcError::UnknownUtxo { .. }
+            | SendBtcError::ValueMismatch { .. }
+            | SendBtcError::CkbtcMinterError { .. }) => BtcBumpFeeError::InternalError {
+                msg: format!("{e:?}"),
+            },
+        }
+    }
+}
+
+impl From<PaymentError> for BtcHtlcError {
+    fn from(e: PaymentError) -> Self {
+        BtcHtlcError::PaymentError(e)
+    }
+}
+
+impl From<SendBtcError> for BtcHtlcError {
+    fn from(e: SendBtcError) -> Self {
+        match e {
+            // HTLC spends are not vault spends, so the vault policy is never invalid.
+            SendBtcError::InternalError { msg } | SendBtcError::InvalidVaultPolicy { msg } => {
+                BtcHtlcError::InternalError { msg }
+            }
+            SendBtcError::PaymentError(e) => BtcHtlcError::PaymentError(e),
+            SendBtcError::BuildP2wpkhError(e) => BtcHtlcError::BuildP2wpkhError(e),
+            SendBtcError::NoBitcoinCanister { network } => {
+                BtcHtlcError::NoBitcoinCanister { network }
+            }
+            // Only `btc_caller_sign`, `btc_caller_send` and `btc_deposit_to_ckbtc` return these.
+            e @ (SendBtcError::UnknownUtxo { .. }
+            | SendBtcError::ValueMismatch { .. }
+            | SendBtcError::UtxoReserved { .. }
+            | SendBtcError::BroadcastFailed { .. }
+            | SendBtcError::CkbtcMinterError { .. }) => BtcHtlcError::InternalError {
+                msg: format!("{e:?}"),
+            },
+        }
+    }
+}
+
+impl From<PaymentError> for BtcSendRunesError {
+    fn from(e: PaymentError) -> Self {
+        BtcSendRunesError::PaymentError(e)
+    }
+}
+
+impl From<SendBtcError> for BtcSendRunesError {
+    fn from(e: SendBtcError) -> Self {
+        match e {
+            // Rune sends are not vault spends, so the vault policy is never invalid.
+            SendBtcError::InternalError { msg } | SendBtcError::InvalidVaultPolicy { msg } => {
+                BtcSendRunesError::InternalError { msg }
+            }
+            SendBtcError::PaymentError(e) => BtcSendRunesError::PaymentError(e),
+            SendBtcError::BuildP2wpkhError(e) => BtcSendRunesError::BuildP2wpkhError(e),
+            SendBtcError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            } => BtcSendRunesError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            },
+            SendBtcError::BroadcastFailed { txid, msg } => {
+                BtcSendRunesError::BroadcastFailed { txid, msg }
+            }
+            SendBtcError::NoBitcoinCanister { network } => {
+                BtcSendRunesError::NoBitcoinCanister { network }
+            }
+            // Only `btc_caller_sign`, `btc_caller_send` and `btc_deposit_to_ckbtc` return these.
+            e @ (SendBtcError::UnknownUtxo { .. }
+            | SendBtcError::ValueMismatch { .. }
+            | SendBtcError::CkbtcMinterError { .. }) => BtcSendRunesError::InternalError {
+                msg: format!("{e:?}"),
+            },
+        }
+    }
+}
+
+impl From<PaymentError> for BtcTaprootError {
+    fn from(e: PaymentError) -> Self {
+        BtcTaprootError::PaymentError(e)
+    }
+}
+
+impl From<SendBtcError> for BtcTaprootError {
+    fn from(e: SendBtcError) -> Self {
+        match e {
+            // Taproot spends are not vault spends, so the vault policy is never invalid.
+            SendBtcError::InternalError { msg } | SendBtcError::InvalidVaultPolicy { msg } => {
+                BtcTaprootError::InternalError { msg }
+            }
+            SendBtcError::PaymentError(e) => BtcTaprootError::PaymentError(e),
+            SendBtcError::BuildP2wpkhError(e) => BtcTaprootError::BuildP2wpkhError(e),
+            // Only `btc_caller_sign`, `btc_caller_send` and `btc_deposit_to_ckbtc` return these.
+            e @ (SendBtcError::UnknownUtxo { .. }
+            | SendBtcError::ValueMismatch { .. }
+            | SendBtcError::UtxoReserved { .. }
+            | SendBtcError::BroadcastFailed { .. }
+            | SendBtcError::NoBitcoinCanister { .. }
+            | SendBtcError::CkbtcMinterError { .. }) => BtcTaprootError::InternalError {
+                msg: format!("{e:?}"),
+            },
+        }
+    }
+}
+
+impl From<PaymentError> for BtcMultisigAddressError {
+    fn from(e: PaymentError) -> Self {
+        BtcMultisigAddressError::PaymentError(e)
+    }
+}
+
+impl From<PaymentError> for BtcSignPsbtError {
+    fn from(e: PaymentError) -> Self {
+        BtcSignPsbtError::PaymentError(e)
+    }
+}
+
+impl From<PaymentError> for BtcInscriptionError {
+    fn from(e: PaymentError) -> Self {
+        BtcInscriptionError::PaymentError(e)
+    }
+}
+
+impl From<SendBtcError> for BtcInscriptionError {
+    fn from(e: SendBtcError) -> Self {
+        match e {
+            // Inscriptions are not vault spends, so the vault policy is never invalid.
+            SendBtcError::InternalError { msg } | SendBtcError::InvalidVaultPolicy { msg } => {
+                BtcInscriptionError::InternalError { msg }
+            }
+            SendBtcError::PaymentError(e) => BtcInscriptionError::PaymentError(e),
+            SendBtcError::BuildP2wpkhError(e) => BtcInscriptionError::BuildP2wpkhError(e),
+            SendBtcError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            } => BtcInscriptionError::UtxoReserved {
+                txid,
+                vout,
+                spending_txid,
+                expires_at_ns,
+            },
+            // Only `btc_caller_sign`, `btc_caller_send` and `btc_deposit_to_ckbtc` return these.
+            e @ (SendBtcError::UnknownUtxo { .. }
+            | SendBtcError::ValueMismatch { .. }
+            | SendBtcError::BroadcastFailed { .. }
+            | SendBtcError::NoBitcoinCanister { .. }
+            | SendBtcError::CkbtcMinterError { .. }) => BtcInscriptionError::InternalError {
+                msg: format!("{e:?}"),
+            },
+        }
+    }
+}
+
+impl From<PaymentError> for BtcRebroadcastError {
+    fn from(e: PaymentError) -> Self {
+        BtcRebroadcastError::PaymentError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::bitcoin::{
+        BtcBumpFeeError, BtcFeePercentiles, BtcFeePercentilesError, SendBtcError,
+    };
+
+    fn percentiles(low: u8, medium: u8, high: u8) -> BtcFeePercentiles {
+        BtcFeePercentiles { low, medium, high }
+    }
+
+    #[test]
+    fn fee_percentiles_accept_ordered_percentiles_up_to_100() {
+        assert_eq!(BtcFeePercentiles::default().check(), Ok(()));
+        assert_eq!(percentiles(0, 50, 100).check(), Ok(()));
+        assert_eq!(percentiles(50, 50, 50).check(), Ok(()));
+    }
+
+    #[test]
+    fn fee_percentiles_reject_percentiles_above_100() {
+        assert_eq!(
+            percentiles(25, 50, 101).check(),
+            Err(BtcFeePercentilesError::PercentileTooHigh { percentile: 101 })
+        );
+    }
+
+    #[test]
+    fn fee_percentiles_reject_unordered_percentiles() {
+        assert_eq!(
+            percentiles(75, 50, 90).check(),
+            Err(BtcFeePercentilesError::NotOrdered {
+                low: 75,
+                medium: 50,
+                high: 90
+            })
+        );
+    }
+
+    #[test]
+    fn bump_fee_error_keeps_the_reserved_utxo() {
+        let error = BtcBumpFeeError::from(SendBtcError::UtxoReserved {
+            txid: "ab".repeat(32),
+            vout: 1,
+            spending_txid: None,
+            expires_at_ns: 42,
+        });
+        assert!(matches!(
+            error,
+            BtcBumpFeeError::UtxoReserved {
+                vout: 1,
+                spending_txid: None,
+                expires_at_ns: 42,
+                ..
+            }
+        ));
+    }
+}
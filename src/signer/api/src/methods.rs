@@ -6,13 +6,36 @@ pub enum SignerMethods {
     EthSignTransaction,
     EthPersonalSign,
     EthSignPrehash,
+    BtcAddress,
     BtcCallerAddress,
+    BtcDescriptor,
     BtcCallerBalance,
     BtcCallerSend,
     BtcCallerSign,
+    BtcBumpFee,
     BtcSignPrehash,
+    BtcMultisigAddress,
+    BtcSignPsbt,
+    BtcVaultSign,
+    BtcHtlcAddress,
+    BtcHtlcClaim,
+    BtcHtlcRefund,
+    BtcTaprootAddress,
+    BtcTaprootSign,
+    BtcSendRunes,
+    BtcInscriptionCommit,
+    BtcInscriptionReveal,
+    BtcRebroadcast,
+    BtcConsolidate,
+    BtcDepositToCkbtc,
+    DogeCallerAddress,
+    DogeCallerSign,
+    LtcCallerAddress,
+    LtcCallerSign,
     SchnorrPublicKey,
     SchnorrSign,
+    SolAddress,
+    SolSignTransaction,
 }
 
 impl SignerMethods {
@@ -35,7 +58,9 @@ impl SignerMethods {
     pub fn fee(&self) -> u128 {
         // Note: Fees are determined with the aid of scripts/check-pricing
         match self {
-            SignerMethods::BtcCallerAddress => 79_000_000,
+            SignerMethods::BtcAddress | SignerMethods::BtcCallerAddress => 79_000_000,
+            // Like `BtcCallerAddress`: one `ecdsa_public_key` call.
+            SignerMethods::BtcDescriptor => 79_000_000,
             SignerMethods::BtcCallerBalance => 113_000_000,
             // Grace-period default sized for a 2-input, 2-output transaction:
             // btc_base_fee() + 2 * btc_per_input_fee() + 2 * btc_per_output_fee()
@@ -44,8 +69,52 @@ impl SignerMethods {
             // Grace-period default sized for a 2-input transaction:
             // btc_base_fee() + 2 * btc_per_input_fee() = 74 B + 2 * 37 B = 148 B
             SignerMethods::BtcCallerSign => 148_000_000_000,
+            // Signs and broadcasts like `BtcCallerSend`; same grace-period default.
+            SignerMethods::BtcBumpFee => 171_000_000_000,
             // Flat: one `sign_with_ecdsa` over a single prehash, no transaction building.
             SignerMethods::BtcSignPrehash => 37_000_000_000,
+            // Like `BtcCallerAddress`: one `ecdsa_public_key` call.
+            SignerMethods::BtcMultisigAddress => 79_000_000,
+            // Grace-period default sized for 2 inputs to sign, like `BtcCallerSign`.
+            SignerMethods::BtcSignPsbt => 148_000_000_000,
+            // Grace-period default sized for a 2-input spend, like `BtcCallerSign`.
+            SignerMethods::BtcVaultSign => 148_000_000_000,
+            // Like `BtcCallerAddress`: one `ecdsa_public_key` call.
+            SignerMethods::BtcHtlcAddress => 79_000_000,
+            // Grace-period default sized for a 2-input spend, like `BtcCallerSign`.
+            SignerMethods::BtcHtlcClaim => 148_000_000_000,
+            // Grace-period default sized for a 2-input spend:
+            // btc_base_fee() + 2 * btc_per_input_fee() = 84 B + 2 * 37 B = 158 B
+            SignerMethods::BtcHtlcRefund => 158_000_000_000,
+            // Like `BtcCallerAddress`, with one `schnorr_public_key` call instead.
+            SignerMethods::BtcTaprootAddress => 79_000_000,
+            // Grace-period default sized for a 2-input spend, like `BtcCallerSign`.
+            SignerMethods::BtcTaprootSign => 148_000_000_000,
+            // Grace-period default sized for 2 inputs and 4 outputs: a transfer, the rune
+            // change, the runestone and the change.  The runestone data is not included.
+            // btc_base_fee() + 2 * btc_per_input_fee() + 4 * btc_per_output_fee()
+            //   = 95 B + 2 * 37 B + 4 * 1 B = 173 B
+            SignerMethods::BtcSendRunes => 173_000_000_000,
+            // Grace-period default sized for a 2-input commit, like `BtcCallerSign`.
+            SignerMethods::BtcInscriptionCommit => 148_000_000_000,
+            // Flat: the reveal spends the single commit output.
+            // btc_base_fee() + btc_per_input_fee() of `BtcTaprootSign` = 74 B + 37 B = 111 B
+            SignerMethods::BtcInscriptionReveal => 111_000_000_000,
+            // Grace-period default sized for a 250-byte transaction:
+            // btc_base_fee() + 250 * btc_per_data_byte_fee() = 10 B + 250 * 25 M = 16.25 B
+            SignerMethods::BtcRebroadcast => 16_250_000_000,
+            // Grace-period default sized for merging 2 supplied inputs into 1 output:
+            // btc_base_fee() + 2 * btc_per_input_fee() + btc_per_output_fee()
+            //   = 95 B + 2 * 37 B + 1 B = 170 B
+            SignerMethods::BtcConsolidate => 170_000_000_000,
+            // Grace-period default sized for a 2-input deposit with a change output:
+            // btc_base_fee() + 2 * btc_per_input_fee() + 2 * btc_per_output_fee()
+            //   = 100 B + 2 * 37 B + 2 * 1 B = 176 B
+            SignerMethods::BtcDepositToCkbtc => 176_000_000_000,
+            // Like `BtcCallerAddress`, with the Dogecoin or Litecoin key.
+            SignerMethods::DogeCallerAddress | SignerMethods::LtcCallerAddress => 79_000_000,
+            // Grace-period default sized for a 2-input transaction, like `BtcCallerSign`.
+            SignerMethods::DogeCallerSign | SignerMethods::LtcCallerSign => 148_000_000_000,
             SignerMethods::EthAddress | SignerMethods::EthAddressOfCaller => 77_000_000,
             SignerMethods::EthPersonalSign => 37_000_000_000,
             SignerMethods::EthSignPrehash => 37_000_000_000,
@@ -54,32 +123,71 @@ impl SignerMethods {
             SignerMethods::GenericSignWithEcdsa => 37_000_000_000,
             SignerMethods::SchnorrPublicKey => 77_000_000,
             SignerMethods::SchnorrSign => 37_000_000_000,
+            // Like `SchnorrPublicKey`: one `schnorr_public_key` call.
+            SignerMethods::SolAddress => 77_000_000,
+            // Like `SchnorrSign`: one `sign_with_schnorr` call, plus the `schnorr_public_key` call
+            // that finds the caller among the signers, which costs execution cycles only.
+            SignerMethods::SolSignTransaction => 37_077_000_000,
         }
     }
 
     /// The per-call base fee, in cycles, for BTC sign/send methods.
     ///
-    /// Returns the fixed per-call overhead for `BtcCallerSign` (74 B) and
-    /// `BtcCallerSend` (95 B). For all other methods the base fee equals
+    /// Returns the fixed per-call overhead for `BtcCallerSign`, `BtcSignPsbt`, `BtcVaultSign`,
+    /// `BtcHtlcClaim`, `BtcTaprootSign`, `BtcInscriptionCommit`, `DogeCallerSign` and
+    /// `LtcCallerSign` (74 B), `BtcHtlcRefund` (84 B,
+    /// which adds the `bitcoin_get_utxos` call that reads the chain tip, at most 10 B) and
+    /// `BtcCallerSend`, `BtcBumpFee`, `BtcSendRunes` and `BtcConsolidate` (95 B),
+    /// `BtcDepositToCkbtc` (100 B, which adds the calls to the ckBTC minter, at most 5 B) and
+    /// `BtcRebroadcast` (10 B, which only broadcasts). For all other methods the base fee equals
     /// [`Self::fee`].
     #[must_use]
     pub fn btc_base_fee(&self) -> u128 {
         match self {
-            SignerMethods::BtcCallerSign => 74_000_000_000,
-            SignerMethods::BtcCallerSend => 95_000_000_000,
+            SignerMethods::BtcCallerSign
+            | SignerMethods::BtcSignPsbt
+            | SignerMethods::BtcVaultSign
+            | SignerMethods::BtcHtlcClaim
+            | SignerMethods::BtcTaprootSign
+            | SignerMethods::BtcInscriptionCommit
+            | SignerMethods::DogeCallerSign
+            | SignerMethods::LtcCallerSign => 74_000_000_000,
+            SignerMethods::BtcHtlcRefund => 84_000_000_000,
+            SignerMethods::BtcCallerSend
+            | SignerMethods::BtcBumpFee
+            | SignerMethods::BtcSendRunes
+            | SignerMethods::BtcConsolidate => 95_000_000_000,
+            SignerMethods::BtcDepositToCkbtc => 100_000_000_000,
+            SignerMethods::BtcRebroadcast => 10_000_000_000,
             _ => self.fee(),
         }
     }
 
     /// The cost, in cycles, charged per BTC transaction input.
     ///
-    /// `BtcCallerSign` and `BtcCallerSend` each run one `sign_with_ecdsa` call per
-    /// input in `utxos_to_spend`; the value here covers that signature plus margin.
-    /// All other methods return `0`.
+    /// `BtcCallerSign`, `BtcCallerSend`, `BtcBumpFee`, `BtcSignPsbt`, `BtcVaultSign`,
+    /// `BtcHtlcClaim`, `BtcHtlcRefund`, `BtcSendRunes`, `BtcInscriptionCommit`,
+    /// `BtcConsolidate`, `BtcDepositToCkbtc`, `DogeCallerSign` and `LtcCallerSign` each run one
+    /// `sign_with_ecdsa` call per transaction input, and `BtcTaprootSign` one `sign_with_schnorr`
+    /// call, which costs the same; the value here covers that signature plus margin. All other
+    /// methods return `0`.
     #[must_use]
     pub fn btc_per_input_fee(&self) -> u128 {
         match self {
-            SignerMethods::BtcCallerSign | SignerMethods::BtcCallerSend => 37_000_000_000,
+            SignerMethods::BtcCallerSign
+            | SignerMethods::BtcCallerSend
+            | SignerMethods::BtcBumpFee
+            | SignerMethods::BtcSignPsbt
+            | SignerMethods::BtcVaultSign
+            | SignerMethods::BtcHtlcClaim
+            | SignerMethods::BtcHtlcRefund
+            | SignerMethods::BtcTaprootSign
+            | SignerMethods::BtcSendRunes
+            | SignerMethods::BtcInscriptionCommit
+            | SignerMethods::BtcConsolidate
+            | SignerMethods::BtcDepositToCkbtc
+            | SignerMethods::DogeCallerSign
+            | SignerMethods::LtcCallerSign => 37_000_000_000,
             _ => 0,
         }
     }
@@ -97,17 +205,57 @@ impl SignerMethods {
         self.btc_base_fee() + u128::from(n_inputs) * self.btc_per_input_fee()
     }
 
+    /// The cost, in cycles, of checking the inputs against the UTXO set with one
+    /// `bitcoin_get_utxos` call, which costs at most 10 B.
+    ///
+    /// `BtcCallerSign` and `BtcCallerSend` charge it on top of their fee when the request sets
+    /// `verify_utxos`, and `BtcConsolidate` when it looks up the UTXOs to merge. All other methods
+    /// return `0`.
+    #[must_use]
+    pub fn btc_utxo_verification_fee(&self) -> u128 {
+        match self {
+            SignerMethods::BtcCallerSign
+            | SignerMethods::BtcCallerSend
+            | SignerMethods::BtcConsolidate => 10_000_000_000,
+            _ => 0,
+        }
+    }
+
     /// The cost, in cycles, charged per BTC transaction *output*.
     ///
     /// `BtcCallerSend` broadcasts the transaction via `bitcoin_send_transaction`, whose
     /// cost is `5e9 + 20e6 * transaction_bytes` (mainnet). Each output adds a fixed
     /// ~31–43 bytes to the serialized transaction (no witness data), i.e. up to ~860M
-    /// cycles; this rounds up to 1e9 to leave margin. `BtcCallerSign` never broadcasts,
-    /// so it pays nothing per output.
+    /// cycles; this rounds up to 1e9 to leave margin. `BtcBumpFee`, `BtcSendRunes`,
+    /// `BtcConsolidate` and `BtcDepositToCkbtc` broadcast too.
+    /// `BtcCallerSign` never broadcasts, so it pays nothing per output.
     #[must_use]
     pub fn btc_per_output_fee(&self) -> u128 {
         match self {
-            SignerMethods::BtcCallerSend => 1_000_000_000,
+            SignerMethods::BtcCallerSend
+            | SignerMethods::BtcBumpFee
+            | SignerMethods::BtcSendRunes
+            | SignerMethods::BtcConsolidate
+            | SignerMethods::BtcDepositToCkbtc => 1_000_000_000,
+            _ => 0,
+        }
+    }
+
+    /// The cost, in cycles, charged per byte of `OP_RETURN` data.
+    ///
+    /// A data output adds its payload to the serialized transaction on top of the fixed
+    /// bytes already covered by [`Self::btc_per_output_fee`].  At `20e6` cycles per
+    /// broadcast byte (mainnet), this rounds up to 25e6 to leave margin.  Only methods that
+    /// broadcast, `BtcCallerSend`, `BtcBumpFee` and `BtcSendRunes` (for its runestone), pay for
+    /// data; `BtcRebroadcast` pays this rate for every byte of the transaction it broadcasts.
+    /// All other methods return `0`.
+    #[must_use]
+    pub fn btc_per_data_byte_fee(&self) -> u128 {
+        match self {
+            SignerMethods::BtcCallerSend
+            | SignerMethods::BtcBumpFee
+            | SignerMethods::BtcSendRunes
+            | SignerMethods::BtcRebroadcast => 25_000_000,
             _ => 0,
         }
     }
@@ -124,11 +272,30 @@ impl SignerMethods {
     pub fn btc_fee_for_tx(&self, n_inputs: u64, n_outputs: u64) -> u128 {
         self.btc_fee_for_inputs(n_inputs) + u128::from(n_outputs) * self.btc_per_output_fee()
     }
+
+    /// Like [`Self::btc_fee_for_tx`], for a transaction whose `OP_RETURN` outputs, counted in
+    /// `n_outputs`, carry `n_data_bytes` of data.
+    #[must_use]
+    pub fn btc_fee_for_tx_with_data(
+        &self,
+        n_inputs: u64,
+        n_outputs: u64,
+        n_data_bytes: u64,
+    ) -> u128 {
+        self.btc_fee_for_tx(n_inputs, n_outputs)
+            + u128::from(n_data_bytes) * self.btc_per_data_byte_fee()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SignerMethods::{BtcCallerSend, BtcCallerSign};
+    use super::SignerMethods::{
+        BtcAddress, BtcBumpFee, BtcCallerAddress, BtcCallerSend, BtcCallerSign, BtcConsolidate,
+        BtcDepositToCkbtc, BtcDescriptor, BtcHtlcClaim, BtcHtlcRefund, BtcInscriptionCommit,
+        BtcInscriptionReveal, BtcRebroadcast, BtcSendRunes, BtcSignPsbt, BtcTaprootSign,
+        BtcVaultSign, DogeCallerAddress, DogeCallerSign, LtcCallerAddress, LtcCallerSign,
+        SchnorrPublicKey, SchnorrSign, SolAddress, SolSignTransaction,
+    };
 
     const B: u128 = 1_000_000_000;
 
@@ -158,5 +325,200 @@ mod tests {
             BtcCallerSign.btc_fee_for_tx(2, 10_000),
             BtcCallerSign.btc_fee_for_inputs(2),
         );
+        // Nor does it pay for data.
+        assert_eq!(BtcCallerSign.btc_per_data_byte_fee(), 0);
+        assert_eq!(
+            BtcCallerSign.btc_fee_for_tx_with_data(2, 10_000, 80),
+            BtcCallerSign.btc_fee_for_inputs(2),
+        );
+    }
+
+    #[test]
+    fn bump_fee_is_priced_like_send() {
+        for (n_in, n_out) in [(1, 1), (2, 2), (10, 3)] {
+            assert_eq!(
+                BtcBumpFee.btc_fee_for_tx_with_data(n_in, n_out, 80),
+                BtcCallerSend.btc_fee_for_tx_with_data(n_in, n_out, 80),
+            );
+        }
+    }
+
+    #[test]
+    fn consolidate_is_priced_like_send() {
+        assert_eq!(BtcConsolidate.btc_fee_for_tx(2, 1), BtcConsolidate.fee());
+        for n_in in [1, 2, 100] {
+            assert_eq!(
+                BtcConsolidate.btc_fee_for_tx(n_in, 1),
+                BtcCallerSend.btc_fee_for_tx(n_in, 1),
+            );
+        }
+        assert_eq!(
+            BtcConsolidate.btc_utxo_verification_fee(),
+            BtcCallerSend.btc_utxo_verification_fee()
+        );
+    }
+
+    #[test]
+    fn send_prices_op_return_data() {
+        // A full 80-byte data output costs more than the flat per-output fee, per byte of data.
+        assert_eq!(
+            BtcCallerSend.btc_fee_for_tx_with_data(1, 3, 80),
+            BtcCallerSend.btc_fee_for_tx(1, 3) + 80 * 25_000_000,
+        );
+        assert!(80 * BtcCallerSend.btc_per_data_byte_fee() > BtcCallerSend.btc_per_output_fee());
+    }
+
+    #[test]
+    fn sign_psbt_is_priced_like_sign() {
+        assert_eq!(BtcSignPsbt.fee(), BtcCallerSign.fee());
+        for n_in in [1, 2, 10] {
+            assert_eq!(
+                BtcSignPsbt.btc_fee_for_inputs(n_in),
+                BtcCallerSign.btc_fee_for_inputs(n_in),
+            );
+        }
+    }
+
+    #[test]
+    fn vault_sign_is_priced_like_sign() {
+        // One `sign_with_ecdsa` per input and no broadcast, like BtcCallerSign.
+        assert_eq!(BtcVaultSign.fee(), BtcCallerSign.fee());
+        assert_eq!(
+            BtcVaultSign.btc_fee_for_tx_with_data(3, 10, 80),
+            BtcCallerSign.btc_fee_for_inputs(3),
+        );
+    }
+
+    #[test]
+    fn htlc_refund_pays_for_the_tip_height() {
+        // A claim signs like BtcCallerSign; a refund also reads the chain tip first.
+        assert_eq!(BtcHtlcClaim.fee(), BtcCallerSign.fee());
+        assert_eq!(BtcHtlcRefund.btc_fee_for_inputs(2), BtcHtlcRefund.fee());
+        for n_in in [1, 2, 10] {
+            assert_eq!(
+                BtcHtlcClaim.btc_fee_for_inputs(n_in),
+                BtcCallerSign.btc_fee_for_inputs(n_in),
+            );
+            assert_eq!(
+                BtcHtlcRefund.btc_fee_for_inputs(n_in),
+                BtcHtlcClaim.btc_fee_for_inputs(n_in) + 10 * B,
+            );
+        }
+    }
+
+    #[test]
+    fn utxo_verification_is_priced_like_a_tip_height_lookup() {
+        // Both read the first page of `bitcoin_get_utxos`.
+        for method in [BtcCallerSign, BtcCallerSend] {
+            assert_eq!(
+                method.btc_utxo_verification_fee(),
+                BtcHtlcRefund.btc_base_fee() - BtcHtlcClaim.btc_base_fee(),
+            );
+        }
+        assert_eq!(BtcSignPsbt.btc_utxo_verification_fee(), 0);
+    }
+
+    #[test]
+    fn taproot_sign_is_priced_like_sign() {
+        // One `sign_with_schnorr` per input costs as much as one `sign_with_ecdsa`.
+        assert_eq!(BtcTaprootSign.fee(), BtcCallerSign.fee());
+        assert_eq!(
+            BtcTaprootSign.btc_fee_for_tx_with_data(3, 10, 80),
+            BtcCallerSign.btc_fee_for_inputs(3),
+        );
+    }
+
+    #[test]
+    fn send_runes_is_priced_like_send() {
+        assert_eq!(BtcSendRunes.btc_fee_for_tx(2, 4), BtcSendRunes.fee());
+        for (n_in, n_out) in [(1, 3), (2, 4), (10, 12)] {
+            assert_eq!(
+                BtcSendRunes.btc_fee_for_tx_with_data(n_in, n_out, 20),
+                BtcCallerSend.btc_fee_for_tx_with_data(n_in, n_out, 20),
+            );
+        }
+    }
+
+    #[test]
+    fn inscription_commit_is_priced_like_sign_and_reveal_like_one_taproot_input() {
+        assert_eq!(
+            BtcInscriptionCommit.btc_fee_for_inputs(2),
+            BtcInscriptionCommit.fee()
+        );
+        for n in [1, 2, 10] {
+            assert_eq!(
+                BtcInscriptionCommit.btc_fee_for_inputs(n),
+                BtcCallerSign.btc_fee_for_inputs(n),
+            );
+        }
+        assert_eq!(
+            BtcInscriptionReveal.fee(),
+            BtcTaprootSign.btc_fee_for_inputs(1)
+        );
+    }
+
+    #[test]
+    fn rebroadcast_pays_only_for_the_broadcast() {
+        // No signatures: the fee is the base plus every byte of the stored transaction.
+        assert_eq!(BtcRebroadcast.btc_per_input_fee(), 0);
+        assert_eq!(
+            BtcRebroadcast.btc_fee_for_tx_with_data(0, 0, 250),
+            BtcRebroadcast.fee()
+        );
+        assert_eq!(
+            BtcRebroadcast.btc_fee_for_tx_with_data(0, 0, 1_000),
+            10 * B + 1_000 * BtcCallerSend.btc_per_data_byte_fee(),
+        );
+    }
+
+    #[test]
+    fn btc_address_is_priced_like_btc_caller_address() {
+        assert_eq!(BtcAddress.fee(), BtcCallerAddress.fee());
+    }
+
+    #[test]
+    fn btc_descriptor_is_priced_like_btc_caller_address() {
+        assert_eq!(BtcDescriptor.fee(), BtcCallerAddress.fee());
+    }
+
+    #[test]
+    fn deposit_to_ckbtc_is_priced_like_send_plus_minter_calls() {
+        for (n_in, n_out) in [(1, 1), (2, 2), (10, 2)] {
+            assert_eq!(
+                BtcDepositToCkbtc.btc_fee_for_tx(n_in, n_out),
+                BtcCallerSend.btc_fee_for_tx(n_in, n_out) + 5 * B
+            );
+        }
+        assert_eq!(
+            BtcDepositToCkbtc.fee(),
+            BtcDepositToCkbtc.btc_fee_for_tx(2, 2)
+        );
+    }
+
+    #[test]
+    fn doge_and_ltc_are_priced_like_btc() {
+        for (address, sign) in [
+            (DogeCallerAddress, DogeCallerSign),
+            (LtcCallerAddress, LtcCallerSign),
+        ] {
+            assert_eq!(address.fee(), BtcCallerAddress.fee());
+            assert_eq!(sign.fee(), BtcCallerSign.fee());
+            for n in [1, 2, 10] {
+                assert_eq!(
+                    sign.btc_fee_for_inputs(n),
+                    BtcCallerSign.btc_fee_for_inputs(n)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sol_is_priced_like_schnorr() {
+        assert_eq!(SolAddress.fee(), SchnorrPublicKey.fee());
+        assert_eq!(
+            SolSignTransaction.fee(),
+            SchnorrSign.fee() + SchnorrPublicKey.fee()
+        );
+        assert_eq!(SolSignTransaction.btc_per_input_fee(), 0);
     }
 }
@@ -5,6 +5,8 @@ use candid::{CandidType, Deserialize, Principal};
 pub mod eth;
 pub mod generic;
 pub mod schnorr;
+pub mod sol;
+pub mod utxo;
 
 pub type Timestamp = u64;
 
@@ -15,6 +17,17 @@ pub struct InitArg {
     pub ic_root_key_der: Option<Vec<u8>>,
     /// Payment canister ID.
     pub cycles_ledger: Option<Principal>,
+    /// Network fee percentiles used for the Bitcoin fee priority levels.  Default: 25, 50, 75.
+    /// The install or upgrade fails if they are not ordered or above 100.
+    pub btc_fee_percentiles: Option<bitcoin::BtcFeePercentiles>,
+    /// Limits on the fees of Bitcoin transactions.  Default: see `BtcFeeLimits::default()`.
+    pub btc_fee_limits: Option<bitcoin::BtcFeeLimits>,
+    /// The number of threshold signatures requested at once when signing the inputs of a
+    /// transaction.  Default: 10.
+    pub max_concurrent_signatures: Option<u32>,
+    /// The ckBTC minter that `btc_deposit_to_ckbtc` deposits to.  Default: the mainnet ckBTC
+    /// minter, `mqygn-kiaaa-aaaar-qaadq-cai`.
+    pub ckbtc_minter: Option<Principal>,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -30,6 +43,16 @@ pub struct Config {
     pub ic_root_key_raw: Option<Vec<u8>>,
     /// Payment canister ID.
     pub cycles_ledger: Principal,
+    /// Network fee percentiles used for the Bitcoin fee priority levels.  Default: 25, 50, 75.
+    pub btc_fee_percentiles: Option<bitcoin::BtcFeePercentiles>,
+    /// Limits on the fees of Bitcoin transactions.  Default: see `BtcFeeLimits::default()`.
+    pub btc_fee_limits: Option<bitcoin::BtcFeeLimits>,
+    /// The number of threshold signatures requested at once when signing the inputs of a
+    /// transaction.  Default: 10.
+    pub max_concurrent_signatures: Option<u32>,
+    /// The ckBTC minter that `btc_deposit_to_ckbtc` deposits to.  Default: the mainnet ckBTC
+    /// minter, `mqygn-kiaaa-aaaar-qaadq-cai`.
+    pub ckbtc_minter: Option<Principal>,
 }
 
 pub mod transaction {
@@ -49,13 +72,56 @@ pub mod transaction {
 }
 
 pub mod bitcoin {
-    use candid::{CandidType, Deserialize};
-    use ic_cdk_bitcoin_canister::{Network as BitcoinNetwork, Utxo};
+    use candid::{CandidType, Deserialize, Principal};
+    use ic_cdk_bitcoin_canister::Utxo;
     use ic_papi_api::PaymentError;
 
+    /// A Bitcoin network.
+    ///
+    /// The networks of the Bitcoin canister, plus `Signet`.  Transactions can be built and signed
+    /// for every network, but only the networks with a Bitcoin canister can look up balances,
+    /// UTXOs and fee rates, or broadcast.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+    pub enum BitcoinNetwork {
+        /// Bitcoin Mainnet.
+        #[serde(rename = "mainnet")]
+        Mainnet,
+        /// Bitcoin Testnet4, under the name of the Bitcoin canister's test network.
+        #[serde(rename = "testnet")]
+        Testnet,
+        /// Bitcoin Testnet4.  Served by the Bitcoin canister's test network.
+        #[serde(rename = "testnet4")]
+        Testnet4,
+        /// Bitcoin Signet.  There is no Bitcoin canister for Signet.
+        #[serde(rename = "signet")]
+        Signet,
+        /// Bitcoin Regtest.
+        #[serde(rename = "regtest")]
+        Regtest,
+    }
+
     #[derive(CandidType, Deserialize, Debug)]
     pub enum BitcoinAddressType {
         P2WPKH,
+        /// A P2WSH vault of the caller's key, see [`BtcVaultPolicy`].
+        ///
+        /// Only the address endpoints support vaults; spend from them with `btc_vault_sign`.
+        P2WSHVault(BtcVaultPolicy),
+    }
+
+    /// A vault that the caller's key spends together with a cosigner's key, or that a recovery
+    /// key spends alone once a vault output is `recovery_delay_blocks` deep.
+    ///
+    /// The witness script implements the policy
+    /// `or(and(pk(signer),pk(cosigner)),and(pk(recovery),older(recovery_delay_blocks)))`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcVaultPolicy {
+        /// Hex-encoded compressed public key that spends together with the caller's key.
+        pub cosigner_public_key: String,
+        /// Hex-encoded compressed public key that spends alone after the delay.
+        pub recovery_public_key: String,
+        /// The relative timelock of the recovery path, in blocks.  Must not be zero.
+        pub recovery_delay_blocks: u16,
     }
 
     #[derive(CandidType, Deserialize, Debug)]
@@ -64,15 +130,37 @@ pub mod bitcoin {
         pub address_type: BitcoinAddressType,
     }
 
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcAddressRequest {
+        /// The principal owning the Bitcoin address.  Default: The caller.
+        pub principal: Option<Principal>,
+        pub network: BitcoinNetwork,
+        pub address_type: BitcoinAddressType,
+    }
+
     #[derive(CandidType, Deserialize, Debug)]
     pub struct GetAddressResponse {
         pub address: String,
     }
 
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcDescriptorResponse {
+        /// The output descriptor, with its BIP-380 checksum.
+        pub descriptor: String,
+        /// The address of the descriptor on the requested network.
+        pub address: String,
+    }
+
     #[derive(CandidType, Deserialize, Debug)]
     pub enum GetAddressError {
-        InternalError { msg: String },
+        InternalError {
+            msg: String,
+        },
         PaymentError(PaymentError),
+        /// The keys or the delay of a `P2WSHVault` are invalid.
+        InvalidVaultPolicy {
+            msg: String,
+        },
     }
 
     #[derive(CandidType, Deserialize, Debug)]
@@ -89,14 +177,112 @@ pub mod bitcoin {
 
     #[derive(CandidType, Deserialize, Debug)]
     pub enum GetBalanceError {
-        InternalError { msg: String },
+        InternalError {
+            msg: String,
+        },
         PaymentError(PaymentError),
+        /// The keys or the delay of a `P2WSHVault` are invalid.
+        InvalidVaultPolicy {
+            msg: String,
+        },
     }
 
-    #[derive(CandidType, Deserialize, Debug)]
-    pub struct BtcTxOutput {
-        pub destination_address: String,
-        pub sent_satoshis: u64,
+    /// An output of a transaction.  Outputs appear in the transaction in the order requested,
+    /// followed by the change.
+    #[derive(CandidType, Deserialize, Debug, Clone)]
+    pub enum BtcTxOutput {
+        /// Sends `sent_satoshis` to `destination_address`.
+        Payment {
+            destination_address: String,
+            sent_satoshis: u64,
+        },
+        /// Embeds `data`, at most 80 bytes, in an `OP_RETURN` output that carries no value.
+        Data { data: Vec<u8> },
+    }
+
+    /// How urgently a transaction should be confirmed.
+    ///
+    /// Each level maps to a percentile of the current network fee rates, as set in
+    /// `Config::btc_fee_percentiles`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcFeePriority {
+        Low,
+        Medium,
+        High,
+    }
+
+    /// The fee rate at which a transaction is built.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcFeeRate {
+        /// An explicit fee rate, in satoshi per virtual byte.
+        SatPerVbyte(u64),
+        /// The fee rate paid by recent transactions at the given priority level.
+        Priority(BtcFeePriority),
+    }
+
+    /// The parts of a transaction that the signature of an input commits to.
+    ///
+    /// See [BIP-143](https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki) for the
+    /// exact semantics.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcSighashType {
+        /// All inputs and outputs.
+        All,
+        /// All inputs and no outputs.  Rejected: anyone could redirect the funds.
+        None,
+        /// All inputs and the output with the same index as the input.
+        Single,
+        /// This input and all outputs.
+        AllPlusAnyoneCanPay,
+        /// This input and no outputs.  Rejected: anyone could redirect the funds.
+        NonePlusAnyoneCanPay,
+        /// This input and the output with the same index.
+        SinglePlusAnyoneCanPay,
+    }
+
+    /// The network fee percentiles (0-100) that the fee priority levels map to, with
+    /// `low <= medium <= high`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct BtcFeePercentiles {
+        pub low: u8,
+        pub medium: u8,
+        pub high: u8,
+    }
+
+    /// Why a `BtcFeePercentiles` is rejected.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcFeePercentilesError {
+        /// The Bitcoin API returns the percentiles 0 to 100.
+        PercentileTooHigh { percentile: u8 },
+        /// A higher priority must not map to a lower percentile.
+        NotOrdered { low: u8, medium: u8, high: u8 },
+    }
+
+    /// Limits that protect callers against paying unreasonable Bitcoin fees.
+    ///
+    /// A transaction that exceeds any of these limits is rejected, unless the request sets
+    /// `allow_high_fee`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct BtcFeeLimits {
+        /// The largest fee, in satoshi.
+        pub max_fee_satoshis: u64,
+        /// The largest fee, as a percentage of the value sent to the (non-change) outputs.
+        pub max_fee_percent_of_sent: u64,
+        /// The largest fee rate, as a multiple of the current network fee rate at the `High`
+        /// priority level.
+        pub max_fee_rate_multiple: u64,
+    }
+
+    /// Cross-checks the inputs of a transaction against the UTXOs that `bitcoin_get_utxos` reports
+    /// for the caller's address, before any input is signed.
+    ///
+    /// Only the first page of UTXOs is fetched, so an address with many UTXOs may have to spend its
+    /// older ones without verification.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct BtcUtxoVerification {
+        /// The minimum number of confirmations of every input.  Default: the default of
+        /// `bitcoin_get_utxos`, one confirmation.
+        pub min_confirmations: Option<u32>,
     }
 
     #[derive(CandidType, Deserialize, Debug)]
@@ -104,35 +290,453 @@ pub mod bitcoin {
         pub network: BitcoinNetwork,
         pub address_type: BitcoinAddressType,
         pub utxos_to_spend: Vec<Utxo>,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
         pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
         pub outputs: Vec<BtcTxOutput>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+        /// Marks the transaction as final instead of signalling BIP-125 replace-by-fee.  Default:
+        /// `false`.
+        pub disable_rbf: Option<bool>,
+        /// The sighash type of each input, in the order of `utxos_to_spend`.  `None` and
+        /// `NonePlusAnyoneCanPay` are rejected.  Default: `All` for every input.
+        pub sighash_types: Option<Vec<BtcSighashType>>,
+        /// Checks every input against the UTXO set of the Bitcoin canister before signing.
+        /// Default: the inputs are signed as supplied.
+        pub verify_utxos: Option<BtcUtxoVerification>,
+        /// Sends the value of all inputs, less the fee, to the only `Payment` output in `outputs`,
+        /// whose `sent_satoshis` is ignored, without a change output.  Default: `false`.
+        pub send_all: Option<bool>,
     }
 
     #[derive(CandidType, Deserialize, Debug)]
     pub struct SendBtcResponse {
         pub txid: String,
+        pub signed_transaction_hex: String,
+        /// The fee paid by the transaction, in satoshi.
+        pub fee_satoshis: u64,
+        /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+        pub fee_rate_millisat_per_vbyte: u64,
+        /// The size of the signed transaction, in virtual bytes.
+        pub vsize: u64,
+        /// The output that returns the change to the caller, or `None` if the transaction has no
+        /// change output.
+        pub change_output_index: Option<u32>,
     }
 
-    #[derive(CandidType, Deserialize, Debug)]
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
     pub struct SignBtcResponse {
         pub signed_transaction_hex: String,
         pub txid: String,
+        /// The fee paid by the transaction, in satoshi.
+        pub fee_satoshis: u64,
+        /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+        pub fee_rate_millisat_per_vbyte: u64,
+        /// The size of the signed transaction, in virtual bytes.
+        pub vsize: u64,
+        /// The output that returns the change to the caller, or `None` if the transaction has no
+        /// change output.
+        pub change_output_index: Option<u32>,
     }
 
-    #[derive(CandidType, Deserialize, Debug)]
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    #[rustfmt::skip]
     pub enum BuildP2wpkhTxError {
         NotP2WPKHSourceAddress,
         InvalidDestinationAddress { address: String },
         InvalidSourceAddress { address: String },
         WrongBitcoinNetwork,
         NotEnoughFunds { required: u64, available: u64 },
+        /// Both `fee_satoshis` and `fee_rate` were set.
+        ConflictingFeeArguments,
+        /// The fee exceeds `BtcFeeLimits::max_fee_satoshis`.
+        FeeTooHigh {
+            fee_satoshis: u64,
+            max_fee_satoshis: u64,
+        },
+        /// The fee exceeds `BtcFeeLimits::max_fee_percent_of_sent` of the sent value.
+        FeeTooHighForSentValue {
+            fee_satoshis: u64,
+            sent_satoshis: u64,
+            max_fee_percent_of_sent: u64,
+        },
+        /// The fee rate exceeds `BtcFeeLimits::max_fee_rate_multiple` times the network fee rate.
+        FeeRateTooHigh {
+            fee_rate_millisat_per_vbyte: u64,
+            max_fee_rate_millisat_per_vbyte: u64,
+        },
+        /// An output sends less than the dust threshold.
+        DustOutput {
+            address: String,
+            sent_satoshis: u64,
+            dust_threshold: u64,
+        },
+        /// The `OP_RETURN` data exceeds the size that nodes relay by default.
+        OpReturnDataTooLarge {
+            data_bytes: u64,
+            max_data_bytes: u64,
+        },
+        /// `sighash_types` does not have one entry per input.
+        WrongNumberOfSighashTypes {
+            inputs: u64,
+            sighash_types: u64,
+        },
+        /// An input signed with `Single` has no output with the same index.
+        SighashSingleWithoutOutput {
+            input_index: u64,
+        },
+        /// An input would be signed with `None` or `NonePlusAnyoneCanPay`, which commit to no
+        /// outputs.
+        SighashNoneNotAllowed {
+            input_index: u64,
+        },
+        /// `send_all` is set, but `outputs` does not have exactly one `Payment` output.
+        SendAllWithoutSingleOutput {
+            outputs: u64,
+        },
+        /// The transaction would spend more inputs than the method allows.
+        TooManyInputs {
+            inputs: u64,
+            max_inputs: u64,
+        },
     }
 
     #[derive(CandidType, Deserialize, Debug)]
     pub enum SendBtcError {
-        InternalError { msg: String },
+        InternalError {
+            msg: String,
+        },
         PaymentError(PaymentError),
         BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The keys or the delay of the vault are invalid.
+        InvalidVaultPolicy {
+            msg: String,
+        },
+        /// An input is not an unspent output of the caller's address with enough confirmations,
+        /// according to `bitcoin_get_utxos`.
+        UnknownUtxo {
+            txid: String,
+            vout: u32,
+        },
+        /// The value supplied for an input differs from the value of the unspent output.
+        ValueMismatch {
+            txid: String,
+            vout: u32,
+            supplied_satoshis: u64,
+            actual_satoshis: u64,
+        },
+        /// An input is spent by another transaction of the caller that is in flight or was sent
+        /// recently.
+        UtxoReserved {
+            txid: String,
+            vout: u32,
+            /// The transaction that spends the input, or `None` while it is being signed.
+            spending_txid: Option<String>,
+            /// When the reservation expires, in nanoseconds since the Unix epoch.
+            expires_at_ns: u64,
+        },
+        /// The transaction was signed, but `bitcoin_send_transaction` rejected it.  It can be
+        /// retried with `btc_rebroadcast`.
+        BroadcastFailed {
+            txid: String,
+            msg: String,
+        },
+        /// The request needs the Bitcoin canister, which does not serve `network`.  Transactions
+        /// for such a network can be signed with `btc_caller_sign` and broadcast elsewhere.
+        NoBitcoinCanister {
+            network: BitcoinNetwork,
+        },
+        /// The ckBTC account is invalid, or the ckBTC minter did not return its deposit address.
+        CkbtcMinterError {
+            msg: String,
+        },
+    }
+
+    /// A UTXO of the caller that a `btc_caller_send` transaction in flight, or sent recently,
+    /// spends.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcReservedUtxo {
+        pub txid: String,
+        pub vout: u32,
+        /// The transaction that spends the UTXO, or `None` while it is being signed.
+        pub spending_txid: Option<String>,
+        /// When the reservation expires, in nanoseconds since the Unix epoch.
+        pub expires_at_ns: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcPendingTransactionsResponse {
+        pub reserved_utxos: Vec<BtcReservedUtxo>,
+    }
+
+    /// Whether a signed transaction has been broadcast.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum BtcSignedTransactionStatus {
+        /// Signed by `btc_caller_sign`, which does not broadcast.
+        Signed,
+        /// Accepted by `bitcoin_send_transaction`.
+        Sent,
+        /// Rejected by `bitcoin_send_transaction`; it can be retried with `btc_rebroadcast`.
+        BroadcastFailed { msg: String },
+    }
+
+    /// A transaction signed for the caller, as listed by `btc_list_signed_transactions`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcSignedTransaction {
+        pub txid: String,
+        pub network: BitcoinNetwork,
+        pub signed_transaction_hex: String,
+        /// The fee paid by the transaction, in satoshi.
+        pub fee_satoshis: u64,
+        /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+        pub fee_rate_millisat_per_vbyte: u64,
+        pub status: BtcSignedTransactionStatus,
+        /// When the transaction was signed, in nanoseconds since the Unix epoch.
+        pub signed_at_ns: u64,
+        /// When the transaction is removed from the journal, in nanoseconds since the Unix epoch.
+        pub expires_at_ns: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcListSignedTransactionsResponse {
+        /// The caller's signed transactions, most recently signed first.
+        pub transactions: Vec<BtcSignedTransaction>,
+    }
+
+    /// Merges UTXOs of the caller's P2WPKH address into a single output.
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcConsolidateRequest {
+        pub network: BitcoinNetwork,
+        /// The UTXOs to merge.  Default: the caller's unreserved UTXOs with `min_confirmations`,
+        /// smallest first, skipping those worth less than the fee of spending them, at most
+        /// `max_inputs` of them.
+        pub utxos: Option<Vec<Utxo>>,
+        /// The minimum number of confirmations of the merged UTXOs, if `utxos` is not set.
+        /// Default: the default of `bitcoin_get_utxos`, one confirmation.
+        pub min_confirmations: Option<u32>,
+        /// The most UTXOs to merge, if `utxos` is not set; the fee is charged for this many
+        /// inputs.  At most 100.  Default: 50.
+        pub max_inputs: Option<u32>,
+        /// Receives the merged output.  Default: the caller's P2WPKH address.
+        pub destination_address: Option<String>,
+        /// Default: `Priority(Low)`.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    /// Sends BTC from the caller's P2WPKH address to the ckBTC minter's deposit address of a
+    /// ckBTC account.
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcDepositToCkbtcRequest {
+        /// The network of the ckBTC minter set in `Config::ckbtc_minter`.
+        pub network: BitcoinNetwork,
+        /// The owner of the ckBTC account to credit.  Default: the caller.
+        pub owner: Option<Principal>,
+        /// The 32-byte subaccount of the ckBTC account to credit.  Default: the default
+        /// subaccount.
+        pub subaccount: Option<Vec<u8>>,
+        pub utxos_to_spend: Vec<Utxo>,
+        /// The value sent to the deposit address, in satoshi.  Ignored with `send_all`.
+        pub deposit_satoshis: u64,
+        /// The fee, in satoshi.  Conflicts with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+        /// Deposits the value of all inputs, less the fee, without a change output.  Default:
+        /// `false`.
+        pub send_all: Option<bool>,
+        /// Calls the minter's `update_balance` for the account once the transaction is sent.
+        /// Default: `false`.
+        pub update_balance: Option<bool>,
+    }
+
+    /// The answer of the ckBTC minter's `update_balance`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum CkbtcUpdateBalance {
+        /// The minter processed new UTXOs of the deposit address.
+        Updated {
+            /// The ckBTC minted for them, in satoshi, after the minter's fees.
+            minted_satoshis: u64,
+            /// The value of those that were too small, were rejected by the Bitcoin check, or
+            /// are still to be minted, in satoshi.
+            unminted_satoshis: u64,
+        },
+        /// The minter has not seen a new UTXO with enough confirmations yet.  A deposit that was
+        /// just sent always ends up here; call the minter's `update_balance` again once the
+        /// transaction has `required_confirmations`.
+        NoNewUtxos {
+            required_confirmations: u32,
+            current_confirmations: Option<u32>,
+        },
+        /// The minter could not be called, or returned an error.
+        Failed { msg: String },
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcDepositToCkbtcResponse {
+        /// The minter's deposit address of the ckBTC account.
+        pub deposit_address: String,
+        pub txid: String,
+        /// The fee paid by the transaction, in satoshi.
+        pub fee_satoshis: u64,
+        /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+        pub fee_rate_millisat_per_vbyte: u64,
+        /// The answer of `update_balance`, if the request set `update_balance`.
+        pub update_balance: Option<CkbtcUpdateBalance>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcRebroadcastRequest {
+        /// The txid of a transaction listed by `btc_list_signed_transactions`.
+        pub txid: String,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub enum BtcRebroadcastError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        /// The caller has no signed transaction with this txid, or it has expired.
+        UnknownTransaction,
+        /// `bitcoin_send_transaction` rejected the transaction.
+        BroadcastFailed {
+            msg: String,
+        },
+        /// The request needs the Bitcoin canister, which does not serve `network`.
+        NoBitcoinCanister {
+            network: BitcoinNetwork,
+        },
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcJobResponse {
+        /// Identifies the job in `btc_job_status`.
+        pub job_id: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcJobStatusRequest {
+        pub job_id: u64,
+    }
+
+    /// The progress of a job started by `btc_caller_sign_job` or `btc_caller_send_job`.
+    #[derive(CandidType, Deserialize, Debug)]
+    pub enum BtcJobStatus {
+        /// The inputs are being signed, in batches.
+        Signing {
+            signed_inputs: u64,
+            total_inputs: u64,
+        },
+        /// The transaction is signed and, for `btc_caller_send_job`, sent.
+        Completed(SignBtcResponse),
+        Failed(SendBtcError),
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub enum BtcJobStatusError {
+        /// The caller has no job with this id, or it has expired.
+        UnknownJob,
+    }
+
+    /// How the fee of a stuck transaction is increased.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcBumpFeeMode {
+        /// Replace-by-fee: a replacement spends the same inputs, paying the higher fee out of the
+        /// change output.
+        Rbf,
+        /// Child-pays-for-parent: a new transaction spends the change output of the original,
+        /// paying a fee that brings the two transactions to the requested fee rate.
+        Cpfp,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcBumpFeeRequest {
+        pub network: BitcoinNetwork,
+        pub address_type: BitcoinAddressType,
+        /// The original transaction, hex-encoded, as signed by the caller.  Cannot be combined
+        /// with `txid`.
+        pub signed_transaction_hex: Option<String>,
+        /// The txid of the original transaction, if the signer signed it for the caller in the
+        /// last week; see `btc_list_signed_transactions`.  Cannot be combined with
+        /// `signed_transaction_hex`.
+        pub txid: Option<String>,
+        /// The UTXOs spent by the original transaction.
+        pub utxos_to_spend: Vec<Utxo>,
+        /// The new fee rate.  For `Cpfp`, the fee rate of the original and the new transaction
+        /// together.
+        pub fee_rate: BtcFeeRate,
+        pub mode: BtcBumpFeeMode,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcBumpFeeResponse {
+        /// The replacement (`Rbf`) or child (`Cpfp`) transaction, as sent to the network.
+        pub signed_transaction_hex: String,
+        pub txid: String,
+        /// The fee paid by the new transaction, in satoshi.
+        pub fee_satoshis: u64,
+        /// The effective fee rate of the new transaction, in millisatoshi per virtual byte.
+        pub fee_rate_millisat_per_vbyte: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub enum BtcBumpFeeError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The original transaction could not be decoded.
+        InvalidTransaction {
+            msg: String,
+        },
+        /// The original transaction does not signal BIP-125 replace-by-fee.
+        NotReplaceable,
+        /// An input of the original transaction is not in `utxos_to_spend`.
+        MissingInputUtxo {
+            txid: String,
+            vout: u32,
+        },
+        /// The original transaction has no change output to the caller's address.
+        NoChangeOutput,
+        /// The replacement must pay the original fee plus 1 satoshi per virtual byte of its own
+        /// size.
+        ReplacementFeeTooLow {
+            fee_satoshis: u64,
+            min_fee_satoshis: u64,
+        },
+        /// The caller has no signed transaction with `txid`, or it has expired.
+        UnknownTransaction,
+        /// The change output that a `Cpfp` child spends is spent by another transaction of the
+        /// caller that is in flight or was sent recently, e.g. an earlier child.
+        UtxoReserved {
+            txid: String,
+            vout: u32,
+            /// The transaction that spends the output, or `None` while it is being signed.
+            spending_txid: Option<String>,
+            /// When the reservation expires, in nanoseconds since the Unix epoch.
+            expires_at_ns: u64,
+        },
+        /// `bitcoin_send_transaction` rejected the new transaction.  It is kept, and can be sent
+        /// again with `btc_rebroadcast`.
+        BroadcastFailed {
+            txid: String,
+            msg: String,
+        },
+        /// The request needs the Bitcoin canister, which does not serve `network`.
+        NoBitcoinCanister {
+            network: BitcoinNetwork,
+        },
     }
 
     #[derive(CandidType, Deserialize, Debug)]
@@ -168,4 +772,532 @@ pub mod bitcoin {
             Self::SigningError(msg)
         }
     }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcMultisigAddressRequest {
+        pub network: BitcoinNetwork,
+        /// Hex-encoded compressed public keys of the other cosigners.  The caller's key is added
+        /// to these.
+        pub cosigner_public_keys: Vec<String>,
+        /// The number of signatures required to spend.
+        pub threshold: u8,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcMultisigAddressResponse {
+        /// The P2WSH address of the multisig account.
+        pub address: String,
+        /// The hex-encoded witness script, as needed by the cosigners to spend.
+        pub witness_script_hex: String,
+        /// All public keys of the account, hex-encoded, in the order of the witness script.
+        pub public_keys: Vec<String>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcMultisigAddressError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        /// A cosigner public key is not a hex-encoded compressed public key.
+        InvalidPublicKey {
+            public_key: String,
+        },
+        /// The same public key appears more than once.
+        DuplicatePublicKey {
+            public_key: String,
+        },
+        /// The threshold is zero or exceeds the number of public keys.
+        InvalidThreshold {
+            threshold: u8,
+            public_keys: u64,
+        },
+        /// The account has more public keys than a standard multisig script allows.
+        TooManyPublicKeys {
+            public_keys: u64,
+            max_public_keys: u64,
+        },
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcSignPsbtRequest {
+        /// The hex-encoded [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki)
+        /// PSBT to sign.
+        ///
+        /// Inputs to be signed need a `witness_utxo` and a `witness_script` that contains the
+        /// caller's public key.  Other inputs are left untouched.
+        pub psbt_hex: String,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcSignPsbtResponse {
+        /// The hex-encoded PSBT with the caller's partial signatures added.  It is not finalized.
+        pub psbt_hex: String,
+        /// The indices of the inputs that were signed.
+        pub signed_inputs: Vec<u32>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcSignPsbtError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        /// The PSBT could not be decoded, or an input is inconsistent.
+        InvalidPsbt {
+            msg: String,
+        },
+        /// No input has a witness script that contains the caller's public key.
+        NoInputsToSign,
+        /// An input to sign has the sighash type `None` or `NonePlusAnyoneCanPay`, which commit to
+        /// no outputs.
+        SighashNoneNotAllowed {
+            input_index: u64,
+        },
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcVaultSignRequest {
+        pub network: BitcoinNetwork,
+        pub vault: BtcVaultPolicy,
+        /// UTXOs of the vault address.
+        pub utxos_to_spend: Vec<Utxo>,
+        pub outputs: Vec<BtcTxOutput>,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    /// The side of an HTLC that the caller is on.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BtcHtlcRole {
+        /// The caller claims the HTLC with the preimage; the counterparty is refunded after the
+        /// timeout.
+        Recipient,
+        /// The caller is refunded after the timeout; the counterparty claims the HTLC with the
+        /// preimage.
+        Sender,
+    }
+
+    /// A hash time-locked contract between the caller and a counterparty, as used in atomic
+    /// swaps.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcHtlc {
+        /// Hex-encoded compressed public key of the counterparty.
+        pub counterparty_public_key: String,
+        /// Hex-encoded SHA-256 hash of the 32-byte preimage that claims the HTLC.
+        pub payment_hash: String,
+        /// The block height from which the sender can be refunded.
+        pub timeout_block_height: u32,
+        pub caller_role: BtcHtlcRole,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcHtlcAddressRequest {
+        pub network: BitcoinNetwork,
+        pub htlc: BtcHtlc,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcHtlcAddressResponse {
+        /// The P2WSH address of the HTLC.
+        pub address: String,
+        /// The hex-encoded witness script, as needed by the counterparty to spend.
+        pub witness_script_hex: String,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcHtlcClaimRequest {
+        pub network: BitcoinNetwork,
+        pub htlc: BtcHtlc,
+        /// The hex-encoded 32-byte preimage of `payment_hash`.
+        pub preimage: String,
+        /// UTXOs of the HTLC address.
+        pub utxos_to_spend: Vec<Utxo>,
+        /// Receives the value of the UTXOs, minus the fee.
+        pub destination_address: String,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcHtlcRefundRequest {
+        pub network: BitcoinNetwork,
+        pub htlc: BtcHtlc,
+        /// UTXOs of the HTLC address.
+        pub utxos_to_spend: Vec<Utxo>,
+        /// Receives the value of the UTXOs, minus the fee.
+        pub destination_address: String,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcHtlcError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The counterparty key, the payment hash or the timeout of the HTLC is invalid.
+        InvalidHtlc {
+            msg: String,
+        },
+        /// Only the recipient can claim the HTLC, and only the sender can be refunded.
+        WrongCallerRole,
+        /// The preimage is not 32 bytes long or does not hash to the payment hash.
+        PreimageMismatch,
+        /// The chain has not reached the timeout of the HTLC yet.
+        TimeoutNotReached {
+            timeout_block_height: u32,
+            tip_height: u32,
+        },
+        /// The request needs the Bitcoin canister, which does not serve `network`.
+        NoBitcoinCanister {
+            network: BitcoinNetwork,
+        },
+    }
+
+    /// A leaf of a taproot script tree.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcTapLeaf {
+        /// The hex-encoded tapscript, with leaf version `0xc0`.
+        pub script_hex: String,
+        /// The depth of the leaf in the tree, from 0 for a single leaf to 128.
+        pub depth: u8,
+    }
+
+    /// A taproot output whose internal key is tweaked with the Merkle root of a script tree.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcTaprootTree {
+        /// Hex-encoded 32-byte x-only internal key.  Default: the caller's Schnorr key.
+        pub internal_key: Option<String>,
+        /// The leaves in depth-first order, left to right.  Their depths must describe a complete
+        /// binary tree, e.g. `[0]`, `[1, 1]` or `[1, 2, 2]`.
+        pub leaves: Vec<BtcTapLeaf>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcTaprootAddressRequest {
+        pub network: BitcoinNetwork,
+        pub tree: BtcTaprootTree,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcTaprootAddressResponse {
+        /// The P2TR address of the output.
+        pub address: String,
+        /// The hex-encoded x-only public key of the caller's Schnorr key, as used in tapscript
+        /// leaves.
+        pub caller_public_key: String,
+        /// The hex-encoded x-only internal key.
+        pub internal_key: String,
+        /// The hex-encoded Merkle root of the script tree.
+        pub merkle_root: String,
+        /// The hex-encoded control block of each leaf, in the order of `leaves`.
+        pub control_blocks: Vec<String>,
+        /// The output descriptor, with its BIP-380 checksum: `tr(<internal key>,{...})` if every
+        /// leaf is `<key> OP_CHECKSIG`, and otherwise `rawtr(<output key>)`, which a
+        /// wallet can watch but not spend.
+        pub descriptor: String,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcTaprootSignRequest {
+        pub network: BitcoinNetwork,
+        pub tree: BtcTaprootTree,
+        /// The index in `tree.leaves` of the leaf to spend.  Its script must contain the caller's
+        /// key and be satisfied by the caller's signature alone.
+        pub leaf_index: u32,
+        /// UTXOs of the taproot address.
+        pub utxos_to_spend: Vec<Utxo>,
+        pub outputs: Vec<BtcTxOutput>,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcTaprootError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The internal key or a leaf is invalid, or the leaves do not form a complete tree.
+        InvalidTaprootTree {
+            msg: String,
+        },
+        /// `leaf_index` is not the index of a leaf.
+        LeafNotFound {
+            leaf_index: u32,
+            leaves: u64,
+        },
+        /// The script of the leaf to spend does not contain the caller's key.
+        LeafWithoutCallerKey,
+    }
+
+    /// The ID of a rune: the block height of its etching transaction and the index of that
+    /// transaction in the block, usually written `BLOCK:TX`.
+    #[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct BtcRuneId {
+        pub block: u64,
+        pub tx: u32,
+    }
+
+    /// Units of a rune to send to an address.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcRuneTransfer {
+        /// The rune to send.  `0:0` is the rune etched by the same transaction.
+        pub rune_id: BtcRuneId,
+        /// The amount, in the smallest unit of the rune.  `0` sends all remaining units.
+        pub amount: u128,
+        pub destination_address: String,
+    }
+
+    /// A new rune without a name, for which the protocol reserves a name.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcRuneEtching {
+        /// The number of decimal places of the rune, at most 38.  Default: 0.
+        pub divisibility: Option<u8>,
+        /// The currency symbol of the rune, a single character.  Default: `¤`.
+        pub symbol: Option<String>,
+        /// Units minted by the etching.  Units not sent with a transfer are returned to the
+        /// caller.  Default: 0.
+        pub premine: Option<u128>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcSendRunesRequest {
+        pub network: BitcoinNetwork,
+        /// UTXOs of the caller's address that hold the runes to send.
+        pub rune_utxos: Vec<Utxo>,
+        /// UTXOs of the caller's address that pay the postage and the fee.
+        pub fee_utxos: Vec<Utxo>,
+        /// The transfers, each to its own output.  Runes that the inputs hold beyond the transfers
+        /// are returned to the caller in a separate output, never in the change output of the fee
+        /// UTXOs.
+        pub transfers: Vec<BtcRuneTransfer>,
+        /// A rune to etch in the same transaction.
+        pub etching: Option<BtcRuneEtching>,
+        /// The value of every output that receives runes, in satoshi.  Default and minimum: the
+        /// dust threshold, 1,000 satoshi.
+        pub postage_satoshis: Option<u64>,
+        /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate; the fee is computed from the size of the built transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcSendRunesError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The transfers or the etching cannot be encoded in a valid runestone.
+        InvalidRunestone {
+            msg: String,
+        },
+        /// An input is spent by another transaction of the caller that is in flight or was sent
+        /// recently.
+        UtxoReserved {
+            txid: String,
+            vout: u32,
+            /// The transaction that spends the input, or `None` while it is being signed.
+            spending_txid: Option<String>,
+            /// When the reservation expires, in nanoseconds since the Unix epoch.
+            expires_at_ns: u64,
+        },
+        /// The transaction was signed, but `bitcoin_send_transaction` rejected it.  It can be
+        /// retried with `btc_rebroadcast`.
+        BroadcastFailed {
+            txid: String,
+            msg: String,
+        },
+        /// The request needs the Bitcoin canister, which does not serve `network`.
+        NoBitcoinCanister {
+            network: BitcoinNetwork,
+        },
+    }
+
+    /// Content to inscribe, revealed in the witness of an input.
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcInscription {
+        /// The MIME type of the body, e.g. `text/plain;charset=utf-8`.
+        pub content_type: String,
+        /// The content.  Split into pushes of at most 520 bytes.
+        pub body: Vec<u8>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcInscriptionCommitRequest {
+        pub network: BitcoinNetwork,
+        pub inscription: BtcInscription,
+        /// UTXOs of the caller's P2WPKH address that fund the commit output and the fee.
+        pub utxos_to_spend: Vec<Utxo>,
+        /// The value of the output that will hold the inscription, in satoshi.  The commit output
+        /// holds this plus the fee of the reveal transaction.  Default: the dust threshold, 1,000
+        /// satoshi.
+        pub postage_satoshis: Option<u64>,
+        /// The fee rate of the reveal transaction.  Default: `Priority(Medium)`.
+        pub reveal_fee_rate: Option<BtcFeeRate>,
+        /// An absolute fee for the commit transaction, in satoshi.  Cannot be combined with
+        /// `fee_rate`.
+        pub fee_satoshis: Option<u64>,
+        /// The fee rate of the commit transaction; the fee is computed from the size of the built
+        /// transaction.
+        ///
+        /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+        pub fee_rate: Option<BtcFeeRate>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcInscriptionCommitResponse {
+        /// The signed commit transaction, which is not broadcast.
+        pub transaction: SignBtcResponse,
+        /// The P2TR address of the commit output, whose script tree has a single leaf with the
+        /// inscription envelope.
+        pub commit_address: String,
+        /// The index of the commit output in the commit transaction.
+        pub commit_vout: u32,
+        /// The value of the commit output, in satoshi: the postage plus the reveal fee.
+        pub commit_satoshis: u64,
+    }
+
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcInscriptionRevealRequest {
+        pub network: BitcoinNetwork,
+        /// The inscription of the commit, unchanged.
+        pub inscription: BtcInscription,
+        /// The commit output.
+        pub commit_utxo: Utxo,
+        /// Receives the inscription.
+        pub destination_address: String,
+        /// The value of the output that receives the inscription, in satoshi.  The rest of the
+        /// commit output pays the fee.  Default: the dust threshold, 1,000 satoshi.
+        pub postage_satoshis: Option<u64>,
+        /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+        pub allow_high_fee: Option<bool>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcInscriptionError {
+        InternalError {
+            msg: String,
+        },
+        PaymentError(PaymentError),
+        BuildP2wpkhError(BuildP2wpkhTxError),
+        /// The content type or the body cannot be inscribed.
+        InvalidInscription {
+            msg: String,
+        },
+        /// An input of the commit transaction is spent by another transaction of the caller that
+        /// is in flight or was sent recently.
+        UtxoReserved {
+            txid: String,
+            vout: u32,
+            /// The transaction that spends the input, or `None` while it is being signed.
+            spending_txid: Option<String>,
+            /// When the reservation expires, in nanoseconds since the Unix epoch.
+            expires_at_ns: u64,
+        },
+    }
+    #[derive(CandidType, Deserialize, Debug)]
+    pub struct BtcDecodeTransactionRequest {
+        pub network: BitcoinNetwork,
+        /// A hex-encoded raw transaction, signed or not, or a hex-encoded
+        /// [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki) PSBT.
+        pub transaction_hex: String,
+        /// The outputs that the transaction spends, to compute its fee.  PSBT inputs also take
+        /// their value from their `witness_utxo` or `non_witness_utxo`.
+        pub utxos: Option<Vec<Utxo>>,
+        /// The caller's address, as returned by `btc_caller_address`, to find the outputs that pay
+        /// back to the caller.  A query cannot derive the caller's key, so the address is taken as
+        /// given.
+        pub own_address: Option<String>,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcDecodedInput {
+        pub txid: String,
+        pub vout: u32,
+        pub sequence: u32,
+        /// The value of the spent output, in satoshi, if it is known.
+        pub value: Option<u64>,
+        /// Whether the input has a witness or a `scriptSig`.
+        pub is_signed: bool,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcDecodedOutput {
+        pub value: u64,
+        pub script_pubkey_hex: String,
+        /// The address of the output on the requested network, or `None` for scripts without an
+        /// address, such as `OP_RETURN` outputs.
+        pub address: Option<String>,
+        /// Whether the output pays to `own_address`.  The address is supplied by the caller and is
+        /// not checked against the caller's key, so this is only as reliable as `own_address`.
+        pub pays_caller: bool,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BtcDecodeTransactionResponse {
+        pub txid: String,
+        /// Whether `transaction_hex` is a PSBT.
+        pub is_psbt: bool,
+        pub inputs: Vec<BtcDecodedInput>,
+        pub outputs: Vec<BtcDecodedOutput>,
+        /// The fee paid by the transaction, in satoshi, if the values of all inputs are known.
+        pub fee_satoshis: Option<u64>,
+        /// The size of the transaction, in virtual bytes.  Unsigned inputs are counted as P2WPKH
+        /// inputs with the largest possible signature.
+        pub vsize: u64,
+        /// The fee rate, in millisatoshi per virtual byte, if the fee is known.
+        pub fee_rate_millisat_per_vbyte: Option<u64>,
+        /// Whether an input signals replaceability, see BIP-125.
+        pub signals_rbf: bool,
+    }
+
+    #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
+    pub enum BtcDecodeTransactionError {
+        /// `transaction_hex` is neither a transaction nor a PSBT.
+        InvalidTransaction { msg: String },
+        /// `own_address` is not an address of the requested network.
+        InvalidOwnAddress { address: String },
+        /// The outputs are worth more than the inputs, so `utxos` or the PSBT carry wrong values.
+        OutputsExceedInputs {
+            inputs_value: u64,
+            outputs_value: u64,
+        },
+    }
 }
@@ -0,0 +1,65 @@
+//! Types for the Solana API.
+//!
+//! The caller's Solana account is the Ed25519 threshold key under the `Sol` derivation path
+//! schema.
+use candid::{CandidType, Deserialize};
+use ic_papi_api::PaymentError;
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SolAddressResponse {
+    /// The base58 encoding of the caller's Ed25519 public key.
+    pub address: String,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum SolAddressError {
+    /// Payment failed.
+    PaymentError(PaymentError),
+    /// An inter-canister call error from the threshold signature API.
+    SigningError(String),
+}
+
+impl From<PaymentError> for SolAddressError {
+    fn from(e: PaymentError) -> Self {
+        Self::PaymentError(e)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SolSignTransactionRequest {
+    /// The hex-encoded serialized message of a legacy or v0 transaction.
+    ///
+    /// The caller's address, as returned by `sol_address`, must be one of the required signers.
+    pub message_hex: String,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SolSignTransactionResponse {
+    /// The hex-encoded transaction in wire format, ready for `sendTransaction` once every
+    /// signature is present.
+    ///
+    /// The signatures of other required signers are left as zeros, for them to fill in.
+    pub signed_transaction_hex: String,
+    /// The caller's base58-encoded signature.  If the caller is the fee payer, this is the
+    /// transaction ID.
+    pub signature: String,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum SolSignTransactionError {
+    /// The message was not valid hex or not a legacy or v0 Solana message.
+    InvalidMessage { msg: String },
+    /// The caller's address is not one of the required signers of the message.  The fee is
+    /// charged all the same.
+    NotASigner { address: String },
+    /// Payment failed.
+    PaymentError(PaymentError),
+    /// An inter-canister call error from the threshold signature API.
+    SigningError(String),
+}
+
+impl From<PaymentError> for SolSignTransactionError {
+    fn from(e: PaymentError) -> Self {
+        Self::PaymentError(e)
+    }
+}
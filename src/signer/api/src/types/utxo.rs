@@ -0,0 +1,77 @@
+//! Types for the Dogecoin and Litecoin APIs, which share the Bitcoin transaction pipeline.
+//!
+//! Amounts are in the smallest unit of the coin: koinu for Dogecoin and litoshi for Litecoin.
+use candid::{CandidType, Deserialize};
+use ic_cdk_bitcoin_canister::Utxo;
+use ic_papi_api::PaymentError;
+
+use super::bitcoin::{BitcoinNetwork, BuildP2wpkhTxError};
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UtxoAddressRequest {
+    /// `Testnet4` and `Signet` select the test network of the chain, like `Testnet`.
+    pub network: BitcoinNetwork,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UtxoAddressResponse {
+    /// A base58 P2PKH address for Dogecoin, and a bech32 P2WPKH address for Litecoin.
+    pub address: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum UtxoAddressError {
+    InternalError { msg: String },
+    PaymentError(PaymentError),
+}
+
+impl From<PaymentError> for UtxoAddressError {
+    fn from(e: PaymentError) -> Self {
+        Self::PaymentError(e)
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UtxoTxOutput {
+    pub destination_address: String,
+    pub value: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct UtxoSignRequest {
+    /// `Testnet4` and `Signet` select the test network of the chain, like `Testnet`.
+    pub network: BitcoinNetwork,
+    /// UTXOs of the caller's address, as returned by `doge_caller_address` or
+    /// `ltc_caller_address`.  All of them are spent.
+    pub utxos_to_spend: Vec<Utxo>,
+    pub outputs: Vec<UtxoTxOutput>,
+    /// An absolute fee.  Cannot be combined with `fee_rate_per_vbyte`.
+    pub fee: Option<u64>,
+    /// The fee rate, per virtual byte of the signed transaction.
+    ///
+    /// Default: 1,000 koinu for Dogecoin and 10 litoshi for Litecoin, unless `fee` is set.
+    pub fee_rate_per_vbyte: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UtxoSignResponse {
+    pub signed_transaction_hex: String,
+    pub txid: String,
+    /// The fee paid by the transaction.
+    pub fee: u64,
+    /// The size of the signed transaction, in virtual bytes.
+    pub vsize: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum UtxoSignError {
+    InternalError { msg: String },
+    PaymentError(PaymentError),
+    BuildError(BuildP2wpkhTxError),
+}
+
+impl From<PaymentError> for UtxoSignError {
+    fn from(e: PaymentError) -> Self {
+        Self::PaymentError(e)
+    }
+}
@@ -12,6 +12,12 @@ pub enum Schema {
     ///
     /// Please see `from_principal` for details.
     Eth = 1,
+    /// A principal's default Dogecoin address.
+    Doge = 2,
+    /// A principal's default Litecoin address.
+    Ltc = 3,
+    /// A principal's default Solana address.
+    Sol = 4,
     /// A generic Schnorr key.  The caller is responsible for managing derivation paths.
     Schnorr = 0xfe,
     /// A generic ECDSA key.  The caller is responsible for managing derivation paths.
@@ -1,10 +1,10 @@
 use core::ops::Deref;
 use std::borrow::Cow;
 
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Principal};
 use ic_stable_structures::storable::{Bound, Storable};
 
-use crate::types::Candid;
+use crate::types::{Candid, SignedTransactionKey, UtxoReservationKey};
 
 impl<T> Storable for Candid<T>
 where
@@ -35,3 +35,67 @@ where
         &self.0
     }
 }
+
+/// Encodes the principal, of at most 29 bytes, with a length prefix, followed by the txid and the
+/// big-endian vout.
+impl Storable for UtxoReservationKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1 + 29 + 32 + 4,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.clone().into_bytes())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let principal = self.principal.as_slice();
+        let mut bytes = Vec::with_capacity(1 + principal.len() + 32 + 4);
+        bytes.push(u8::try_from(principal.len()).expect("principals are at most 29 bytes long"));
+        bytes.extend_from_slice(principal);
+        bytes.extend_from_slice(&self.txid);
+        bytes.extend_from_slice(&self.vout.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let (principal_len, rest) = bytes.split_first().expect("decoding should succeed");
+        let (principal, rest) = rest.split_at(usize::from(*principal_len));
+        let (txid, vout) = rest.split_at(32);
+        Self {
+            principal: Principal::from_slice(principal),
+            txid: txid.try_into().expect("decoding should succeed"),
+            vout: u32::from_be_bytes(vout.try_into().expect("decoding should succeed")),
+        }
+    }
+}
+
+/// Encodes the principal, of at most 29 bytes, with a length prefix, followed by the txid.
+impl Storable for SignedTransactionKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1 + 29 + 32,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.clone().into_bytes())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let principal = self.principal.as_slice();
+        let mut bytes = Vec::with_capacity(1 + principal.len() + 32);
+        bytes.push(u8::try_from(principal.len()).expect("principals are at most 29 bytes long"));
+        bytes.extend_from_slice(principal);
+        bytes.extend_from_slice(&self.txid);
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let (principal_len, rest) = bytes.split_first().expect("decoding should succeed");
+        let (principal, txid) = rest.split_at(usize::from(*principal_len));
+        Self {
+            principal: Principal::from_slice(principal),
+            txid: txid.try_into().expect("decoding should succeed"),
+        }
+    }
+}
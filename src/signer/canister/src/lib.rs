@@ -1,5 +1,10 @@
+use bitcoin::{hashes::Hash, ScriptBuf};
 use candid::Principal;
-use ic_cdk::{api::msg_caller, export_candid, init, post_upgrade, query, update};
+use ic_cdk::{
+    api::{msg_caller, time},
+    export_candid, init, post_upgrade, query, update,
+};
+use ic_cdk_bitcoin_canister::Utxo;
 use ic_cdk_management_canister::{
     EcdsaPublicKeyArgs, EcdsaPublicKeyResult, SchnorrPublicKeyArgs, SchnorrPublicKeyResult,
     SignWithEcdsaArgs, SignWithEcdsaResult, SignWithSchnorrArgs, SignWithSchnorrResult,
@@ -11,10 +16,23 @@ use ic_chain_fusion_signer_api::{
     std_canister_status,
     types::{
         bitcoin::{
-            BitcoinAddressType, BtcSignPrehashError, BtcSignPrehashRequest, BtcSignPrehashResponse,
-            GetAddressError, GetAddressRequest, GetAddressResponse, GetBalanceError,
-            GetBalanceRequest, GetBalanceResponse, SendBtcError, SendBtcRequest, SendBtcResponse,
-            SignBtcResponse,
+            BitcoinAddressType, BitcoinNetwork, BtcAddressRequest, BtcBumpFeeError, BtcBumpFeeMode,
+            BtcBumpFeeRequest, BtcBumpFeeResponse, BtcConsolidateRequest,
+            BtcDecodeTransactionError, BtcDecodeTransactionRequest, BtcDecodeTransactionResponse,
+            BtcDepositToCkbtcRequest, BtcDepositToCkbtcResponse, BtcDescriptorResponse,
+            BtcFeePriority, BtcFeeRate, BtcHtlcAddressRequest, BtcHtlcAddressResponse,
+            BtcHtlcClaimRequest, BtcHtlcError, BtcHtlcRefundRequest, BtcHtlcRole,
+            BtcInscriptionCommitRequest, BtcInscriptionCommitResponse, BtcInscriptionError,
+            BtcInscriptionRevealRequest, BtcJobResponse, BtcJobStatus, BtcJobStatusError,
+            BtcJobStatusRequest, BtcListSignedTransactionsResponse, BtcMultisigAddressError,
+            BtcMultisigAddressRequest, BtcMultisigAddressResponse, BtcPendingTransactionsResponse,
+            BtcRebroadcastError, BtcRebroadcastRequest, BtcSendRunesError, BtcSendRunesRequest,
+            BtcSignPrehashError, BtcSignPrehashRequest, BtcSignPrehashResponse, BtcSignPsbtError,
+            BtcSignPsbtRequest, BtcSignPsbtResponse, BtcTaprootAddressRequest,
+            BtcTaprootAddressResponse, BtcTaprootError, BtcTaprootSignRequest, BtcTxOutput,
+            BtcVaultSignRequest, BuildP2wpkhTxError, GetAddressError, GetAddressRequest,
+            GetAddressResponse, GetBalanceError, GetBalanceRequest, GetBalanceResponse,
+            SendBtcError, SendBtcRequest, SendBtcResponse, SignBtcResponse,
         },
         eth::{
             EthPersonalSignError, EthPersonalSignRequest, EthPersonalSignResponse,
@@ -22,6 +40,14 @@ use ic_chain_fusion_signer_api::{
             EthSignTransactionError, EthSignTransactionRequest, EthSignTransactionResponse,
         },
         schnorr::{SchnorrPublicKeyError, SchnorrSigningError},
+        sol::{
+            SolAddressError, SolAddressResponse, SolSignTransactionError,
+            SolSignTransactionRequest, SolSignTransactionResponse,
+        },
+        utxo::{
+            UtxoAddressError, UtxoAddressRequest, UtxoAddressResponse, UtxoSignError,
+            UtxoSignRequest, UtxoSignResponse,
+        },
         Arg, Config,
     },
 };
@@ -29,16 +55,38 @@ use ic_papi_api::PaymentType;
 use serde_bytes::ByteBuf;
 use sign::{
     bitcoin::{
-        bitcoin_api, bitcoin_utils,
-        fee_utils::calculate_fee,
-        tx_utils::{btc_sign_transaction, build_p2wpkh_transaction},
+        bitcoin_api, bitcoin_utils, bump_fee,
+        chain::{ChainParams, UtxoChain},
+        ckbtc,
+        consolidation::{self, DEFAULT_CONSOLIDATION_INPUTS, MAX_CONSOLIDATION_INPUTS},
+        decode, descriptor,
+        fee_utils::{check_fee, fee_for_vsize, resolve_fee_rate, Fee, RequestFee},
+        htlc::{self, HtlcSpend},
+        inscription, jobs, journal, multisig, psbt, reservations, runes,
+        taproot::{self, TapLeafSpend},
+        tx_utils::{
+            btc_sign_transaction, build_chain_transaction_with_fee,
+            build_htlc_transaction_with_fee, build_p2wpkh_sweep_transaction_with_fee,
+            build_p2wpkh_transaction_with_fee, build_runes_transaction_with_fee,
+            build_taproot_transaction_with_fee, build_vault_transaction_with_fee,
+            change_output_index, check_data_outputs, check_sighash_types, disable_rbf,
+            estimate_signed_htlc_vsize, estimate_signed_reveal_vsize,
+            estimate_signed_taproot_vsize, estimate_signed_vault_vsize, estimate_signed_vsize,
+            input_sighash_types, op_return_data_bytes, p2wpkh_sighashes, sent_satoshis,
+            sign_chain_transaction, sign_htlc_transaction, sign_taproot_script_path_transaction,
+            transaction_fee, vault_psbt, SignedTransaction, DUST_THRESHOLD,
+        },
+        utxo_verification::verify_utxos,
+        vault,
     },
     eth,
     eth::{EthAddressError, EthAddressRequest, EthAddressResponse},
     generic,
     generic::{GenericCallerEcdsaPublicKeyError, GenericSignWithEcdsaError},
+    sol,
 };
-use state::{read_config, read_state, set_config, PAYMENT_GUARD};
+use state::{mutate_state, read_config, read_state, set_config, PAYMENT_GUARD};
+use types::{SigningJob, SigningJobStatus};
 
 use crate::guards::caller_is_not_anonymous;
 
@@ -80,6 +128,8 @@ pub fn post_upgrade(arg: Option<Arg>) {
             });
         }
     }
+    // Timers do not survive upgrades; resume the jobs that have inputs left to sign.
+    jobs::schedule_batch();
 }
 
 /// Show the canister configuration.
@@ -424,12 +474,78 @@ pub async fn eth_sign_prehash(
 // // BITCOIN UTILS //
 // ///////////////////
 
+/// Internal helper that computes the P2WPKH address of `principal`, or the P2WSH address of a
+/// vault whose key is the principal's.
+async fn principal_btc_address(
+    principal: &Principal,
+    network: BitcoinNetwork,
+    address_type: BitcoinAddressType,
+) -> Result<GetAddressResponse, GetAddressError> {
+    match address_type {
+        BitcoinAddressType::P2WPKH => {
+            let address = bitcoin_utils::principal_to_p2wpkh_address(network, principal)
+                .await
+                .map_err(|msg| GetAddressError::InternalError { msg })?;
+
+            Ok(GetAddressResponse { address })
+        }
+        BitcoinAddressType::P2WSHVault(policy) => {
+            let policy = vault::parse_vault_policy(&policy)
+                .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?;
+            let own_public_key = bitcoin_utils::principal_to_public_key(principal)
+                .await
+                .map_err(|msg| GetAddressError::InternalError { msg })?;
+            let vault = vault::vault(own_public_key, &policy)
+                .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?;
+
+            Ok(GetAddressResponse {
+                address: vault.address(network).to_string(),
+            })
+        }
+    }
+}
+
+/// Returns the Bitcoin address of a specified user.
+///
+/// If no user is specified, the caller's address is returned.
+///
+/// # Details
+/// - Gets the specified user's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address, or to the P2WSH address of a vault.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+/// - If the specified user is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_address(
+    params: BtcAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<GetAddressResponse, GetAddressError> {
+    let principal = params.principal.unwrap_or_else(msg_caller);
+    if principal == Principal::anonymous() {
+        ic_cdk::trap("Anonymous principal is not authorized");
+    }
+    if let BitcoinAddressType::P2WSHVault(policy) = &params.address_type {
+        vault::parse_vault_policy(policy)
+            .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?;
+    }
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcAddress.fee(),
+        )
+        .await?;
+    principal_btc_address(&principal, params.network, params.address_type).await
+}
+
 /// Returns the Bitcoin address of the caller.
 ///
 /// # Details
 /// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
-/// - Converts the public key to a P2WPKH address.
+/// - Converts the public key to a P2WPKH address, or to the P2WSH address of a vault.
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
 ///
 /// # Panics
@@ -440,19 +556,68 @@ pub async fn btc_caller_address(
     payment: Option<PaymentType>, /* Note: Do NOT use underscore, please, so that the underscore
                                    * doesn't show up in the generated candid. */
 ) -> Result<GetAddressResponse, GetAddressError> {
+    if let BitcoinAddressType::P2WSHVault(policy) = &params.address_type {
+        vault::parse_vault_policy(policy)
+            .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?;
+    }
     PAYMENT_GUARD
         .deduct(
             payment.unwrap_or(PaymentType::AttachedCycles),
             SignerMethods::BtcCallerAddress.fee(),
         )
         .await?;
-    match params.address_type {
-        BitcoinAddressType::P2WPKH => {
-            let address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &msg_caller())
-                .await
-                .map_err(|msg| GetAddressError::InternalError { msg })?;
+    principal_btc_address(&msg_caller(), params.network, params.address_type).await
+}
 
-            Ok(GetAddressResponse { address })
+/// Returns the BIP-380 output descriptor of the caller's Bitcoin address, so that the address can
+/// be watched by any descriptor-aware wallet.
+///
+/// A P2WPKH address is described as `wpkh(<public key>)` and a vault as the `wsh(...)` of its
+/// miniscript.  Every caller has a single key per address type, so the descriptors have no
+/// wildcards.  Taproot outputs commit to a script tree that the caller chooses per request, so
+/// their descriptors are returned by `btc_taproot_address` instead.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the descriptor, its checksum and its address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_descriptor(
+    params: GetAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcDescriptorResponse, GetAddressError> {
+    let policy = match &params.address_type {
+        BitcoinAddressType::P2WPKH => None,
+        BitcoinAddressType::P2WSHVault(policy) => Some(
+            vault::parse_vault_policy(policy)
+                .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?,
+        ),
+    };
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcDescriptor.fee(),
+        )
+        .await?;
+    let public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+        .await
+        .map_err(|msg| GetAddressError::InternalError { msg })?;
+    match policy {
+        None => Ok(BtcDescriptorResponse {
+            descriptor: descriptor::wpkh(&public_key),
+            address: ChainParams::bitcoin(params.network).address(&public_key),
+        }),
+        Some(policy) => {
+            let vault = vault::vault(public_key, &policy)
+                .map_err(|msg| GetAddressError::InvalidVaultPolicy { msg })?;
+            Ok(BtcDescriptorResponse {
+                descriptor: descriptor::vault(&public_key, &policy),
+                address: vault.address(params.network).to_string(),
+            })
         }
     }
 }
@@ -465,7 +630,7 @@ pub async fn btc_caller_address(
 /// # Details
 /// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
-/// - Converts the public key to a P2WPKH address.
+/// - Converts the public key to a P2WPKH address, or to the P2WSH address of a vault.
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
 /// - Gets the Bitcoin balance from [the deprecated system Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_get_balance)
 ///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
@@ -478,6 +643,10 @@ pub async fn btc_caller_balance(
     payment: Option<PaymentType>, /* Note: Do NOT use underscore, please, so that the underscore
                                    * doesn't show up in the generated candid. */
 ) -> Result<GetBalanceResponse, GetBalanceError> {
+    if let BitcoinAddressType::P2WSHVault(policy) = &params.address_type {
+        vault::parse_vault_policy(policy)
+            .map_err(|msg| GetBalanceError::InvalidVaultPolicy { msg })?;
+    }
     PAYMENT_GUARD
         .deduct(
             payment.unwrap_or(PaymentType::AttachedCycles),
@@ -495,46 +664,193 @@ pub async fn btc_caller_balance(
                     .await
                     .map_err(|msg| GetBalanceError::InternalError { msg })?;
 
+            Ok(GetBalanceResponse { balance })
+        }
+        BitcoinAddressType::P2WSHVault(policy) => {
+            let policy = vault::parse_vault_policy(&policy)
+                .map_err(|msg| GetBalanceError::InvalidVaultPolicy { msg })?;
+            let own_public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+                .await
+                .map_err(|msg| GetBalanceError::InternalError { msg })?;
+            let vault = vault::vault(own_public_key, &policy)
+                .map_err(|msg| GetBalanceError::InvalidVaultPolicy { msg })?;
+
+            let balance = bitcoin_api::get_balance(
+                params.network,
+                vault.address(params.network).to_string(),
+                params.min_confirmations,
+            )
+            .await
+            .map_err(|msg| GetBalanceError::InternalError { msg })?;
+
             Ok(GetBalanceResponse { balance })
         }
     }
 }
 
-/// Internal helper that builds and signs a P2WPKH transaction.
-async fn sign_btc_transaction_p2wpkh(
+/// The fee of `btc_caller_sign` and `btc_caller_sign_job`.
+fn caller_sign_fee(params: &SendBtcRequest) -> u128 {
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    let verification_fee = if params.verify_utxos.is_some() {
+        SignerMethods::BtcCallerSign.btc_utxo_verification_fee()
+    } else {
+        0
+    };
+    SignerMethods::BtcCallerSign.btc_fee_for_inputs(n_inputs) + verification_fee
+}
+
+/// The fee of `btc_caller_send` and `btc_caller_send_job`.
+fn caller_send_fee(params: &SendBtcRequest) -> u128 {
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    // The builder may append a change output, so price one extra output beyond those
+    // the caller requested. Pricing outputs (not just inputs) prevents a caller from
+    // inflating the byte-based `bitcoin_send_transaction` cost with many cheap outputs.
+    let n_outputs = params.outputs.len() as u64 + 1;
+    let n_data_bytes = params
+        .outputs
+        .iter()
+        .filter_map(BtcTxOutput::data)
+        .map(|data| data.len() as u64)
+        .sum();
+    let verification_fee = if params.verify_utxos.is_some() {
+        SignerMethods::BtcCallerSend.btc_utxo_verification_fee()
+    } else {
+        0
+    };
+    SignerMethods::BtcCallerSend.btc_fee_for_tx_with_data(n_inputs, n_outputs, n_data_bytes)
+        + verification_fee
+}
+
+/// Internal helper that rejects requests that need the Bitcoin canister on a network without one.
+fn require_bitcoin_canister(network: BitcoinNetwork) -> Result<(), SendBtcError> {
+    bitcoin_api::canister_network(network)
+        .map(|_| ())
+        .ok_or(SendBtcError::NoBitcoinCanister { network })
+}
+
+/// The change output of a transaction that `prepare_btc_transaction_p2wpkh` built for `params`.
+fn send_change_output_index(
     params: &SendBtcRequest,
-) -> Result<sign::bitcoin::tx_utils::SignedTransaction, SendBtcError> {
+    transaction: &bitcoin::Transaction,
+) -> Option<u32> {
+    change_output_index(transaction, params.outputs.len())
+}
+
+/// Internal helper that builds a P2WPKH transaction from the caller's address and checks its fee.
+///
+/// Returns the source address, the unsigned transaction and the sighash type of every input.
+async fn prepare_btc_transaction_p2wpkh(
+    params: &SendBtcRequest,
+) -> Result<(String, bitcoin::Transaction, Vec<bitcoin::EcdsaSighashType>), SendBtcError> {
+    let send_all = params.send_all.unwrap_or(false);
+    let n_payments = params
+        .outputs
+        .iter()
+        .filter(|output| matches!(output, BtcTxOutput::Payment { .. }))
+        .count();
+    if send_all && n_payments != 1 {
+        return Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::SendAllWithoutSingleOutput {
+                outputs: n_payments as u64,
+            },
+        ));
+    }
     let principal = msg_caller();
     let source_address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &principal)
         .await
         .map_err(|msg| SendBtcError::InternalError { msg })?;
-    let fee = calculate_fee(
+    if let Some(verification) = params.verify_utxos {
+        let known_utxos = bitcoin_api::get_utxos(
+            params.network,
+            source_address.clone(),
+            verification.min_confirmations,
+        )
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+        verify_utxos(&params.utxos_to_spend, &known_utxos)?;
+    }
+    let fee = RequestFee::resolve(
         params.fee_satoshis,
-        &params.utxos_to_spend,
+        params.fee_rate,
+        params.allow_high_fee,
         params.network,
-        params.outputs.len() as u64,
     )
-    .await
-    .map_err(|msg| SendBtcError::InternalError { msg })?;
+    .await?;
 
-    let transaction = build_p2wpkh_transaction(
-        &source_address,
-        params.network,
-        &params.utxos_to_spend,
-        fee,
-        &params.outputs,
-    )
+    let mut transaction = if send_all {
+        build_p2wpkh_sweep_transaction_with_fee(
+            &source_address,
+            params.network,
+            &params.utxos_to_spend,
+            fee.fee,
+            &params.outputs,
+        )
+    } else {
+        build_p2wpkh_transaction_with_fee(
+            &source_address,
+            params.network,
+            &params.utxos_to_spend,
+            fee.fee,
+            &params.outputs,
+        )
+    }
     .map_err(SendBtcError::BuildP2wpkhError)?;
+    if params.disable_rbf.unwrap_or(false) {
+        disable_rbf(&mut transaction);
+    }
+    let sighash_types = input_sighash_types(&transaction, params.sighash_types.as_deref())
+        .map_err(SendBtcError::BuildP2wpkhError)?;
+
+    // A sweep has no change, so it sends the value of all its outputs.
+    let sent_satoshis = if send_all {
+        Some(
+            transaction
+                .output
+                .iter()
+                .map(|output| output.value.to_sat())
+                .sum(),
+        )
+    } else {
+        sent_satoshis(&params.outputs)
+    };
+    fee.check(
+        transaction_fee(&transaction, &params.utxos_to_spend),
+        estimate_signed_vsize(&transaction),
+        sent_satoshis,
+    )
+    .await?;
+    Ok((source_address, transaction, sighash_types))
+}
 
-    btc_sign_transaction(
+/// Internal helper that builds and signs a P2WPKH transaction.
+async fn sign_btc_transaction_p2wpkh(
+    params: &SendBtcRequest,
+) -> Result<SignedTransaction, SendBtcError> {
+    let principal = msg_caller();
+    let (source_address, transaction, sighash_types) =
+        prepare_btc_transaction_p2wpkh(params).await?;
+    let change_output_index = send_change_output_index(params, &transaction);
+    let signed_transaction = btc_sign_transaction(
         &principal,
         transaction,
         &params.utxos_to_spend,
+        &sighash_types,
         source_address,
         params.network,
     )
     .await
-    .map_err(|msg| SendBtcError::InternalError { msg })
+    .map_err(|msg| SendBtcError::InternalError { msg })?
+    .with_change_output_index(change_output_index);
+    mutate_state(|state| {
+        journal::record_signed(
+            &mut state.signed_transactions,
+            principal,
+            params.network,
+            &signed_transaction,
+            time(),
+        );
+    });
+    Ok(signed_transaction)
 }
 
 /// Creates and signs a BTC transaction from the caller's address without broadcasting it.
@@ -544,9 +860,28 @@ async fn sign_btc_transaction_p2wpkh(
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
 /// - Converts the public key to a P2WPKH address.
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - If `verify_utxos` is set, gets the UTXOs of the caller's address with
+///   `bitcoin_api::get_utxos(..)`, and checks the outpoint and the value of every input against
+///   them before signing.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
 /// - For every transaction input:
 ///   - Calls `sign_with_ecdsa(..)` on that input.
 ///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Keeps the signed transaction; see `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// Transactions can be signed for networks without a Bitcoin canister, such as Signet, to be
+/// broadcast elsewhere.  On those networks, fee priorities use the default fee rate and
+/// `verify_utxos` is rejected with `NoBitcoinCanister`.
+///
+/// The fee, including that of `verify_utxos`, is charged in full before the inputs are verified.
+/// Charging the signatures separately, once the inputs pass, would take a second payment from the
+/// caller, and with ICRC-2 a second transfer with its own ledger fee.  A request whose inputs fail
+/// the verification thus pays for signatures that it does not get.
 ///
 /// # Panics
 /// - If the caller is the anonymous user.
@@ -555,22 +890,23 @@ pub async fn btc_caller_sign(
     params: SendBtcRequest,
     payment: Option<PaymentType>,
 ) -> Result<SignBtcResponse, SendBtcError> {
-    let n_inputs = params.utxos_to_spend.len() as u64;
+    // Vaults need the cosigner's signature; they are spent with `btc_vault_sign`.
+    if let BitcoinAddressType::P2WSHVault(_) = params.address_type {
+        return Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::NotP2WPKHSourceAddress,
+        ));
+    }
+    if params.verify_utxos.is_some() {
+        require_bitcoin_canister(params.network)?;
+    }
+    check_sighash_types(params.sighash_types.as_deref()).map_err(SendBtcError::BuildP2wpkhError)?;
     PAYMENT_GUARD
         .deduct(
             payment.unwrap_or(PaymentType::AttachedCycles),
-            SignerMethods::BtcCallerSign.btc_fee_for_inputs(n_inputs),
+            caller_sign_fee(&params),
         )
         .await?;
-    match params.address_type {
-        BitcoinAddressType::P2WPKH => {
-            let signed_transaction = sign_btc_transaction_p2wpkh(&params).await?;
-            Ok(SignBtcResponse {
-                signed_transaction_hex: hex::encode(&signed_transaction.signed_transaction_bytes),
-                txid: signed_transaction.txid,
-            })
-        }
-    }
+    Ok(sign_btc_transaction_p2wpkh(&params).await?.into())
 }
 
 /// Signs a precomputed 32-byte digest under the caller's Bitcoin key.
@@ -626,6 +962,49 @@ pub async fn btc_sign_prehash(
     })
 }
 
+/// Charges for, signs and sends a `btc_caller_send` transaction.
+async fn send_btc_transaction(
+    params: &SendBtcRequest,
+    payment: Option<PaymentType>,
+) -> Result<SendBtcResponse, SendBtcError> {
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            caller_send_fee(params),
+        )
+        .await?;
+    sign_and_send_btc_transaction(params).await
+}
+
+/// Signs and sends a transaction from the caller's address.
+async fn sign_and_send_btc_transaction(
+    params: &SendBtcRequest,
+) -> Result<SendBtcResponse, SendBtcError> {
+    match params.address_type {
+        BitcoinAddressType::P2WPKH => {
+            let signed_transaction = sign_btc_transaction_p2wpkh(params).await?;
+
+            reservations::send_reserved(
+                msg_caller(),
+                params.network,
+                &params.utxos_to_spend,
+                &signed_transaction,
+            )
+            .await
+            .map_err(|msg| SendBtcError::BroadcastFailed {
+                txid: signed_transaction.txid.clone(),
+                msg,
+            })?;
+
+            Ok(signed_transaction.into())
+        }
+        // Vaults need the cosigner's signature; they are spent with `btc_vault_sign`.
+        BitcoinAddressType::P2WSHVault(_) => Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::NotP2WPKHSourceAddress,
+        )),
+    }
+}
+
 /// Creates, signs and sends a BTC transaction from the caller's address.
 ///
 /// # Details
@@ -633,12 +1012,28 @@ pub async fn btc_sign_prehash(
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
 /// - Converts the public key to a P2WPKH address.
 ///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - If `verify_utxos` is set, gets the UTXOs of the caller's address with
+///   `bitcoin_api::get_utxos(..)`, and checks the outpoint and the value of every input against
+///   them before signing.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
 /// - For every transaction input:
 ///   - Calls `sign_with_ecdsa(..)` on that input.
 ///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs and keeps the signed transaction; see `btc_pending_transactions` and
+///   `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
 /// - Sends the transaction with `bitcoin_api::send_transaction(..)`
 ///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
 ///
+/// With `send_all`, the only output receives the value of all inputs less the fee, and the
+/// transaction has no change output, e.g. to empty the caller's address.
+///
+/// The fee is charged in full before the inputs are verified, as for `btc_caller_sign`.
+///
 /// # Panics
 /// - If the caller is the anonymous user.
 #[update(guard = "caller_is_not_anonymous")]
@@ -646,33 +1041,1877 @@ pub async fn btc_caller_send(
     params: SendBtcRequest,
     payment: Option<PaymentType>,
 ) -> Result<SendBtcResponse, SendBtcError> {
-    let n_inputs = params.utxos_to_spend.len() as u64;
-    // The builder may append a change output, so price one extra output beyond those
-    // the caller requested. Pricing outputs (not just inputs) prevents a caller from
-    // inflating the byte-based `bitcoin_send_transaction` cost with many cheap outputs.
-    let n_outputs = params.outputs.len() as u64 + 1;
+    require_bitcoin_canister(params.network)?;
+    check_data_outputs(&params.outputs).map_err(SendBtcError::BuildP2wpkhError)?;
+    check_sighash_types(params.sighash_types.as_deref()).map_err(SendBtcError::BuildP2wpkhError)?;
+    reservations::spend_reserved(
+        msg_caller(),
+        &params.utxos_to_spend,
+        send_btc_transaction(&params, payment),
+    )
+    .await
+}
+
+/// Charges for, selects, signs and sends a `btc_consolidate` transaction.
+///
+/// The UTXOs supplied by the caller are reserved already; UTXOs that are looked up instead are
+/// reserved once they are selected.
+async fn consolidate_utxos(
+    params: &BtcConsolidateRequest,
+    payment: Option<PaymentType>,
+    max_inputs: u64,
+) -> Result<SendBtcResponse, SendBtcError> {
+    let (n_inputs, lookup_fee) = if let Some(utxos) = &params.utxos {
+        (utxos.len() as u64, 0)
+    } else {
+        (
+            max_inputs,
+            SignerMethods::BtcConsolidate.btc_utxo_verification_fee(),
+        )
+    };
     PAYMENT_GUARD
         .deduct(
             payment.unwrap_or(PaymentType::AttachedCycles),
-            SignerMethods::BtcCallerSend.btc_fee_for_tx(n_inputs, n_outputs),
+            SignerMethods::BtcConsolidate.btc_fee_for_tx(n_inputs, 1) + lookup_fee,
         )
         .await?;
-    match params.address_type {
-        BitcoinAddressType::P2WPKH => {
-            let signed_transaction = sign_btc_transaction_p2wpkh(&params).await?;
-
-            bitcoin_api::send_transaction(
-                params.network,
-                signed_transaction.signed_transaction_bytes,
-            )
+    let principal = msg_caller();
+    let source_address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &principal)
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+    let fee_rate = params
+        .fee_rate
+        .unwrap_or(BtcFeeRate::Priority(BtcFeePriority::Low));
+    let destination_address = params
+        .destination_address
+        .clone()
+        .unwrap_or_else(|| source_address.clone());
+    let request = |utxos_to_spend| SendBtcRequest {
+        network: params.network,
+        address_type: BitcoinAddressType::P2WPKH,
+        utxos_to_spend,
+        fee_satoshis: None,
+        fee_rate: Some(fee_rate),
+        outputs: vec![BtcTxOutput::Payment {
+            destination_address: destination_address.clone(),
+            sent_satoshis: 0,
+        }],
+        allow_high_fee: params.allow_high_fee,
+        disable_rbf: None,
+        sighash_types: None,
+        verify_utxos: None,
+        send_all: Some(true),
+    };
+    if let Some(utxos) = &params.utxos {
+        return sign_and_send_btc_transaction(&request(utxos.clone())).await;
+    }
+    let known_utxos =
+        bitcoin_api::get_utxos(params.network, source_address, params.min_confirmations)
             .await
             .map_err(|msg| SendBtcError::InternalError { msg })?;
+    let fee_rate_millisat_per_vbyte = resolve_fee_rate(fee_rate, params.network)
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+    let unreserved = read_state(|state| {
+        reservations::unreserved_utxos(&state.utxo_reservations, principal, known_utxos, time())
+    });
+    let selected = consolidation::select_utxos(unreserved, fee_rate_millisat_per_vbyte, max_inputs);
+    let request = request(selected);
+    reservations::spend_reserved(
+        principal,
+        &request.utxos_to_spend,
+        sign_and_send_btc_transaction(&request),
+    )
+    .await
+}
 
-            Ok(SendBtcResponse {
-                txid: signed_transaction.txid,
-            })
-        }
+/// Merges UTXOs of the caller's P2WPKH address into a single output and sends the transaction.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - If `utxos` is not set, gets the UTXOs of the caller's address with
+///   `bitcoin_api::get_utxos(..)` and picks those to merge.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs and keeps the signed transaction; see `btc_pending_transactions` and
+///   `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Sends the transaction with `bitcoin_api::send_transaction(..)`
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+///
+/// The transaction is a `btc_caller_send` transaction with `send_all`: the merged output receives
+/// the value of all inputs less the fee, and there is no change output.  If `utxos` is not set,
+/// the fee is charged for `max_inputs` inputs, however many UTXOs are merged.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_consolidate(
+    params: BtcConsolidateRequest,
+    payment: Option<PaymentType>,
+) -> Result<SendBtcResponse, SendBtcError> {
+    let max_inputs = params
+        .max_inputs
+        .map_or(DEFAULT_CONSOLIDATION_INPUTS, u64::from);
+    require_bitcoin_canister(params.network)?;
+    // The fee is charged for `max_inputs`, so it must not exceed the inputs a call can sign.
+    if params.utxos.is_none() && max_inputs > MAX_CONSOLIDATION_INPUTS {
+        return Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::TooManyInputs {
+                inputs: max_inputs,
+                max_inputs: MAX_CONSOLIDATION_INPUTS,
+            },
+        ));
+    }
+    reservations::spend_reserved(
+        msg_caller(),
+        params.utxos.as_deref().unwrap_or_default(),
+        consolidate_utxos(&params, payment, max_inputs),
+    )
+    .await
+}
+
+/// Charges for, signs and sends a `btc_deposit_to_ckbtc` transaction to the deposit address of
+/// `account`, and returns that address with the sent transaction.
+async fn deposit_to_ckbtc(
+    params: &BtcDepositToCkbtcRequest,
+    account: &ckbtc::MinterAccount,
+    payment: Option<PaymentType>,
+) -> Result<(String, SendBtcResponse), SendBtcError> {
+    // The deposit output and a potential change output.
+    let n_outputs = 2;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcDepositToCkbtc
+                .btc_fee_for_tx(params.utxos_to_spend.len() as u64, n_outputs),
+        )
+        .await?;
+    let deposit_address = ckbtc::get_btc_address(ckbtc::ckbtc_minter(), account)
+        .await
+        .map_err(|msg| SendBtcError::CkbtcMinterError { msg })?;
+    // A minter of another network would receive coins it never sees.
+    if ChainParams::bitcoin(params.network)
+        .script_pubkey(&deposit_address)
+        .is_err()
+    {
+        return Err(SendBtcError::CkbtcMinterError {
+            msg: format!(
+                "the deposit address {deposit_address} is not an address of {:?}",
+                params.network
+            ),
+        });
     }
+    let response = sign_and_send_btc_transaction(&SendBtcRequest {
+        network: params.network,
+        address_type: BitcoinAddressType::P2WPKH,
+        utxos_to_spend: params.utxos_to_spend.clone(),
+        fee_satoshis: params.fee_satoshis,
+        fee_rate: params.fee_rate,
+        outputs: vec![BtcTxOutput::Payment {
+            destination_address: deposit_address.clone(),
+            sent_satoshis: params.deposit_satoshis,
+        }],
+        allow_high_fee: params.allow_high_fee,
+        disable_rbf: None,
+        sighash_types: None,
+        verify_utxos: None,
+        send_all: params.send_all,
+    })
+    .await?;
+    Ok((deposit_address, response))
+}
+
+/// Deposits BTC from the caller's P2WPKH address to a ckBTC account.
+///
+/// # Details
+/// - Gets the deposit address of the ckBTC account from the `get_btc_address` method of the ckBTC
+///   minter set in `Config::ckbtc_minter`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi and
+///   `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs and keeps the signed transaction; see `btc_pending_transactions` and
+///   `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Sends the transaction with `bitcoin_api::send_transaction(..)`
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - If `update_balance` is set, calls the minter's `update_balance` for the ckBTC account.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// The transaction is a `btc_caller_send` transaction with a single output to the deposit
+/// address.  The minter mints ckBTC only once the transaction has enough confirmations, so
+/// `update_balance` right after the deposit usually answers `NoNewUtxos`; call the minter's
+/// `update_balance` again later.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_deposit_to_ckbtc(
+    params: BtcDepositToCkbtcRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcDepositToCkbtcResponse, SendBtcError> {
+    require_bitcoin_canister(params.network)?;
+    let principal = msg_caller();
+    let account =
+        ckbtc::MinterAccount::new(params.owner.unwrap_or(principal), params.subaccount.clone())
+            .map_err(|msg| SendBtcError::CkbtcMinterError { msg })?;
+    let (deposit_address, response) = reservations::spend_reserved(
+        principal,
+        &params.utxos_to_spend,
+        deposit_to_ckbtc(&params, &account, payment),
+    )
+    .await?;
+    let update_balance = if params.update_balance.unwrap_or(false) {
+        Some(ckbtc::update_balance(ckbtc::ckbtc_minter(), &account).await)
+    } else {
+        None
+    };
+    Ok(BtcDepositToCkbtcResponse {
+        deposit_address,
+        txid: response.txid,
+        fee_satoshis: response.fee_satoshis,
+        fee_rate_millisat_per_vbyte: response.fee_rate_millisat_per_vbyte,
+        update_balance,
+    })
+}
+
+/// Internal helper that decodes the original transaction of a `btc_bump_fee` request, given in the
+/// request or by the txid of a transaction in the caller's journal.
+fn bump_fee_original(
+    params: &BtcBumpFeeRequest,
+    principal: Principal,
+) -> Result<bitcoin::Transaction, BtcBumpFeeError> {
+    match (&params.signed_transaction_hex, &params.txid) {
+        (Some(signed_transaction_hex), None) => {
+            bump_fee::decode_signed_transaction(signed_transaction_hex)
+        }
+        (None, Some(txid)) => {
+            let record = read_state(|state| {
+                journal::signed_transaction(&state.signed_transactions, principal, txid, time())
+            })
+            .ok_or(BtcBumpFeeError::UnknownTransaction)?;
+            if record.network != params.network {
+                return Err(BtcBumpFeeError::InvalidTransaction {
+                    msg: format!("the transaction was signed for {:?}", record.network),
+                });
+            }
+            bump_fee::deserialize_signed_transaction(&record.signed_transaction_bytes)
+        }
+        _ => Err(BtcBumpFeeError::InvalidTransaction {
+            msg: "exactly one of signed_transaction_hex and txid must be set".to_string(),
+        }),
+    }
+}
+
+/// Internal helper that builds, signs and sends a transaction that bumps the fee of a P2WPKH
+/// transaction.
+async fn bump_fee_p2wpkh(
+    params: &BtcBumpFeeRequest,
+    original: &bitcoin::Transaction,
+) -> Result<SignedTransaction, BtcBumpFeeError> {
+    let principal = msg_caller();
+    let source_address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &principal)
+        .await
+        .map_err(|msg| BtcBumpFeeError::InternalError { msg })?;
+    let fee = RequestFee::resolve(
+        None,
+        Some(params.fee_rate),
+        params.allow_high_fee,
+        params.network,
+    )
+    .await?;
+    let Fee::Rate(fee_rate) = fee.fee else {
+        unreachable!("A request without an absolute fee resolves to a fee rate");
+    };
+
+    let bump = match params.mode {
+        BtcBumpFeeMode::Rbf => bump_fee::build_replacement_transaction(
+            original,
+            &params.utxos_to_spend,
+            &source_address,
+            params.network,
+            fee_rate,
+        )?,
+        BtcBumpFeeMode::Cpfp => bump_fee::build_cpfp_transaction(
+            original,
+            &params.utxos_to_spend,
+            &source_address,
+            params.network,
+            fee_rate,
+        )?,
+    };
+
+    fee.check(
+        bump.package_fee_satoshis,
+        bump.package_vsize,
+        Some(bump.sent_satoshis),
+    )
+    .await?;
+
+    let sighash_types = input_sighash_types(&bump.transaction, None)?;
+    let send = async {
+        let signed_transaction = btc_sign_transaction(
+            &principal,
+            bump.transaction,
+            &bump.utxos,
+            &sighash_types,
+            source_address,
+            params.network,
+        )
+        .await
+        .map_err(|msg| BtcBumpFeeError::InternalError { msg })?;
+        mutate_state(|state| {
+            journal::record_signed(
+                &mut state.signed_transactions,
+                principal,
+                params.network,
+                &signed_transaction,
+                time(),
+            );
+        });
+        reservations::send_reserved(principal, params.network, &bump.utxos, &signed_transaction)
+            .await
+            .map_err(|msg| BtcBumpFeeError::BroadcastFailed {
+                txid: signed_transaction.txid.clone(),
+                msg,
+            })?;
+        Ok(signed_transaction)
+    };
+    match params.mode {
+        // The replacement spends the inputs of the original, and takes over their reservations
+        // once it is sent.
+        BtcBumpFeeMode::Rbf => send.await,
+        BtcBumpFeeMode::Cpfp => reservations::spend_reserved(principal, &bump.utxos, send).await,
+    }
+}
+
+/// Increases the fee of a transaction from the caller's address that is stuck in the mempool, and
+/// sends the transaction that does so.
+///
+/// # Details
+/// - Decodes the original transaction, given in the request or by the txid of a transaction that
+///   the signer signed for the caller.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi per vbyte
+///   and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Builds the new transaction:
+///   - `Rbf`: A replacement that spends the same inputs and takes the higher fee from the change
+///     output.  The original transaction has to signal replace-by-fee.
+///   - `Cpfp`: A transaction that spends the change output of the original transaction, paying
+///     enough for both transactions together to reach the fee rate.
+/// - For every input of the new transaction:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs and keeps the new transaction, like `btc_caller_send`.  A replacement
+///   takes over the reservations of the original's inputs.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Sends the new transaction with `bitcoin_api::send_transaction(..)`
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_bump_fee(
+    params: BtcBumpFeeRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcBumpFeeResponse, BtcBumpFeeError> {
+    require_bitcoin_canister(params.network)?;
+    let principal = msg_caller();
+    let original = bump_fee_original(&params, principal)?;
+    let (n_inputs, n_outputs, n_data_bytes) = match params.mode {
+        BtcBumpFeeMode::Rbf => (
+            original.input.len() as u64,
+            original.output.len() as u64,
+            op_return_data_bytes(&original),
+        ),
+        BtcBumpFeeMode::Cpfp => (1, 1, 0),
+    };
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcBumpFee.btc_fee_for_tx_with_data(n_inputs, n_outputs, n_data_bytes),
+        )
+        .await?;
+    match params.address_type {
+        BitcoinAddressType::P2WPKH => {
+            let signed_transaction = bump_fee_p2wpkh(&params, &original).await?;
+            Ok(BtcBumpFeeResponse {
+                signed_transaction_hex: hex::encode(&signed_transaction.signed_transaction_bytes),
+                fee_rate_millisat_per_vbyte: signed_transaction.fee_rate_millisat_per_vbyte(),
+                txid: signed_transaction.txid,
+                fee_satoshis: signed_transaction.fee_satoshis,
+            })
+        }
+        BitcoinAddressType::P2WSHVault(_) => Err(BtcBumpFeeError::BuildP2wpkhError(
+            BuildP2wpkhTxError::NotP2WPKHSourceAddress,
+        )),
+    }
+}
+
+/// Returns the address and witness script of a P2WSH multisig account of the caller and the given
+/// cosigners.
+///
+/// The caller's Bitcoin key is added to the cosigners' public keys and the keys are sorted, so the
+/// account does not depend on the order of the cosigners.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the `threshold`-of-n witness script and converts it to a P2WSH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_multisig_address(
+    params: BtcMultisigAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcMultisigAddressResponse, BtcMultisigAddressError> {
+    let cosigner_public_keys =
+        multisig::parse_cosigner_public_keys(&params.cosigner_public_keys, params.threshold)?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcMultisigAddress.fee(),
+        )
+        .await?;
+    let own_public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+        .await
+        .map_err(|msg| BtcMultisigAddressError::InternalError { msg })?;
+    let account =
+        multisig::multisig_account(own_public_key, &cosigner_public_keys, params.threshold)?;
+
+    Ok(BtcMultisigAddressResponse {
+        address: account.address(params.network).to_string(),
+        witness_script_hex: hex::encode(account.witness_script.as_bytes()),
+        public_keys: account
+            .public_keys
+            .iter()
+            .map(|public_key| hex::encode(public_key.to_bytes()))
+            .collect(),
+    })
+}
+
+/// Adds the caller's partial signatures to a PSBT, e.g. to co-sign a spend from a multisig
+/// account.
+///
+/// Every P2WSH input whose witness script contains the caller's Bitcoin key is signed, with the
+/// sighash type of the input in the PSBT or `All`.  The PSBT is not finalized.  Inputs with the
+/// sighash type `None` or `NonePlusAnyoneCanPay` are rejected.
+///
+/// The value of every input is taken from its `witness_utxo` and is not checked against a
+/// `non_witness_utxo` or the UTXO set.  Callers must check the input values, and hence the fee,
+/// themselves before asking for a signature.
+///
+/// # Details
+/// - Decodes the PSBT.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - For every input to sign:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_sign_psbt(
+    params: BtcSignPsbtRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcSignPsbtResponse, BtcSignPsbtError> {
+    let psbt = psbt::decode_psbt(&params.psbt_hex)?;
+    // Every input with a witness script may need a signature.
+    let n_inputs = psbt::witness_script_inputs(&psbt);
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcSignPsbt.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let (psbt, signed_inputs) = psbt::sign_psbt(&msg_caller(), psbt).await?;
+
+    Ok(BtcSignPsbtResponse {
+        psbt_hex: hex::encode(psbt.serialize()),
+        signed_inputs,
+    })
+}
+
+/// Builds a transaction that spends from a vault of the caller, and signs it on the signer's
+/// branch.
+///
+/// Returns a PSBT with the caller's signatures, for the cosigner to add theirs and finalize.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the vault's witness script and converts it to a P2WSH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_vault_sign(
+    params: BtcVaultSignRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcSignPsbtResponse, SendBtcError> {
+    let policy = vault::parse_vault_policy(&params.vault)
+        .map_err(|msg| SendBtcError::InvalidVaultPolicy { msg })?;
+    check_data_outputs(&params.outputs).map_err(SendBtcError::BuildP2wpkhError)?;
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcVaultSign.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let principal = msg_caller();
+    let own_public_key = bitcoin_utils::principal_to_public_key(&principal)
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+    let vault = vault::vault(own_public_key, &policy)
+        .map_err(|msg| SendBtcError::InvalidVaultPolicy { msg })?;
+    let fee = RequestFee::resolve(
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+        params.network,
+    )
+    .await?;
+
+    let transaction = build_vault_transaction_with_fee(
+        &vault,
+        params.network,
+        &params.utxos_to_spend,
+        fee.fee,
+        &params.outputs,
+    )
+    .map_err(SendBtcError::BuildP2wpkhError)?;
+
+    fee.check(
+        transaction_fee(&transaction, &params.utxos_to_spend),
+        estimate_signed_vault_vsize(&transaction, &vault),
+        sent_satoshis(&params.outputs),
+    )
+    .await?;
+
+    let mut psbt = vault_psbt(transaction, &params.utxos_to_spend, &vault);
+    let inputs =
+        psbt::inputs_to_sign(&psbt, &own_public_key).map_err(|e| SendBtcError::InternalError {
+            msg: format!("{e:?}"),
+        })?;
+    let signed_inputs = psbt::add_partial_signatures(&principal, own_public_key, &mut psbt, inputs)
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+
+    Ok(BtcSignPsbtResponse {
+        psbt_hex: hex::encode(psbt.serialize()),
+        signed_inputs,
+    })
+}
+
+/// Returns the address and witness script of an HTLC between the caller and a counterparty.
+///
+/// The recipient and the sender of the HTLC derive the same address, each giving the other's key
+/// as the counterparty key.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the HTLC's witness script and converts it to a P2WSH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_htlc_address(
+    params: BtcHtlcAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcHtlcAddressResponse, BtcHtlcError> {
+    let terms = htlc::parse_htlc(&params.htlc).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcHtlcAddress.fee(),
+        )
+        .await?;
+    let own_public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+        .await
+        .map_err(|msg| BtcHtlcError::InternalError { msg })?;
+    let htlc =
+        htlc::htlc(own_public_key, &terms).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+
+    Ok(BtcHtlcAddressResponse {
+        address: htlc.address(params.network).to_string(),
+        witness_script_hex: hex::encode(htlc.witness_script.as_bytes()),
+    })
+}
+
+/// Internal helper that builds a transaction sweeping HTLC UTXOs on the branch of `spend`, checks
+/// its fee and signs it.
+#[allow(clippy::too_many_arguments)]
+async fn sign_htlc_spend(
+    htlc: &htlc::Htlc,
+    spend: &HtlcSpend<'_>,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    destination_address: &str,
+    fee_satoshis: Option<u64>,
+    fee_rate: Option<BtcFeeRate>,
+    allow_high_fee: Option<bool>,
+) -> Result<SignBtcResponse, BtcHtlcError> {
+    let fee = RequestFee::resolve(fee_satoshis, fee_rate, allow_high_fee, network).await?;
+    let transaction = build_htlc_transaction_with_fee(
+        htlc,
+        spend,
+        destination_address,
+        network,
+        utxos_to_spend,
+        fee.fee,
+    )
+    .map_err(BtcHtlcError::BuildP2wpkhError)?;
+
+    let sent_satoshis = transaction
+        .output
+        .iter()
+        .map(|output| output.value.to_sat())
+        .sum();
+    fee.check(
+        transaction_fee(&transaction, utxos_to_spend),
+        estimate_signed_htlc_vsize(&transaction, htlc, spend),
+        Some(sent_satoshis),
+    )
+    .await?;
+
+    // The transaction sweeps the HTLC to a single output, so it has no change output.
+    let signed_transaction =
+        sign_htlc_transaction(&msg_caller(), transaction, utxos_to_spend, htlc, spend)
+            .await
+            .map_err(|msg| BtcHtlcError::InternalError { msg })?;
+    Ok(signed_transaction.into())
+}
+
+/// Signs a transaction that claims HTLC UTXOs with the preimage, without broadcasting it.
+///
+/// The caller must be the recipient of the HTLC.  The preimage is put in the witness of every
+/// input, so it becomes public once the transaction is broadcast, and the sender can use it to
+/// claim the other side of a swap.
+///
+/// # Details
+/// - Checks the caller's role and that the preimage hashes to the payment hash.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_htlc_claim(
+    params: BtcHtlcClaimRequest,
+    payment: Option<PaymentType>,
+) -> Result<SignBtcResponse, BtcHtlcError> {
+    let terms = htlc::parse_htlc(&params.htlc).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+    terms.check_caller_role(BtcHtlcRole::Recipient)?;
+    let preimage = terms.check_preimage(&params.preimage)?;
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcHtlcClaim.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let own_public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+        .await
+        .map_err(|msg| BtcHtlcError::InternalError { msg })?;
+    let htlc =
+        htlc::htlc(own_public_key, &terms).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+
+    sign_htlc_spend(
+        &htlc,
+        &HtlcSpend::Claim {
+            preimage: &preimage,
+        },
+        params.network,
+        &params.utxos_to_spend,
+        &params.destination_address,
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+    )
+    .await
+}
+
+/// Signs a transaction that refunds HTLC UTXOs after the timeout, without broadcasting it.
+///
+/// The caller must be the sender of the HTLC, and the chain must have reached the timeout: the
+/// transaction is locked until the timeout, so nodes would not accept it earlier.
+///
+/// # Details
+/// - Checks the caller's role.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the height of the chain tip with `bitcoin_api::get_tip_height(..)`, to check the timeout.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_htlc_refund(
+    params: BtcHtlcRefundRequest,
+    payment: Option<PaymentType>,
+) -> Result<SignBtcResponse, BtcHtlcError> {
+    require_bitcoin_canister(params.network)?;
+    // The timeout is checked after charging, as reading the chain tip costs cycles.
+    let terms = htlc::parse_htlc(&params.htlc).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+    terms.check_caller_role(BtcHtlcRole::Sender)?;
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcHtlcRefund.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let own_public_key = bitcoin_utils::principal_to_public_key(&msg_caller())
+        .await
+        .map_err(|msg| BtcHtlcError::InternalError { msg })?;
+    let htlc =
+        htlc::htlc(own_public_key, &terms).map_err(|msg| BtcHtlcError::InvalidHtlc { msg })?;
+
+    let tip_height =
+        bitcoin_api::get_tip_height(params.network, htlc.address(params.network).to_string())
+            .await
+            .map_err(|msg| BtcHtlcError::InternalError { msg })?;
+    if tip_height < params.htlc.timeout_block_height {
+        return Err(BtcHtlcError::TimeoutNotReached {
+            timeout_block_height: params.htlc.timeout_block_height,
+            tip_height,
+        });
+    }
+
+    sign_htlc_spend(
+        &htlc,
+        &HtlcSpend::Refund,
+        params.network,
+        &params.utxos_to_spend,
+        &params.destination_address,
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+    )
+    .await
+}
+
+/// Returns the P2TR address of a taproot output that commits to a tree of tapscript leaves, with
+/// the control block of every leaf.
+///
+/// Leaves that the caller should sign push the caller's x-only Schnorr key, which is returned as
+/// `caller_public_key`; it is also the key that `schnorr_public_key` returns for an empty
+/// derivation path.  The output descriptor of the tree is returned as `descriptor`.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::schnorr::schnorr_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the script tree, tweaks the internal key with its Merkle root and builds the
+///   descriptor.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_taproot_address(
+    params: BtcTaprootAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcTaprootAddressResponse, BtcTaprootError> {
+    let terms = taproot::parse_taproot_tree(&params.tree)
+        .map_err(|msg| BtcTaprootError::InvalidTaprootTree { msg })?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcTaprootAddress.fee(),
+        )
+        .await?;
+    let caller_key = bitcoin_utils::principal_to_x_only_public_key(&msg_caller())
+        .await
+        .map_err(|msg| BtcTaprootError::InternalError { msg })?;
+    let tree = taproot::taproot_tree(terms, caller_key);
+    let depths: Vec<u8> = params.tree.leaves.iter().map(|leaf| leaf.depth).collect();
+
+    Ok(BtcTaprootAddressResponse {
+        address: tree.address(params.network).to_string(),
+        caller_public_key: hex::encode(caller_key.serialize()),
+        internal_key: hex::encode(tree.spend_info.internal_key().serialize()),
+        merkle_root: tree
+            .spend_info
+            .merkle_root()
+            .map(|merkle_root| hex::encode(merkle_root.to_byte_array()))
+            .unwrap_or_default(),
+        control_blocks: tree
+            .leaves
+            .iter()
+            .map(|leaf| hex::encode(tree.control_block(leaf).serialize()))
+            .collect(),
+        descriptor: descriptor::taproot(&tree, &depths),
+    })
+}
+
+/// Builds a transaction that spends taproot UTXOs on the script path of one leaf, and signs it
+/// without broadcasting it.
+///
+/// Every input is signed with the caller's untweaked Schnorr key, and its witness is the
+/// signature, the leaf script and the control block.  So the leaf must be satisfied by the
+/// caller's signature alone, e.g. `<caller_public_key> OP_CHECKSIG`.  Any change is returned to the
+/// taproot output.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::schnorr::schnorr_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the script tree and tweaks the internal key with its Merkle root.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_schnorr(..)` on the input's BIP-341 script-path sighash.
+///   - Costs: See [Fees for the t-Schnorr production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-schnorr-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_taproot_sign(
+    params: BtcTaprootSignRequest,
+    payment: Option<PaymentType>,
+) -> Result<SignBtcResponse, BtcTaprootError> {
+    let terms = taproot::parse_taproot_tree(&params.tree)
+        .map_err(|msg| BtcTaprootError::InvalidTaprootTree { msg })?;
+    let leaf_index = usize::try_from(params.leaf_index).unwrap_or(usize::MAX);
+    if leaf_index >= terms.leaves.len() {
+        return Err(BtcTaprootError::LeafNotFound {
+            leaf_index: params.leaf_index,
+            leaves: terms.leaves.len() as u64,
+        });
+    }
+    check_data_outputs(&params.outputs).map_err(BtcTaprootError::BuildP2wpkhError)?;
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcTaprootSign.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let principal = msg_caller();
+    let caller_key = bitcoin_utils::principal_to_x_only_public_key(&principal)
+        .await
+        .map_err(|msg| BtcTaprootError::InternalError { msg })?;
+    if !taproot::pushes_x_only_key(&terms.leaves[leaf_index], &caller_key) {
+        return Err(BtcTaprootError::LeafWithoutCallerKey);
+    }
+    let tree = taproot::taproot_tree(terms, caller_key);
+    let leaf = TapLeafSpend::new(&tree, leaf_index).expect("The leaf index was checked");
+    let fee = RequestFee::resolve(
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+        params.network,
+    )
+    .await?;
+
+    let transaction = build_taproot_transaction_with_fee(
+        &tree,
+        &leaf,
+        params.network,
+        &params.utxos_to_spend,
+        fee.fee,
+        &params.outputs,
+    )
+    .map_err(BtcTaprootError::BuildP2wpkhError)?;
+    let change_output_index = change_output_index(&transaction, params.outputs.len());
+
+    fee.check(
+        transaction_fee(&transaction, &params.utxos_to_spend),
+        estimate_signed_taproot_vsize(&transaction, &leaf),
+        sent_satoshis(&params.outputs),
+    )
+    .await?;
+
+    let signed_transaction = sign_taproot_script_path_transaction(
+        &principal,
+        transaction,
+        &params.utxos_to_spend,
+        &tree,
+        &leaf,
+    )
+    .await
+    .map_err(|msg| BtcTaprootError::InternalError { msg })?
+    .with_change_output_index(change_output_index);
+    Ok(signed_transaction.into())
+}
+
+/// Creates, signs and sends a transaction from the caller's address that transfers runes, etches
+/// a new rune, or both.
+///
+/// The transaction spends the `rune_utxos` and then the `fee_utxos`.  Its outputs are:
+/// - One output per transfer, to the transfer's destination, in order.
+/// - An output to the caller that receives the runes left over after the transfers, including any
+///   premine not transferred.
+/// - The runestone, an `OP_RETURN` output with an edict per transfer that points the left-over
+///   runes to the previous output.
+/// - The change, if it is above the dust threshold.
+///
+/// Every output that receives runes holds `postage_satoshis`.  The change output never receives
+/// runes, so it can later be spent as a fee UTXO.
+///
+/// # Details
+/// - Encodes the runestone.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs and keeps the signed transaction; see `btc_pending_transactions` and
+///   `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Sends the transaction with `bitcoin_api::send_transaction(..)`
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_send_runes(
+    params: BtcSendRunesRequest,
+    payment: Option<PaymentType>,
+) -> Result<SendBtcResponse, BtcSendRunesError> {
+    require_bitcoin_canister(params.network)?;
+    let runestone = runes::runestone(&params)
+        .map_err(|msg| BtcSendRunesError::InvalidRunestone { msg })?
+        .script_pubkey()
+        .map_err(BtcSendRunesError::BuildP2wpkhError)?;
+    let utxos = [params.rune_utxos.as_slice(), params.fee_utxos.as_slice()].concat();
+    reservations::spend_reserved(
+        msg_caller(),
+        &utxos,
+        send_runes_transaction(&params, payment, &runestone, &utxos),
+    )
+    .await
+}
+
+/// Charges for, builds, signs and sends a `btc_send_runes` transaction that spends `utxos`.
+async fn send_runes_transaction(
+    params: &BtcSendRunesRequest,
+    payment: Option<PaymentType>,
+    runestone: &ScriptBuf,
+    utxos: &[Utxo],
+) -> Result<SendBtcResponse, BtcSendRunesError> {
+    // The transfers, the left-over runes, the runestone and the change.
+    let n_outputs = params.transfers.len() as u64 + 3;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcSendRunes.btc_fee_for_tx_with_data(
+                utxos.len() as u64,
+                n_outputs,
+                runestone.len() as u64,
+            ),
+        )
+        .await?;
+    let principal = msg_caller();
+    let source_address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &principal)
+        .await
+        .map_err(|msg| BtcSendRunesError::InternalError { msg })?;
+    let fee = RequestFee::resolve(
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+        params.network,
+    )
+    .await?;
+
+    let transaction = build_runes_transaction_with_fee(
+        &source_address,
+        params.network,
+        utxos,
+        fee.fee,
+        &params.transfers,
+        params.postage_satoshis.unwrap_or(DUST_THRESHOLD),
+        runestone,
+    )
+    .map_err(BtcSendRunesError::BuildP2wpkhError)?;
+    // Every transfer, the leftover runes and the runestone have an output.
+    let change_output_index = change_output_index(&transaction, params.transfers.len() + 2);
+
+    // The transfers move runes, not satoshi, so the fee cannot be compared with their value.
+    fee.check(
+        transaction_fee(&transaction, utxos),
+        estimate_signed_vsize(&transaction),
+        None,
+    )
+    .await?;
+
+    let sighash_types =
+        input_sighash_types(&transaction, None).map_err(BtcSendRunesError::BuildP2wpkhError)?;
+    let signed_transaction = btc_sign_transaction(
+        &principal,
+        transaction,
+        utxos,
+        &sighash_types,
+        source_address,
+        params.network,
+    )
+    .await
+    .map_err(|msg| BtcSendRunesError::InternalError { msg })?
+    .with_change_output_index(change_output_index);
+    mutate_state(|state| {
+        journal::record_signed(
+            &mut state.signed_transactions,
+            principal,
+            params.network,
+            &signed_transaction,
+            time(),
+        );
+    });
+
+    reservations::send_reserved(principal, params.network, utxos, &signed_transaction)
+        .await
+        .map_err(|msg| BtcSendRunesError::BroadcastFailed {
+            txid: signed_transaction.txid.clone(),
+            msg,
+        })?;
+
+    Ok(signed_transaction.into())
+}
+
+/// Builds and signs the commit transaction of an inscription, without broadcasting it.
+///
+/// The commit transaction pays from the caller's P2WPKH address to a P2TR output whose script tree
+/// has a single leaf: `<caller_public_key> OP_CHECKSIG`, followed by an envelope with the content
+/// type and the body of the inscription.  The commit output holds `postage_satoshis` plus the fee
+/// of the reveal transaction at `reveal_fee_rate`; any change is returned to the caller.
+///
+/// Once the commit transaction is confirmed, `btc_inscription_reveal` spends the commit output and
+/// inscribes the content.
+///
+/// # Details
+/// - Gets the principal's public keys with `management_canister::ecdsa::ecdsa_public_key(..)` and
+///   `management_canister::schnorr::schnorr_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the envelope and the script tree, and tweaks the caller's Schnorr key with its Merkle
+///   root.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, for the reveal
+///   fee, and to compute the commit fee or to check it against the fee limits.  Skipped if both
+///   fees are given in satoshi or satoshi per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs, as if the caller had sent the transaction, and keeps the signed
+///   transaction; see `btc_pending_transactions` and `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_inscription_commit(
+    params: BtcInscriptionCommitRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcInscriptionCommitResponse, BtcInscriptionError> {
+    inscription::check_inscription(&params.inscription)
+        .map_err(|msg| BtcInscriptionError::InvalidInscription { msg })?;
+    reservations::spend_reserved(
+        msg_caller(),
+        &params.utxos_to_spend,
+        inscription_commit(&params, payment),
+    )
+    .await
+}
+
+/// Internal helper that charges for, builds and signs the commit transaction of an inscription.
+async fn inscription_commit(
+    params: &BtcInscriptionCommitRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcInscriptionCommitResponse, BtcInscriptionError> {
+    let n_inputs = params.utxos_to_spend.len() as u64;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcInscriptionCommit.btc_fee_for_inputs(n_inputs),
+        )
+        .await?;
+    let principal = msg_caller();
+    let source_address = bitcoin_utils::principal_to_p2wpkh_address(params.network, &principal)
+        .await
+        .map_err(|msg| BtcInscriptionError::InternalError { msg })?;
+    let caller_key = bitcoin_utils::principal_to_x_only_public_key(&principal)
+        .await
+        .map_err(|msg| BtcInscriptionError::InternalError { msg })?;
+    let (tree, leaf) = inscription::inscription_tree(caller_key, &params.inscription);
+    let reveal_fee_rate = resolve_fee_rate(
+        params
+            .reveal_fee_rate
+            .unwrap_or(BtcFeeRate::Priority(BtcFeePriority::Medium)),
+        params.network,
+    )
+    .await
+    .map_err(|msg| BtcInscriptionError::InternalError { msg })?;
+    let commit_satoshis = params.postage_satoshis.unwrap_or(DUST_THRESHOLD)
+        + fee_for_vsize(estimate_signed_reveal_vsize(&tree, &leaf), reveal_fee_rate);
+    let commit_address = tree.address(params.network).to_string();
+    let fee = RequestFee::resolve(
+        params.fee_satoshis,
+        params.fee_rate,
+        params.allow_high_fee,
+        params.network,
+    )
+    .await?;
+
+    let transaction = build_p2wpkh_transaction_with_fee(
+        &source_address,
+        params.network,
+        &params.utxos_to_spend,
+        fee.fee,
+        &[BtcTxOutput::Payment {
+            destination_address: commit_address.clone(),
+            sent_satoshis: commit_satoshis,
+        }],
+    )
+    .map_err(BtcInscriptionError::BuildP2wpkhError)?;
+    let change_output_index = change_output_index(&transaction, 1);
+
+    fee.check(
+        transaction_fee(&transaction, &params.utxos_to_spend),
+        estimate_signed_vsize(&transaction),
+        Some(commit_satoshis),
+    )
+    .await?;
+
+    let sighash_types =
+        input_sighash_types(&transaction, None).map_err(BtcInscriptionError::BuildP2wpkhError)?;
+    let signed_transaction = btc_sign_transaction(
+        &principal,
+        transaction,
+        &params.utxos_to_spend,
+        &sighash_types,
+        source_address,
+        params.network,
+    )
+    .await
+    .map_err(|msg| BtcInscriptionError::InternalError { msg })?
+    .with_change_output_index(change_output_index);
+    mutate_state(|state| {
+        journal::record_signed(
+            &mut state.signed_transactions,
+            principal,
+            params.network,
+            &signed_transaction,
+            time(),
+        );
+        reservations::mark_sent(
+            &mut state.utxo_reservations,
+            principal,
+            &params.utxos_to_spend,
+            &signed_transaction.txid,
+            time(),
+        );
+    });
+    Ok(BtcInscriptionCommitResponse {
+        transaction: signed_transaction.into(),
+        commit_address,
+        commit_vout: 0,
+        commit_satoshis,
+    })
+}
+
+/// Builds and signs the reveal transaction of an inscription, without broadcasting it.
+///
+/// The reveal transaction spends the commit output of `btc_inscription_commit` on the script path
+/// of the envelope leaf, which inscribes the content on the first satoshi of the input.  Its only
+/// output sends that satoshi, with the rest of `postage_satoshis`, to `destination_address`; the
+/// remaining value of the commit output pays the fee.
+///
+/// # Details
+/// - Gets the principal's public key with `management_canister::schnorr::schnorr_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds the envelope and the script tree, and tweaks the caller's Schnorr key with its Merkle
+///   root.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to check the
+///   fee against the fee limits.  Skipped if `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Calls `sign_with_schnorr(..)` on the input's BIP-341 script-path sighash.
+///   - Costs: See [Fees for the t-Schnorr production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-schnorr-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_inscription_reveal(
+    params: BtcInscriptionRevealRequest,
+    payment: Option<PaymentType>,
+) -> Result<SignBtcResponse, BtcInscriptionError> {
+    inscription::check_inscription(&params.inscription)
+        .map_err(|msg| BtcInscriptionError::InvalidInscription { msg })?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcInscriptionReveal.fee(),
+        )
+        .await?;
+    let principal = msg_caller();
+    let caller_key = bitcoin_utils::principal_to_x_only_public_key(&principal)
+        .await
+        .map_err(|msg| BtcInscriptionError::InternalError { msg })?;
+    let (tree, leaf) = inscription::inscription_tree(caller_key, &params.inscription);
+    let postage_satoshis = params.postage_satoshis.unwrap_or(DUST_THRESHOLD);
+    let utxos = [params.commit_utxo];
+
+    let transaction = build_taproot_transaction_with_fee(
+        &tree,
+        &leaf,
+        params.network,
+        &utxos,
+        Fee::Absolute(utxos[0].value.saturating_sub(postage_satoshis)),
+        &[BtcTxOutput::Payment {
+            destination_address: params.destination_address,
+            sent_satoshis: postage_satoshis,
+        }],
+    )
+    .map_err(BtcInscriptionError::BuildP2wpkhError)?;
+
+    if !params.allow_high_fee.unwrap_or(false) {
+        // The reveal sends an inscription, not satoshi, so the fee cannot be compared with the
+        // postage.
+        check_fee(
+            transaction_fee(&transaction, &utxos),
+            estimate_signed_taproot_vsize(&transaction, &leaf),
+            None,
+            false,
+            params.network,
+        )
+        .await?;
+    }
+
+    // The fee takes all of the commit output but the postage, so there is no change output.
+    let signed_transaction =
+        sign_taproot_script_path_transaction(&principal, transaction, &utxos, &tree, &leaf)
+            .await
+            .map_err(|msg| BtcInscriptionError::InternalError { msg })?;
+    Ok(signed_transaction.into())
+}
+
+/// Lists the caller's UTXOs that transactions in flight, or sent recently, spend.
+///
+/// # Details
+/// The endpoints that send a transaction reserve its inputs while it is signed, then for a day
+/// once it is sent, or for a week if `bitcoin_api::send_transaction(..)` rejected it, so that it
+/// can still be sent with `btc_rebroadcast`.  A call of the caller that spends a reserved UTXO
+/// fails with `UtxoReserved` instead of signing a conflicting transaction.  This query is free.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn btc_pending_transactions() -> BtcPendingTransactionsResponse {
+    let reserved_utxos = read_state(|state| {
+        reservations::reserved_utxos(&state.utxo_reservations, msg_caller(), time())
+    });
+    BtcPendingTransactionsResponse { reserved_utxos }
+}
+
+/// Sends a transaction signed for the caller again.
+///
+/// # Details
+/// - Sends the transaction with `bitcoin_api::send_transaction(..)`
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+///
+/// Only transactions listed by `btc_list_signed_transactions` can be sent; the fee covers the
+/// broadcast of the stored transaction, not its signatures.  Once sent, the inputs of the
+/// transaction are reserved; see `btc_pending_transactions`.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_rebroadcast(
+    params: BtcRebroadcastRequest,
+    payment: Option<PaymentType>,
+) -> Result<SendBtcResponse, BtcRebroadcastError> {
+    let principal = msg_caller();
+    let record = read_state(|state| {
+        journal::signed_transaction(&state.signed_transactions, principal, &params.txid, time())
+    })
+    .ok_or(BtcRebroadcastError::UnknownTransaction)?;
+    if bitcoin_api::canister_network(record.network).is_none() {
+        return Err(BtcRebroadcastError::NoBitcoinCanister {
+            network: record.network,
+        });
+    }
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::BtcRebroadcast.btc_fee_for_tx_with_data(
+                0,
+                0,
+                record.signed_transaction_bytes.len() as u64,
+            ),
+        )
+        .await?;
+    journal::send_signed_transaction(
+        principal,
+        record.network,
+        &params.txid,
+        record.signed_transaction_bytes.clone(),
+    )
+    .await
+    .map_err(|msg| BtcRebroadcastError::BroadcastFailed { msg })?;
+    if let Ok(transaction) =
+        bitcoin::consensus::deserialize::<bitcoin::Transaction>(&record.signed_transaction_bytes)
+    {
+        mutate_state(|state| {
+            reservations::mark_transaction_sent(
+                &mut state.utxo_reservations,
+                principal,
+                &transaction,
+                time(),
+            );
+        });
+    }
+    Ok(SendBtcResponse {
+        txid: params.txid,
+        signed_transaction_hex: hex::encode(&record.signed_transaction_bytes),
+        fee_satoshis: record.fee_satoshis,
+        fee_rate_millisat_per_vbyte: record.fee_rate_millisat_per_vbyte,
+        vsize: record.vsize,
+        change_output_index: record.change_output_index,
+    })
+}
+
+/// Lists the transactions signed for the caller in the last week, most recently signed first.
+///
+/// # Details
+/// The endpoints that sign a whole transaction keep it, whether or not they send it.  Every
+/// transaction is listed with its raw hex and whether it was sent, so that it can be recovered or
+/// sent again with `btc_rebroadcast` without paying for its signatures again.  Only the 1000 most
+/// recently signed transactions of a caller are kept.  This query is free.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[query(guard = "caller_is_not_anonymous")]
+#[must_use]
+pub fn btc_list_signed_transactions() -> BtcListSignedTransactionsResponse {
+    let transactions = read_state(|state| {
+        journal::signed_transactions(&state.signed_transactions, msg_caller(), time())
+    });
+    BtcListSignedTransactionsResponse { transactions }
+}
+
+/// Decodes a raw transaction or a PSBT, e.g. to review a transaction before approving a
+/// `btc_caller_send`, or to check the `signed_transaction_hex` of a signed transaction.
+///
+/// # Details
+/// Returns the inputs, the outputs with their addresses on the requested network, the fee, the
+/// size and the fee rate, and whether the transaction signals replaceability.  The fee is only
+/// known if the values of all inputs are, from `utxos` or from the PSBT, and is rejected if the
+/// outputs exceed them.  Outputs that pay to `own_address` are marked; the address is taken as
+/// given, so pass the one returned by `btc_caller_address`.  This query is free.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[query(guard = "caller_is_not_anonymous")]
+#[allow(clippy::needless_pass_by_value)]
+pub fn btc_decode_transaction(
+    params: BtcDecodeTransactionRequest,
+) -> Result<BtcDecodeTransactionResponse, BtcDecodeTransactionError> {
+    decode::decode_transaction(&params)
+}
+
+/// Internal helper that builds a P2WPKH transaction, computes its sighashes and starts a job that
+/// signs them.
+async fn start_signing_job(
+    params: &SendBtcRequest,
+    send: bool,
+) -> Result<BtcJobResponse, SendBtcError> {
+    let principal = msg_caller();
+    let (source_address, transaction, sighash_types) =
+        prepare_btc_transaction_p2wpkh(params).await?;
+    let change_output_index = send_change_output_index(params, &transaction);
+    let public_key = bitcoin_utils::principal_to_public_key(&principal)
+        .await
+        .map_err(|msg| SendBtcError::InternalError { msg })?;
+    let sighashes = p2wpkh_sighashes(
+        &transaction,
+        &params.utxos_to_spend,
+        &sighash_types,
+        &source_address,
+        params.network,
+    );
+    let job = SigningJob {
+        principal,
+        network: params.network,
+        send,
+        utxos: params.utxos_to_spend.clone(),
+        unsigned_transaction_bytes: bitcoin::consensus::serialize(&transaction),
+        change_output_index,
+        sighash_types: sighash_types
+            .iter()
+            .map(|sighash_type| sighash_type.to_u32())
+            .collect(),
+        sighashes,
+        signatures: vec![],
+        public_key: public_key.to_bytes().to_vec(),
+        status: SigningJobStatus::Signing,
+        created_at_ns: time(),
+        finished_at_ns: None,
+    };
+    let job_id = mutate_state(|state| jobs::create_job(&mut state.signing_jobs, job, time()));
+    jobs::schedule_batch();
+    Ok(BtcJobResponse { job_id })
+}
+
+/// Creates a BTC transaction from the caller's address, like `btc_caller_sign`, and starts a job
+/// that signs it.
+///
+/// # Details
+/// The call builds the transaction and returns the id of the job; timers then sign the inputs in
+/// batches of `max_concurrent_signatures`.  Use `btc_job_status` to follow the progress and to get
+/// the signed transaction.  This suits transactions with too many inputs to sign in one call.
+///
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - If `verify_utxos` is set, gets the UTXOs of the caller's address with
+///   `bitcoin_api::get_utxos(..)`, and checks the outpoint and the value of every input against
+///   them.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input, in a timer:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Keeps the signed transaction; see `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// The fee is that of `btc_caller_sign`, and is likewise charged in full before the inputs are
+/// verified.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_caller_sign_job(
+    params: SendBtcRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcJobResponse, SendBtcError> {
+    // Vaults need the cosigner's signature; they are spent with `btc_vault_sign`.
+    if let BitcoinAddressType::P2WSHVault(_) = params.address_type {
+        return Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::NotP2WPKHSourceAddress,
+        ));
+    }
+    if params.verify_utxos.is_some() {
+        require_bitcoin_canister(params.network)?;
+    }
+    check_sighash_types(params.sighash_types.as_deref()).map_err(SendBtcError::BuildP2wpkhError)?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            caller_sign_fee(&params),
+        )
+        .await?;
+    start_signing_job(&params, false).await
+}
+
+/// Creates a BTC transaction from the caller's address, like `btc_caller_send`, and starts a job
+/// that signs and then sends it.
+///
+/// # Details
+/// The call builds the transaction and returns the id of the job; timers then sign the inputs in
+/// batches of `max_concurrent_signatures` and send the transaction once all of them are signed.
+/// Use `btc_job_status` to follow the progress and to get the txid.
+///
+/// - Gets the principal's public key with `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - If `verify_utxos` is set, gets the UTXOs of the caller's address with
+///   `bitcoin_api::get_utxos(..)`, and checks the outpoint and the value of every input against
+///   them.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - Gets the network fee rates with `bitcoin_api::get_current_fee_percentiles(..)`, to compute the
+///   fee or to check it against the fee limits.  Skipped if the fee is given in satoshi or satoshi
+///   per vbyte and `allow_high_fee` is set.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+/// - For every transaction input, in a timer:
+///   - Calls `sign_with_ecdsa(..)` on that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+/// - Reserves the inputs from the call on, and keeps the signed transaction; see
+///   `btc_pending_transactions` and `btc_list_signed_transactions`.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Sends the transaction with `bitcoin_api::send_transaction(..)`, in a timer.
+///   - Costs: See [Bitcoin API fees and pricing](https://internetcomputer.org/docs/current/references/bitcoin-how-it-works#api-fees-and-pricing)
+///
+/// The fee is that of `btc_caller_send`, and is likewise charged in full before the inputs are
+/// verified.  If the transaction cannot be sent, the job fails with `BroadcastFailed`.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn btc_caller_send_job(
+    params: SendBtcRequest,
+    payment: Option<PaymentType>,
+) -> Result<BtcJobResponse, SendBtcError> {
+    // Vaults need the cosigner's signature; they are spent with `btc_vault_sign`.
+    if let BitcoinAddressType::P2WSHVault(_) = params.address_type {
+        return Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::NotP2WPKHSourceAddress,
+        ));
+    }
+    require_bitcoin_canister(params.network)?;
+    check_data_outputs(&params.outputs).map_err(SendBtcError::BuildP2wpkhError)?;
+    check_sighash_types(params.sighash_types.as_deref()).map_err(SendBtcError::BuildP2wpkhError)?;
+    // The job releases the inputs if it fails to sign, and sends them with `send_reserved`.
+    reservations::spend_reserved(msg_caller(), &params.utxos_to_spend, async {
+        PAYMENT_GUARD
+            .deduct(
+                payment.unwrap_or(PaymentType::AttachedCycles),
+                caller_send_fee(&params),
+            )
+            .await?;
+        start_signing_job(&params, true).await
+    })
+    .await
+}
+
+/// Gets the status of a job started by `btc_caller_sign_job` or `btc_caller_send_job`.
+///
+/// # Details
+/// Returns the number of inputs signed so far, the signed transaction and its txid once the job
+/// has completed, or the error that the job failed with.  Only the caller that started a job can
+/// see it, for a week.  This query is free.
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[query(guard = "caller_is_not_anonymous")]
+#[allow(clippy::needless_pass_by_value)]
+pub fn btc_job_status(params: BtcJobStatusRequest) -> Result<BtcJobStatus, BtcJobStatusError> {
+    read_state(|state| jobs::job_status(&state.signing_jobs, msg_caller(), params.job_id))
+        .ok_or(BtcJobStatusError::UnknownJob)
+}
+
+// /////////////////////////////////
+// // DOGECOIN AND LITECOIN UTILS //
+// /////////////////////////////////
+
+/// Internal helper that returns the caller's address on a chain that shares the Bitcoin
+/// transaction pipeline.
+async fn utxo_chain_caller_address(
+    chain: UtxoChain,
+    method: SignerMethods,
+    params: UtxoAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoAddressResponse, UtxoAddressError> {
+    PAYMENT_GUARD
+        .deduct(payment.unwrap_or(PaymentType::AttachedCycles), method.fee())
+        .await?;
+    let address = bitcoin_utils::principal_to_chain_address(
+        &ChainParams::new(chain, params.network),
+        &msg_caller(),
+    )
+    .await
+    .map_err(|msg| UtxoAddressError::InternalError { msg })?;
+    Ok(UtxoAddressResponse { address })
+}
+
+/// Internal helper that creates and signs a transaction from the caller's address on a chain that
+/// shares the Bitcoin transaction pipeline.
+async fn utxo_chain_caller_sign(
+    chain: UtxoChain,
+    method: SignerMethods,
+    params: UtxoSignRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoSignResponse, UtxoSignError> {
+    if params.fee.is_some() && params.fee_rate_per_vbyte.is_some() {
+        return Err(UtxoSignError::BuildError(
+            BuildP2wpkhTxError::ConflictingFeeArguments,
+        ));
+    }
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            method.btc_fee_for_inputs(params.utxos_to_spend.len() as u64),
+        )
+        .await?;
+    let principal = msg_caller();
+    let chain = ChainParams::new(chain, params.network);
+    let fee = match (params.fee, params.fee_rate_per_vbyte) {
+        (Some(fee), _) => Fee::Absolute(fee),
+        (None, Some(fee_rate)) => Fee::Rate(fee_rate.saturating_mul(1000)),
+        (None, None) => Fee::Rate(chain.default_fee_rate),
+    };
+    let outputs: Vec<BtcTxOutput> = params
+        .outputs
+        .iter()
+        .map(|output| BtcTxOutput::Payment {
+            destination_address: output.destination_address.clone(),
+            sent_satoshis: output.value,
+        })
+        .collect();
+    let public_key = bitcoin_utils::principal_to_schema_public_key(chain.schema, &principal)
+        .await
+        .map_err(|msg| UtxoSignError::InternalError { msg })?;
+    let transaction = build_chain_transaction_with_fee(
+        &chain,
+        &chain.own_script_pubkey(&public_key),
+        &params.utxos_to_spend,
+        fee,
+        &outputs,
+    )
+    .map_err(UtxoSignError::BuildError)?;
+    let signed_transaction = sign_chain_transaction(
+        &principal,
+        &chain,
+        &public_key,
+        transaction,
+        &params.utxos_to_spend,
+    )
+    .await
+    .map_err(|msg| UtxoSignError::InternalError { msg })?;
+    Ok(UtxoSignResponse {
+        signed_transaction_hex: hex::encode(&signed_transaction.signed_transaction_bytes),
+        txid: signed_transaction.txid,
+        fee: signed_transaction.fee_satoshis,
+        vsize: signed_transaction.vsize,
+    })
+}
+
+/// Returns the Dogecoin address of the caller.
+///
+/// # Details
+/// - Gets the principal's Dogecoin public key with
+///   `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2PKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn doge_caller_address(
+    params: UtxoAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoAddressResponse, UtxoAddressError> {
+    utxo_chain_caller_address(
+        UtxoChain::Dogecoin,
+        SignerMethods::DogeCallerAddress,
+        params,
+        payment,
+    )
+    .await
+}
+
+/// Creates and signs a Dogecoin transaction from the caller's address without broadcasting it.
+///
+/// # Details
+/// - Gets the principal's Dogecoin public key with
+///   `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds a transaction that spends all of `utxos_to_spend` and returns the change to the
+///   caller's P2PKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on the legacy sighash of that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn doge_caller_sign(
+    params: UtxoSignRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoSignResponse, UtxoSignError> {
+    utxo_chain_caller_sign(
+        UtxoChain::Dogecoin,
+        SignerMethods::DogeCallerSign,
+        params,
+        payment,
+    )
+    .await
+}
+
+/// Returns the Litecoin address of the caller.
+///
+/// # Details
+/// - Gets the principal's Litecoin public key with
+///   `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Converts the public key to a P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn ltc_caller_address(
+    params: UtxoAddressRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoAddressResponse, UtxoAddressError> {
+    utxo_chain_caller_address(
+        UtxoChain::Litecoin,
+        SignerMethods::LtcCallerAddress,
+        params,
+        payment,
+    )
+    .await
+}
+
+/// Creates and signs a Litecoin transaction from the caller's address without broadcasting it.
+///
+/// # Details
+/// - Gets the principal's Litecoin public key with
+///   `management_canister::ecdsa::ecdsa_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Builds a transaction that spends all of `utxos_to_spend` and returns the change to the
+///   caller's P2WPKH address.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - For every transaction input:
+///   - Calls `sign_with_ecdsa(..)` on the BIP-143 sighash of that input.
+///   - Costs: See [Fees for the t-ECDSA production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-ecdsa-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn ltc_caller_sign(
+    params: UtxoSignRequest,
+    payment: Option<PaymentType>,
+) -> Result<UtxoSignResponse, UtxoSignError> {
+    utxo_chain_caller_sign(
+        UtxoChain::Litecoin,
+        SignerMethods::LtcCallerSign,
+        params,
+        payment,
+    )
+    .await
+}
+
+// //////////////////
+// // SOLANA UTILS //
+// //////////////////
+
+/// Returns the Solana address of the caller.
+///
+/// # Details
+/// - Gets the caller's Ed25519 public key with
+///   `management_canister::schnorr::schnorr_public_key(..)`
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Encodes the public key in base58.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn sol_address(
+    payment: Option<PaymentType>,
+) -> Result<SolAddressResponse, SolAddressError> {
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::SolAddress.fee(),
+        )
+        .await?;
+    let address = sol::sol_address(&msg_caller())
+        .await
+        .map_err(SolAddressError::SigningError)?;
+    Ok(SolAddressResponse { address })
+}
+
+/// Signs a serialized legacy or v0 Solana message with the caller's Ed25519 key.
+///
+/// The caller's address, as returned by `sol_address`, must be one of the required signers of the
+/// message.  The signatures of other required signers are left as zeros in the returned
+/// transaction.
+///
+/// The full fee is charged before the caller's address is known, so a message that the caller
+/// does not sign fails with `NotASigner` and the fee is not refunded.  Check the signers against
+/// `sol_address` first.
+///
+/// # Details
+/// - Parses the message to find its required signers.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Gets the caller's Ed25519 public key with
+///   `management_canister::schnorr::schnorr_public_key(..)`, and fails with `NotASigner` if it is
+///   not a required signer.
+///   - Costs: [Execution cycles](https://internetcomputer.org/docs/current/developer-docs/gas-cost#execution)
+/// - Calls `sign_with_schnorr(..)` on the message.
+///   - Costs: See [Fees for the t-Schnorr production key](https://internetcomputer.org/docs/current/references/t-sigs-how-it-works#fees-for-the-t-schnorr-production-key)
+///
+/// # Panics
+/// - If the caller is the anonymous user.
+#[update(guard = "caller_is_not_anonymous")]
+pub async fn sol_sign_transaction(
+    params: SolSignTransactionRequest,
+    payment: Option<PaymentType>,
+) -> Result<SolSignTransactionResponse, SolSignTransactionError> {
+    let message = hex::decode(&params.message_hex).map_err(|err| {
+        SolSignTransactionError::InvalidMessage {
+            msg: format!("Invalid hex: {err}"),
+        }
+    })?;
+    let signers = sol::required_signers(&message)
+        .map_err(|msg| SolSignTransactionError::InvalidMessage { msg })?;
+    PAYMENT_GUARD
+        .deduct(
+            payment.unwrap_or(PaymentType::AttachedCycles),
+            SignerMethods::SolSignTransaction.fee(),
+        )
+        .await?;
+    sol::sign_transaction(&msg_caller(), message, &signers).await
 }
 
 // /////////////////////
@@ -1,21 +1,39 @@
 use ic_cdk_bitcoin_canister::{
-    bitcoin_get_balance, bitcoin_get_current_fee_percentiles, bitcoin_send_transaction,
-    GetBalanceRequest, GetCurrentFeePercentilesRequest, MillisatoshiPerByte, Network,
-    SendTransactionRequest,
+    bitcoin_get_balance, bitcoin_get_current_fee_percentiles, bitcoin_get_utxos,
+    bitcoin_send_transaction, GetBalanceRequest, GetCurrentFeePercentilesRequest, GetUtxosRequest,
+    MillisatoshiPerByte, Network, SendTransactionRequest, Utxo, UtxosFilter,
 };
+use ic_chain_fusion_signer_api::types::bitcoin::BitcoinNetwork;
+
+/// Returns the network of the Bitcoin canister that serves `network`, if there is one.
+///
+/// The Bitcoin canister's test network is Testnet4.  There is no Bitcoin canister for Signet.
+pub fn canister_network(network: BitcoinNetwork) -> Option<Network> {
+    match network {
+        BitcoinNetwork::Mainnet => Some(Network::Mainnet),
+        BitcoinNetwork::Testnet | BitcoinNetwork::Testnet4 => Some(Network::Testnet),
+        BitcoinNetwork::Regtest => Some(Network::Regtest),
+        BitcoinNetwork::Signet => None,
+    }
+}
+
+/// Like `canister_network`, with an error for networks that have no Bitcoin canister.
+fn require_canister_network(network: BitcoinNetwork) -> Result<Network, String> {
+    canister_network(network).ok_or_else(|| format!("There is no Bitcoin canister for {network:?}"))
+}
 
 /// Returns the balance of the given bitcoin address.
 ///
 /// Relies on the `bitcoin_get_balance` endpoint.
 /// See [Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_get_balance)
 pub async fn get_balance(
-    network: Network,
+    network: BitcoinNetwork,
     address: String,
     min_confirmations: Option<u32>,
 ) -> Result<u64, String> {
     let balance_res = bitcoin_get_balance(&GetBalanceRequest {
         address,
-        network: network.into(),
+        network: require_canister_network(network)?.into(),
         min_confirmations,
     })
     .await
@@ -29,9 +47,11 @@ pub async fn get_balance(
 ///
 /// Relies on the `bitcoin_get_current_fee_percentiles` endpoint.
 /// See [Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_get_current_fee_percentiles)
-async fn get_current_fee_percentiles(network: Network) -> Result<Vec<MillisatoshiPerByte>, String> {
+pub async fn get_current_fee_percentiles(
+    network: BitcoinNetwork,
+) -> Result<Vec<MillisatoshiPerByte>, String> {
     let res = bitcoin_get_current_fee_percentiles(&GetCurrentFeePercentilesRequest {
-        network: network.into(),
+        network: require_canister_network(network)?.into(),
     })
     .await
     .map_err(|err| format!("{err:?}"))?;
@@ -39,30 +59,52 @@ async fn get_current_fee_percentiles(network: Network) -> Result<Vec<Millisatosh
     Ok(res)
 }
 
-/// Returns the 50th percentile for sending fees.
-pub async fn get_fee_per_byte(network: Network) -> Result<u64, String> {
-    // Get fee percentiles from previous transactions to estimate our own fee.
-    let fee_percentiles = get_current_fee_percentiles(network).await?;
+/// Returns the height of the tip of the bitcoin chain.
+///
+/// Relies on the `bitcoin_get_utxos` endpoint, which reports the tip along with the UTXOs of
+/// `address`.  Only the first page of UTXOs is fetched.
+/// See [Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_get_utxos)
+pub async fn get_tip_height(network: BitcoinNetwork, address: String) -> Result<u32, String> {
+    let res = bitcoin_get_utxos(&GetUtxosRequest {
+        address,
+        network: require_canister_network(network)?.into(),
+        filter: None,
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+
+    Ok(res.tip_height)
+}
+
+/// Returns the UTXOs of the given bitcoin address with at least `min_confirmations`
+/// confirmations.
+///
+/// Relies on the `bitcoin_get_utxos` endpoint.  Only the first page of UTXOs is fetched.
+/// See [Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_get_utxos)
+pub async fn get_utxos(
+    network: BitcoinNetwork,
+    address: String,
+    min_confirmations: Option<u32>,
+) -> Result<Vec<Utxo>, String> {
+    let res = bitcoin_get_utxos(&GetUtxosRequest {
+        address,
+        network: require_canister_network(network)?.into(),
+        filter: min_confirmations.map(UtxosFilter::MinConfirmations),
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
 
-    if fee_percentiles.is_empty() {
-        // There are no fee percentiles. This case can only happen on a regtest
-        // network where there are no non-coinbase transactions. In this case,
-        // we use a default of 2000 millisatoshis/byte (i.e. 2 satoshi/byte)
-        Ok(2000)
-    } else {
-        let middle = fee_percentiles.len() / 2;
-        Ok(fee_percentiles[middle])
-    }
+    Ok(res.utxos)
 }
 
 /// Sends a (signed) transaction to the bitcoin network.
 ///
 /// Relies on the `bitcoin_send_transaction` endpoint.
 /// See [Bitcoin API](https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-bitcoin_send_transaction)
-pub async fn send_transaction(network: Network, transaction: Vec<u8>) -> Result<(), String> {
+pub async fn send_transaction(network: BitcoinNetwork, transaction: Vec<u8>) -> Result<(), String> {
     bitcoin_send_transaction(&SendTransactionRequest {
         transaction,
-        network: network.into(),
+        network: require_canister_network(network)?.into(),
     })
     .await
     .map_err(|err| format!("{err:?}"))?;
@@ -1,17 +1,24 @@
 //! Code for signing Bitcoin transactions.
-use bitcoin::{Address, CompressedPublicKey, Network};
+use bitcoin::{
+    hashes::Hash, secp256k1, CompressedPublicKey, EcdsaSighashType, Network, Txid, XOnlyPublicKey,
+};
 use candid::Principal;
-use ic_cdk_bitcoin_canister::Network as BitcoinNetwork;
+use ic_cdk_bitcoin_canister::Txid as IcCdkTxid;
 use ic_cdk_management_canister::{ecdsa_public_key, EcdsaCurve, EcdsaKeyId, EcdsaPublicKeyArgs};
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcSighashType};
 
-use crate::{derivation_path::Schema, sign::ecdsa_api, state::read_config};
+use crate::{
+    derivation_path::Schema,
+    sign::{bitcoin::chain::ChainParams, ecdsa_api, schnorr_api},
+    state::read_config,
+};
 
-/// Computes the public key of the specified principal.
-async fn ecdsa_pubkey_of(principal: &Principal) -> Result<Vec<u8>, String> {
+/// Computes the public key of the specified principal for the given schema.
+async fn ecdsa_pubkey_of(schema: Schema, principal: &Principal) -> Result<Vec<u8>, String> {
     let name = read_config(|s| s.ecdsa_key_name.clone());
     if let Ok(key) = ecdsa_public_key(&EcdsaPublicKeyArgs {
         canister_id: None,
-        derivation_path: Schema::Btc.derivation_path(principal),
+        derivation_path: schema.derivation_path(principal),
         key_id: EcdsaKeyId {
             curve: EcdsaCurve::Secp256k1,
             name,
@@ -28,11 +35,38 @@ async fn ecdsa_pubkey_of(principal: &Principal) -> Result<Vec<u8>, String> {
 pub fn transform_network(network: BitcoinNetwork) -> Network {
     match network {
         BitcoinNetwork::Mainnet => Network::Bitcoin,
-        BitcoinNetwork::Testnet => Network::Testnet,
+        // The Bitcoin canister's test network is Testnet4.
+        BitcoinNetwork::Testnet | BitcoinNetwork::Testnet4 => Network::Testnet4,
+        BitcoinNetwork::Signet => Network::Signet,
         BitcoinNetwork::Regtest => Network::Regtest,
     }
 }
 
+pub fn transform_sighash_type(sighash_type: BtcSighashType) -> EcdsaSighashType {
+    match sighash_type {
+        BtcSighashType::All => EcdsaSighashType::All,
+        BtcSighashType::None => EcdsaSighashType::None,
+        BtcSighashType::Single => EcdsaSighashType::Single,
+        BtcSighashType::AllPlusAnyoneCanPay => EcdsaSighashType::AllPlusAnyoneCanPay,
+        BtcSighashType::NonePlusAnyoneCanPay => EcdsaSighashType::NonePlusAnyoneCanPay,
+        BtcSighashType::SinglePlusAnyoneCanPay => EcdsaSighashType::SinglePlusAnyoneCanPay,
+    }
+}
+
+/// Parses a hex-encoded compressed public key, as supplied by callers for cosigners and
+/// counterparties.
+pub fn parse_compressed_public_key(public_key: &str) -> Option<CompressedPublicKey> {
+    hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| CompressedPublicKey::from_slice(&bytes).ok())
+}
+
+/// The txid as block explorers show it, byte-reversed.
+pub fn display_txid(txid: &IcCdkTxid) -> String {
+    Txid::from_slice(txid.as_ref())
+        .map_or_else(|_| hex::encode(txid.as_ref()), |txid| txid.to_string())
+}
+
 /// Signs a precomputed 32-byte digest under the caller's Bitcoin key (schema `Btc`).
 ///
 /// Returns the raw 64-byte ECDSA signature (`r || s`). Unlike `btc_caller_sign`, which builds and
@@ -47,20 +81,57 @@ pub async fn sign_prehash(principal: &Principal, message_hash: Vec<u8>) -> Resul
             message_hash.len()
         ));
     }
-    ecdsa_api::get_ecdsa_signature(Schema::Btc.derivation_path(principal), message_hash).await
+    Ok(
+        ecdsa_api::get_ecdsa_signature(Schema::Btc.derivation_path(principal), message_hash)
+            .await?,
+    )
+}
+
+/// Gets the compressed public key of the principal's Bitcoin key.
+pub async fn principal_to_public_key(principal: &Principal) -> Result<CompressedPublicKey, String> {
+    principal_to_schema_public_key(Schema::Btc, principal).await
+}
+
+/// Gets the compressed public key of the principal's key for the given schema.
+pub async fn principal_to_schema_public_key(
+    schema: Schema,
+    principal: &Principal,
+) -> Result<CompressedPublicKey, String> {
+    let ecdsa_pubkey = ecdsa_pubkey_of(schema, principal)
+        .await
+        .map_err(|_| "Error getting ECDSA public key".to_string())?;
+    CompressedPublicKey::from_slice(&ecdsa_pubkey)
+        .map_err(|_| "Error parsing ECDSA public key".to_string())
 }
 
-/// Converts a public key to a P2PKH address.
+/// Gets the x-only public key of the principal's BIP-340 Schnorr key, as used in tapscript leaves.
+///
+/// This is the key that `schnorr_public_key` returns to the principal for an empty derivation
+/// path.
+pub async fn principal_to_x_only_public_key(
+    principal: &Principal,
+) -> Result<XOnlyPublicKey, String> {
+    let schnorr_pubkey =
+        schnorr_api::bip340_pubkey_of(Schema::Schnorr.derivation_path(principal)).await?;
+    secp256k1::PublicKey::from_slice(&schnorr_pubkey)
+        .map(|public_key| public_key.x_only_public_key().0)
+        .map_err(|_| "Error parsing Schnorr public key".to_string())
+}
+
+/// Converts a public key to a P2WPKH address.
 pub async fn principal_to_p2wpkh_address(
     network: BitcoinNetwork,
     principal: &Principal,
 ) -> Result<String, String> {
-    let ecdsa_pubkey = ecdsa_pubkey_of(principal)
-        .await
-        .map_err(|_| "Error getting ECDSA public key".to_string())?;
-    if let Ok(compressed_public_key) = CompressedPublicKey::from_slice(&ecdsa_pubkey) {
-        Ok(Address::p2wpkh(&compressed_public_key, transform_network(network)).to_string())
-    } else {
-        Err("Error getting P2WPKH from public key".to_string())
-    }
+    principal_to_chain_address(&ChainParams::bitcoin(network), principal).await
+}
+
+/// Gets the address of the principal's key on a chain that shares the Bitcoin transaction
+/// pipeline, see [`ChainParams::address`].
+pub async fn principal_to_chain_address(
+    chain: &ChainParams,
+    principal: &Principal,
+) -> Result<String, String> {
+    let public_key = principal_to_schema_public_key(chain.schema, principal).await?;
+    Ok(chain.address(&public_key))
 }
@@ -0,0 +1,422 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    absolute::LockTime, consensus::deserialize, hashes::Hash, transaction::Version, Address,
+    Amount, OutPoint as BitcoinOutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Witness,
+};
+use ic_cdk_bitcoin_canister::{
+    MillisatoshiPerByte, OutPoint as IcCdkOutPoint, Txid as IcCdkTxid, Utxo,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BitcoinNetwork, BtcBumpFeeError, BuildP2wpkhTxError,
+};
+
+use crate::sign::bitcoin::{
+    bitcoin_utils::transform_network,
+    fee_utils::fee_for_vsize,
+    tx_utils::{estimate_signed_vsize, get_input_value, transaction_fee, DUST_THRESHOLD},
+};
+
+/// The minimum relay fee rate.  A replacement has to pay at least this rate for its own size on
+/// top of the fee of the transaction it replaces (BIP-125 rule 4).
+const MIN_RELAY_FEE_RATE_MILLISAT_PER_VBYTE: MillisatoshiPerByte = 1000;
+
+/// An unsigned transaction that increases the fee of a stuck transaction.
+pub struct FeeBumpTransaction {
+    pub transaction: Transaction,
+    /// The UTXOs spent by `transaction`.
+    pub utxos: Vec<Utxo>,
+    /// The fee of the transactions that miners consider together: the replacement, or the parent
+    /// and the child.
+    pub package_fee_satoshis: u64,
+    /// The size, in virtual bytes, of the transactions that miners consider together.
+    pub package_vsize: u64,
+    /// The value sent to other addresses than the caller's.
+    pub sent_satoshis: u64,
+}
+
+/// Decodes a hex-encoded, signed transaction.
+pub fn decode_signed_transaction(
+    signed_transaction_hex: &str,
+) -> Result<Transaction, BtcBumpFeeError> {
+    let bytes =
+        hex::decode(signed_transaction_hex).map_err(|e| BtcBumpFeeError::InvalidTransaction {
+            msg: format!("failed to decode hex: {e}"),
+        })?;
+    deserialize_signed_transaction(&bytes)
+}
+
+/// Decodes a signed transaction.
+pub fn deserialize_signed_transaction(bytes: &[u8]) -> Result<Transaction, BtcBumpFeeError> {
+    deserialize(bytes).map_err(|e| BtcBumpFeeError::InvalidTransaction {
+        msg: format!("failed to decode transaction: {e}"),
+    })
+}
+
+/// The script that locks the outputs paying to the caller's address.
+fn own_script_pubkey(
+    source_address: &str,
+    network: BitcoinNetwork,
+) -> Result<ScriptBuf, BtcBumpFeeError> {
+    let own_address = Address::from_str(source_address)
+        .map_err(|_| BuildP2wpkhTxError::InvalidSourceAddress {
+            address: source_address.to_string(),
+        })?
+        .require_network(transform_network(network))
+        .map_err(|_| BuildP2wpkhTxError::WrongBitcoinNetwork)?;
+    Ok(own_address.script_pubkey())
+}
+
+/// Checks that every input of the transaction spends one of `utxos`.
+fn check_input_utxos(transaction: &Transaction, utxos: &[Utxo]) -> Result<(), BtcBumpFeeError> {
+    match transaction
+        .input
+        .iter()
+        .find(|input| get_input_value(input, utxos).is_none())
+    {
+        Some(input) => Err(BtcBumpFeeError::MissingInputUtxo {
+            txid: input.previous_output.txid.to_string(),
+            vout: input.previous_output.vout,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The index of the change output, i.e. of the last output that pays to the caller.
+fn change_output_index(
+    transaction: &Transaction,
+    own_script_pubkey: &Script,
+) -> Result<usize, BtcBumpFeeError> {
+    transaction
+        .output
+        .iter()
+        .rposition(|output| output.script_pubkey.as_script() == own_script_pubkey)
+        .ok_or(BtcBumpFeeError::NoChangeOutput)
+}
+
+/// The value that a transaction sends to other addresses than the caller's.
+fn sent_satoshis(transaction: &Transaction, own_script_pubkey: &Script) -> u64 {
+    transaction
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey.as_script() != own_script_pubkey)
+        .map(|output| output.value.to_sat())
+        .sum()
+}
+
+/// Builds a replacement for `original` that pays the given fee rate (replace-by-fee).
+///
+/// The replacement spends the same inputs and pays the same outputs, except that the additional
+/// fee is taken from the change output.  If the change would be dust, the change output is dropped
+/// and its value goes to the fee.
+pub fn build_replacement_transaction(
+    original: &Transaction,
+    utxos: &[Utxo],
+    source_address: &str,
+    network: BitcoinNetwork,
+    fee_rate: MillisatoshiPerByte,
+) -> Result<FeeBumpTransaction, BtcBumpFeeError> {
+    if !original.is_explicitly_rbf() {
+        return Err(BtcBumpFeeError::NotReplaceable);
+    }
+    check_input_utxos(original, utxos)?;
+    let own_script_pubkey = own_script_pubkey(source_address, network)?;
+    let change_index = change_output_index(original, &own_script_pubkey)?;
+    let original_fee = transaction_fee(original, utxos);
+
+    let mut replacement = original.clone();
+    for input in &mut replacement.input {
+        input.witness = Witness::new();
+    }
+    let vsize = estimate_signed_vsize(&replacement);
+    let fee = fee_for_vsize(vsize, fee_rate);
+    let min_fee = original_fee + fee_for_vsize(vsize, MIN_RELAY_FEE_RATE_MILLISAT_PER_VBYTE);
+    if fee < min_fee {
+        return Err(BtcBumpFeeError::ReplacementFeeTooLow {
+            fee_satoshis: fee,
+            min_fee_satoshis: min_fee,
+        });
+    }
+
+    let sent_satoshis = sent_satoshis(original, &own_script_pubkey);
+    let change = replacement.output[change_index].value.to_sat();
+    let available = change + original_fee + sent_satoshis;
+    let remaining =
+        (change + original_fee)
+            .checked_sub(fee)
+            .ok_or(BuildP2wpkhTxError::NotEnoughFunds {
+                required: sent_satoshis + fee,
+                available,
+            })?;
+    if remaining < DUST_THRESHOLD {
+        replacement.output.remove(change_index);
+    } else {
+        replacement.output[change_index].value = Amount::from_sat(remaining);
+    }
+
+    Ok(FeeBumpTransaction {
+        package_fee_satoshis: transaction_fee(&replacement, utxos),
+        package_vsize: estimate_signed_vsize(&replacement),
+        sent_satoshis,
+        transaction: replacement,
+        utxos: utxos.to_vec(),
+    })
+}
+
+/// Builds a transaction that spends the change output of `parent` to the caller
+/// (child-pays-for-parent).
+///
+/// The child pays enough for the parent and the child together to reach the given fee rate, and at
+/// least the minimum relay fee for its own size.
+pub fn build_cpfp_transaction(
+    parent: &Transaction,
+    utxos: &[Utxo],
+    source_address: &str,
+    network: BitcoinNetwork,
+    fee_rate: MillisatoshiPerByte,
+) -> Result<FeeBumpTransaction, BtcBumpFeeError> {
+    check_input_utxos(parent, utxos)?;
+    let own_script_pubkey = own_script_pubkey(source_address, network)?;
+    let change_index = change_output_index(parent, &own_script_pubkey)?;
+    let parent_fee = transaction_fee(parent, utxos);
+    let parent_vsize = parent.vsize() as u64;
+    let parent_txid = parent.compute_txid();
+    let vout = u32::try_from(change_index).expect("Transaction has too many outputs");
+    let change_utxo = Utxo {
+        outpoint: IcCdkOutPoint {
+            txid: IcCdkTxid::from(*parent_txid.as_byte_array()),
+            vout,
+        },
+        value: parent.output[change_index].value.to_sat(),
+        height: 0,
+    };
+
+    let mut child = Transaction {
+        input: vec![TxIn {
+            previous_output: BitcoinOutPoint {
+                txid: parent_txid,
+                vout,
+            },
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+            script_sig: ScriptBuf::new(),
+        }],
+        output: vec![TxOut {
+            script_pubkey: own_script_pubkey,
+            value: Amount::ZERO,
+        }],
+        lock_time: LockTime::ZERO,
+        version: Version::TWO,
+    };
+    let child_vsize = estimate_signed_vsize(&child);
+    let fee = fee_for_vsize(parent_vsize + child_vsize, fee_rate)
+        .saturating_sub(parent_fee)
+        .max(fee_for_vsize(
+            child_vsize,
+            MIN_RELAY_FEE_RATE_MILLISAT_PER_VBYTE,
+        ));
+    let remaining = change_utxo
+        .value
+        .checked_sub(fee)
+        .filter(|remaining| *remaining >= DUST_THRESHOLD)
+        .ok_or(BuildP2wpkhTxError::NotEnoughFunds {
+            required: fee + DUST_THRESHOLD,
+            available: change_utxo.value,
+        })?;
+    child.output[0].value = Amount::from_sat(remaining);
+
+    Ok(FeeBumpTransaction {
+        package_fee_satoshis: parent_fee + fee,
+        package_vsize: parent_vsize + child_vsize,
+        sent_satoshis: sent_satoshis(parent, &child.output[0].script_pubkey),
+        transaction: child,
+        utxos: vec![change_utxo],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{hashes::Hash, Transaction, Txid, Witness};
+    use ic_cdk_bitcoin_canister::{OutPoint as IcCdkOutPoint, Txid as IcCdkTxid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcBumpFeeError, BtcTxOutput,
+    };
+
+    use super::{build_cpfp_transaction, build_replacement_transaction, decode_signed_transaction};
+    use crate::sign::bitcoin::tx_utils::{build_p2wpkh_transaction, disable_rbf};
+
+    const SOURCE_ADDRESS: &str = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+    const DESTINATION_ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    const TXID: &str = "36f3a7fcb6b5ebd9fa4041928da89cd423662f9c5c12e41c80e07a6559d178ef";
+
+    fn mock_utxos() -> Vec<Utxo> {
+        let txid = Txid::from_str(TXID).unwrap();
+        vec![Utxo {
+            outpoint: IcCdkOutPoint {
+                txid: IcCdkTxid::from(*txid.as_byte_array()),
+                vout: 0,
+            },
+            value: 100_000,
+            height: 100,
+        }]
+    }
+
+    /// A transaction sending 50,000 satoshi with a fee of 200 satoshi, with placeholder witnesses
+    /// of the size of real signatures.
+    fn mock_signed_transaction(utxos: &[Utxo]) -> Transaction {
+        let mut transaction = build_p2wpkh_transaction(
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            utxos,
+            200,
+            &[BtcTxOutput::Payment {
+                destination_address: DESTINATION_ADDRESS.to_string(),
+                sent_satoshis: 50_000,
+            }],
+        )
+        .expect("Failed to build transaction");
+        for input in &mut transaction.input {
+            let mut witness = Witness::new();
+            witness.push([0u8; 72]);
+            witness.push([0u8; 33]);
+            input.witness = witness;
+        }
+        transaction
+    }
+
+    #[test]
+    fn replacement_pays_higher_fee_from_change() {
+        let utxos = mock_utxos();
+        let original = mock_signed_transaction(&utxos);
+
+        let bump = build_replacement_transaction(
+            &original,
+            &utxos,
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            10_000,
+        )
+        .expect("Failed to build replacement");
+
+        let replacement = &bump.transaction;
+        assert_eq!(replacement.input.len(), original.input.len());
+        assert_eq!(
+            replacement.input[0].previous_output,
+            original.input[0].previous_output
+        );
+        assert!(replacement.is_explicitly_rbf());
+        assert_eq!(replacement.output[0], original.output[0]);
+        assert_eq!(bump.sent_satoshis, 50_000);
+        assert!(bump.package_fee_satoshis >= bump.package_vsize * 10);
+        assert_eq!(
+            replacement.output[1].value.to_sat(),
+            100_000 - 50_000 - bump.package_fee_satoshis
+        );
+    }
+
+    #[test]
+    fn replacement_requires_rbf_signal() {
+        let utxos = mock_utxos();
+        let mut original = mock_signed_transaction(&utxos);
+        disable_rbf(&mut original);
+
+        let result = build_replacement_transaction(
+            &original,
+            &utxos,
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            10_000,
+        );
+
+        assert!(matches!(result, Err(BtcBumpFeeError::NotReplaceable)));
+    }
+
+    #[test]
+    fn replacement_must_pay_more_than_original() {
+        let utxos = mock_utxos();
+        let original = mock_signed_transaction(&utxos);
+
+        // The original pays 200 satoshi for ~141 vbytes; 2 sat/vbyte does not cover the original
+        // fee plus the relay fee of the replacement.
+        let result = build_replacement_transaction(
+            &original,
+            &utxos,
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            2_000,
+        );
+
+        assert!(matches!(
+            result,
+            Err(BtcBumpFeeError::ReplacementFeeTooLow { .. })
+        ));
+    }
+
+    #[test]
+    fn replacement_requires_input_utxos() {
+        let utxos = mock_utxos();
+        let original = mock_signed_transaction(&utxos);
+
+        let result = build_replacement_transaction(
+            &original,
+            &[],
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            10_000,
+        );
+
+        match result {
+            Err(BtcBumpFeeError::MissingInputUtxo { txid, vout }) => {
+                assert_eq!(txid, TXID);
+                assert_eq!(vout, 0);
+            }
+            _ => panic!("Expected MissingInputUtxo error"),
+        }
+    }
+
+    #[test]
+    fn cpfp_child_spends_change_and_pays_for_package() {
+        let utxos = mock_utxos();
+        let parent = mock_signed_transaction(&utxos);
+
+        let bump = build_cpfp_transaction(
+            &parent,
+            &utxos,
+            SOURCE_ADDRESS,
+            BitcoinNetwork::Mainnet,
+            10_000,
+        )
+        .expect("Failed to build child");
+
+        let child = &bump.transaction;
+        assert_eq!(child.input.len(), 1);
+        assert_eq!(child.input[0].previous_output.txid, parent.compute_txid());
+        assert_eq!(child.input[0].previous_output.vout, 1);
+        assert_eq!(child.output.len(), 1);
+        assert_eq!(
+            child.output[0].script_pubkey,
+            parent.output[1].script_pubkey
+        );
+        assert_eq!(bump.utxos[0].value, parent.output[1].value.to_sat());
+        assert!(bump.package_fee_satoshis >= bump.package_vsize * 10);
+        assert_eq!(
+            child.output[0].value.to_sat(),
+            bump.utxos[0].value - (bump.package_fee_satoshis - 200)
+        );
+    }
+
+    #[test]
+    fn decode_rejects_invalid_transaction() {
+        assert!(matches!(
+            decode_signed_transaction("not hex"),
+            Err(BtcBumpFeeError::InvalidTransaction { .. })
+        ));
+        assert!(matches!(
+            decode_signed_transaction("deadbeef"),
+            Err(BtcBumpFeeError::InvalidTransaction { .. })
+        ));
+    }
+}
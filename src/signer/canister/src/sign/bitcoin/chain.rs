@@ -0,0 +1,323 @@
+//! The parameters of the UTXO chains that share the Bitcoin transaction pipeline.
+//!
+//! Dogecoin and Litecoin use Bitcoin's transaction format and secp256k1 ECDSA keys, so the same
+//! code builds and signs their transactions.  They differ in how addresses are encoded, in the
+//! output type that holds the caller's funds, and in their dust threshold and customary fee rate.
+use std::str::FromStr;
+
+use bitcoin::{
+    base58,
+    bech32::{segwit, Hrp},
+    hashes::Hash,
+    Address, CompressedPublicKey, PubkeyHash, ScriptBuf, ScriptHash, Transaction, WitnessProgram,
+    WitnessVersion,
+};
+use ic_cdk_bitcoin_canister::MillisatoshiPerByte;
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BuildP2wpkhTxError};
+
+use crate::{
+    derivation_path::Schema,
+    sign::bitcoin::{
+        bitcoin_utils::transform_network,
+        fee_utils::DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE,
+        tx_utils::{estimate_signed_p2pkh_vsize, estimate_signed_vsize, DUST_THRESHOLD},
+    },
+};
+
+/// The networks on which every chain is supported.
+const NETWORKS: [BitcoinNetwork; 5] = [
+    BitcoinNetwork::Mainnet,
+    BitcoinNetwork::Testnet,
+    BitcoinNetwork::Testnet4,
+    BitcoinNetwork::Signet,
+    BitcoinNetwork::Regtest,
+];
+
+/// A chain that shares the Bitcoin transaction pipeline.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UtxoChain {
+    Bitcoin,
+    Dogecoin,
+    Litecoin,
+}
+
+/// The output type that holds the caller's funds, which determines how its inputs are signed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AddressKind {
+    /// A version 0 witness key hash, signed with the BIP-143 sighash.
+    P2wpkh,
+    /// A legacy key hash, signed with the original sighash algorithm.
+    P2pkh,
+}
+
+/// The parameters of a chain on one of its networks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChainParams {
+    pub chain: UtxoChain,
+    pub network: BitcoinNetwork,
+    /// The schema of the derivation path of the caller's key on this chain.
+    pub schema: Schema,
+    pub address_kind: AddressKind,
+    /// The human-readable part of bech32 addresses, if the chain has segregated witness.
+    pub bech32_hrp: Option<&'static str>,
+    /// The version byte of base58 P2PKH addresses.
+    pub pubkey_hash_version: u8,
+    /// The version byte of base58 P2SH addresses.
+    pub script_hash_version: u8,
+    /// Outputs worth less than this, in the smallest unit of the coin, are rejected as dust.
+    pub dust_threshold: u64,
+    /// The fee rate used if a request sets none, in thousandths of the smallest unit of the coin
+    /// per virtual byte.
+    pub default_fee_rate: MillisatoshiPerByte,
+}
+
+impl ChainParams {
+    pub fn new(chain: UtxoChain, network: BitcoinNetwork) -> Self {
+        match chain {
+            UtxoChain::Bitcoin => Self::bitcoin(network),
+            UtxoChain::Dogecoin => Self::dogecoin(network),
+            UtxoChain::Litecoin => Self::litecoin(network),
+        }
+    }
+
+    pub fn bitcoin(network: BitcoinNetwork) -> Self {
+        let (bech32_hrp, pubkey_hash_version, script_hash_version) = match network {
+            BitcoinNetwork::Mainnet => ("bc", 0x00, 0x05),
+            BitcoinNetwork::Testnet | BitcoinNetwork::Testnet4 | BitcoinNetwork::Signet => {
+                ("tb", 0x6f, 0xc4)
+            }
+            BitcoinNetwork::Regtest => ("bcrt", 0x6f, 0xc4),
+        };
+        ChainParams {
+            chain: UtxoChain::Bitcoin,
+            network,
+            schema: Schema::Btc,
+            address_kind: AddressKind::P2wpkh,
+            bech32_hrp: Some(bech32_hrp),
+            pubkey_hash_version,
+            script_hash_version,
+            dust_threshold: DUST_THRESHOLD,
+            default_fee_rate: DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE,
+        }
+    }
+
+    /// Dogecoin has no segregated witness, so the caller's funds are held in a P2PKH output.
+    ///
+    /// The dust threshold and the default fee rate are the recommended 0.01 DOGE and 0.01 DOGE per
+    /// kilobyte.
+    pub fn dogecoin(network: BitcoinNetwork) -> Self {
+        let (pubkey_hash_version, script_hash_version) = match network {
+            BitcoinNetwork::Mainnet => (0x1e, 0x16),
+            BitcoinNetwork::Testnet | BitcoinNetwork::Testnet4 | BitcoinNetwork::Signet => {
+                (0x71, 0xc4)
+            }
+            BitcoinNetwork::Regtest => (0x6f, 0xc4),
+        };
+        ChainParams {
+            chain: UtxoChain::Dogecoin,
+            network,
+            schema: Schema::Doge,
+            address_kind: AddressKind::P2pkh,
+            bech32_hrp: None,
+            pubkey_hash_version,
+            script_hash_version,
+            dust_threshold: 1_000_000,
+            default_fee_rate: 1_000_000,
+        }
+    }
+
+    /// Litecoin holds the caller's funds in a P2WPKH output, like Bitcoin, with its own address
+    /// prefixes.
+    pub fn litecoin(network: BitcoinNetwork) -> Self {
+        let (bech32_hrp, pubkey_hash_version, script_hash_version) = match network {
+            BitcoinNetwork::Mainnet => ("ltc", 0x30, 0x32),
+            BitcoinNetwork::Testnet | BitcoinNetwork::Testnet4 | BitcoinNetwork::Signet => {
+                ("tltc", 0x6f, 0x3a)
+            }
+            BitcoinNetwork::Regtest => ("rltc", 0x6f, 0x3a),
+        };
+        ChainParams {
+            chain: UtxoChain::Litecoin,
+            network,
+            schema: Schema::Ltc,
+            address_kind: AddressKind::P2wpkh,
+            bech32_hrp: Some(bech32_hrp),
+            pubkey_hash_version,
+            script_hash_version,
+            dust_threshold: DUST_THRESHOLD,
+            default_fee_rate: 10_000,
+        }
+    }
+
+    /// The address of the output that holds the funds of `public_key`.
+    pub fn address(&self, public_key: &CompressedPublicKey) -> String {
+        match self.address_kind {
+            AddressKind::P2wpkh => {
+                let hrp = Hrp::parse(
+                    self.bech32_hrp
+                        .expect("A P2WPKH chain has bech32 addresses"),
+                )
+                .expect("The human-readable part of a chain is valid");
+                segwit::encode_v0(hrp, public_key.wpubkey_hash().as_byte_array())
+                    .expect("A key hash is a valid witness program")
+            }
+            AddressKind::P2pkh => {
+                let mut payload = vec![self.pubkey_hash_version];
+                payload.extend_from_slice(public_key.pubkey_hash().as_byte_array());
+                base58::encode_check(&payload)
+            }
+        }
+    }
+
+    /// The script of the output that holds the funds of `public_key`.
+    pub fn own_script_pubkey(&self, public_key: &CompressedPublicKey) -> ScriptBuf {
+        match self.address_kind {
+            AddressKind::P2wpkh => ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()),
+            AddressKind::P2pkh => ScriptBuf::new_p2pkh(&public_key.pubkey_hash()),
+        }
+    }
+
+    /// The output script that pays to `address`.
+    ///
+    /// Bitcoin addresses are parsed with all the address types of the `bitcoin` crate.  Other
+    /// chains accept base58 P2PKH and P2SH addresses and, if they have segregated witness, bech32
+    /// addresses.
+    pub fn script_pubkey(&self, address: &str) -> Result<ScriptBuf, BuildP2wpkhTxError> {
+        if self.chain == UtxoChain::Bitcoin {
+            return Address::from_str(address)
+                .map_err(|_| BuildP2wpkhTxError::InvalidDestinationAddress {
+                    address: address.to_string(),
+                })?
+                .require_network(transform_network(self.network))
+                .map(|address| address.script_pubkey())
+                .map_err(|_| BuildP2wpkhTxError::WrongBitcoinNetwork);
+        }
+        if let Some(script_pubkey) = self.parse_address(address) {
+            return Ok(script_pubkey);
+        }
+        let is_on_other_network = NETWORKS.into_iter().any(|network| {
+            ChainParams::new(self.chain, network)
+                .parse_address(address)
+                .is_some()
+        });
+        if is_on_other_network {
+            Err(BuildP2wpkhTxError::WrongBitcoinNetwork)
+        } else {
+            Err(BuildP2wpkhTxError::InvalidDestinationAddress {
+                address: address.to_string(),
+            })
+        }
+    }
+
+    /// Parses an address of this chain and network.
+    fn parse_address(&self, address: &str) -> Option<ScriptBuf> {
+        if let Some(bech32_hrp) = self.bech32_hrp {
+            if let Ok((hrp, version, program)) = segwit::decode(address) {
+                if hrp.to_lowercase() != bech32_hrp {
+                    return None;
+                }
+                let version = WitnessVersion::try_from(version).ok()?;
+                let program = WitnessProgram::new(version, &program).ok()?;
+                return Some(ScriptBuf::new_witness_program(&program));
+            }
+        }
+        let payload = base58::decode_check(address).ok()?;
+        let (version, hash) = payload.split_first()?;
+        let hash: [u8; 20] = hash.try_into().ok()?;
+        if *version == self.pubkey_hash_version {
+            Some(ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(hash)))
+        } else if *version == self.script_hash_version {
+            Some(ScriptBuf::new_p2sh(&ScriptHash::from_byte_array(hash)))
+        } else {
+            None
+        }
+    }
+
+    /// Computes the size, in virtual bytes, that a transaction spending the caller's outputs will
+    /// have once signed.
+    pub fn estimate_signed_vsize(&self, transaction: &Transaction) -> u64 {
+        match self.address_kind {
+            AddressKind::P2wpkh => estimate_signed_vsize(transaction),
+            AddressKind::P2pkh => estimate_signed_p2pkh_vsize(transaction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{Address, Network};
+    use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BuildP2wpkhTxError};
+
+    use super::{ChainParams, UtxoChain};
+    use crate::sign::bitcoin::test_utils::generator_public_key;
+
+    #[test]
+    fn bitcoin_address_matches_the_bitcoin_crate() {
+        for (network, bitcoin_network) in [
+            (BitcoinNetwork::Mainnet, Network::Bitcoin),
+            (BitcoinNetwork::Testnet, Network::Testnet4),
+            (BitcoinNetwork::Testnet4, Network::Testnet4),
+            (BitcoinNetwork::Signet, Network::Signet),
+            (BitcoinNetwork::Regtest, Network::Regtest),
+        ] {
+            assert_eq!(
+                ChainParams::bitcoin(network).address(&generator_public_key()),
+                Address::p2wpkh(&generator_public_key(), bitcoin_network).to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn dogecoin_address_is_p2pkh_with_the_dogecoin_prefix() {
+        let params = ChainParams::dogecoin(BitcoinNetwork::Mainnet);
+        let address = params.address(&generator_public_key());
+        assert_eq!(address, "DFpN6QqFfUm3gKNaxN6tNcab1FArL9cZLE");
+        assert_eq!(
+            params.script_pubkey(&address),
+            Ok(params.own_script_pubkey(&generator_public_key()))
+        );
+    }
+
+    #[test]
+    fn litecoin_address_is_p2wpkh_with_the_litecoin_prefix() {
+        let params = ChainParams::litecoin(BitcoinNetwork::Mainnet);
+        let address = params.address(&generator_public_key());
+        assert_eq!(address, "ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9");
+        assert_eq!(
+            params.script_pubkey(&address),
+            Ok(params.own_script_pubkey(&generator_public_key()))
+        );
+    }
+
+    #[test]
+    fn testnet4_and_signet_use_the_test_network_of_the_chain() {
+        for chain in [UtxoChain::Bitcoin, UtxoChain::Dogecoin, UtxoChain::Litecoin] {
+            let testnet_address =
+                ChainParams::new(chain, BitcoinNetwork::Testnet).address(&generator_public_key());
+            for network in [BitcoinNetwork::Testnet4, BitcoinNetwork::Signet] {
+                let params = ChainParams::new(chain, network);
+                assert_eq!(params.address(&generator_public_key()), testnet_address);
+                assert!(params.script_pubkey(&testnet_address).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn address_of_another_network_is_rejected() {
+        let mainnet_address =
+            ChainParams::litecoin(BitcoinNetwork::Mainnet).address(&generator_public_key());
+        assert_eq!(
+            ChainParams::litecoin(BitcoinNetwork::Testnet).script_pubkey(&mainnet_address),
+            Err(BuildP2wpkhTxError::WrongBitcoinNetwork)
+        );
+        let bitcoin_address =
+            ChainParams::bitcoin(BitcoinNetwork::Mainnet).address(&generator_public_key());
+        assert_eq!(
+            ChainParams::new(UtxoChain::Dogecoin, BitcoinNetwork::Mainnet)
+                .script_pubkey(&bitcoin_address),
+            Err(BuildP2wpkhTxError::InvalidDestinationAddress {
+                address: bitcoin_address
+            })
+        );
+    }
+}
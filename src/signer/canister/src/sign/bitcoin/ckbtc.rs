@@ -0,0 +1,219 @@
+//! Deposits to ckBTC: the deposit address and the `update_balance` call of the ckBTC minter.
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::call::Call;
+use ic_chain_fusion_signer_api::types::bitcoin::CkbtcUpdateBalance;
+
+use crate::state::read_config;
+
+/// The mainnet ckBTC minter, used if `Config::ckbtc_minter` is not set.
+pub const MAINNET_CKBTC_MINTER: &str = "mqygn-kiaaa-aaaar-qaadq-cai";
+
+/// The length of an ICRC-1 subaccount, in bytes.
+const SUBACCOUNT_BYTES: usize = 32;
+
+/// The ckBTC minter that deposits go to.
+pub fn ckbtc_minter() -> Principal {
+    read_config(|config| config.ckbtc_minter).unwrap_or_else(|| {
+        Principal::from_text(MAINNET_CKBTC_MINTER).expect("Invalid mainnet ckBTC minter")
+    })
+}
+
+/// The argument of the minter's `get_btc_address` and `update_balance`.
+///
+/// The owner defaults to the minter's caller, so it is always set: the caller is this canister.
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct MinterAccount {
+    pub owner: Option<Principal>,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+impl MinterAccount {
+    /// The account of `owner` and `subaccount`, if the subaccount is 32 bytes long.
+    pub fn new(owner: Principal, subaccount: Option<Vec<u8>>) -> Result<Self, String> {
+        if let Some(subaccount) = &subaccount {
+            if subaccount.len() != SUBACCOUNT_BYTES {
+                return Err(format!(
+                    "the subaccount must be {SUBACCOUNT_BYTES} bytes, got {}",
+                    subaccount.len()
+                ));
+            }
+        }
+        Ok(MinterAccount {
+            owner: Some(owner),
+            subaccount,
+        })
+    }
+}
+
+/// A UTXO of the deposit address.  The other fields are not needed.
+#[derive(CandidType, Deserialize, Debug)]
+struct MinterUtxo {
+    value: u64,
+}
+
+/// A UTXO that the minter minted ckBTC for.  The other fields are not needed.
+#[derive(CandidType, Deserialize, Debug)]
+struct MintedUtxo {
+    minted_amount: u64,
+}
+
+/// The status of a UTXO of the deposit address, as returned by `update_balance`.
+#[derive(CandidType, Deserialize, Debug)]
+enum UtxoStatus {
+    ValueTooSmall(MinterUtxo),
+    Tainted(MinterUtxo),
+    /// Checked, but not minted yet; a later `update_balance` call mints it.
+    Checked(MinterUtxo),
+    Minted(MintedUtxo),
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum UpdateBalanceError {
+    GenericError {
+        error_code: u64,
+        error_message: String,
+    },
+    TemporarilyUnavailable(String),
+    AlreadyProcessing,
+    NoNewUtxos {
+        current_confirmations: Option<u32>,
+        required_confirmations: u32,
+    },
+}
+
+/// Gets the deposit address of `account` from `minter`.
+pub async fn get_btc_address(minter: Principal, account: &MinterAccount) -> Result<String, String> {
+    Call::bounded_wait(minter, "get_btc_address")
+        .with_arg(account)
+        .await
+        .map_err(|e| format!("get_btc_address failed: {e}"))?
+        .candid()
+        .map_err(|e| format!("get_btc_address returned an unexpected reply: {e}"))
+}
+
+/// Asks `minter` to mint ckBTC for new UTXOs of the deposit address of `account`.
+pub async fn update_balance(minter: Principal, account: &MinterAccount) -> CkbtcUpdateBalance {
+    let reply = match Call::bounded_wait(minter, "update_balance")
+        .with_arg(account)
+        .await
+    {
+        Ok(response) => response
+            .candid()
+            .map_err(|e| format!("update_balance returned an unexpected reply: {e}")),
+        Err(e) => Err(format!("update_balance failed: {e}")),
+    };
+    update_balance_outcome(reply)
+}
+
+fn update_balance_outcome(
+    reply: Result<Result<Vec<UtxoStatus>, UpdateBalanceError>, String>,
+) -> CkbtcUpdateBalance {
+    match reply {
+        Ok(Ok(statuses)) => {
+            let (mut minted_satoshis, mut unminted_satoshis) = (0, 0);
+            for status in statuses {
+                match status {
+                    UtxoStatus::Minted(utxo) => minted_satoshis += utxo.minted_amount,
+                    UtxoStatus::ValueTooSmall(utxo)
+                    | UtxoStatus::Tainted(utxo)
+                    | UtxoStatus::Checked(utxo) => unminted_satoshis += utxo.value,
+                }
+            }
+            CkbtcUpdateBalance::Updated {
+                minted_satoshis,
+                unminted_satoshis,
+            }
+        }
+        Ok(Err(UpdateBalanceError::NoNewUtxos {
+            current_confirmations,
+            required_confirmations,
+        })) => CkbtcUpdateBalance::NoNewUtxos {
+            required_confirmations,
+            current_confirmations,
+        },
+        Ok(Err(UpdateBalanceError::GenericError {
+            error_code,
+            error_message,
+        })) => CkbtcUpdateBalance::Failed {
+            msg: format!("error {error_code}: {error_message}"),
+        },
+        Ok(Err(UpdateBalanceError::TemporarilyUnavailable(msg))) => {
+            CkbtcUpdateBalance::Failed { msg }
+        }
+        Ok(Err(UpdateBalanceError::AlreadyProcessing)) => CkbtcUpdateBalance::Failed {
+            msg: "the minter is already processing the account".to_string(),
+        },
+        Err(msg) => CkbtcUpdateBalance::Failed { msg },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use ic_chain_fusion_signer_api::types::bitcoin::CkbtcUpdateBalance;
+
+    use super::{
+        update_balance_outcome, MintedUtxo, MinterAccount, MinterUtxo, UpdateBalanceError,
+        UtxoStatus,
+    };
+
+    #[test]
+    fn test_minter_account_needs_a_32_byte_subaccount() {
+        let owner = Principal::anonymous();
+
+        assert_eq!(
+            MinterAccount::new(owner, None),
+            Ok(MinterAccount {
+                owner: Some(owner),
+                subaccount: None
+            })
+        );
+        assert!(MinterAccount::new(owner, Some(vec![1; 32])).is_ok());
+        assert_eq!(
+            MinterAccount::new(owner, Some(vec![1; 31])),
+            Err("the subaccount must be 32 bytes, got 31".to_string())
+        );
+    }
+
+    #[test]
+    fn test_update_balance_outcome() {
+        assert_eq!(
+            update_balance_outcome(Ok(Ok(vec![
+                UtxoStatus::Minted(MintedUtxo {
+                    minted_amount: 90_000
+                }),
+                UtxoStatus::ValueTooSmall(MinterUtxo { value: 500 }),
+                UtxoStatus::Checked(MinterUtxo { value: 20_000 }),
+                UtxoStatus::Minted(MintedUtxo {
+                    minted_amount: 5_000
+                }),
+            ]))),
+            CkbtcUpdateBalance::Updated {
+                minted_satoshis: 95_000,
+                unminted_satoshis: 20_500,
+            }
+        );
+        assert_eq!(
+            update_balance_outcome(Ok(Err(UpdateBalanceError::NoNewUtxos {
+                current_confirmations: Some(2),
+                required_confirmations: 6,
+            }))),
+            CkbtcUpdateBalance::NoNewUtxos {
+                required_confirmations: 6,
+                current_confirmations: Some(2),
+            }
+        );
+        assert_eq!(
+            update_balance_outcome(Ok(Err(UpdateBalanceError::AlreadyProcessing))),
+            CkbtcUpdateBalance::Failed {
+                msg: "the minter is already processing the account".to_string()
+            }
+        );
+        assert_eq!(
+            update_balance_outcome(Err("update_balance failed: timeout".to_string())),
+            CkbtcUpdateBalance::Failed {
+                msg: "update_balance failed: timeout".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Selection of the UTXOs that `btc_consolidate` merges when the caller does not list them.
+use ic_cdk_bitcoin_canister::Utxo;
+
+use crate::sign::bitcoin::fee_utils::fee_for_vsize;
+
+/// The number of UTXOs merged by default.
+pub const DEFAULT_CONSOLIDATION_INPUTS: u64 = 50;
+/// The most UTXOs that one consolidation merges, so that it can be signed in one call.
+pub const MAX_CONSOLIDATION_INPUTS: u64 = 100;
+/// The size that a P2WPKH input adds to a signed transaction, in virtual bytes, rounded up.
+const P2WPKH_INPUT_VSIZE: u64 = 68;
+
+/// Picks the UTXOs to merge: the smallest first, so that consolidating reduces the number of UTXOs
+/// the most, skipping those that are worth no more than the fee of spending them.
+pub fn select_utxos(
+    mut utxos: Vec<Utxo>,
+    fee_rate_millisat_per_vbyte: u64,
+    max_inputs: u64,
+) -> Vec<Utxo> {
+    let input_fee = fee_for_vsize(P2WPKH_INPUT_VSIZE, fee_rate_millisat_per_vbyte);
+    utxos.retain(|utxo| utxo.value > input_fee);
+    utxos.sort_by_key(|utxo| utxo.value);
+    utxos.truncate(usize::try_from(max_inputs).unwrap_or(usize::MAX));
+    utxos
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_cdk_bitcoin_canister::{OutPoint, Txid, Utxo};
+
+    use super::select_utxos;
+
+    fn utxo(vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint {
+                txid: Txid::from([1; 32]),
+                vout,
+            },
+            value,
+            height: 100,
+        }
+    }
+
+    #[test]
+    fn test_select_utxos_merges_the_smallest_first() {
+        let utxos = vec![utxo(0, 30_000), utxo(1, 10_000), utxo(2, 20_000)];
+
+        assert_eq!(
+            select_utxos(utxos.clone(), 1_000, 2),
+            vec![utxo(1, 10_000), utxo(2, 20_000)]
+        );
+        assert_eq!(select_utxos(utxos, 1_000, 10).len(), 3);
+    }
+
+    #[test]
+    fn test_select_utxos_skips_uneconomic_utxos() {
+        // At 10 sat/vbyte, spending an input costs 680 satoshi.
+        let utxos = vec![utxo(0, 680), utxo(1, 681), utxo(2, 100)];
+
+        assert_eq!(select_utxos(utxos, 10_000, 10), vec![utxo(1, 681)]);
+    }
+}
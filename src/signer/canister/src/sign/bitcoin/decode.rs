@@ -0,0 +1,343 @@
+//! Decoding of raw transactions and PSBTs, so that callers can review a transaction before and
+//! after it is signed.
+use bitcoin::{consensus, psbt::Psbt, Address, Amount, Transaction, TxIn};
+use ic_cdk_bitcoin_canister::Utxo;
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BtcDecodeTransactionError, BtcDecodeTransactionRequest, BtcDecodeTransactionResponse,
+    BtcDecodedInput, BtcDecodedOutput,
+};
+
+use crate::sign::bitcoin::{
+    bitcoin_utils::transform_network,
+    chain::ChainParams,
+    tx_utils::{get_input_value, max_p2wpkh_witness},
+};
+
+/// A transaction with the values of the outputs it spends, where they are known.
+struct DecodedTransaction {
+    transaction: Transaction,
+    input_values: Vec<Option<u64>>,
+    is_psbt: bool,
+}
+
+/// Parses `transaction_hex` as a PSBT and, failing that, as a raw transaction.
+///
+/// The inputs of a PSBT take their values from their `witness_utxo` or `non_witness_utxo`, and
+/// then from `utxos`.
+fn parse_transaction(
+    transaction_hex: &str,
+    utxos: &[Utxo],
+) -> Result<DecodedTransaction, BtcDecodeTransactionError> {
+    let bytes = hex::decode(transaction_hex).map_err(|e| {
+        BtcDecodeTransactionError::InvalidTransaction {
+            msg: format!("failed to decode hex: {e}"),
+        }
+    })?;
+    let (transaction, psbt_values, is_psbt) = if let Ok(psbt) = Psbt::deserialize(&bytes) {
+        let psbt_values: Vec<Option<u64>> = psbt
+            .inputs
+            .iter()
+            .zip(&psbt.unsigned_tx.input)
+            .map(|(input, txin)| {
+                input
+                    .witness_utxo
+                    .as_ref()
+                    .or_else(|| {
+                        input.non_witness_utxo.as_ref().and_then(|previous| {
+                            previous
+                                .output
+                                .get(usize::try_from(txin.previous_output.vout).ok()?)
+                        })
+                    })
+                    .map(|output| output.value.to_sat())
+            })
+            .collect();
+        // Finalized inputs keep their witness or `scriptSig`; others are left empty.
+        (psbt.extract_tx_unchecked_fee_rate(), psbt_values, true)
+    } else {
+        let transaction: Transaction = consensus::deserialize(&bytes).map_err(|e| {
+            BtcDecodeTransactionError::InvalidTransaction {
+                msg: format!("failed to decode transaction: {e}"),
+            }
+        })?;
+        (transaction, Vec::new(), false)
+    };
+    let input_values = transaction
+        .input
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            psbt_values
+                .get(index)
+                .copied()
+                .flatten()
+                .or_else(|| get_input_value(input, utxos).map(Amount::to_sat))
+        })
+        .collect();
+    Ok(DecodedTransaction {
+        transaction,
+        input_values,
+        is_psbt,
+    })
+}
+
+fn is_signed(input: &TxIn) -> bool {
+    !input.witness.is_empty() || !input.script_sig.is_empty()
+}
+
+/// The size of the transaction, in virtual bytes, with unsigned inputs counted as P2WPKH inputs.
+fn vsize(transaction: &Transaction) -> u64 {
+    let mut transaction = transaction.clone();
+    for input in &mut transaction.input {
+        if !is_signed(input) {
+            input.witness = max_p2wpkh_witness();
+        }
+    }
+    transaction.vsize() as u64
+}
+
+/// Decodes a transaction or a PSBT for `btc_decode_transaction`.
+pub fn decode_transaction(
+    request: &BtcDecodeTransactionRequest,
+) -> Result<BtcDecodeTransactionResponse, BtcDecodeTransactionError> {
+    let own_script_pubkey = request
+        .own_address
+        .as_deref()
+        .map(|address| {
+            ChainParams::bitcoin(request.network)
+                .script_pubkey(address)
+                .map_err(|_| BtcDecodeTransactionError::InvalidOwnAddress {
+                    address: address.to_string(),
+                })
+        })
+        .transpose()?;
+    let DecodedTransaction {
+        transaction,
+        input_values,
+        is_psbt,
+    } = parse_transaction(
+        &request.transaction_hex,
+        request.utxos.as_deref().unwrap_or_default(),
+    )?;
+
+    let inputs = transaction
+        .input
+        .iter()
+        .zip(&input_values)
+        .map(|(input, value)| BtcDecodedInput {
+            txid: input.previous_output.txid.to_string(),
+            vout: input.previous_output.vout,
+            sequence: input.sequence.to_consensus_u32(),
+            value: *value,
+            is_signed: is_signed(input),
+        })
+        .collect();
+    let network = transform_network(request.network);
+    let outputs = transaction
+        .output
+        .iter()
+        .map(|output| BtcDecodedOutput {
+            value: output.value.to_sat(),
+            script_pubkey_hex: hex::encode(output.script_pubkey.as_bytes()),
+            address: Address::from_script(&output.script_pubkey, network)
+                .ok()
+                .map(|address| address.to_string()),
+            pays_caller: own_script_pubkey.as_ref() == Some(&output.script_pubkey),
+        })
+        .collect();
+
+    let vsize = vsize(&transaction);
+    let fee_satoshis = input_values
+        .iter()
+        .copied()
+        .sum::<Option<u64>>()
+        .map(|inputs_value| {
+            let outputs_value: u64 = transaction
+                .output
+                .iter()
+                .map(|output| output.value.to_sat())
+                .sum();
+            // Wrong `utxos` would otherwise show as a free transaction.
+            inputs_value.checked_sub(outputs_value).ok_or(
+                BtcDecodeTransactionError::OutputsExceedInputs {
+                    inputs_value,
+                    outputs_value,
+                },
+            )
+        })
+        .transpose()?;
+    Ok(BtcDecodeTransactionResponse {
+        txid: transaction.compute_txid().to_string(),
+        is_psbt,
+        inputs,
+        outputs,
+        fee_satoshis,
+        vsize,
+        fee_rate_millisat_per_vbyte: fee_satoshis.map(|fee| fee * 1000 / vsize.max(1)),
+        signals_rbf: transaction.is_explicitly_rbf(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        absolute::LockTime, consensus, hashes::Hash, psbt::Psbt, transaction::Version, OutPoint,
+        ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+    use ic_cdk_bitcoin_canister::{OutPoint as IcCdkOutPoint, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcDecodeTransactionError, BtcDecodeTransactionRequest,
+    };
+
+    use super::decode_transaction;
+    use crate::sign::bitcoin::{
+        chain::ChainParams, test_utils::generator_public_key, tx_utils::estimate_signed_vsize,
+    };
+
+    const TXID: &str = "36f3a7fcb6b5ebd9fa4041928da89cd423662f9c5c12e41c80e07a6559d178ef";
+
+    /// An unsigned transaction that spends 100,000 satoshi to an `OP_RETURN` output and to the
+    /// address of `public_key`.
+    fn unsigned_transaction() -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_str(TXID).unwrap(), 1),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![
+                TxOut {
+                    value: bitcoin::Amount::ZERO,
+                    script_pubkey: ScriptBuf::new_op_return([1, 2, 3]),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(99_000),
+                    script_pubkey: ScriptBuf::new_p2wpkh(&generator_public_key().wpubkey_hash()),
+                },
+            ],
+        }
+    }
+
+    fn spent_utxo() -> Utxo {
+        Utxo {
+            outpoint: IcCdkOutPoint {
+                txid: Txid::from_str(TXID).unwrap().to_byte_array().into(),
+                vout: 1,
+            },
+            value: 100_000,
+            height: 100,
+        }
+    }
+
+    fn request(transaction_hex: String) -> BtcDecodeTransactionRequest {
+        BtcDecodeTransactionRequest {
+            network: BitcoinNetwork::Regtest,
+            transaction_hex,
+            utxos: None,
+            own_address: None,
+        }
+    }
+
+    #[test]
+    fn raw_transaction_is_decoded_with_the_fee_of_the_given_utxos() {
+        let transaction = unsigned_transaction();
+        let own_address =
+            ChainParams::bitcoin(BitcoinNetwork::Regtest).address(&generator_public_key());
+
+        let decoded = decode_transaction(&BtcDecodeTransactionRequest {
+            utxos: Some(vec![spent_utxo()]),
+            own_address: Some(own_address.clone()),
+            ..request(consensus::encode::serialize_hex(&transaction))
+        })
+        .unwrap();
+
+        assert_eq!(decoded.txid, transaction.compute_txid().to_string());
+        assert!(!decoded.is_psbt);
+        assert_eq!(decoded.inputs[0].value, Some(100_000));
+        assert!(!decoded.inputs[0].is_signed);
+        assert_eq!(decoded.outputs[0].address, None);
+        assert!(!decoded.outputs[0].pays_caller);
+        assert_eq!(decoded.outputs[1].address, Some(own_address));
+        assert!(decoded.outputs[1].pays_caller);
+        assert_eq!(decoded.fee_satoshis, Some(1_000));
+        // The unsigned input is counted as a signed P2WPKH input.
+        assert_eq!(decoded.vsize, estimate_signed_vsize(&transaction));
+        assert_eq!(
+            decoded.fee_rate_millisat_per_vbyte,
+            Some(1_000_000 / decoded.vsize)
+        );
+        assert!(decoded.signals_rbf);
+    }
+
+    #[test]
+    fn fee_is_unknown_without_the_values_of_the_inputs() {
+        let decoded = decode_transaction(&request(consensus::encode::serialize_hex(
+            &unsigned_transaction(),
+        )))
+        .unwrap();
+
+        assert_eq!(decoded.inputs[0].value, None);
+        assert_eq!(decoded.fee_satoshis, None);
+        assert_eq!(decoded.fee_rate_millisat_per_vbyte, None);
+    }
+
+    #[test]
+    fn utxos_worth_less_than_the_outputs_are_rejected() {
+        let utxo = Utxo {
+            value: 98_000,
+            ..spent_utxo()
+        };
+
+        let decoded = decode_transaction(&BtcDecodeTransactionRequest {
+            utxos: Some(vec![utxo]),
+            ..request(consensus::encode::serialize_hex(&unsigned_transaction()))
+        });
+
+        assert_eq!(
+            decoded,
+            Err(BtcDecodeTransactionError::OutputsExceedInputs {
+                inputs_value: 98_000,
+                outputs_value: 99_000
+            })
+        );
+    }
+
+    #[test]
+    fn psbt_inputs_take_their_value_from_the_witness_utxo() {
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_transaction()).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: bitcoin::Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&generator_public_key().wpubkey_hash()),
+        });
+
+        let decoded = decode_transaction(&request(hex::encode(psbt.serialize()))).unwrap();
+
+        assert!(decoded.is_psbt);
+        assert_eq!(decoded.inputs[0].value, Some(100_000));
+        assert_eq!(decoded.fee_satoshis, Some(1_000));
+    }
+
+    #[test]
+    fn invalid_transaction_and_own_address_are_rejected() {
+        assert!(matches!(
+            decode_transaction(&request("00".to_string())),
+            Err(BtcDecodeTransactionError::InvalidTransaction { .. })
+        ));
+        let mainnet_address =
+            ChainParams::bitcoin(BitcoinNetwork::Mainnet).address(&generator_public_key());
+        assert_eq!(
+            decode_transaction(&BtcDecodeTransactionRequest {
+                own_address: Some(mainnet_address.clone()),
+                ..request(consensus::encode::serialize_hex(&unsigned_transaction()))
+            }),
+            Err(BtcDecodeTransactionError::InvalidOwnAddress {
+                address: mainnet_address
+            })
+        );
+    }
+}
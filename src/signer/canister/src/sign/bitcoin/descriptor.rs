@@ -0,0 +1,250 @@
+//! BIP-380 output descriptors of the caller's outputs, so that descriptor-aware wallets can watch
+//! them.
+use bitcoin::{opcodes::all::OP_CHECKSIG, CompressedPublicKey, Script, XOnlyPublicKey};
+
+use crate::sign::bitcoin::{taproot::TaprootTree, vault::VaultPolicy};
+
+/// The characters that may appear in a descriptor, in the order of their checksum values.
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+/// The characters of a checksum, as in bech32.
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn poly_mod(c: u64, value: u64) -> u64 {
+    const GENERATORS: [u64; 5] = [
+        0xf5_dee5_1989,
+        0xa9_fdca_3312,
+        0x1b_ab10_e32d,
+        0x37_06b1_677a,
+        0x64_4d62_6ffd,
+    ];
+    let top = c >> 35;
+    GENERATORS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (top >> i) & 1 == 1)
+        .fold(((c & 0x7_ffff_ffff) << 5) ^ value, |c, (_, generator)| {
+            c ^ generator
+        })
+}
+
+/// The BIP-380 checksum of `descriptor`, or `None` if it has a character that descriptors may not
+/// contain.
+pub fn checksum(descriptor: &str) -> Option<String> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+    Some(
+        (0..8)
+            .map(|i| char::from(CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize]))
+            .collect(),
+    )
+}
+
+/// Appends the checksum to a descriptor that this module built.
+fn with_checksum(descriptor: &str) -> String {
+    let checksum = checksum(descriptor).expect("Descriptors are built from valid characters");
+    format!("{descriptor}#{checksum}")
+}
+
+/// The descriptor of the P2WPKH output of `public_key`.
+pub fn wpkh(public_key: &CompressedPublicKey) -> String {
+    with_checksum(&format!("wpkh({public_key})"))
+}
+
+/// The descriptor of the vault of `signer`, with the miniscript of [`super::vault::vault`].
+pub fn vault(signer: &CompressedPublicKey, policy: &VaultPolicy) -> String {
+    with_checksum(&format!(
+        "wsh(or_d(multi(2,{signer},{}),and_v(v:pk({}),older({}))))",
+        policy.cosigner, policy.recovery, policy.recovery_delay_blocks
+    ))
+}
+
+/// The descriptor of a taproot output, with the depths of its leaves in the order of
+/// `tree.leaves`.
+///
+/// Descriptors only describe leaves in miniscript, while a tree may hold any tapscript.  So the
+/// tree is written as `tr(<internal key>,{...})` if every leaf is `<key> OP_CHECKSIG`, i.e.
+/// `pk(<key>)`, and otherwise as `rawtr(<output key>)`, which watches the output but cannot spend
+/// it.
+pub fn taproot(tree: &TaprootTree, depths: &[u8]) -> String {
+    let leaves: Option<Vec<XOnlyPublicKey>> =
+        tree.leaves.iter().map(|leaf| checksig_key(leaf)).collect();
+    let descriptor = match leaves {
+        Some(keys) => {
+            let internal_key = tree.spend_info.internal_key();
+            let mut leaves = keys.iter().zip(depths).peekable();
+            match depths {
+                [0] => format!("tr({internal_key},pk({}))", keys[0]),
+                _ => format!("tr({internal_key},{})", tap_tree(&mut leaves, 0)),
+            }
+        }
+        None => format!("rawtr({})", tree.spend_info.output_key()),
+    };
+    with_checksum(&descriptor)
+}
+
+/// The key of a `<key> OP_CHECKSIG` leaf.
+fn checksig_key(leaf: &Script) -> Option<XOnlyPublicKey> {
+    match leaf.as_bytes() {
+        [0x20, key @ .., checksig] if key.len() == 32 && *checksig == OP_CHECKSIG.to_u8() => {
+            XOnlyPublicKey::from_slice(key).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The `{left,right}` expression of the subtree at `depth` that starts with the next leaf.  The
+/// depths are those of a complete binary tree, as checked by
+/// [`super::taproot::parse_taproot_tree`].
+fn tap_tree<'a>(
+    leaves: &mut std::iter::Peekable<impl Iterator<Item = (&'a XOnlyPublicKey, &'a u8)>>,
+    depth: u8,
+) -> String {
+    match leaves.peek() {
+        Some((key, leaf_depth)) if **leaf_depth == depth => {
+            let key = **key;
+            leaves.next();
+            format!("pk({key})")
+        }
+        _ => {
+            let left = tap_tree(leaves, depth + 1);
+            let right = tap_tree(leaves, depth + 1);
+            format!("{{{left},{right}}}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::ScriptBuf;
+    use ic_chain_fusion_signer_api::types::bitcoin::{BtcTapLeaf, BtcTaprootTree};
+
+    use super::{checksum, taproot, vault, wpkh};
+    use crate::sign::bitcoin::{
+        taproot::{parse_taproot_tree, taproot_tree, TaprootTree},
+        test_utils::{generator_public_key, public_key, x_only_key},
+        vault::VaultPolicy,
+    };
+
+    #[test]
+    fn test_checksum_matches_bip_380() {
+        assert_eq!(checksum("raw(deadbeef)"), Some("89f8spxm".to_string()));
+        assert_eq!(checksum("raw(deadbeef)\u{e9}"), None);
+    }
+
+    #[test]
+    fn test_wpkh_descriptor() {
+        let generator = generator_public_key();
+
+        assert_eq!(
+            wpkh(&generator),
+            "wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#ucxz0gak"
+        );
+    }
+
+    #[test]
+    fn test_vault_descriptor() {
+        let policy = VaultPolicy {
+            cosigner: public_key(2),
+            recovery: public_key(3),
+            recovery_delay_blocks: 144,
+        };
+
+        let descriptor = vault(&public_key(1), &policy);
+
+        let (body, descriptor_checksum) = descriptor.split_once('#').unwrap();
+        assert_eq!(
+            body,
+            format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(144))))",
+                hex::encode(public_key(1).to_bytes()),
+                hex::encode(public_key(2).to_bytes()),
+                hex::encode(public_key(3).to_bytes())
+            )
+        );
+        assert_eq!(checksum(body).as_deref(), Some(descriptor_checksum));
+    }
+
+    /// The tree of the caller `x_only_key(1)` with leaves of the given scripts and depths, and
+    /// its descriptor.
+    fn taproot_descriptor(leaves: &[(ScriptBuf, u8)]) -> (TaprootTree, String) {
+        let leaves: Vec<BtcTapLeaf> = leaves
+            .iter()
+            .map(|(script, depth)| BtcTapLeaf {
+                script_hex: hex::encode(script.as_bytes()),
+                depth: *depth,
+            })
+            .collect();
+        let depths: Vec<u8> = leaves.iter().map(|leaf| leaf.depth).collect();
+        let terms = parse_taproot_tree(&BtcTaprootTree {
+            internal_key: None,
+            leaves,
+        })
+        .unwrap();
+        let tree = taproot_tree(terms, x_only_key(1));
+        let descriptor = taproot(&tree, &depths);
+        (tree, descriptor)
+    }
+
+    fn checksig_leaf(seed: u8) -> ScriptBuf {
+        ScriptBuf::builder()
+            .push_x_only_key(&x_only_key(seed))
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script()
+    }
+
+    #[test]
+    fn test_taproot_descriptor_of_checksig_leaves() {
+        let (_, descriptor) = taproot_descriptor(&[
+            (checksig_leaf(2), 1),
+            (checksig_leaf(3), 2),
+            (checksig_leaf(4), 2),
+        ]);
+
+        let (body, descriptor_checksum) = descriptor.split_once('#').unwrap();
+        assert_eq!(
+            body,
+            format!(
+                "tr({},{{pk({}),{{pk({}),pk({})}}}})",
+                x_only_key(1),
+                x_only_key(2),
+                x_only_key(3),
+                x_only_key(4)
+            )
+        );
+        assert_eq!(checksum(body).as_deref(), Some(descriptor_checksum));
+        assert!(taproot_descriptor(&[(checksig_leaf(2), 0)])
+            .1
+            .starts_with(&format!("tr({},pk({}))#", x_only_key(1), x_only_key(2))));
+    }
+
+    #[test]
+    fn test_taproot_descriptor_of_other_leaves_is_raw() {
+        // `OP_TRUE` is not a miniscript.
+        let (tree, descriptor) = taproot_descriptor(&[
+            (checksig_leaf(2), 1),
+            (ScriptBuf::from_bytes(vec![0x51]), 1),
+        ]);
+
+        assert!(descriptor.starts_with(&format!("rawtr({})#", tree.spend_info.output_key())));
+    }
+}
@@ -1,59 +1,323 @@
-use ic_cdk_bitcoin_canister::{Network, Utxo};
+use ic_cdk_bitcoin_canister::MillisatoshiPerByte;
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BitcoinNetwork, BtcFeeLimits, BtcFeePriority, BtcFeeRate, BuildP2wpkhTxError, SendBtcError,
+};
 
 use super::bitcoin_api;
+use crate::state::read_config;
 
-/// Computes an estimate for the size of transaction (in vbytes) with the given number of inputs and
-/// outputs.
+/// The fee rate used when the network reports no fee percentiles.
 ///
-/// Functions stolen from [ckBTC Minter](https://github.com/dfinity/ic/blob/285a5db07da50a4e350ec43bf3b488cc6fe36102/rs/bitcoin/ckbtc/minter/src/lib.rs#L1258)
-fn tx_vsize_estimate(input_count: u64, output_count: u64) -> u64 {
-    // See
-    // [Bitcoin wiki](https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki)
-    // for the transaction structure and
-    // [StackExchange]](https://bitcoin.stackexchange.com/questions/92587/calculate-transaction-fee-for-external-addresses-which-doesnt-belong-to-my-loca/92600#92600)
-    // for transaction size estimate.
-    const INPUT_SIZE_VBYTES: u64 = 68;
-    const OUTPUT_SIZE_VBYTES: u64 = 31;
-    const TX_OVERHEAD_VBYTES: u64 = 11;
+/// This case can only happen on a regtest network where there are no non-coinbase transactions,
+/// or on a network without a Bitcoin canister, such as Signet.  In this case, we use a default of
+/// 2000 millisatoshis/byte (i.e. 2 satoshi/byte).
+pub const DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE: MillisatoshiPerByte = 2000;
 
-    input_count * INPUT_SIZE_VBYTES + output_count * OUTPUT_SIZE_VBYTES + TX_OVERHEAD_VBYTES
+/// The fee with which a transaction is built.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fee {
+    /// A fixed fee, in satoshi.
+    Absolute(u64),
+    /// A fee rate, in millisatoshi per virtual byte, applied to the size of the built transaction.
+    Rate(MillisatoshiPerByte),
 }
 
-/// Computes an estimate for the passed selected utxos.
+/// The fee of a transaction, resolved from the fee arguments of a request, and how to check it
+/// against `Config::btc_fee_limits` once the transaction is built.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestFee {
+    /// The fee with which the transaction is built.
+    pub fee: Fee,
+    /// Whether the fee rate was taken from the network fee percentiles.
+    from_network: bool,
+    allow_high_fee: bool,
+    network: BitcoinNetwork,
+}
+
+impl RequestFee {
+    /// Resolves the fee arguments of a request, as [`calculate_fee`] does.
+    pub async fn resolve(
+        fee_satoshis: Option<u64>,
+        fee_rate: Option<BtcFeeRate>,
+        allow_high_fee: Option<bool>,
+        network: BitcoinNetwork,
+    ) -> Result<Self, SendBtcError> {
+        Ok(RequestFee {
+            fee: calculate_fee(fee_satoshis, fee_rate, network).await?,
+            from_network: fee_satoshis.is_none()
+                && !matches!(fee_rate, Some(BtcFeeRate::SatPerVbyte(_))),
+            allow_high_fee: allow_high_fee.unwrap_or(false),
+            network,
+        })
+    }
+
+    /// Checks the fee of the built transaction with [`check_fee`], unless the request sets
+    /// `allow_high_fee`.
+    pub async fn check(
+        &self,
+        fee_satoshis: u64,
+        vsize: u64,
+        sent_satoshis: Option<u64>,
+    ) -> Result<(), SendBtcError> {
+        if self.allow_high_fee {
+            return Ok(());
+        }
+        check_fee(
+            fee_satoshis,
+            vsize,
+            sent_satoshis,
+            self.from_network,
+            self.network,
+        )
+        .await
+    }
+}
+
+/// Computes the fee, in satoshi, of a transaction of `vsize` virtual bytes at the given rate.
 ///
-/// Arguments:
-///   * `available_utxos` - the list of UTXOs that will be sent in the transaction.
-///   * `median_fee_millisatoshi_per_vbyte` - the median network fee, in millisatoshi per vbyte.
+/// Rounds up, so that the transaction never pays less than the requested rate.
+pub fn fee_for_vsize(vsize: u64, fee_rate_millisat_per_vbyte: MillisatoshiPerByte) -> u64 {
+    (vsize * fee_rate_millisat_per_vbyte).div_ceil(1000)
+}
+
+/// Selects a percentile from the 101 fee percentiles, 0 to 100, returned by the Bitcoin API.
+///
+/// `Config::btc_fee_percentiles` are checked to be at most 100 when they are set, so the highest
+/// rate is only taken instead if the API returns fewer percentiles.
+fn select_fee_percentile(
+    fee_percentiles: &[MillisatoshiPerByte],
+    percentile: u8,
+) -> MillisatoshiPerByte {
+    fee_percentiles
+        .last()
+        .map_or(DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE, |highest| {
+            fee_percentiles
+                .get(usize::from(percentile))
+                .copied()
+                .unwrap_or(*highest)
+        })
+}
+
+/// Returns the current network fee rate, in millisatoshi per virtual byte, for a priority level.
 ///
-/// Functions stolen from ckBTC Minter: [Reference](https://github.com/dfinity/ic/blob/285a5db07da50a4e350ec43bf3b488cc6fe36102/rs/bitcoin/ckbtc/minter/src/lib.rs#L1258)
-fn estimate_fee(
-    selected_utxos: &[Utxo],
-    median_fee_millisatoshi_per_vbyte: u64,
-    output_count: u64,
-) -> u64 {
-    let input_count = selected_utxos.len() as u64;
-
-    let vsize = tx_vsize_estimate(input_count, output_count);
-    vsize * median_fee_millisatoshi_per_vbyte / 1000
-}
-
-async fn get_default_fee(
-    utxos: &[Utxo],
-    network: Network,
-    output_count: u64,
-) -> Result<u64, String> {
-    let fee_per_byte = bitcoin_api::get_fee_per_byte(network).await?;
-    Ok(estimate_fee(utxos, fee_per_byte, output_count))
-}
-
-pub async fn calculate_fee(
-    maybe_fee: Option<u64>,
-    utxos: &[Utxo],
-    network: Network,
-    output_count: u64,
-) -> Result<u64, String> {
-    match maybe_fee {
-        Some(fee) => Ok(fee),
-        None => get_default_fee(utxos, network, output_count).await,
+/// Networks without a Bitcoin canister report no fee percentiles, so the default rate is used.
+pub async fn fee_rate_for_priority(
+    network: BitcoinNetwork,
+    priority: BtcFeePriority,
+) -> Result<MillisatoshiPerByte, String> {
+    let percentiles = read_config(|config| config.btc_fee_percentiles.unwrap_or_default());
+    let percentile = match priority {
+        BtcFeePriority::Low => percentiles.low,
+        BtcFeePriority::Medium => percentiles.medium,
+        BtcFeePriority::High => percentiles.high,
+    };
+    if bitcoin_api::canister_network(network).is_none() {
+        return Ok(DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE);
+    }
+    // Get fee percentiles from previous transactions to estimate our own fee.
+    let fee_percentiles = bitcoin_api::get_current_fee_percentiles(network).await?;
+    Ok(select_fee_percentile(&fee_percentiles, percentile))
+}
+
+/// Determines the fee of a transaction from the fee arguments of a request.
+///
+/// - `fee_satoshis` sets an absolute fee.
+/// - `fee_rate` sets a fee rate, either explicitly or as a priority level.
+/// - If neither is set, the `Medium` priority fee rate is used.
+async fn calculate_fee(
+    fee_satoshis: Option<u64>,
+    fee_rate: Option<BtcFeeRate>,
+    network: BitcoinNetwork,
+) -> Result<Fee, SendBtcError> {
+    match (fee_satoshis, fee_rate) {
+        (Some(_), Some(_)) => Err(SendBtcError::BuildP2wpkhError(
+            BuildP2wpkhTxError::ConflictingFeeArguments,
+        )),
+        (Some(fee), None) => Ok(Fee::Absolute(fee)),
+        (None, fee_rate) => resolve_fee_rate(
+            fee_rate.unwrap_or(BtcFeeRate::Priority(BtcFeePriority::Medium)),
+            network,
+        )
+        .await
+        .map(Fee::Rate)
+        .map_err(|msg| SendBtcError::InternalError { msg }),
+    }
+}
+
+/// Returns the fee rate, in millisatoshi per virtual byte, that a `BtcFeeRate` stands for.
+pub async fn resolve_fee_rate(
+    fee_rate: BtcFeeRate,
+    network: BitcoinNetwork,
+) -> Result<MillisatoshiPerByte, String> {
+    match fee_rate {
+        BtcFeeRate::SatPerVbyte(sat_per_vbyte) => Ok(sat_per_vbyte.saturating_mul(1000)),
+        BtcFeeRate::Priority(priority) => fee_rate_for_priority(network, priority).await,
+    }
+}
+
+/// Checks a fee against the limits that protect callers from overpaying.
+///
+/// `max_fee_rate_millisat_per_vbyte` is only checked if set; fee rates taken from the network
+/// percentiles do not need to be compared with the network again.  Likewise, the fee is only
+/// compared with `sent_satoshis` if set.
+fn check_fee_limits(
+    limits: &BtcFeeLimits,
+    fee_satoshis: u64,
+    vsize: u64,
+    sent_satoshis: Option<u64>,
+    max_fee_rate_millisat_per_vbyte: Option<MillisatoshiPerByte>,
+) -> Result<(), BuildP2wpkhTxError> {
+    if fee_satoshis > limits.max_fee_satoshis {
+        return Err(BuildP2wpkhTxError::FeeTooHigh {
+            fee_satoshis,
+            max_fee_satoshis: limits.max_fee_satoshis,
+        });
+    }
+    if let Some(sent_satoshis) = sent_satoshis {
+        if u128::from(fee_satoshis) * 100
+            > u128::from(sent_satoshis) * u128::from(limits.max_fee_percent_of_sent)
+        {
+            return Err(BuildP2wpkhTxError::FeeTooHighForSentValue {
+                fee_satoshis,
+                sent_satoshis,
+                max_fee_percent_of_sent: limits.max_fee_percent_of_sent,
+            });
+        }
+    }
+    if let Some(max_fee_rate_millisat_per_vbyte) = max_fee_rate_millisat_per_vbyte {
+        let fee_rate_millisat_per_vbyte = fee_satoshis.saturating_mul(1000) / vsize.max(1);
+        if fee_rate_millisat_per_vbyte > max_fee_rate_millisat_per_vbyte {
+            return Err(BuildP2wpkhTxError::FeeRateTooHigh {
+                fee_rate_millisat_per_vbyte,
+                max_fee_rate_millisat_per_vbyte,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the fee of a built transaction against `Config::btc_fee_limits`.
+///
+/// - `sent_satoshis` is `None` if the value that the transaction moves is not in satoshi, e.g. for
+///   runes or for a transaction that only carries `OP_RETURN` data, so that the fee cannot be
+///   compared with it.
+/// - `fee_from_network` is set if the fee rate was taken from the network fee percentiles;
+///   otherwise the fee rate is compared with the current `High` priority network fee rate.  On
+///   networks without a Bitcoin canister, there is no network fee rate to compare with.
+pub async fn check_fee(
+    fee_satoshis: u64,
+    vsize: u64,
+    sent_satoshis: Option<u64>,
+    fee_from_network: bool,
+    network: BitcoinNetwork,
+) -> Result<(), SendBtcError> {
+    let limits = read_config(|config| config.btc_fee_limits.unwrap_or_default());
+    let max_fee_rate_millisat_per_vbyte =
+        if fee_from_network || bitcoin_api::canister_network(network).is_none() {
+            None
+        } else {
+            let network_fee_rate = fee_rate_for_priority(network, BtcFeePriority::High)
+                .await
+                .map_err(|msg| SendBtcError::InternalError { msg })?;
+            Some(network_fee_rate.saturating_mul(limits.max_fee_rate_multiple))
+        };
+    check_fee_limits(
+        &limits,
+        fee_satoshis,
+        vsize,
+        sent_satoshis,
+        max_fee_rate_millisat_per_vbyte,
+    )
+    .map_err(SendBtcError::BuildP2wpkhError)
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_chain_fusion_signer_api::types::bitcoin::{BtcFeeLimits, BuildP2wpkhTxError};
+
+    use super::{
+        check_fee_limits, fee_for_vsize, select_fee_percentile, DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE,
+    };
+
+    #[test]
+    fn fee_for_vsize_rounds_up() {
+        assert_eq!(fee_for_vsize(141, 1000), 141);
+        assert_eq!(fee_for_vsize(141, 1500), 212);
+        assert_eq!(fee_for_vsize(0, 1500), 0);
+    }
+
+    #[test]
+    fn select_fee_percentile_uses_default_without_data() {
+        assert_eq!(
+            select_fee_percentile(&[], 50),
+            DEFAULT_FEE_RATE_MILLISAT_PER_VBYTE
+        );
+    }
+
+    #[test]
+    fn select_fee_percentile_picks_requested_percentile() {
+        let percentiles: Vec<u64> = (0..=100).map(|i| 1000 + i * 10).collect();
+        assert_eq!(select_fee_percentile(&percentiles, 0), 1000);
+        assert_eq!(select_fee_percentile(&percentiles, 25), 1250);
+        assert_eq!(select_fee_percentile(&percentiles, 50), 1500);
+        assert_eq!(select_fee_percentile(&percentiles, 100), 2000);
+    }
+
+    #[test]
+    fn select_fee_percentile_clamps_to_highest() {
+        let percentiles = [1000, 2000, 3000];
+        assert_eq!(select_fee_percentile(&percentiles, 75), 3000);
+    }
+
+    #[test]
+    fn check_fee_limits_accepts_reasonable_fee() {
+        let limits = BtcFeeLimits::default();
+        assert_eq!(
+            check_fee_limits(&limits, 1_410, 141, Some(50_000), Some(20_000)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn check_fee_limits_rejects_high_absolute_fee() {
+        let limits = BtcFeeLimits::default();
+        assert_eq!(
+            check_fee_limits(&limits, 100_000_000, 141, Some(1_000_000_000), None),
+            Err(BuildP2wpkhTxError::FeeTooHigh {
+                fee_satoshis: 100_000_000,
+                max_fee_satoshis: limits.max_fee_satoshis,
+            })
+        );
+    }
+
+    #[test]
+    fn check_fee_limits_rejects_fee_above_share_of_sent_value() {
+        let limits = BtcFeeLimits::default();
+        assert_eq!(
+            check_fee_limits(&limits, 6_000, 141, Some(10_000), None),
+            Err(BuildP2wpkhTxError::FeeTooHighForSentValue {
+                fee_satoshis: 6_000,
+                sent_satoshis: 10_000,
+                max_fee_percent_of_sent: limits.max_fee_percent_of_sent,
+            })
+        );
+    }
+
+    #[test]
+    fn check_fee_limits_skips_share_without_sent_value() {
+        let limits = BtcFeeLimits::default();
+        assert_eq!(check_fee_limits(&limits, 6_000, 141, None, None), Ok(()));
+    }
+
+    #[test]
+    fn check_fee_limits_rejects_high_fee_rate() {
+        let limits = BtcFeeLimits::default();
+        assert_eq!(
+            check_fee_limits(&limits, 14_100, 141, Some(50_000), Some(20_000)),
+            Err(BuildP2wpkhTxError::FeeRateTooHigh {
+                fee_rate_millisat_per_vbyte: 100_000,
+                max_fee_rate_millisat_per_vbyte: 20_000,
+            })
+        );
     }
 }
@@ -0,0 +1,336 @@
+//! Hash time-locked contracts (HTLCs) for atomic swaps: the recipient claims with the preimage of a
+//! SHA-256 hash, and the sender is refunded after an absolute timelock.
+use bitcoin::{
+    absolute::LockTime,
+    hashes::{sha256, Hash},
+    opcodes::all::{
+        OP_CHECKSIG, OP_CLTV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_IF, OP_SHA256, OP_SIZE,
+    },
+    script::Builder,
+    Address, CompressedPublicKey, PublicKey, ScriptBuf, Witness,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BitcoinNetwork, BtcHtlc, BtcHtlcError, BtcHtlcRole,
+};
+
+use crate::sign::bitcoin::bitcoin_utils::{parse_compressed_public_key, transform_network};
+
+/// The length of the preimage that claims an HTLC.
+pub const PREIMAGE_BYTES: usize = 32;
+
+/// The terms of an HTLC, before the caller's own key is known.
+#[derive(Debug, Eq, PartialEq)]
+pub struct HtlcTerms {
+    pub counterparty: CompressedPublicKey,
+    pub payment_hash: sha256::Hash,
+    pub timeout: LockTime,
+    pub caller_role: BtcHtlcRole,
+}
+
+impl HtlcTerms {
+    /// Checks that the caller is on the side of the HTLC that can make the given spend.
+    pub fn check_caller_role(&self, role: BtcHtlcRole) -> Result<(), BtcHtlcError> {
+        if self.caller_role == role {
+            Ok(())
+        } else {
+            Err(BtcHtlcError::WrongCallerRole)
+        }
+    }
+
+    /// Decodes a hex-encoded preimage and checks it against the payment hash.
+    pub fn check_preimage(&self, preimage: &str) -> Result<Vec<u8>, BtcHtlcError> {
+        let preimage = hex::decode(preimage).map_err(|_| BtcHtlcError::PreimageMismatch)?;
+        if preimage.len() != PREIMAGE_BYTES || sha256::Hash::hash(&preimage) != self.payment_hash {
+            return Err(BtcHtlcError::PreimageMismatch);
+        }
+        Ok(preimage)
+    }
+}
+
+/// An HTLC between the caller and a counterparty.
+pub struct Htlc {
+    pub witness_script: ScriptBuf,
+    pub timeout: LockTime,
+}
+
+impl Htlc {
+    pub fn address(&self, network: BitcoinNetwork) -> Address {
+        Address::p2wsh(&self.witness_script, transform_network(network))
+    }
+}
+
+/// A spend of an HTLC output.
+pub enum HtlcSpend<'a> {
+    /// The recipient's branch, satisfied with the preimage.
+    Claim { preimage: &'a [u8] },
+    /// The sender's branch, valid once the chain has reached the timeout.
+    Refund,
+}
+
+impl HtlcSpend<'_> {
+    /// The lock time of a transaction that makes this spend.
+    ///
+    /// `OP_CHECKLOCKTIMEVERIFY` compares the timeout with the lock time of the spending
+    /// transaction, so a refund has to be locked until the timeout.
+    pub fn lock_time(&self, htlc: &Htlc) -> LockTime {
+        match self {
+            HtlcSpend::Claim { .. } => LockTime::ZERO,
+            HtlcSpend::Refund => htlc.timeout,
+        }
+    }
+
+    /// The witness of an input that makes this spend with the given signature, which includes the
+    /// sighash type byte.
+    pub fn witness(&self, htlc: &Htlc, signature: &[u8]) -> Witness {
+        let mut witness = Witness::new();
+        witness.push(signature);
+        match self {
+            HtlcSpend::Claim { preimage } => {
+                witness.push(preimage);
+                // Selects the `OP_IF` branch; `MINIMALIF` requires exactly `0x01`.
+                witness.push([1]);
+            }
+            // Selects the `OP_ELSE` branch.
+            HtlcSpend::Refund => witness.push([]),
+        }
+        witness.push(htlc.witness_script.as_bytes());
+        witness
+    }
+}
+
+fn parse_public_key(name: &str, public_key: &str) -> Result<CompressedPublicKey, String> {
+    parse_compressed_public_key(public_key).ok_or_else(|| format!("invalid {name}: {public_key}"))
+}
+
+/// Parses the counterparty key, the payment hash and the timeout of an HTLC.
+pub fn parse_htlc(htlc: &BtcHtlc) -> Result<HtlcTerms, String> {
+    let counterparty = parse_public_key("counterparty_public_key", &htlc.counterparty_public_key)?;
+    let payment_hash = hex::decode(&htlc.payment_hash)
+        .ok()
+        .and_then(|bytes| sha256::Hash::from_slice(&bytes).ok())
+        .ok_or_else(|| format!("invalid payment_hash: {}", htlc.payment_hash))?;
+    if htlc.timeout_block_height == 0 {
+        return Err("timeout_block_height must not be zero".to_string());
+    }
+    // Larger lock times are interpreted as UNIX timestamps.
+    let timeout = LockTime::from_height(htlc.timeout_block_height).map_err(|_| {
+        format!(
+            "timeout_block_height must be a block height, got {}",
+            htlc.timeout_block_height
+        )
+    })?;
+    Ok(HtlcTerms {
+        counterparty,
+        payment_hash,
+        timeout,
+        caller_role: htlc.caller_role,
+    })
+}
+
+/// Builds the witness script of an HTLC between `signer` and the counterparty:
+///
+/// ```text
+/// IF
+///     SIZE 32 EQUALVERIFY SHA256 <payment_hash> EQUALVERIFY <recipient>
+/// ELSE
+///     <timeout> CHECKLOCKTIMEVERIFY DROP <sender>
+/// ENDIF
+/// CHECKSIG
+/// ```
+///
+/// The claim branch is satisfied by `<recipient_sig> <preimage> 1`, and the refund branch by
+/// `<sender_sig> <>` in a transaction whose lock time is at least the timeout.  The preimage size
+/// is fixed so that the HTLC can be mirrored on chains with different limits on stack elements.
+pub fn htlc(signer: CompressedPublicKey, terms: &HtlcTerms) -> Result<Htlc, String> {
+    if signer == terms.counterparty {
+        return Err("the caller's key must differ from the counterparty key".to_string());
+    }
+    let (recipient, sender) = match terms.caller_role {
+        BtcHtlcRole::Recipient => (signer, terms.counterparty),
+        BtcHtlcRole::Sender => (terms.counterparty, signer),
+    };
+    let witness_script = Builder::new()
+        .push_opcode(OP_IF)
+        .push_opcode(OP_SIZE)
+        .push_int(i64::try_from(PREIMAGE_BYTES).expect("The preimage size fits in i64"))
+        .push_opcode(OP_EQUALVERIFY)
+        .push_opcode(OP_SHA256)
+        .push_slice(terms.payment_hash.to_byte_array())
+        .push_opcode(OP_EQUALVERIFY)
+        .push_key(&PublicKey::from(recipient))
+        .push_opcode(OP_ELSE)
+        .push_lock_time(terms.timeout)
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_DROP)
+        .push_key(&PublicKey::from(sender))
+        .push_opcode(OP_ENDIF)
+        .push_opcode(OP_CHECKSIG)
+        .into_script();
+    Ok(Htlc {
+        witness_script,
+        timeout: terms.timeout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        hashes::{sha256, Hash},
+        opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_SHA256},
+        script::Instruction,
+        AddressType, ScriptBuf,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcHtlc, BtcHtlcError, BtcHtlcRole,
+    };
+
+    use super::{htlc, parse_htlc, HtlcSpend};
+    use crate::sign::bitcoin::test_utils::public_key;
+
+    const PREIMAGE: [u8; 32] = [7; 32];
+
+    fn btc_htlc(counterparty: u8, caller_role: BtcHtlcRole) -> BtcHtlc {
+        BtcHtlc {
+            counterparty_public_key: hex::encode(public_key(counterparty).to_bytes()),
+            payment_hash: hex::encode(sha256::Hash::hash(&PREIMAGE).to_byte_array()),
+            timeout_block_height: 800_000,
+            caller_role,
+        }
+    }
+
+    fn pushed_bytes(instruction: &Instruction) -> Option<Vec<u8>> {
+        instruction
+            .push_bytes()
+            .map(|bytes| bytes.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_htlc_witness_script() {
+        let terms = parse_htlc(&btc_htlc(2, BtcHtlcRole::Recipient)).expect("Expected valid terms");
+        let htlc = htlc(public_key(1), &terms).expect("Expected a valid HTLC");
+
+        let instructions: Vec<Instruction> = htlc
+            .witness_script
+            .instructions()
+            .collect::<Result<_, _>>()
+            .expect("Expected a valid script");
+        assert_eq!(instructions.len(), 15);
+        assert_eq!(instructions[4].opcode(), Some(OP_SHA256));
+        assert_eq!(
+            pushed_bytes(&instructions[5]),
+            Some(sha256::Hash::hash(&PREIMAGE).to_byte_array().to_vec())
+        );
+        assert_eq!(
+            pushed_bytes(&instructions[7]),
+            Some(public_key(1).to_bytes().to_vec()),
+            "the caller should claim as the recipient"
+        );
+        assert_eq!(instructions[9].script_num(), Some(800_000));
+        assert_eq!(instructions[10].opcode(), Some(OP_CLTV));
+        assert_eq!(
+            pushed_bytes(&instructions[12]),
+            Some(public_key(2).to_bytes().to_vec()),
+            "the counterparty should be refunded as the sender"
+        );
+        assert_eq!(instructions[14].opcode(), Some(OP_CHECKSIG));
+
+        let address = htlc.address(BitcoinNetwork::Mainnet);
+        assert_eq!(address.address_type(), Some(AddressType::P2wsh));
+        assert_eq!(
+            address.script_pubkey(),
+            ScriptBuf::new_p2wsh(&htlc.witness_script.wscript_hash())
+        );
+    }
+
+    #[test]
+    fn test_htlc_is_the_same_for_both_parties() {
+        // The recipient and the sender derive the same HTLC, each with the other as counterparty.
+        let recipient_terms = parse_htlc(&btc_htlc(2, BtcHtlcRole::Recipient)).unwrap();
+        let sender_terms = parse_htlc(&btc_htlc(1, BtcHtlcRole::Sender)).unwrap();
+
+        assert_eq!(
+            htlc(public_key(1), &recipient_terms)
+                .unwrap()
+                .witness_script,
+            htlc(public_key(2), &sender_terms).unwrap().witness_script
+        );
+    }
+
+    #[test]
+    fn test_parse_htlc_rejects_invalid_terms() {
+        let invalid_key = BtcHtlc {
+            counterparty_public_key: "02deadbeef".to_string(),
+            ..btc_htlc(2, BtcHtlcRole::Sender)
+        };
+        assert_eq!(
+            parse_htlc(&invalid_key).err(),
+            Some("invalid counterparty_public_key: 02deadbeef".to_string())
+        );
+        let invalid_hash = BtcHtlc {
+            payment_hash: "deadbeef".to_string(),
+            ..btc_htlc(2, BtcHtlcRole::Sender)
+        };
+        assert_eq!(
+            parse_htlc(&invalid_hash).err(),
+            Some("invalid payment_hash: deadbeef".to_string())
+        );
+        for timeout_block_height in [0, 500_000_000] {
+            let invalid_timeout = BtcHtlc {
+                timeout_block_height,
+                ..btc_htlc(2, BtcHtlcRole::Sender)
+            };
+            assert!(parse_htlc(&invalid_timeout).is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_preimage() {
+        let terms = parse_htlc(&btc_htlc(2, BtcHtlcRole::Recipient)).unwrap();
+
+        assert_eq!(
+            terms.check_preimage(&hex::encode(PREIMAGE)),
+            Ok(PREIMAGE.to_vec())
+        );
+        assert_eq!(
+            terms.check_preimage(&hex::encode([8; 32])),
+            Err(BtcHtlcError::PreimageMismatch)
+        );
+        assert_eq!(
+            terms.check_preimage("not hex"),
+            Err(BtcHtlcError::PreimageMismatch)
+        );
+        assert_eq!(
+            terms.check_caller_role(BtcHtlcRole::Sender),
+            Err(BtcHtlcError::WrongCallerRole)
+        );
+    }
+
+    #[test]
+    fn test_spend_witnesses() {
+        let terms = parse_htlc(&btc_htlc(2, BtcHtlcRole::Sender)).unwrap();
+        let htlc = htlc(public_key(1), &terms).unwrap();
+        let signature = [0x30; 71];
+
+        let claim = HtlcSpend::Claim {
+            preimage: &PREIMAGE,
+        };
+        let witness = claim.witness(&htlc, &signature);
+        assert_eq!(
+            witness.iter().collect::<Vec<_>>(),
+            vec![
+                &signature[..],
+                &PREIMAGE[..],
+                &[1][..],
+                htlc.witness_script.as_bytes()
+            ]
+        );
+        assert_eq!(claim.lock_time(&htlc), bitcoin::absolute::LockTime::ZERO);
+
+        let witness = HtlcSpend::Refund.witness(&htlc, &signature);
+        assert_eq!(
+            witness.iter().collect::<Vec<_>>(),
+            vec![&signature[..], &[][..], htlc.witness_script.as_bytes()]
+        );
+        assert_eq!(HtlcSpend::Refund.lock_time(&htlc), htlc.timeout);
+    }
+}
@@ -0,0 +1,173 @@
+//! Ordinals inscriptions: content that a transaction reveals by spending a taproot output on the
+//! script path of a leaf that carries the content in an envelope.
+use bitcoin::{
+    opcodes::{
+        all::{OP_CHECKSIG, OP_ENDIF, OP_IF},
+        OP_FALSE,
+    },
+    script::{Builder, PushBytes},
+    ScriptBuf, XOnlyPublicKey,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::BtcInscription;
+
+use crate::sign::bitcoin::taproot::{single_leaf_terms, taproot_tree, TapLeafSpend, TaprootTree};
+
+/// The largest script element; longer content is split into several pushes.
+const MAX_PUSH_BYTES: usize = 520;
+/// The largest body that fits in a transaction that nodes relay by default, whose weight is at
+/// most 400,000 units, with room for the rest of the reveal transaction.
+pub const MAX_INSCRIPTION_BODY_BYTES: usize = 390_000;
+/// Marks an envelope as an inscription.
+const PROTOCOL_ID: &[u8; 3] = b"ord";
+/// Precedes the content type in the envelope.
+const CONTENT_TYPE_TAG: &[u8; 1] = &[1];
+/// Precedes the body in the envelope.
+const BODY_TAG: &[u8; 0] = &[];
+
+/// Checks that the inscription can be carried by an envelope that nodes relay by default.
+pub fn check_inscription(inscription: &BtcInscription) -> Result<(), String> {
+    if inscription.content_type.is_empty() {
+        return Err("content_type must not be empty".to_string());
+    }
+    if inscription.content_type.len() > MAX_PUSH_BYTES {
+        return Err(format!(
+            "content_type is {} bytes, more than {MAX_PUSH_BYTES}",
+            inscription.content_type.len()
+        ));
+    }
+    if inscription.body.len() > MAX_INSCRIPTION_BODY_BYTES {
+        return Err(format!(
+            "body is {} bytes, more than {MAX_INSCRIPTION_BODY_BYTES}",
+            inscription.body.len()
+        ));
+    }
+    Ok(())
+}
+
+/// The leaf that reveals the inscription: `<caller_key> OP_CHECKSIG`, followed by the envelope
+/// `OP_FALSE OP_IF "ord" 1 <content_type> 0 <body>... OP_ENDIF`, which is never executed.
+///
+/// The inscription must have been checked with [`check_inscription`].
+pub fn envelope_leaf(caller_key: &XOnlyPublicKey, inscription: &BtcInscription) -> ScriptBuf {
+    fn push(bytes: &[u8]) -> &PushBytes {
+        <&PushBytes>::try_from(bytes).expect("Pushes are at most MAX_PUSH_BYTES long")
+    }
+    let mut builder = Builder::new()
+        .push_x_only_key(caller_key)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF)
+        .push_slice(PROTOCOL_ID)
+        .push_slice(CONTENT_TYPE_TAG)
+        .push_slice(push(inscription.content_type.as_bytes()))
+        .push_slice(BODY_TAG);
+    for chunk in inscription.body.chunks(MAX_PUSH_BYTES) {
+        builder = builder.push_slice(push(chunk));
+    }
+    builder.push_opcode(OP_ENDIF).into_script()
+}
+
+/// The taproot output of an inscription, committed to the caller's key, and the spend of its only
+/// leaf.
+pub fn inscription_tree(
+    caller_key: XOnlyPublicKey,
+    inscription: &BtcInscription,
+) -> (TaprootTree, TapLeafSpend) {
+    let terms = single_leaf_terms(envelope_leaf(&caller_key, inscription));
+    let tree = taproot_tree(terms, caller_key);
+    let leaf = TapLeafSpend::new(&tree, 0).expect("The tree has one leaf");
+    (tree, leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF},
+        script::Instruction,
+        secp256k1::{Secp256k1, SecretKey},
+        XOnlyPublicKey,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::BtcInscription;
+
+    use super::{check_inscription, envelope_leaf, inscription_tree, MAX_INSCRIPTION_BODY_BYTES};
+    use crate::sign::bitcoin::taproot::pushes_x_only_key;
+
+    fn x_only_key() -> XOnlyPublicKey {
+        let secret_key = SecretKey::from_slice(&[7; 32]).expect("Invalid secret key");
+        secret_key.x_only_public_key(&Secp256k1::new()).0
+    }
+
+    fn inscription(body_bytes: usize) -> BtcInscription {
+        BtcInscription {
+            content_type: "text/plain;charset=utf-8".to_string(),
+            body: vec![b'a'; body_bytes],
+        }
+    }
+
+    #[test]
+    fn test_envelope_leaf_chunks_body() {
+        let key = x_only_key();
+        let leaf = envelope_leaf(&key, &inscription(1_100));
+
+        let instructions = leaf
+            .instructions()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Expected a valid script");
+        let pushed = |index: usize| instructions[index].push_bytes().unwrap().as_bytes();
+        assert_eq!(pushed(0), key.serialize());
+        assert_eq!(instructions[1], Instruction::Op(OP_CHECKSIG));
+        // `OP_FALSE` is an empty push.
+        assert_eq!(pushed(2), []);
+        assert_eq!(instructions[3], Instruction::Op(OP_IF));
+        assert_eq!(pushed(4), b"ord");
+        assert_eq!(pushed(5), [1]);
+        assert_eq!(pushed(6), b"text/plain;charset=utf-8");
+        assert_eq!(pushed(7), []);
+        assert_eq!(
+            (8..11).map(|index| pushed(index).len()).collect::<Vec<_>>(),
+            [520, 520, 60]
+        );
+        assert_eq!(instructions[11], Instruction::Op(OP_ENDIF));
+        assert_eq!(instructions.len(), 12);
+    }
+
+    #[test]
+    fn test_inscription_tree_reveals_with_caller_key() {
+        let key = x_only_key();
+        let (tree, leaf) = inscription_tree(key, &inscription(10));
+
+        assert_eq!(tree.spend_info.internal_key(), key);
+        assert_eq!(leaf.script, envelope_leaf(&key, &inscription(10)));
+        assert!(pushes_x_only_key(&leaf.script, &key));
+        assert!(leaf.control_block.verify_taproot_commitment(
+            &Secp256k1::verification_only(),
+            tree.spend_info.output_key().to_x_only_public_key(),
+            &leaf.script
+        ));
+    }
+
+    #[test]
+    fn test_check_inscription() {
+        assert_eq!(check_inscription(&inscription(0)), Ok(()));
+        assert_eq!(
+            check_inscription(&inscription(MAX_INSCRIPTION_BODY_BYTES)),
+            Ok(())
+        );
+        assert_eq!(
+            check_inscription(&inscription(MAX_INSCRIPTION_BODY_BYTES + 1)),
+            Err("body is 390001 bytes, more than 390000".to_string())
+        );
+        let mut without_content_type = inscription(1);
+        without_content_type.content_type.clear();
+        assert_eq!(
+            check_inscription(&without_content_type),
+            Err("content_type must not be empty".to_string())
+        );
+        let mut long_content_type = inscription(1);
+        long_content_type.content_type = "a".repeat(521);
+        assert_eq!(
+            check_inscription(&long_content_type),
+            Err("content_type is 521 bytes, more than 520".to_string())
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! Jobs that sign the inputs of large P2WPKH transactions in batches, in timers, so that no single
+//! call has to sign all of them.
+//!
+//! `btc_caller_sign_job` and `btc_caller_send_job` build the transaction, compute the sighashes
+//! and store them in a job.  Each timer then signs the next batch of the oldest unfinished job,
+//! at most `Config::max_concurrent_signatures` inputs at once, and stores the signatures.  Once all
+//! inputs are signed, the witnesses are assembled and the transaction is journaled and, for
+//! `btc_caller_send_job`, sent.  A batch that fails for a transient reason is retried later, with
+//! a delay that doubles after every failure; any other failure fails the job.
+use std::{cell::Cell, time::Duration};
+
+use bitcoin::{consensus::deserialize, EcdsaSighashType, Transaction};
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_chain_fusion_signer_api::types::bitcoin::{BtcJobStatus, SendBtcError, SignBtcResponse};
+use ic_stable_structures::Memory;
+
+use crate::{
+    derivation_path::Schema,
+    sign::{
+        bitcoin::{
+            journal, reservations,
+            tx_utils::{add_p2wpkh_witnesses, SignedTransaction},
+        },
+        ecdsa_api::get_ecdsa_signatures,
+        fan_out::max_concurrent_signatures,
+    },
+    state::{mutate_state, read_state},
+    types::{Candid, SigningJob, SigningJobStatus, SigningJobs, UtxoReservations},
+};
+
+/// How long a job stays available to `btc_job_status` once it has finished.
+pub const JOB_RETENTION_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// How long a batch waits before it is retried after a first transient failure.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest a batch waits before it is retried.
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(5);
+
+/// How often the inputs of the jobs still signing are reserved again, well before their
+/// reservations expire.
+const RESERVATION_REFRESH_NS: u64 = reservations::IN_FLIGHT_RESERVATION_NS / 3;
+
+thread_local! {
+    /// Whether a timer that signs the next batch is set.
+    static BATCH_SCHEDULED: Cell<bool> = const { Cell::new(false) };
+    /// How long the next batch waits, which is zero unless the last batch failed for a transient
+    /// reason.
+    static RETRY_DELAY: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+    /// When the inputs of the jobs still signing were last reserved again.
+    static RESERVATIONS_REFRESHED_AT_NS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Clears `BATCH_SCHEDULED` once the timer that signs a batch ends, even if it traps, so that
+/// later jobs still schedule their batches.
+struct ScheduledBatch;
+
+impl Drop for ScheduledBatch {
+    fn drop(&mut self) {
+        BATCH_SCHEDULED.set(false);
+    }
+}
+
+/// The inputs of a job that are signed together.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Batch {
+    pub job_id: u64,
+    pub principal: Principal,
+    pub sighashes: Vec<Vec<u8>>,
+}
+
+/// Stores a new job, and removes the jobs that finished long ago.
+///
+/// Returns the id of the job.
+pub fn create_job<M: Memory>(jobs: &mut SigningJobs<M>, job: SigningJob, now_ns: u64) -> u64 {
+    remove_expired(jobs, now_ns);
+    let job_id = jobs.last_key_value().map_or(0, |(job_id, _)| job_id + 1);
+    jobs.insert(job_id, Candid(job));
+    job_id
+}
+
+/// The status of one of the caller's jobs.
+pub fn job_status<M: Memory>(
+    jobs: &SigningJobs<M>,
+    principal: Principal,
+    job_id: u64,
+) -> Option<BtcJobStatus> {
+    let Candid(job) = jobs.get(&job_id)?;
+    if job.principal != principal {
+        return None;
+    }
+    Some(match job.status {
+        SigningJobStatus::Signing => BtcJobStatus::Signing {
+            signed_inputs: job.signatures.len() as u64,
+            total_inputs: job.sighashes.len() as u64,
+        },
+        SigningJobStatus::Completed(response) => BtcJobStatus::Completed(response),
+        SigningJobStatus::Failed { msg } => {
+            BtcJobStatus::Failed(SendBtcError::InternalError { msg })
+        }
+        SigningJobStatus::BroadcastFailed { response, msg } => {
+            BtcJobStatus::Failed(SendBtcError::BroadcastFailed {
+                txid: response.txid,
+                msg,
+            })
+        }
+    })
+}
+
+/// The next inputs to sign, of the oldest job that has inputs left to sign.
+pub fn next_batch<M: Memory>(jobs: &SigningJobs<M>, batch_size: usize) -> Option<Batch> {
+    jobs.keys_range(..).find_map(|job_id| {
+        let Candid(job) = jobs.get(&job_id)?;
+        (job.status == SigningJobStatus::Signing).then(|| Batch {
+            job_id,
+            principal: job.principal,
+            sighashes: job
+                .sighashes
+                .iter()
+                .skip(job.signatures.len())
+                .take(batch_size.max(1))
+                .cloned()
+                .collect(),
+        })
+    })
+}
+
+/// Adds the signatures of the next inputs of a job.
+///
+/// Returns the job if all its inputs are signed.
+pub fn add_signatures<M: Memory>(
+    jobs: &mut SigningJobs<M>,
+    job_id: u64,
+    signatures: Vec<Vec<u8>>,
+) -> Option<SigningJob> {
+    let Candid(mut job) = jobs.get(&job_id)?;
+    job.signatures.extend(signatures);
+    let signed = job.signatures.len() >= job.sighashes.len();
+    jobs.insert(job_id, Candid(job.clone()));
+    signed.then_some(job)
+}
+
+/// Finishes a job with the given status, and returns it.
+pub fn set_status<M: Memory>(
+    jobs: &mut SigningJobs<M>,
+    job_id: u64,
+    status: SigningJobStatus,
+    now_ns: u64,
+) -> Option<SigningJob> {
+    let Candid(mut job) = jobs.get(&job_id)?;
+    job.status = status;
+    job.finished_at_ns = Some(now_ns);
+    jobs.insert(job_id, Candid(job.clone()));
+    Some(job)
+}
+
+/// Keeps the inputs of the jobs from `first_job_id` on that are still signing a transaction to
+/// send reserved.
+///
+/// A job waits for the jobs started before it, so it can take longer to sign than its inputs are
+/// reserved for at first.  Jobs are signed in order, so the jobs before the one whose batch is
+/// signed next have all finished and are skipped.
+pub fn refresh_reservations<M: Memory, N: Memory>(
+    jobs: &SigningJobs<M>,
+    utxo_reservations: &mut UtxoReservations<N>,
+    first_job_id: u64,
+    now_ns: u64,
+) {
+    for job_id in jobs.keys_range(first_job_id..) {
+        let Some(Candid(job)) = jobs.get(&job_id) else {
+            continue;
+        };
+        if job.send && job.status == SigningJobStatus::Signing {
+            reservations::refresh_in_flight(utxo_reservations, job.principal, &job.utxos, now_ns);
+        }
+    }
+}
+
+/// Sets a timer that signs the next batch, unless one is set already or no job has inputs left to
+/// sign.  Every batch schedules the next one.
+pub fn schedule_batch() {
+    if BATCH_SCHEDULED.get() || read_state(|state| next_batch(&state.signing_jobs, 1).is_none()) {
+        return;
+    }
+    BATCH_SCHEDULED.set(true);
+    ic_cdk_timers::set_timer(RETRY_DELAY.get(), async {
+        let scheduled = ScheduledBatch;
+        sign_batch().await;
+        drop(scheduled);
+        schedule_batch();
+    });
+}
+
+/// How long to wait after a transient failure, given the wait after the previous one.
+fn next_retry_delay(delay: Duration) -> Duration {
+    delay
+        .saturating_mul(2)
+        .clamp(FIRST_RETRY_DELAY, MAX_RETRY_DELAY)
+}
+
+/// Signs the next batch and, if that was the last one of its job, finishes the job.
+///
+/// A transient failure leaves the job as it is, to be retried by the next batch.
+async fn sign_batch() {
+    let Some(batch) =
+        read_state(|state| next_batch(&state.signing_jobs, max_concurrent_signatures()))
+    else {
+        return;
+    };
+    let now_ns = time();
+    if now_ns
+        >= RESERVATIONS_REFRESHED_AT_NS
+            .get()
+            .saturating_add(RESERVATION_REFRESH_NS)
+    {
+        mutate_state(|state| {
+            refresh_reservations(
+                &state.signing_jobs,
+                &mut state.utxo_reservations,
+                batch.job_id,
+                now_ns,
+            );
+        });
+        RESERVATIONS_REFRESHED_AT_NS.set(now_ns);
+    }
+    let derivation_path = Schema::Btc.derivation_path(&batch.principal);
+    match get_ecdsa_signatures(derivation_path, batch.sighashes).await {
+        Ok(signatures) => {
+            RETRY_DELAY.set(Duration::ZERO);
+            let job = mutate_state(|state| {
+                add_signatures(&mut state.signing_jobs, batch.job_id, signatures)
+            });
+            if let Some(job) = job {
+                finish_job(batch.job_id, job).await;
+            }
+        }
+        Err(err) if err.transient => RETRY_DELAY.set(next_retry_delay(RETRY_DELAY.get())),
+        Err(err) => {
+            RETRY_DELAY.set(Duration::ZERO);
+            fail_job(batch.job_id, err.msg);
+        }
+    }
+}
+
+/// Assembles the transaction of a job whose inputs are all signed, journals it and, if the job
+/// sends it, sends it.
+async fn finish_job(job_id: u64, job: SigningJob) {
+    let signed_transaction = match signed_transaction(&job) {
+        Ok(signed_transaction) => signed_transaction,
+        Err(msg) => return fail_job(job_id, msg),
+    };
+    mutate_state(|state| {
+        journal::record_signed(
+            &mut state.signed_transactions,
+            job.principal,
+            job.network,
+            &signed_transaction,
+            time(),
+        );
+    });
+    let sent = if job.send {
+        reservations::send_reserved(job.principal, job.network, &job.utxos, &signed_transaction)
+            .await
+    } else {
+        Ok(())
+    };
+    let response = SignBtcResponse::from(signed_transaction);
+    let status = match sent {
+        Ok(()) => SigningJobStatus::Completed(response),
+        Err(msg) => SigningJobStatus::BroadcastFailed { response, msg },
+    };
+    mutate_state(|state| set_status(&mut state.signing_jobs, job_id, status, time()));
+}
+
+/// Fails a job, releasing the inputs of a transaction that will not be sent.
+fn fail_job(job_id: u64, msg: String) {
+    mutate_state(|state| {
+        let job = set_status(
+            &mut state.signing_jobs,
+            job_id,
+            SigningJobStatus::Failed { msg },
+            time(),
+        );
+        if let Some(job) = job.filter(|job| job.send) {
+            reservations::release_utxos(&mut state.utxo_reservations, job.principal, &job.utxos);
+        }
+    });
+}
+
+/// The transaction of a job whose inputs are all signed, with the witnesses assembled.
+fn signed_transaction(job: &SigningJob) -> Result<SignedTransaction, String> {
+    let mut transaction: Transaction = deserialize(&job.unsigned_transaction_bytes)
+        .map_err(|e| format!("malformed transaction: {e}"))?;
+    let sighash_types = job
+        .sighash_types
+        .iter()
+        .map(|&sighash_type| EcdsaSighashType::from_standard(sighash_type))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("malformed sighash type: {e}"))?;
+    add_p2wpkh_witnesses(
+        &mut transaction,
+        job.signatures.clone(),
+        &sighash_types,
+        &job.public_key,
+    );
+    Ok(SignedTransaction::new(&transaction, &job.utxos)
+        .with_change_output_index(job.change_output_index))
+}
+
+/// Removes the jobs that finished more than [`JOB_RETENTION_NS`] ago.
+///
+/// Job ids grow with time, so the oldest jobs come first.  The last job is kept, so that its id is
+/// not reused.
+fn remove_expired<M: Memory>(jobs: &mut SigningJobs<M>, now_ns: u64) {
+    let Some((last_job_id, _)) = jobs.last_key_value() else {
+        return;
+    };
+    let expired = jobs
+        .keys_range(..last_job_id)
+        .take_while(|job_id| {
+            jobs.get(job_id).is_some_and(|job| {
+                job.finished_at_ns.is_some_and(|finished_at_ns| {
+                    finished_at_ns.saturating_add(JOB_RETENTION_NS) <= now_ns
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    for job_id in expired {
+        jobs.remove(&job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use candid::Principal;
+    use ic_cdk_bitcoin_canister::{OutPoint, Txid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcJobStatus, SendBtcError, SignBtcResponse,
+    };
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    use super::{
+        add_signatures, create_job, job_status, next_batch, next_retry_delay, refresh_reservations,
+        set_status, Batch, FIRST_RETRY_DELAY, JOB_RETENTION_NS, MAX_RETRY_DELAY,
+    };
+    use crate::{
+        sign::bitcoin::reservations::{reserve_utxos, reserved_utxos, IN_FLIGHT_RESERVATION_NS},
+        types::{SigningJob, SigningJobStatus, SigningJobs, UtxoReservations},
+    };
+
+    const NOW_NS: u64 = 1_700_000_000_000_000_000;
+
+    fn job(principal: Principal, n_inputs: u8) -> SigningJob {
+        SigningJob {
+            principal,
+            network: BitcoinNetwork::Regtest,
+            send: false,
+            utxos: vec![],
+            unsigned_transaction_bytes: vec![],
+            change_output_index: None,
+            sighash_types: vec![1; usize::from(n_inputs)],
+            sighashes: (0..n_inputs).map(|i| vec![i; 32]).collect(),
+            signatures: vec![],
+            public_key: vec![],
+            status: SigningJobStatus::Signing,
+            created_at_ns: NOW_NS,
+            finished_at_ns: None,
+        }
+    }
+
+    fn response() -> SignBtcResponse {
+        SignBtcResponse {
+            signed_transaction_hex: "00".to_string(),
+            txid: "ab".repeat(32),
+            fee_satoshis: 1_000,
+            fee_rate_millisat_per_vbyte: 10_000,
+            vsize: 100,
+            change_output_index: Some(1),
+        }
+    }
+
+    fn jobs() -> SigningJobs<DefaultMemoryImpl> {
+        SigningJobs::init(DefaultMemoryImpl::default())
+    }
+
+    #[test]
+    fn test_jobs_are_signed_in_batches() {
+        let mut jobs = jobs();
+        let caller = Principal::from_slice(&[1]);
+        let job_id = create_job(&mut jobs, job(caller, 5), NOW_NS);
+
+        let batch = next_batch(&jobs, 2).unwrap();
+        assert_eq!(
+            batch,
+            Batch {
+                job_id,
+                principal: caller,
+                sighashes: vec![vec![0; 32], vec![1; 32]],
+            }
+        );
+        assert!(add_signatures(&mut jobs, job_id, vec![vec![0; 64]; 2]).is_none());
+        assert!(matches!(
+            job_status(&jobs, caller, job_id),
+            Some(BtcJobStatus::Signing {
+                signed_inputs: 2,
+                total_inputs: 5
+            })
+        ));
+
+        assert_eq!(
+            next_batch(&jobs, 2).unwrap().sighashes,
+            vec![vec![2; 32], vec![3; 32]]
+        );
+        assert!(add_signatures(&mut jobs, job_id, vec![vec![0; 64]; 2]).is_none());
+        assert_eq!(next_batch(&jobs, 2).unwrap().sighashes, vec![vec![4; 32]]);
+        let signed = add_signatures(&mut jobs, job_id, vec![vec![0; 64]]).unwrap();
+        assert_eq!(signed.signatures.len(), 5);
+    }
+
+    #[test]
+    fn test_the_oldest_unfinished_job_is_signed_first() {
+        let mut jobs = jobs();
+        let caller = Principal::from_slice(&[1]);
+        let first = create_job(&mut jobs, job(caller, 1), NOW_NS);
+        let second = create_job(&mut jobs, job(caller, 1), NOW_NS);
+        assert_eq!(second, first + 1);
+
+        assert_eq!(next_batch(&jobs, 10).unwrap().job_id, first);
+        set_status(
+            &mut jobs,
+            first,
+            SigningJobStatus::Completed(response()),
+            NOW_NS,
+        );
+        assert_eq!(next_batch(&jobs, 10).unwrap().job_id, second);
+        set_status(
+            &mut jobs,
+            second,
+            SigningJobStatus::Failed {
+                msg: "error".to_string(),
+            },
+            NOW_NS,
+        );
+        assert!(next_batch(&jobs, 10).is_none());
+    }
+
+    #[test]
+    fn test_job_status_is_private_and_typed() {
+        let mut jobs = jobs();
+        let caller = Principal::from_slice(&[1]);
+        let job_id = create_job(&mut jobs, job(caller, 1), NOW_NS);
+
+        assert!(job_status(&jobs, Principal::from_slice(&[2]), job_id).is_none());
+        assert!(job_status(&jobs, caller, job_id + 1).is_none());
+
+        set_status(
+            &mut jobs,
+            job_id,
+            SigningJobStatus::BroadcastFailed {
+                response: response(),
+                msg: "rejected".to_string(),
+            },
+            NOW_NS,
+        );
+        assert!(matches!(
+            job_status(&jobs, caller, job_id),
+            Some(BtcJobStatus::Failed(SendBtcError::BroadcastFailed { txid, msg }))
+                if txid == "ab".repeat(32) && msg == "rejected"
+        ));
+    }
+
+    #[test]
+    fn test_finished_jobs_expire() {
+        let mut jobs = jobs();
+        let caller = Principal::from_slice(&[1]);
+        let finished = create_job(&mut jobs, job(caller, 1), NOW_NS);
+        set_status(
+            &mut jobs,
+            finished,
+            SigningJobStatus::Completed(response()),
+            NOW_NS,
+        );
+        let signing = create_job(&mut jobs, job(caller, 1), NOW_NS);
+
+        let expiry = NOW_NS + JOB_RETENTION_NS;
+        let last = create_job(&mut jobs, job(caller, 1), expiry);
+        assert!(job_status(&jobs, caller, finished).is_none());
+        assert!(job_status(&jobs, caller, signing).is_some());
+        assert_eq!(last, signing + 1);
+    }
+
+    #[test]
+    fn test_jobs_are_retained_from_when_they_finish() {
+        let mut jobs = jobs();
+        let caller = Principal::from_slice(&[1]);
+        let finished = create_job(&mut jobs, job(caller, 1), NOW_NS);
+        // The job takes a day to sign.
+        let finished_at_ns = NOW_NS + 24 * 60 * 60 * 1_000_000_000;
+        set_status(
+            &mut jobs,
+            finished,
+            SigningJobStatus::Completed(response()),
+            finished_at_ns,
+        );
+
+        create_job(&mut jobs, job(caller, 1), NOW_NS + JOB_RETENTION_NS);
+        assert!(job_status(&jobs, caller, finished).is_some());
+        create_job(&mut jobs, job(caller, 1), finished_at_ns + JOB_RETENTION_NS);
+        assert!(job_status(&jobs, caller, finished).is_none());
+    }
+
+    #[test]
+    fn test_signing_jobs_keep_their_inputs_reserved() {
+        let mut jobs = jobs();
+        let mut utxo_reservations = UtxoReservations::init(DefaultMemoryImpl::default());
+        let caller = Principal::from_slice(&[1]);
+        let utxos = vec![Utxo {
+            outpoint: OutPoint {
+                txid: Txid::from([1; 32]),
+                vout: 0,
+            },
+            value: 10_000,
+            height: 100,
+        }];
+        let job_id = create_job(
+            &mut jobs,
+            SigningJob {
+                send: true,
+                utxos: utxos.clone(),
+                ..job(caller, 1)
+            },
+            NOW_NS,
+        );
+        reserve_utxos(&mut utxo_reservations, caller, &utxos, NOW_NS).unwrap();
+
+        // The job is still signing when its first reservation would expire.
+        let later = NOW_NS + IN_FLIGHT_RESERVATION_NS;
+        refresh_reservations(&jobs, &mut utxo_reservations, job_id, later);
+        assert_eq!(
+            reserved_utxos(&utxo_reservations, caller, later)[0].expires_at_ns,
+            later + IN_FLIGHT_RESERVATION_NS
+        );
+
+        // A finished job no longer refreshes them.
+        set_status(
+            &mut jobs,
+            job_id,
+            SigningJobStatus::Failed {
+                msg: "error".to_string(),
+            },
+            later,
+        );
+        let much_later = later + IN_FLIGHT_RESERVATION_NS;
+        refresh_reservations(&jobs, &mut utxo_reservations, job_id, much_later);
+        assert!(reserved_utxos(&utxo_reservations, caller, much_later).is_empty());
+    }
+
+    #[test]
+    fn test_refresh_reservations_skips_jobs_before_the_first() {
+        let mut jobs = jobs();
+        let mut utxo_reservations = UtxoReservations::init(DefaultMemoryImpl::default());
+        let caller = Principal::from_slice(&[1]);
+        let utxo = |vout| Utxo {
+            outpoint: OutPoint {
+                txid: Txid::from([1; 32]),
+                vout,
+            },
+            value: 10_000,
+            height: 100,
+        };
+        let mut send_job = |vout| {
+            create_job(
+                &mut jobs,
+                SigningJob {
+                    send: true,
+                    utxos: vec![utxo(vout)],
+                    ..job(caller, 1)
+                },
+                NOW_NS,
+            )
+        };
+        send_job(0);
+        let second = send_job(1);
+        reserve_utxos(&mut utxo_reservations, caller, &[utxo(0), utxo(1)], NOW_NS).unwrap();
+
+        let later = NOW_NS + IN_FLIGHT_RESERVATION_NS;
+        refresh_reservations(&jobs, &mut utxo_reservations, second, later);
+        let reserved = reserved_utxos(&utxo_reservations, caller, later);
+        assert_eq!(reserved.len(), 1);
+        assert_eq!(reserved[0].vout, 1);
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_the_maximum() {
+        assert_eq!(next_retry_delay(Duration::ZERO), FIRST_RETRY_DELAY);
+        assert_eq!(next_retry_delay(FIRST_RETRY_DELAY), 2 * FIRST_RETRY_DELAY);
+        assert_eq!(next_retry_delay(MAX_RETRY_DELAY), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,441 @@
+//! A journal of the transactions signed for callers, which lets callers broadcast a transaction
+//! again, or recover it, without paying for another signature.
+//!
+//! Every endpoint that signs a whole transaction records it with [`record_signed`], whether or not
+//! it sends it, and the endpoints that send it record whether `bitcoin_send_transaction` accepted
+//! it.  `btc_list_signed_transactions` lists the caller's transactions, and `btc_rebroadcast`
+//! sends one of them again.
+//!
+//! A transaction is kept for [`SIGNED_TRANSACTION_RETENTION_NS`], and a caller keeps at most
+//! [`MAX_SIGNED_TRANSACTIONS_PER_CALLER`].  Expired transactions are removed as new ones are
+//! recorded: all those of the caller, and a bounded number of those of other callers.
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    ops::{Bound, RangeInclusive},
+    str::FromStr,
+};
+
+use bitcoin::{hashes::Hash, Txid};
+use candid::Principal;
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BitcoinNetwork, BtcSignedTransaction, BtcSignedTransactionStatus,
+};
+use ic_stable_structures::Memory;
+
+use crate::{
+    sign::bitcoin::{bitcoin_api, tx_utils::SignedTransaction},
+    state::mutate_state,
+    types::{Candid, SignedTransactionKey, SignedTransactionRecord, SignedTransactions},
+};
+
+/// How long a signed transaction stays in the journal.
+pub const SIGNED_TRANSACTION_RETENTION_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// How many transactions of a caller the journal keeps at most.
+pub const MAX_SIGNED_TRANSACTIONS_PER_CALLER: usize = 1_000;
+
+/// How many transactions of any caller recording a transaction checks for expiry.
+const SWEEP_BATCH: usize = 100;
+
+thread_local! {
+    /// The last transaction checked by [`sweep_expired`], after which the next sweep resumes.
+    static SWEEP_CURSOR: RefCell<Option<SignedTransactionKey>> = const { RefCell::new(None) };
+}
+
+/// Records a transaction that was just signed for the caller, and removes the caller's expired
+/// transactions and, beyond [`MAX_SIGNED_TRANSACTIONS_PER_CALLER`], the oldest ones.
+pub fn record_signed<M: Memory>(
+    journal: &mut SignedTransactions<M>,
+    principal: Principal,
+    network: BitcoinNetwork,
+    signed_transaction: &SignedTransaction,
+    now_ns: u64,
+) {
+    remove_expired(journal, principal, now_ns);
+    sweep_expired(journal, now_ns);
+    if let Some(key) = journal_key(principal, &signed_transaction.txid) {
+        if !journal.contains_key(&key) {
+            remove_oldest(journal, principal, MAX_SIGNED_TRANSACTIONS_PER_CALLER - 1);
+        }
+        journal.insert(
+            key,
+            Candid(SignedTransactionRecord {
+                network,
+                signed_transaction_bytes: signed_transaction.signed_transaction_bytes.clone(),
+                fee_satoshis: signed_transaction.fee_satoshis,
+                fee_rate_millisat_per_vbyte: signed_transaction.fee_rate_millisat_per_vbyte(),
+                vsize: signed_transaction.vsize,
+                change_output_index: signed_transaction.change_output_index,
+                status: BtcSignedTransactionStatus::Signed,
+                signed_at_ns: now_ns,
+            }),
+        );
+    }
+}
+
+/// Records the outcome of broadcasting one of the caller's transactions.
+pub fn set_status<M: Memory>(
+    journal: &mut SignedTransactions<M>,
+    principal: Principal,
+    txid: &str,
+    status: BtcSignedTransactionStatus,
+) {
+    let Some(key) = journal_key(principal, txid) else {
+        return;
+    };
+    if let Some(Candid(mut record)) = journal.get(&key) {
+        record.status = status;
+        journal.insert(key, Candid(record));
+    }
+}
+
+/// Sends one of the caller's transactions and records whether `bitcoin_send_transaction` accepted
+/// it.
+pub async fn send_signed_transaction(
+    principal: Principal,
+    network: BitcoinNetwork,
+    txid: &str,
+    signed_transaction_bytes: Vec<u8>,
+) -> Result<(), String> {
+    let sent = bitcoin_api::send_transaction(network, signed_transaction_bytes).await;
+    let status = match &sent {
+        Ok(()) => BtcSignedTransactionStatus::Sent,
+        Err(msg) => BtcSignedTransactionStatus::BroadcastFailed { msg: msg.clone() },
+    };
+    mutate_state(|state| set_status(&mut state.signed_transactions, principal, txid, status));
+    sent
+}
+
+/// The caller's unexpired transaction with the given txid, as block explorers show it.
+pub fn signed_transaction<M: Memory>(
+    journal: &SignedTransactions<M>,
+    principal: Principal,
+    txid: &str,
+    now_ns: u64,
+) -> Option<SignedTransactionRecord> {
+    let key = journal_key(principal, txid)?;
+    let Candid(record) = journal.get(&key)?;
+    (expires_at_ns(&record) > now_ns).then_some(record)
+}
+
+/// The caller's unexpired transactions, most recently signed first.
+pub fn signed_transactions<M: Memory>(
+    journal: &SignedTransactions<M>,
+    principal: Principal,
+    now_ns: u64,
+) -> Vec<BtcSignedTransaction> {
+    let mut transactions = journal
+        .keys_range(principal_range(principal))
+        .filter_map(|key| {
+            let Candid(record) = journal.get(&key)?;
+            let expires_at_ns = expires_at_ns(&record);
+            (expires_at_ns > now_ns).then(|| BtcSignedTransaction {
+                txid: Txid::from_byte_array(key.txid).to_string(),
+                network: record.network,
+                signed_transaction_hex: hex::encode(&record.signed_transaction_bytes),
+                fee_satoshis: record.fee_satoshis,
+                fee_rate_millisat_per_vbyte: record.fee_rate_millisat_per_vbyte,
+                status: record.status,
+                signed_at_ns: record.signed_at_ns,
+                expires_at_ns,
+            })
+        })
+        .collect::<Vec<_>>();
+    transactions.sort_by_key(|transaction| Reverse(transaction.signed_at_ns));
+    transactions
+}
+
+fn expires_at_ns(record: &SignedTransactionRecord) -> u64 {
+    record
+        .signed_at_ns
+        .saturating_add(SIGNED_TRANSACTION_RETENTION_NS)
+}
+
+/// Removes the caller's expired transactions.
+fn remove_expired<M: Memory>(
+    journal: &mut SignedTransactions<M>,
+    principal: Principal,
+    now_ns: u64,
+) {
+    let expired = journal
+        .keys_range(principal_range(principal))
+        .filter(|key| {
+            journal
+                .get(key)
+                .is_some_and(|record| expires_at_ns(&record) <= now_ns)
+        })
+        .collect::<Vec<_>>();
+    for key in expired {
+        journal.remove(&key);
+    }
+}
+
+/// Removes the expired transactions among the next [`SWEEP_BATCH`] transactions of any caller, so
+/// that the transactions of callers who stop calling the canister do not pile up.
+///
+/// Each sweep resumes after the last transaction checked by the previous one, and starts over
+/// from the first transaction once it reaches the last.
+fn sweep_expired<M: Memory>(journal: &mut SignedTransactions<M>, now_ns: u64) {
+    let start = SWEEP_CURSOR
+        .take()
+        .map_or(Bound::Unbounded, Bound::Excluded);
+    let checked = journal
+        .keys_range((start, Bound::Unbounded))
+        .take(SWEEP_BATCH)
+        .collect::<Vec<_>>();
+    if checked.len() == SWEEP_BATCH {
+        SWEEP_CURSOR.set(checked.last().cloned());
+    }
+    for key in checked {
+        if journal
+            .get(&key)
+            .is_some_and(|record| expires_at_ns(&record) <= now_ns)
+        {
+            journal.remove(&key);
+        }
+    }
+}
+
+/// Removes the caller's least recently signed transactions, keeping at most `max` of them.
+fn remove_oldest<M: Memory>(journal: &mut SignedTransactions<M>, principal: Principal, max: usize) {
+    let mut transactions = journal
+        .keys_range(principal_range(principal))
+        .filter_map(|key| {
+            let Candid(record) = journal.get(&key)?;
+            Some((record.signed_at_ns, key))
+        })
+        .collect::<Vec<_>>();
+    let Some(excess) = transactions.len().checked_sub(max) else {
+        return;
+    };
+    transactions.sort_unstable();
+    for (_, key) in transactions.into_iter().take(excess) {
+        journal.remove(&key);
+    }
+}
+
+/// A txid that does not parse cannot be the txid of a signed transaction.
+fn journal_key(principal: Principal, txid: &str) -> Option<SignedTransactionKey> {
+    let txid = Txid::from_str(txid).ok()?;
+    Some(SignedTransactionKey {
+        principal,
+        txid: txid.to_byte_array(),
+    })
+}
+
+/// The keys of all the transactions of the caller.
+fn principal_range(principal: Principal) -> RangeInclusive<SignedTransactionKey> {
+    SignedTransactionKey {
+        principal,
+        txid: [0; 32],
+    }..=SignedTransactionKey {
+        principal,
+        txid: [u8::MAX; 32],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcSignedTransactionStatus};
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    use super::{
+        record_signed, set_status, signed_transaction, signed_transactions,
+        MAX_SIGNED_TRANSACTIONS_PER_CALLER, SIGNED_TRANSACTION_RETENTION_NS, SWEEP_CURSOR,
+    };
+    use crate::{sign::bitcoin::tx_utils::SignedTransaction, types::SignedTransactions};
+
+    const NOW_NS: u64 = 1_700_000_000_000_000_000;
+
+    fn signed(txid_byte: &str) -> SignedTransaction {
+        SignedTransaction {
+            signed_transaction_bytes: vec![1, 2, 3],
+            txid: txid_byte.repeat(32),
+            fee_satoshis: 1_000,
+            vsize: 100,
+            change_output_index: None,
+        }
+    }
+
+    fn journal() -> SignedTransactions<DefaultMemoryImpl> {
+        SignedTransactions::init(DefaultMemoryImpl::default())
+    }
+
+    #[test]
+    fn test_signed_transactions_are_listed_most_recent_first() {
+        let mut journal = journal();
+        let caller = Principal::from_slice(&[1]);
+
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("01"),
+            NOW_NS,
+        );
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("ff"),
+            NOW_NS + 1,
+        );
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("0a"),
+            NOW_NS + 2,
+        );
+
+        let transactions = signed_transactions(&journal, caller, NOW_NS + 2);
+        let txids = transactions
+            .iter()
+            .map(|transaction| transaction.txid.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(txids, ["0a".repeat(32), "ff".repeat(32), "01".repeat(32)]);
+        assert_eq!(transactions[0].signed_transaction_hex, "010203");
+        assert_eq!(transactions[0].fee_rate_millisat_per_vbyte, 10_000);
+        assert_eq!(transactions[0].status, BtcSignedTransactionStatus::Signed);
+        assert_eq!(
+            transactions[0].expires_at_ns,
+            NOW_NS + 2 + SIGNED_TRANSACTION_RETENTION_NS
+        );
+    }
+
+    #[test]
+    fn test_signed_transactions_are_per_caller() {
+        let mut journal = journal();
+        let caller = Principal::from_slice(&[1]);
+        let other_caller = Principal::from_slice(&[1, 2]);
+
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("01"),
+            NOW_NS,
+        );
+        assert!(signed_transaction(&journal, caller, &"01".repeat(32), NOW_NS).is_some());
+        assert!(signed_transaction(&journal, other_caller, &"01".repeat(32), NOW_NS).is_none());
+        assert!(signed_transactions(&journal, other_caller, NOW_NS).is_empty());
+    }
+
+    #[test]
+    fn test_set_status_records_broadcast_outcome() {
+        let mut journal = journal();
+        let caller = Principal::from_slice(&[1]);
+        let txid = "01".repeat(32);
+
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("01"),
+            NOW_NS,
+        );
+        set_status(
+            &mut journal,
+            caller,
+            &txid,
+            BtcSignedTransactionStatus::BroadcastFailed {
+                msg: "rejected".to_string(),
+            },
+        );
+        assert_eq!(
+            signed_transaction(&journal, caller, &txid, NOW_NS).map(|record| record.status),
+            Some(BtcSignedTransactionStatus::BroadcastFailed {
+                msg: "rejected".to_string()
+            })
+        );
+        set_status(
+            &mut journal,
+            caller,
+            &txid,
+            BtcSignedTransactionStatus::Sent,
+        );
+        assert_eq!(
+            signed_transaction(&journal, caller, &txid, NOW_NS).map(|record| record.status),
+            Some(BtcSignedTransactionStatus::Sent)
+        );
+    }
+
+    #[test]
+    fn test_expired_transactions_are_removed() {
+        let mut journal = journal();
+        let caller = Principal::from_slice(&[1]);
+        let expiry = NOW_NS + SIGNED_TRANSACTION_RETENTION_NS;
+
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("01"),
+            NOW_NS,
+        );
+        assert!(signed_transaction(&journal, caller, &"01".repeat(32), expiry).is_none());
+        assert!(signed_transactions(&journal, caller, expiry).is_empty());
+
+        record_signed(
+            &mut journal,
+            caller,
+            BitcoinNetwork::Regtest,
+            &signed("02"),
+            expiry,
+        );
+        assert_eq!(journal.len(), 1);
+    }
+
+    #[test]
+    fn test_expired_transactions_of_other_callers_are_removed() {
+        let mut journal = journal();
+        let idle_caller = Principal::from_slice(&[1]);
+        let active_caller = Principal::from_slice(&[2]);
+        SWEEP_CURSOR.set(None);
+
+        record_signed(
+            &mut journal,
+            idle_caller,
+            BitcoinNetwork::Regtest,
+            &signed("01"),
+            NOW_NS,
+        );
+        record_signed(
+            &mut journal,
+            active_caller,
+            BitcoinNetwork::Regtest,
+            &signed("02"),
+            NOW_NS + SIGNED_TRANSACTION_RETENTION_NS,
+        );
+
+        assert_eq!(journal.len(), 1);
+        assert!(signed_transaction(&journal, idle_caller, &"01".repeat(32), NOW_NS).is_none());
+    }
+
+    #[test]
+    fn test_oldest_transactions_are_removed_beyond_the_cap() {
+        let mut journal = journal();
+        let caller = Principal::from_slice(&[1]);
+        let txid = |i: u64| format!("{i:064x}");
+
+        for i in 0..=MAX_SIGNED_TRANSACTIONS_PER_CALLER as u64 {
+            let signed_transaction = SignedTransaction {
+                txid: txid(i),
+                ..signed("01")
+            };
+            record_signed(
+                &mut journal,
+                caller,
+                BitcoinNetwork::Regtest,
+                &signed_transaction,
+                NOW_NS + i,
+            );
+        }
+
+        assert_eq!(journal.len(), MAX_SIGNED_TRANSACTIONS_PER_CALLER as u64);
+        assert!(signed_transaction(&journal, caller, &txid(0), NOW_NS).is_none());
+        assert!(signed_transaction(&journal, caller, &txid(1), NOW_NS).is_some());
+    }
+}
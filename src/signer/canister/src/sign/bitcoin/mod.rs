@@ -1,4 +1,23 @@
 pub mod bitcoin_api;
 pub mod bitcoin_utils;
+pub mod bump_fee;
+pub mod chain;
+pub mod ckbtc;
+pub mod consolidation;
+pub mod decode;
+pub mod descriptor;
 pub mod fee_utils;
+pub mod htlc;
+pub mod inscription;
+pub mod jobs;
+pub mod journal;
+pub mod multisig;
+pub mod psbt;
+pub mod reservations;
+pub mod runes;
+pub mod taproot;
+#[cfg(test)]
+pub mod test_utils;
 pub mod tx_utils;
+pub mod utxo_verification;
+pub mod vault;
@@ -0,0 +1,218 @@
+//! P2WSH multisig accounts, co-signed by the caller's Bitcoin key and external keys.
+use bitcoin::{
+    opcodes::all::OP_CHECKMULTISIG, script::Builder, Address, CompressedPublicKey, PublicKey,
+    ScriptBuf,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcMultisigAddressError};
+
+use crate::sign::bitcoin::bitcoin_utils::{parse_compressed_public_key, transform_network};
+
+/// The most public keys in a standard `OP_CHECKMULTISIG` script.
+pub const MAX_MULTISIG_PUBLIC_KEYS: usize = 15;
+
+/// A `threshold`-of-n multisig account.
+pub struct MultisigAccount {
+    pub witness_script: ScriptBuf,
+    /// All public keys of the account, sorted as in
+    /// [BIP-67](https://github.com/bitcoin/bips/blob/master/bip-0067.mediawiki).
+    pub public_keys: Vec<CompressedPublicKey>,
+}
+
+impl MultisigAccount {
+    pub fn address(&self, network: BitcoinNetwork) -> Address {
+        Address::p2wsh(&self.witness_script, transform_network(network))
+    }
+}
+
+/// Parses the cosigner public keys and checks them and the threshold, before the caller's own key
+/// is known.
+pub fn parse_cosigner_public_keys(
+    cosigner_public_keys: &[String],
+    threshold: u8,
+) -> Result<Vec<CompressedPublicKey>, BtcMultisigAddressError> {
+    // The caller's key is added to the cosigners' keys.
+    let n_public_keys = cosigner_public_keys.len() + 1;
+    if n_public_keys > MAX_MULTISIG_PUBLIC_KEYS {
+        return Err(BtcMultisigAddressError::TooManyPublicKeys {
+            public_keys: n_public_keys as u64,
+            max_public_keys: MAX_MULTISIG_PUBLIC_KEYS as u64,
+        });
+    }
+    if threshold == 0 || usize::from(threshold) > n_public_keys {
+        return Err(BtcMultisigAddressError::InvalidThreshold {
+            threshold,
+            public_keys: n_public_keys as u64,
+        });
+    }
+    cosigner_public_keys
+        .iter()
+        .map(|public_key| {
+            parse_compressed_public_key(public_key).ok_or_else(|| {
+                BtcMultisigAddressError::InvalidPublicKey {
+                    public_key: public_key.clone(),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Builds the `threshold`-of-n witness script over the caller's key and the cosigners' keys.
+pub fn multisig_account(
+    own_public_key: CompressedPublicKey,
+    cosigner_public_keys: &[CompressedPublicKey],
+    threshold: u8,
+) -> Result<MultisigAccount, BtcMultisigAddressError> {
+    let mut public_keys = cosigner_public_keys.to_vec();
+    public_keys.push(own_public_key);
+    public_keys.sort_by_key(CompressedPublicKey::to_bytes);
+    if let Some(duplicate) = public_keys.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(BtcMultisigAddressError::DuplicatePublicKey {
+            public_key: hex::encode(duplicate[0].to_bytes()),
+        });
+    }
+
+    let builder = public_keys.iter().fold(
+        Builder::new().push_int(i64::from(threshold)),
+        |builder, key| builder.push_key(&PublicKey::from(*key)),
+    );
+    let witness_script = builder
+        .push_int(i64::try_from(public_keys.len()).expect("Too many public keys"))
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script();
+
+    Ok(MultisigAccount {
+        witness_script,
+        public_keys,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::OP_CHECKMULTISIG, script::Instruction, AddressType, CompressedPublicKey,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcMultisigAddressError};
+
+    use super::{multisig_account, parse_cosigner_public_keys, MAX_MULTISIG_PUBLIC_KEYS};
+    use crate::sign::bitcoin::test_utils::public_key;
+
+    fn public_key_hex(seed: u8) -> String {
+        hex::encode(public_key(seed).to_bytes())
+    }
+
+    #[test]
+    fn test_multisig_account_2_of_3() {
+        let cosigners = parse_cosigner_public_keys(&[public_key_hex(2), public_key_hex(3)], 2)
+            .expect("Expected valid cosigner keys");
+        let account =
+            multisig_account(public_key(1), &cosigners, 2).expect("Expected a valid account");
+
+        let mut expected_keys = vec![public_key(1), public_key(2), public_key(3)];
+        expected_keys.sort_by_key(CompressedPublicKey::to_bytes);
+        assert_eq!(account.public_keys, expected_keys);
+
+        let instructions: Vec<Instruction> = account
+            .witness_script
+            .instructions()
+            .collect::<Result<_, _>>()
+            .expect("Expected a valid script");
+        assert_eq!(instructions.len(), 6);
+        assert_eq!(
+            instructions[0].script_num(),
+            Some(2),
+            "threshold should be 2"
+        );
+        for (instruction, key) in instructions[1..4].iter().zip(&expected_keys) {
+            assert_eq!(
+                instruction
+                    .push_bytes()
+                    .map(|bytes| bytes.as_bytes().to_vec()),
+                Some(key.to_bytes().to_vec())
+            );
+        }
+        assert_eq!(
+            instructions[4].script_num(),
+            Some(3),
+            "number of keys should be 3"
+        );
+        assert_eq!(
+            instructions[5].opcode(),
+            Some(OP_CHECKMULTISIG),
+            "script should end with OP_CHECKMULTISIG"
+        );
+
+        let address = account.address(BitcoinNetwork::Mainnet);
+        assert_eq!(address.address_type(), Some(AddressType::P2wsh));
+        assert!(address.to_string().starts_with("bc1q"));
+    }
+
+    #[test]
+    fn test_multisig_account_is_independent_of_key_order() {
+        let forward = parse_cosigner_public_keys(&[public_key_hex(2), public_key_hex(3)], 2)
+            .expect("Expected valid cosigner keys");
+        let backward = parse_cosigner_public_keys(&[public_key_hex(3), public_key_hex(2)], 2)
+            .expect("Expected valid cosigner keys");
+
+        assert_eq!(
+            multisig_account(public_key(1), &forward, 2)
+                .unwrap()
+                .witness_script,
+            multisig_account(public_key(1), &backward, 2)
+                .unwrap()
+                .witness_script
+        );
+    }
+
+    #[test]
+    fn test_parse_cosigner_public_keys_rejects_invalid_key() {
+        let invalid = "02deadbeef".to_string();
+
+        assert_eq!(
+            parse_cosigner_public_keys(&[public_key_hex(2), invalid.clone()], 2).err(),
+            Some(BtcMultisigAddressError::InvalidPublicKey {
+                public_key: invalid
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_cosigner_public_keys_rejects_invalid_threshold() {
+        for threshold in [0, 3] {
+            assert_eq!(
+                parse_cosigner_public_keys(&[public_key_hex(2)], threshold).err(),
+                Some(BtcMultisigAddressError::InvalidThreshold {
+                    threshold,
+                    public_keys: 2,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cosigner_public_keys_rejects_too_many_keys() {
+        let cosigners: Vec<String> = (2..=u8::try_from(MAX_MULTISIG_PUBLIC_KEYS).unwrap() + 1)
+            .map(public_key_hex)
+            .collect();
+
+        assert_eq!(
+            parse_cosigner_public_keys(&cosigners, 2).err(),
+            Some(BtcMultisigAddressError::TooManyPublicKeys {
+                public_keys: MAX_MULTISIG_PUBLIC_KEYS as u64 + 1,
+                max_public_keys: MAX_MULTISIG_PUBLIC_KEYS as u64,
+            })
+        );
+    }
+
+    #[test]
+    fn test_multisig_account_rejects_duplicate_key() {
+        let cosigners = parse_cosigner_public_keys(&[public_key_hex(1)], 1)
+            .expect("Expected valid cosigner keys");
+
+        assert_eq!(
+            multisig_account(public_key(1), &cosigners, 1).err(),
+            Some(BtcMultisigAddressError::DuplicatePublicKey {
+                public_key: public_key_hex(1)
+            })
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Partial signing of [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki)
+//! PSBTs with the caller's Bitcoin key.
+use bitcoin::{
+    ecdsa, hashes::Hash, psbt::Psbt, script::Instruction, secp256k1, sighash::SighashCache,
+    CompressedPublicKey, EcdsaSighashType, PublicKey, ScriptBuf,
+};
+use candid::Principal;
+use ic_chain_fusion_signer_api::types::bitcoin::BtcSignPsbtError;
+
+use crate::{
+    derivation_path::Schema,
+    sign::{bitcoin::bitcoin_utils::principal_to_public_key, ecdsa_api::get_ecdsa_signatures},
+};
+
+/// An input of a PSBT that the caller can sign.
+#[derive(Debug, Eq, PartialEq)]
+pub struct InputToSign {
+    pub index: usize,
+    pub sighash: [u8; 32],
+    pub sighash_type: EcdsaSighashType,
+}
+
+pub fn decode_psbt(psbt_hex: &str) -> Result<Psbt, BtcSignPsbtError> {
+    let bytes = hex::decode(psbt_hex).map_err(|e| BtcSignPsbtError::InvalidPsbt {
+        msg: format!("failed to decode hex: {e}"),
+    })?;
+    Psbt::deserialize(&bytes).map_err(|e| BtcSignPsbtError::InvalidPsbt {
+        msg: format!("failed to decode PSBT: {e}"),
+    })
+}
+
+/// The number of inputs that have a witness script, i.e. at most the number of inputs to sign.
+pub fn witness_script_inputs(psbt: &Psbt) -> u64 {
+    psbt.inputs
+        .iter()
+        .filter(|input| input.witness_script.is_some())
+        .count() as u64
+}
+
+fn pushes_public_key(script: &ScriptBuf, public_key: &CompressedPublicKey) -> bool {
+    let public_key = public_key.to_bytes();
+    script.instructions().any(|instruction| {
+        matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == public_key)
+    })
+}
+
+/// Finds the P2WSH inputs whose witness script contains `public_key` and computes their BIP-143
+/// sighashes.
+///
+/// The sighash type of an input is taken from the PSBT, and defaults to `All`.  `None` and
+/// `NonePlusAnyoneCanPay` are rejected: they commit to no outputs, so anyone who sees the signature
+/// could redirect the input's value.
+///
+/// The value of an input is taken from its `witness_utxo` and not checked against a
+/// `non_witness_utxo`.  A BIP-143 sighash commits to the value of its own input only, so
+/// signatures made over different wrong values of the other inputs still combine into a valid
+/// transaction with a larger fee than the PSBT shows.
+pub fn inputs_to_sign(
+    psbt: &Psbt,
+    public_key: &CompressedPublicKey,
+) -> Result<Vec<InputToSign>, BtcSignPsbtError> {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut inputs = Vec::new();
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let Some(witness_script) = &input.witness_script else {
+            continue;
+        };
+        if !pushes_public_key(witness_script, public_key) {
+            continue;
+        }
+        let witness_utxo =
+            input
+                .witness_utxo
+                .as_ref()
+                .ok_or_else(|| BtcSignPsbtError::InvalidPsbt {
+                    msg: format!("input {index} has no witness_utxo"),
+                })?;
+        if witness_utxo.script_pubkey != ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) {
+            return Err(BtcSignPsbtError::InvalidPsbt {
+                msg: format!("input {index} does not spend its witness_script"),
+            });
+        }
+        let sighash_type = match input.sighash_type {
+            Some(sighash_type) => {
+                sighash_type
+                    .ecdsa_hash_ty()
+                    .map_err(|e| BtcSignPsbtError::InvalidPsbt {
+                        msg: format!("input {index}: {e}"),
+                    })?
+            }
+            None => EcdsaSighashType::All,
+        };
+        if matches!(
+            sighash_type,
+            EcdsaSighashType::None | EcdsaSighashType::NonePlusAnyoneCanPay
+        ) {
+            return Err(BtcSignPsbtError::SighashNoneNotAllowed {
+                input_index: index as u64,
+            });
+        }
+        let sighash = cache
+            .p2wsh_signature_hash(index, witness_script, witness_utxo.value, sighash_type)
+            .map_err(|e| BtcSignPsbtError::InvalidPsbt {
+                msg: format!("input {index}: {e}"),
+            })?;
+        inputs.push(InputToSign {
+            index,
+            sighash: sighash.to_byte_array(),
+            sighash_type,
+        });
+    }
+    Ok(inputs)
+}
+
+/// Adds the caller's partial signature to every input that the caller can sign.
+///
+/// Returns the PSBT and the indices of the signed inputs.  The PSBT is not finalized; that is
+/// left to the cosigner that adds the last signature.
+pub async fn sign_psbt(
+    principal: &Principal,
+    mut psbt: Psbt,
+) -> Result<(Psbt, Vec<u32>), BtcSignPsbtError> {
+    let public_key = principal_to_public_key(principal)
+        .await
+        .map_err(|msg| BtcSignPsbtError::InternalError { msg })?;
+
+    let inputs = inputs_to_sign(&psbt, &public_key)?;
+    if inputs.is_empty() {
+        return Err(BtcSignPsbtError::NoInputsToSign);
+    }
+
+    let signed_inputs = add_partial_signatures(principal, public_key, &mut psbt, inputs)
+        .await
+        .map_err(|msg| BtcSignPsbtError::InternalError { msg })?;
+    Ok((psbt, signed_inputs))
+}
+
+/// Signs the given inputs concurrently with the principal's Bitcoin key, whose public key is
+/// `public_key`, and adds the signatures to the PSBT.
+///
+/// Returns the indices of the signed inputs.
+pub async fn add_partial_signatures(
+    principal: &Principal,
+    public_key: CompressedPublicKey,
+    psbt: &mut Psbt,
+    inputs: Vec<InputToSign>,
+) -> Result<Vec<u32>, String> {
+    let derivation_path = Schema::Btc.derivation_path(principal);
+    let sighashes = inputs.iter().map(|input| input.sighash.to_vec()).collect();
+    let signatures = get_ecdsa_signatures(derivation_path, sighashes).await?;
+    let mut signed_inputs = Vec::with_capacity(inputs.len());
+    for (input, signature) in inputs.into_iter().zip(signatures) {
+        let signature = secp256k1::ecdsa::Signature::from_compact(&signature)
+            .map_err(|e| format!("malformed signature: {e}"))?;
+        psbt.inputs[input.index].partial_sigs.insert(
+            PublicKey::from(public_key),
+            ecdsa::Signature {
+                signature,
+                sighash_type: input.sighash_type,
+            },
+        );
+        signed_inputs.push(u32::try_from(input.index).expect("PSBT has too many inputs"));
+    }
+    Ok(signed_inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime,
+        hashes::Hash,
+        psbt::{Psbt, PsbtSighashType},
+        sighash::SighashCache,
+        transaction::Version,
+        Amount, EcdsaSighashType, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+        Witness,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::BtcSignPsbtError;
+
+    use super::{decode_psbt, inputs_to_sign, witness_script_inputs, InputToSign};
+    use crate::sign::bitcoin::{multisig::multisig_account, test_utils::public_key};
+
+    /// A PSBT that spends a 2-of-2 multisig output of keys 1 and 2, and a P2WPKH output of key 3.
+    fn multisig_psbt() -> Psbt {
+        let input = |vout| TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        };
+        let transaction = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![input(0), input(1)],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&public_key(3).wpubkey_hash()),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(transaction).expect("Expected an unsigned tx");
+        let witness_script = multisig_account(public_key(1), &[public_key(2)], 2)
+            .expect("Expected a valid account")
+            .witness_script;
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(60_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script);
+        psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(40_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&public_key(3).wpubkey_hash()),
+        });
+        psbt
+    }
+
+    #[test]
+    fn test_decode_psbt_round_trips() {
+        let psbt = multisig_psbt();
+
+        assert_eq!(
+            decode_psbt(&hex::encode(psbt.serialize())).expect("Expected a valid PSBT"),
+            psbt
+        );
+    }
+
+    #[test]
+    fn test_decode_psbt_rejects_invalid_psbt() {
+        assert!(matches!(
+            decode_psbt("not hex"),
+            Err(BtcSignPsbtError::InvalidPsbt { msg }) if msg.contains("failed to decode hex")
+        ));
+        assert!(matches!(
+            decode_psbt("deadbeef"),
+            Err(BtcSignPsbtError::InvalidPsbt { msg }) if msg.contains("failed to decode PSBT")
+        ));
+    }
+
+    #[test]
+    fn test_inputs_to_sign_finds_multisig_input() {
+        let psbt = multisig_psbt();
+        assert_eq!(witness_script_inputs(&psbt), 1);
+
+        let witness_script = psbt.inputs[0].witness_script.clone().unwrap();
+        let expected_sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wsh_signature_hash(
+                0,
+                &witness_script,
+                Amount::from_sat(60_000),
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        let expected = vec![InputToSign {
+            index: 0,
+            sighash: expected_sighash.to_byte_array(),
+            sighash_type: EcdsaSighashType::All,
+        }];
+
+        assert_eq!(inputs_to_sign(&psbt, &public_key(1)), Ok(expected));
+        // Both cosigners sign the same input; other keys sign nothing.
+        assert_eq!(
+            inputs_to_sign(&psbt, &public_key(2)).map(|inputs| inputs.len()),
+            Ok(1)
+        );
+        assert_eq!(inputs_to_sign(&psbt, &public_key(3)), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_inputs_to_sign_uses_psbt_sighash_type() {
+        let mut psbt = multisig_psbt();
+        psbt.inputs[0].sighash_type = Some(PsbtSighashType::from(
+            EcdsaSighashType::SinglePlusAnyoneCanPay,
+        ));
+
+        let inputs = inputs_to_sign(&psbt, &public_key(1)).expect("Expected inputs to sign");
+        assert_eq!(
+            inputs[0].sighash_type,
+            EcdsaSighashType::SinglePlusAnyoneCanPay
+        );
+    }
+
+    #[test]
+    fn test_inputs_to_sign_rejects_sighash_none() {
+        for sighash_type in [
+            EcdsaSighashType::None,
+            EcdsaSighashType::NonePlusAnyoneCanPay,
+        ] {
+            let mut psbt = multisig_psbt();
+            psbt.inputs[0].sighash_type = Some(PsbtSighashType::from(sighash_type));
+
+            assert_eq!(
+                inputs_to_sign(&psbt, &public_key(1)),
+                Err(BtcSignPsbtError::SighashNoneNotAllowed { input_index: 0 })
+            );
+            // Inputs that the caller does not sign may use any sighash type.
+            assert_eq!(inputs_to_sign(&psbt, &public_key(3)), Ok(vec![]));
+        }
+    }
+
+    #[test]
+    fn test_inputs_to_sign_rejects_mismatched_witness_utxo() {
+        let mut psbt = multisig_psbt();
+        psbt.inputs[0].witness_utxo.as_mut().unwrap().script_pubkey =
+            ScriptBuf::new_p2wpkh(&public_key(1).wpubkey_hash());
+
+        assert_eq!(
+            inputs_to_sign(&psbt, &public_key(1)),
+            Err(BtcSignPsbtError::InvalidPsbt {
+                msg: "input 0 does not spend its witness_script".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,621 @@
+//! Reservations of the UTXOs that the caller's transactions spend, which keep concurrent calls of a
+//! caller from signing transactions that spend the same UTXOs.
+//!
+//! Every endpoint that signs a transaction to send reserves its inputs with [`spend_reserved`]
+//! before it charges the caller, and fails with `UtxoReserved` if another transaction of the
+//! caller holds one of them.  The inputs stay reserved for [`IN_FLIGHT_RESERVATION_NS`] while the
+//! transaction is signed, and are released if it is not.  [`send_reserved`] then keeps them
+//! reserved for the transaction: for [`SENT_RESERVATION_NS`] once it is sent, by when its inputs
+//! are usually confirmed as spent, or for as long as the journal keeps it if
+//! `bitcoin_send_transaction` rejects it, so that it can still be sent with `btc_rebroadcast`.
+//!
+//! Expired reservations are removed as new ones are made: all those of the caller, and a bounded
+//! number of those of other callers, so that the reservations of idle callers do not pile up.
+use std::{
+    cell::RefCell,
+    future::Future,
+    ops::{Bound, RangeInclusive},
+};
+
+use bitcoin::{hashes::Hash, Transaction};
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_cdk_bitcoin_canister::{Txid, Utxo};
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcReservedUtxo, SendBtcError};
+use ic_stable_structures::Memory;
+
+use crate::{
+    sign::bitcoin::{
+        bitcoin_utils::display_txid,
+        journal::{self, SIGNED_TRANSACTION_RETENTION_NS},
+        tx_utils::SignedTransaction,
+    },
+    state::mutate_state,
+    types::{Candid, UtxoReservation, UtxoReservationKey, UtxoReservations},
+};
+
+/// How long the UTXOs of a transaction that is being signed stay reserved.  The reservation is
+/// updated when the call completes, and refreshed by every batch of a signing job until the job
+/// finishes; the expiry only matters if neither ever happens.
+pub const IN_FLIGHT_RESERVATION_NS: u64 = 30 * 60 * 1_000_000_000;
+/// How long the UTXOs of a sent transaction stay reserved: long enough for the transaction to be
+/// confirmed, after which `bitcoin_get_utxos` no longer reports them.
+pub const SENT_RESERVATION_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// How many reservations of any caller a new reservation checks for expiry.
+const SWEEP_BATCH: usize = 100;
+
+thread_local! {
+    /// The last reservation checked by [`sweep_expired`], after which the next sweep resumes.
+    static SWEEP_CURSOR: RefCell<Option<UtxoReservationKey>> = const { RefCell::new(None) };
+}
+
+/// Reserves `utxos` for the caller while `spend` signs a transaction that spends them and sends it
+/// with [`send_reserved`].
+///
+/// Fails before running `spend` if one of the UTXOs is reserved already.  If `spend` fails before
+/// the transaction is sent, the UTXOs are released.
+pub async fn spend_reserved<T, E: From<SendBtcError>>(
+    principal: Principal,
+    utxos: &[Utxo],
+    spend: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    mutate_state(|state| reserve_utxos(&mut state.utxo_reservations, principal, utxos, time()))?;
+    let result = spend.await;
+    if result.is_err() {
+        mutate_state(|state| release_unsent(&mut state.utxo_reservations, principal, utxos));
+    }
+    result
+}
+
+/// Sends a transaction of the caller that spends the reserved `utxos`, records the outcome in the
+/// journal, and keeps the UTXOs reserved for the transaction.
+pub async fn send_reserved(
+    principal: Principal,
+    network: BitcoinNetwork,
+    utxos: &[Utxo],
+    signed_transaction: &SignedTransaction,
+) -> Result<(), String> {
+    let txid = &signed_transaction.txid;
+    let sent = journal::send_signed_transaction(
+        principal,
+        network,
+        txid,
+        signed_transaction.signed_transaction_bytes.clone(),
+    )
+    .await;
+    mutate_state(|state| match &sent {
+        Ok(()) => mark_sent(&mut state.utxo_reservations, principal, utxos, txid, time()),
+        Err(_) => {
+            mark_broadcast_failed(&mut state.utxo_reservations, principal, utxos, txid, time());
+        }
+    });
+    sent
+}
+
+/// Reserves the UTXOs for a transaction of the caller that is about to be signed.
+///
+/// Fails without reserving anything if another transaction of the caller holds an unexpired
+/// reservation of one of the UTXOs.
+pub fn reserve_utxos<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+    now_ns: u64,
+) -> Result<(), SendBtcError> {
+    release_expired(reservations, principal, now_ns);
+    sweep_expired(reservations, now_ns);
+    let keys = utxos
+        .iter()
+        .map(|utxo| reservation_key(principal, utxo))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some((key, reservation)) = keys
+        .iter()
+        .find_map(|key| reservations.get(key).map(|reservation| (key, reservation)))
+    {
+        return Err(SendBtcError::UtxoReserved {
+            txid: display_txid(&Txid::from(key.txid)),
+            vout: key.vout,
+            spending_txid: reservation.spending_txid.clone(),
+            expires_at_ns: reservation.expires_at_ns,
+        });
+    }
+    keep_reserved(
+        reservations,
+        keys.into_iter(),
+        None,
+        now_ns.saturating_add(IN_FLIGHT_RESERVATION_NS),
+    );
+    Ok(())
+}
+
+/// Keeps the UTXOs of a transaction that is still being signed reserved for another
+/// [`IN_FLIGHT_RESERVATION_NS`].
+pub fn refresh_in_flight<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+    now_ns: u64,
+) {
+    let keys = utxos
+        .iter()
+        .filter_map(|utxo| reservation_key(principal, utxo).ok());
+    keep_reserved(
+        reservations,
+        keys,
+        None,
+        now_ns.saturating_add(IN_FLIGHT_RESERVATION_NS),
+    );
+}
+
+/// Keeps the UTXOs reserved for the sent transaction `spending_txid`.
+pub fn mark_sent<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+    spending_txid: &str,
+    now_ns: u64,
+) {
+    let keys = utxos
+        .iter()
+        .filter_map(|utxo| reservation_key(principal, utxo).ok());
+    keep_reserved(
+        reservations,
+        keys,
+        Some(spending_txid),
+        now_ns.saturating_add(SENT_RESERVATION_NS),
+    );
+}
+
+/// Keeps the inputs of a stored transaction that was sent again reserved for it, whether or not
+/// they were reserved when it was signed.
+pub fn mark_transaction_sent<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    transaction: &Transaction,
+    now_ns: u64,
+) {
+    let keys = transaction.input.iter().map(|input| UtxoReservationKey {
+        principal,
+        txid: input.previous_output.txid.to_byte_array(),
+        vout: input.previous_output.vout,
+    });
+    keep_reserved(
+        reservations,
+        keys,
+        Some(&transaction.compute_txid().to_string()),
+        now_ns.saturating_add(SENT_RESERVATION_NS),
+    );
+}
+
+/// Keeps the UTXOs reserved for `spending_txid`, which `bitcoin_send_transaction` rejected, for as
+/// long as the journal keeps it to be sent again with `btc_rebroadcast`.
+pub fn mark_broadcast_failed<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+    spending_txid: &str,
+    now_ns: u64,
+) {
+    let keys = utxos
+        .iter()
+        .filter_map(|utxo| reservation_key(principal, utxo).ok());
+    keep_reserved(
+        reservations,
+        keys,
+        Some(spending_txid),
+        now_ns.saturating_add(SIGNED_TRANSACTION_RETENTION_NS),
+    );
+}
+
+fn keep_reserved<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    keys: impl Iterator<Item = UtxoReservationKey>,
+    spending_txid: Option<&str>,
+    expires_at_ns: u64,
+) {
+    for key in keys {
+        reservations.insert(
+            key,
+            Candid(UtxoReservation {
+                spending_txid: spending_txid.map(str::to_string),
+                expires_at_ns,
+            }),
+        );
+    }
+}
+
+/// Releases the UTXOs of a transaction that was not signed.
+pub fn release_utxos<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+) {
+    for key in utxos
+        .iter()
+        .filter_map(|utxo| reservation_key(principal, utxo).ok())
+    {
+        reservations.remove(&key);
+    }
+}
+
+/// Releases the UTXOs that are still reserved for a transaction in flight, i.e. that were not
+/// reserved for a sent transaction since.
+fn release_unsent<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    utxos: &[Utxo],
+) {
+    for key in utxos
+        .iter()
+        .filter_map(|utxo| reservation_key(principal, utxo).ok())
+    {
+        if reservations
+            .get(&key)
+            .is_some_and(|reservation| reservation.spending_txid.is_none())
+        {
+            reservations.remove(&key);
+        }
+    }
+}
+
+/// The caller's UTXOs with an unexpired reservation.
+pub fn reserved_utxos<M: Memory>(
+    reservations: &UtxoReservations<M>,
+    principal: Principal,
+    now_ns: u64,
+) -> Vec<BtcReservedUtxo> {
+    reservations
+        .keys_range(principal_range(principal))
+        .filter_map(|key| {
+            let reservation = reservations.get(&key)?;
+            (reservation.expires_at_ns > now_ns).then(|| BtcReservedUtxo {
+                txid: display_txid(&Txid::from(key.txid)),
+                vout: key.vout,
+                spending_txid: reservation.spending_txid.clone(),
+                expires_at_ns: reservation.expires_at_ns,
+            })
+        })
+        .collect()
+}
+
+/// The UTXOs of `utxos` that the caller has no unexpired reservation of.
+pub fn unreserved_utxos<M: Memory>(
+    reservations: &UtxoReservations<M>,
+    principal: Principal,
+    utxos: Vec<Utxo>,
+    now_ns: u64,
+) -> Vec<Utxo> {
+    utxos
+        .into_iter()
+        .filter(|utxo| {
+            reservation_key(principal, utxo).is_ok_and(|key| {
+                reservations
+                    .get(&key)
+                    .is_none_or(|reservation| reservation.expires_at_ns <= now_ns)
+            })
+        })
+        .collect()
+}
+
+/// Removes the caller's expired reservations.
+fn release_expired<M: Memory>(
+    reservations: &mut UtxoReservations<M>,
+    principal: Principal,
+    now_ns: u64,
+) {
+    let expired = reservations
+        .keys_range(principal_range(principal))
+        .filter(|key| {
+            reservations
+                .get(key)
+                .is_some_and(|reservation| reservation.expires_at_ns <= now_ns)
+        })
+        .collect::<Vec<_>>();
+    for key in expired {
+        reservations.remove(&key);
+    }
+}
+
+/// Removes the expired reservations among the next [`SWEEP_BATCH`] reservations of any caller, so
+/// that the reservations of callers who stop calling the canister do not pile up.
+///
+/// Each sweep resumes after the last reservation checked by the previous one, and starts over
+/// from the first reservation once it reaches the last.
+fn sweep_expired<M: Memory>(reservations: &mut UtxoReservations<M>, now_ns: u64) {
+    let start = SWEEP_CURSOR
+        .take()
+        .map_or(Bound::Unbounded, Bound::Excluded);
+    let checked = reservations
+        .keys_range((start, Bound::Unbounded))
+        .take(SWEEP_BATCH)
+        .collect::<Vec<_>>();
+    if checked.len() == SWEEP_BATCH {
+        SWEEP_CURSOR.set(checked.last().cloned());
+    }
+    for key in checked {
+        if reservations
+            .get(&key)
+            .is_some_and(|reservation| reservation.expires_at_ns <= now_ns)
+        {
+            reservations.remove(&key);
+        }
+    }
+}
+
+/// A txid that is not 32 bytes long cannot be the txid of a UTXO.
+fn reservation_key(principal: Principal, utxo: &Utxo) -> Result<UtxoReservationKey, SendBtcError> {
+    let txid = <[u8; 32]>::try_from(utxo.outpoint.txid.as_ref()).map_err(|_| {
+        SendBtcError::UnknownUtxo {
+            txid: hex::encode(utxo.outpoint.txid.as_ref()),
+            vout: utxo.outpoint.vout,
+        }
+    })?;
+    Ok(UtxoReservationKey {
+        principal,
+        txid,
+        vout: utxo.outpoint.vout,
+    })
+}
+
+/// The keys of all the UTXOs that the caller can reserve.
+fn principal_range(principal: Principal) -> RangeInclusive<UtxoReservationKey> {
+    UtxoReservationKey {
+        principal,
+        txid: [0; 32],
+        vout: 0,
+    }..=UtxoReservationKey {
+        principal,
+        txid: [u8::MAX; 32],
+        vout: u32::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, OutPoint as BitcoinOutPoint,
+        Transaction, TxIn, Txid as BitcoinTxid,
+    };
+    use candid::Principal;
+    use ic_cdk_bitcoin_canister::{OutPoint, Txid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::SendBtcError;
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    use super::{
+        mark_broadcast_failed, mark_sent, mark_transaction_sent, release_unsent, release_utxos,
+        reserve_utxos, reserved_utxos, unreserved_utxos, IN_FLIGHT_RESERVATION_NS,
+        SENT_RESERVATION_NS, SWEEP_BATCH, SWEEP_CURSOR,
+    };
+    use crate::{sign::bitcoin::journal::SIGNED_TRANSACTION_RETENTION_NS, types::UtxoReservations};
+
+    const NOW_NS: u64 = 1_700_000_000_000_000_000;
+
+    fn utxo(txid_byte: u8, vout: u32) -> Utxo {
+        Utxo {
+            outpoint: OutPoint {
+                txid: Txid::from([txid_byte; 32]),
+                vout,
+            },
+            value: 10_000,
+            height: 100,
+        }
+    }
+
+    fn reservations() -> UtxoReservations<DefaultMemoryImpl> {
+        UtxoReservations::init(DefaultMemoryImpl::default())
+    }
+
+    #[test]
+    fn test_reserve_utxos_rejects_overlapping_transaction() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+
+        assert!(
+            reserve_utxos(&mut reservations, caller, &[utxo(1, 0), utxo(1, 1)], NOW_NS).is_ok()
+        );
+        assert!(matches!(
+            reserve_utxos(&mut reservations, caller, &[utxo(2, 0), utxo(1, 1)], NOW_NS),
+            Err(SendBtcError::UtxoReserved {
+                txid,
+                vout: 1,
+                spending_txid: None,
+                expires_at_ns,
+            }) if txid == "01".repeat(32) && expires_at_ns == NOW_NS + IN_FLIGHT_RESERVATION_NS
+        ));
+        // The rejected transaction reserves nothing.
+        assert_eq!(reserved_utxos(&reservations, caller, NOW_NS).len(), 2);
+        assert!(reserve_utxos(&mut reservations, caller, &[utxo(2, 0)], NOW_NS).is_ok());
+    }
+
+    #[test]
+    fn test_reservations_are_per_caller() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+        let other_caller = Principal::from_slice(&[1, 2]);
+
+        assert!(reserve_utxos(&mut reservations, caller, &[utxo(1, 0)], NOW_NS).is_ok());
+        assert!(reserve_utxos(&mut reservations, other_caller, &[utxo(1, 0)], NOW_NS).is_ok());
+        assert_eq!(reserved_utxos(&reservations, caller, NOW_NS).len(), 1);
+        assert_eq!(reserved_utxos(&reservations, other_caller, NOW_NS).len(), 1);
+    }
+
+    #[test]
+    fn test_sent_reservation_records_txid_and_expires() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+        let utxos = [utxo(1, 0)];
+
+        reserve_utxos(&mut reservations, caller, &utxos, NOW_NS).unwrap();
+        mark_sent(
+            &mut reservations,
+            caller,
+            &utxos,
+            "ab".repeat(32).as_str(),
+            NOW_NS,
+        );
+        let reserved = reserved_utxos(&reservations, caller, NOW_NS);
+        assert_eq!(reserved.len(), 1);
+        assert_eq!(reserved[0].spending_txid, Some("ab".repeat(32)));
+        assert_eq!(reserved[0].expires_at_ns, NOW_NS + SENT_RESERVATION_NS);
+
+        let expiry = NOW_NS + SENT_RESERVATION_NS;
+        assert!(reserved_utxos(&reservations, caller, expiry).is_empty());
+        assert!(reserve_utxos(&mut reservations, caller, &utxos, expiry).is_ok());
+    }
+
+    #[test]
+    fn test_broadcast_failed_reservation_lasts_as_long_as_the_journal() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+        let utxos = [utxo(1, 0)];
+
+        reserve_utxos(&mut reservations, caller, &utxos, NOW_NS).unwrap();
+        mark_broadcast_failed(
+            &mut reservations,
+            caller,
+            &utxos,
+            "ab".repeat(32).as_str(),
+            NOW_NS,
+        );
+        let reserved = reserved_utxos(&reservations, caller, NOW_NS);
+        assert_eq!(reserved.len(), 1);
+        assert_eq!(reserved[0].spending_txid, Some("ab".repeat(32)));
+        assert_eq!(
+            reserved[0].expires_at_ns,
+            NOW_NS + SIGNED_TRANSACTION_RETENTION_NS
+        );
+    }
+
+    #[test]
+    fn test_rebroadcast_transaction_reserves_its_inputs() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+        let transaction = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: BitcoinOutPoint {
+                    txid: BitcoinTxid::from_byte_array([1; 32]),
+                    vout: 0,
+                },
+                ..TxIn::default()
+            }],
+            output: vec![],
+        };
+
+        mark_transaction_sent(&mut reservations, caller, &transaction, NOW_NS);
+        let reserved = reserved_utxos(&reservations, caller, NOW_NS);
+        assert_eq!(reserved.len(), 1);
+        assert_eq!(reserved[0].txid, "01".repeat(32));
+        assert_eq!(
+            reserved[0].spending_txid,
+            Some(transaction.compute_txid().to_string())
+        );
+        assert_eq!(reserved[0].expires_at_ns, NOW_NS + SENT_RESERVATION_NS);
+        assert!(reserve_utxos(&mut reservations, caller, &[utxo(1, 0)], NOW_NS).is_err());
+    }
+
+    #[test]
+    fn test_released_utxos_can_be_reserved_again() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+        let utxos = [utxo(1, 0)];
+
+        reserve_utxos(&mut reservations, caller, &utxos, NOW_NS).unwrap();
+        release_utxos(&mut reservations, caller, &utxos);
+        assert!(reserved_utxos(&reservations, caller, NOW_NS).is_empty());
+        assert!(reserve_utxos(&mut reservations, caller, &utxos, NOW_NS).is_ok());
+    }
+
+    #[test]
+    fn test_release_unsent_keeps_sent_utxos_reserved() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+
+        reserve_utxos(&mut reservations, caller, &[utxo(1, 0), utxo(1, 1)], NOW_NS).unwrap();
+        mark_sent(
+            &mut reservations,
+            caller,
+            &[utxo(1, 0)],
+            "ab".repeat(32).as_str(),
+            NOW_NS,
+        );
+        release_unsent(&mut reservations, caller, &[utxo(1, 0), utxo(1, 1)]);
+
+        let reserved = reserved_utxos(&reservations, caller, NOW_NS);
+        assert_eq!(reserved.len(), 1);
+        assert_eq!(reserved[0].vout, 0);
+    }
+
+    #[test]
+    fn test_reserve_utxos_removes_expired_reservations_of_other_callers() {
+        let mut reservations = reservations();
+        let idle_caller = Principal::from_slice(&[1]);
+        let active_caller = Principal::from_slice(&[2]);
+        SWEEP_CURSOR.set(None);
+
+        reserve_utxos(
+            &mut reservations,
+            idle_caller,
+            &[utxo(1, 0), utxo(1, 1)],
+            NOW_NS,
+        )
+        .unwrap();
+        let later_ns = NOW_NS + IN_FLIGHT_RESERVATION_NS;
+        reserve_utxos(&mut reservations, active_caller, &[utxo(2, 0)], later_ns).unwrap();
+
+        assert!(reserved_utxos(&reservations, idle_caller, NOW_NS).is_empty());
+        assert_eq!(reservations.len(), 1);
+    }
+
+    #[test]
+    fn test_reserve_utxos_sweeps_reservations_in_batches() {
+        let mut reservations = reservations();
+        let idle_caller = Principal::from_slice(&[1]);
+        let active_caller = Principal::from_slice(&[2]);
+        SWEEP_CURSOR.set(None);
+
+        let idle_utxos = (0..2 * SWEEP_BATCH)
+            .map(|vout| utxo(1, u32::try_from(vout).unwrap()))
+            .collect::<Vec<_>>();
+        reserve_utxos(&mut reservations, idle_caller, &idle_utxos, NOW_NS).unwrap();
+        let later_ns = NOW_NS + IN_FLIGHT_RESERVATION_NS;
+        reserve_utxos(&mut reservations, active_caller, &[utxo(2, 0)], later_ns).unwrap();
+        assert_eq!(reservations.len(), SWEEP_BATCH as u64 + 1);
+        reserve_utxos(&mut reservations, active_caller, &[utxo(2, 1)], later_ns).unwrap();
+
+        assert_eq!(reservations.len(), 2);
+    }
+
+    #[test]
+    fn test_unreserved_utxos_skips_reserved_ones() {
+        let mut reservations = reservations();
+        let caller = Principal::from_slice(&[1]);
+
+        reserve_utxos(&mut reservations, caller, &[utxo(1, 0)], NOW_NS).unwrap();
+        let candidates = vec![utxo(1, 0), utxo(1, 1)];
+        assert_eq!(
+            unreserved_utxos(&reservations, caller, candidates.clone(), NOW_NS),
+            vec![utxo(1, 1)]
+        );
+        assert_eq!(
+            unreserved_utxos(
+                &reservations,
+                Principal::from_slice(&[2]),
+                candidates.clone(),
+                NOW_NS
+            ),
+            candidates
+        );
+        assert_eq!(
+            unreserved_utxos(
+                &reservations,
+                caller,
+                candidates.clone(),
+                NOW_NS + IN_FLIGHT_RESERVATION_NS
+            ),
+            candidates
+        );
+    }
+}
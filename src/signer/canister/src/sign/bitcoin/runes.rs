@@ -0,0 +1,406 @@
+//! Runestones: the `OP_RETURN` outputs with which a transaction transfers runes between its outputs
+//! and etches new runes.
+use bitcoin::{
+    opcodes::all::{OP_PUSHNUM_13, OP_RETURN},
+    script::{Builder, PushBytesBuf},
+    ScriptBuf,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BtcRuneId, BtcSendRunesRequest, BuildP2wpkhTxError,
+};
+
+use crate::sign::bitcoin::tx_utils::MAX_OP_RETURN_DATA_BYTES;
+
+/// The largest runestone payload that fits in an `OP_RETURN` output that nodes relay by default,
+/// after the `OP_13` that marks the output as a runestone.
+pub const MAX_RUNESTONE_PAYLOAD_BYTES: usize = MAX_OP_RETURN_DATA_BYTES - 1;
+/// The largest divisibility of a rune.
+const MAX_DIVISIBILITY: u8 = 38;
+/// The ID with which an edict refers to the rune etched by the same transaction.
+const ETCHED_RUNE_ID: BtcRuneId = BtcRuneId { block: 0, tx: 0 };
+
+/// The tags of the runestone fields, see the [Runes specification](https://docs.ordinals.com/runes/specification.html).
+mod tag {
+    pub const BODY: u128 = 0;
+    pub const DIVISIBILITY: u128 = 1;
+    pub const FLAGS: u128 = 2;
+    pub const SYMBOL: u128 = 5;
+    pub const PREMINE: u128 = 6;
+    pub const POINTER: u128 = 22;
+}
+/// The flag that marks a runestone as an etching.
+const FLAG_ETCHING: u128 = 1;
+
+/// Moves `amount` units of the rune `id` to the output at index `output`.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Edict {
+    pub id: BtcRuneId,
+    pub amount: u128,
+    pub output: u32,
+}
+
+/// A new rune without a name.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Etching {
+    pub divisibility: Option<u8>,
+    pub symbol: Option<char>,
+    pub premine: Option<u128>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Runestone {
+    pub edicts: Vec<Edict>,
+    pub etching: Option<Etching>,
+    /// The output that receives the runes that no edict allocates.
+    pub pointer: Option<u32>,
+}
+
+impl Runestone {
+    /// The runestone's fields and edicts, encoded as LEB128 integers.
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        if let Some(etching) = &self.etching {
+            encode_field(&mut payload, tag::FLAGS, FLAG_ETCHING);
+            if let Some(divisibility) = etching.divisibility {
+                encode_field(&mut payload, tag::DIVISIBILITY, u128::from(divisibility));
+            }
+            if let Some(symbol) = etching.symbol {
+                encode_field(&mut payload, tag::SYMBOL, u128::from(u32::from(symbol)));
+            }
+            if let Some(premine) = etching.premine {
+                encode_field(&mut payload, tag::PREMINE, premine);
+            }
+        }
+        if let Some(pointer) = self.pointer {
+            encode_field(&mut payload, tag::POINTER, u128::from(pointer));
+        }
+        if !self.edicts.is_empty() {
+            encode_varint(&mut payload, tag::BODY);
+            let mut edicts = self.edicts.iter().collect::<Vec<_>>();
+            edicts.sort_by_key(|edict| edict.id);
+            // Rune IDs are delta encoded: the block relative to the previous edict, and the
+            // transaction index relative to the previous edict in the same block.
+            let mut previous = ETCHED_RUNE_ID;
+            for edict in edicts {
+                let block = edict.id.block - previous.block;
+                let tx = if block == 0 {
+                    edict.id.tx - previous.tx
+                } else {
+                    edict.id.tx
+                };
+                encode_varint(&mut payload, u128::from(block));
+                encode_varint(&mut payload, u128::from(tx));
+                encode_varint(&mut payload, edict.amount);
+                encode_varint(&mut payload, u128::from(edict.output));
+                previous = edict.id;
+            }
+        }
+        payload
+    }
+
+    /// The `OP_RETURN OP_13 <payload>` script of the runestone's output.
+    pub fn script_pubkey(&self) -> Result<ScriptBuf, BuildP2wpkhTxError> {
+        let payload = self.payload();
+        if payload.len() > MAX_RUNESTONE_PAYLOAD_BYTES {
+            return Err(BuildP2wpkhTxError::OpReturnDataTooLarge {
+                data_bytes: payload.len() as u64,
+                max_data_bytes: MAX_RUNESTONE_PAYLOAD_BYTES as u64,
+            });
+        }
+        let payload = PushBytesBuf::try_from(payload)
+            .expect("A payload within the OP_RETURN limit is a valid push");
+        Ok(Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_opcode(OP_PUSHNUM_13)
+            .push_slice(payload)
+            .into_script())
+    }
+}
+
+fn encode_varint(payload: &mut Vec<u8>, mut n: u128) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            payload.push(byte);
+            return;
+        }
+        payload.push(byte | 0x80);
+    }
+}
+
+fn encode_field(payload: &mut Vec<u8>, tag: u128, value: u128) {
+    encode_varint(payload, tag);
+    encode_varint(payload, value);
+}
+
+/// The runestone of a `btc_send_runes` request.
+///
+/// The transfers are sent to the first outputs, in order, and the runes that they leave are sent
+/// to the output that follows them.
+pub fn runestone(request: &BtcSendRunesRequest) -> Result<Runestone, String> {
+    if request.transfers.is_empty() && request.etching.is_none() {
+        return Err("a runestone needs a transfer or an etching".to_string());
+    }
+    let pointer = u32::try_from(request.transfers.len())
+        .map_err(|_| format!("too many transfers: {}", request.transfers.len()))?;
+    let edicts = request
+        .transfers
+        .iter()
+        .zip(0..pointer)
+        .map(|(transfer, output)| {
+            let id = transfer.rune_id;
+            if id == ETCHED_RUNE_ID {
+                if request.etching.is_none() {
+                    return Err(format!(
+                        "transfer {output}: rune 0:0 can only be sent with an etching"
+                    ));
+                }
+            } else if id.block == 0 {
+                return Err(format!("transfer {output}: invalid rune id 0:{}", id.tx));
+            } else if request.rune_utxos.is_empty() {
+                return Err(format!(
+                    "transfer {output}: rune {}:{} needs rune_utxos",
+                    id.block, id.tx
+                ));
+            }
+            Ok(Edict {
+                id,
+                amount: transfer.amount,
+                output,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let etching = request
+        .etching
+        .as_ref()
+        .map(|etching| {
+            if etching
+                .divisibility
+                .is_some_and(|divisibility| divisibility > MAX_DIVISIBILITY)
+            {
+                return Err(format!("divisibility must be at most {MAX_DIVISIBILITY}"));
+            }
+            let symbol = etching
+                .symbol
+                .as_ref()
+                .map(|symbol| {
+                    let mut chars = symbol.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(symbol), None) => Ok(symbol),
+                        _ => Err(format!("symbol must be a single character: {symbol:?}")),
+                    }
+                })
+                .transpose()?;
+            Ok(Etching {
+                divisibility: etching.divisibility,
+                symbol,
+                premine: etching.premine,
+            })
+        })
+        .transpose()?;
+    Ok(Runestone {
+        edicts,
+        etching,
+        pointer: Some(pointer),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::{OP_PUSHNUM_13, OP_RETURN},
+        script::Instruction,
+    };
+    use ic_cdk_bitcoin_canister::{OutPoint, Txid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcRuneEtching, BtcRuneId, BtcRuneTransfer, BtcSendRunesRequest,
+        BuildP2wpkhTxError,
+    };
+
+    use super::{runestone, Edict, Etching, Runestone, MAX_RUNESTONE_PAYLOAD_BYTES};
+
+    fn transfer(block: u64, tx: u32, amount: u128) -> BtcRuneTransfer {
+        BtcRuneTransfer {
+            rune_id: BtcRuneId { block, tx },
+            amount,
+            destination_address: "bcrt1qdestination".to_string(),
+        }
+    }
+
+    fn request(
+        transfers: Vec<BtcRuneTransfer>,
+        etching: Option<BtcRuneEtching>,
+    ) -> BtcSendRunesRequest {
+        BtcSendRunesRequest {
+            network: BitcoinNetwork::Regtest,
+            rune_utxos: vec![Utxo {
+                outpoint: OutPoint {
+                    txid: Txid::from([1; 32]),
+                    vout: 0,
+                },
+                value: 10_000,
+                height: 1,
+            }],
+            fee_utxos: vec![],
+            transfers,
+            etching,
+            postage_satoshis: None,
+            fee_satoshis: None,
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    #[test]
+    fn test_payload_encodes_edicts_and_pointer() {
+        let runestone = Runestone {
+            edicts: vec![Edict {
+                id: BtcRuneId {
+                    block: 840_000,
+                    tx: 3,
+                },
+                amount: 10,
+                output: 1,
+            }],
+            etching: None,
+            pointer: Some(2),
+        };
+
+        assert_eq!(runestone.payload(), [22, 2, 0, 0xc0, 0xa2, 0x33, 3, 10, 1]);
+    }
+
+    #[test]
+    fn test_payload_delta_encodes_sorted_edicts() {
+        let edict = |block, tx, output| Edict {
+            id: BtcRuneId { block, tx },
+            amount: 5,
+            output,
+        };
+        let runestone = Runestone {
+            edicts: vec![edict(3, 2, 0), edict(1, 4, 1), edict(3, 7, 2)],
+            ..Runestone::default()
+        };
+
+        assert_eq!(runestone.payload(), [0, 1, 4, 5, 1, 2, 2, 5, 0, 0, 5, 5, 2]);
+    }
+
+    #[test]
+    fn test_payload_encodes_etching() {
+        let runestone = Runestone {
+            edicts: vec![],
+            etching: Some(Etching {
+                divisibility: Some(2),
+                symbol: Some('$'),
+                premine: Some(1_000),
+            }),
+            pointer: Some(0),
+        };
+
+        assert_eq!(
+            runestone.payload(),
+            [2, 1, 1, 2, 5, 36, 6, 0xe8, 0x07, 22, 0]
+        );
+    }
+
+    #[test]
+    fn test_script_pubkey_is_op_return_op_13() {
+        let script = Runestone {
+            pointer: Some(1),
+            ..Runestone::default()
+        }
+        .script_pubkey()
+        .expect("Expected a valid runestone");
+
+        let instructions = script
+            .instructions()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0], Instruction::Op(OP_RETURN));
+        assert_eq!(instructions[1], Instruction::Op(OP_PUSHNUM_13));
+        assert_eq!(instructions[2].push_bytes().unwrap().as_bytes(), [22, 1]);
+        assert!(script.is_op_return());
+    }
+
+    #[test]
+    fn test_script_pubkey_rejects_large_payload() {
+        let runestone = Runestone {
+            edicts: (0..10)
+                .map(|output| Edict {
+                    id: BtcRuneId {
+                        block: 840_000 + u64::from(output),
+                        tx: 1,
+                    },
+                    amount: u128::MAX,
+                    output,
+                })
+                .collect(),
+            ..Runestone::default()
+        };
+
+        assert_eq!(
+            runestone.script_pubkey(),
+            Err(BuildP2wpkhTxError::OpReturnDataTooLarge {
+                data_bytes: runestone.payload().len() as u64,
+                max_data_bytes: MAX_RUNESTONE_PAYLOAD_BYTES as u64,
+            })
+        );
+    }
+
+    #[test]
+    fn test_runestone_sends_leftover_runes_after_transfers() {
+        let runestone = runestone(&request(
+            vec![transfer(840_000, 3, 10), transfer(840_000, 3, 0)],
+            None,
+        ))
+        .expect("Expected a valid runestone");
+
+        assert_eq!(runestone.pointer, Some(2));
+        assert_eq!(
+            runestone
+                .edicts
+                .iter()
+                .map(|edict| (edict.amount, edict.output))
+                .collect::<Vec<_>>(),
+            [(10, 0), (0, 1)]
+        );
+        assert_eq!(runestone.etching, None);
+    }
+
+    #[test]
+    fn test_runestone_rejects_invalid_requests() {
+        assert_eq!(
+            runestone(&request(vec![], None)).err(),
+            Some("a runestone needs a transfer or an etching".to_string())
+        );
+        assert_eq!(
+            runestone(&request(vec![transfer(0, 0, 10)], None)).err(),
+            Some("transfer 0: rune 0:0 can only be sent with an etching".to_string())
+        );
+        assert_eq!(
+            runestone(&request(vec![transfer(1, 1, 10), transfer(0, 2, 10)], None)).err(),
+            Some("transfer 1: invalid rune id 0:2".to_string())
+        );
+        let mut without_rune_utxos = request(vec![transfer(1, 1, 10)], None);
+        without_rune_utxos.rune_utxos.clear();
+        assert_eq!(
+            runestone(&without_rune_utxos).err(),
+            Some("transfer 0: rune 1:1 needs rune_utxos".to_string())
+        );
+        let etching = |divisibility, symbol: &str| BtcRuneEtching {
+            divisibility: Some(divisibility),
+            symbol: Some(symbol.to_string()),
+            premine: None,
+        };
+        assert_eq!(
+            runestone(&request(vec![], Some(etching(39, "$")))).err(),
+            Some("divisibility must be at most 38".to_string())
+        );
+        assert_eq!(
+            runestone(&request(vec![], Some(etching(2, "ab")))).err(),
+            Some("symbol must be a single character: \"ab\"".to_string())
+        );
+        assert!(runestone(&request(vec![transfer(0, 0, 10)], Some(etching(2, "€")))).is_ok());
+    }
+}
@@ -0,0 +1,259 @@
+//! Taproot outputs committing to a tree of tapscript leaves, and script-path spends of a leaf that
+//! the caller's Schnorr key signs.
+use bitcoin::{
+    script::Instruction,
+    secp256k1::Secp256k1,
+    taproot::{ControlBlock, LeafVersion, NodeInfo, TaprootBuilder, TaprootSpendInfo},
+    Address, ScriptBuf, Witness, XOnlyPublicKey,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcTaprootTree};
+
+use crate::sign::bitcoin::bitcoin_utils::transform_network;
+
+/// The script tree of a taproot output, before the caller's own key is known.
+#[derive(Debug)]
+pub struct TapTreeTerms {
+    /// The internal key, or `None` for the caller's key.
+    pub internal_key: Option<XOnlyPublicKey>,
+    /// The leaf scripts, in the order of the request.
+    pub leaves: Vec<ScriptBuf>,
+    node_info: NodeInfo,
+}
+
+/// A taproot output of a script tree.
+pub struct TaprootTree {
+    pub spend_info: TaprootSpendInfo,
+    pub leaves: Vec<ScriptBuf>,
+}
+
+impl TaprootTree {
+    pub fn address(&self, network: BitcoinNetwork) -> Address {
+        Address::p2tr_tweaked(self.spend_info.output_key(), transform_network(network))
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2tr_tweaked(self.spend_info.output_key())
+    }
+
+    /// The control block that proves that `leaf` is in the tree.
+    pub fn control_block(&self, leaf: &ScriptBuf) -> ControlBlock {
+        self.spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .expect("Every leaf of the tree has a control block")
+    }
+}
+
+/// A script-path spend of one leaf.
+pub struct TapLeafSpend {
+    pub script: ScriptBuf,
+    pub control_block: ControlBlock,
+}
+
+impl TapLeafSpend {
+    /// The leaf at `leaf_index` of `tree`, if there is one.
+    pub fn new(tree: &TaprootTree, leaf_index: usize) -> Option<Self> {
+        let script = tree.leaves.get(leaf_index)?.clone();
+        let control_block = tree.control_block(&script);
+        Some(TapLeafSpend {
+            script,
+            control_block,
+        })
+    }
+
+    /// The witness of an input that spends the leaf with a single signature.
+    pub fn witness(&self, signature: &[u8]) -> Witness {
+        let mut witness = Witness::new();
+        witness.push(signature);
+        witness.push(self.script.as_bytes());
+        witness.push(self.control_block.serialize());
+        witness
+    }
+}
+
+/// Parses the internal key and the leaves of a script tree, and checks that the leaves form a
+/// complete binary tree.
+pub fn parse_taproot_tree(tree: &BtcTaprootTree) -> Result<TapTreeTerms, String> {
+    let internal_key = tree
+        .internal_key
+        .as_ref()
+        .map(|internal_key| {
+            hex::decode(internal_key)
+                .ok()
+                .and_then(|bytes| XOnlyPublicKey::from_slice(&bytes).ok())
+                .ok_or_else(|| format!("invalid internal_key: {internal_key}"))
+        })
+        .transpose()?;
+    if tree.leaves.is_empty() {
+        return Err("a script tree needs at least one leaf".to_string());
+    }
+    let mut builder = TaprootBuilder::new();
+    let mut leaves = Vec::with_capacity(tree.leaves.len());
+    for (index, leaf) in tree.leaves.iter().enumerate() {
+        let script = hex::decode(&leaf.script_hex)
+            .map(ScriptBuf::from_bytes)
+            .map_err(|e| format!("leaf {index}: invalid script_hex: {e}"))?;
+        builder = builder
+            .add_leaf(leaf.depth, script.clone())
+            .map_err(|e| format!("leaf {index}: {e}"))?;
+        leaves.push(script);
+    }
+    let node_info = builder
+        .try_into_node_info()
+        .map_err(|_| "the leaves do not form a complete binary tree".to_string())?;
+    Ok(TapTreeTerms {
+        internal_key,
+        leaves,
+        node_info,
+    })
+}
+
+/// The script tree of a single leaf, committed to the caller's key.
+pub fn single_leaf_terms(leaf: ScriptBuf) -> TapTreeTerms {
+    let node_info = NodeInfo::new_leaf_with_ver(leaf.clone(), LeafVersion::TapScript);
+    TapTreeTerms {
+        internal_key: None,
+        leaves: vec![leaf],
+        node_info,
+    }
+}
+
+/// Commits the internal key, or `caller_key` if there is none, to the script tree.
+///
+/// If the internal key is the caller's key, the output can also be spent on the key path with the
+/// caller's key tweaked by the Merkle root.
+pub fn taproot_tree(terms: TapTreeTerms, caller_key: XOnlyPublicKey) -> TaprootTree {
+    let internal_key = terms.internal_key.unwrap_or(caller_key);
+    let spend_info = TaprootSpendInfo::from_node_info(
+        &Secp256k1::verification_only(),
+        internal_key,
+        terms.node_info,
+    );
+    TaprootTree {
+        spend_info,
+        leaves: terms.leaves,
+    }
+}
+
+/// Whether `script` pushes `key`, i.e. whether a signature of `key` can help satisfy it.
+pub fn pushes_x_only_key(script: &ScriptBuf, key: &XOnlyPublicKey) -> bool {
+    let key = key.serialize();
+    script.instructions().any(|instruction| {
+        matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == key)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::OP_CHECKSIG,
+        script::Builder,
+        secp256k1::Secp256k1,
+        taproot::{LeafVersion, TapLeafHash},
+        AddressType, ScriptBuf,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcTapLeaf, BtcTaprootTree};
+
+    use super::{parse_taproot_tree, pushes_x_only_key, taproot_tree, TapLeafSpend};
+    use crate::sign::bitcoin::test_utils::x_only_key;
+
+    fn checksig_leaf(seed: u8) -> ScriptBuf {
+        Builder::new()
+            .push_x_only_key(&x_only_key(seed))
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    fn tree(internal_key: Option<u8>, leaves: &[(u8, u8)]) -> BtcTaprootTree {
+        BtcTaprootTree {
+            internal_key: internal_key.map(|seed| hex::encode(x_only_key(seed).serialize())),
+            leaves: leaves
+                .iter()
+                .map(|(seed, depth)| BtcTapLeaf {
+                    script_hex: hex::encode(checksig_leaf(*seed).as_bytes()),
+                    depth: *depth,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_taproot_tree_commits_to_every_leaf() {
+        let terms = parse_taproot_tree(&tree(Some(9), &[(1, 1), (2, 2), (3, 2)]))
+            .expect("Expected a valid tree");
+        let tree = taproot_tree(terms, x_only_key(1));
+
+        assert_eq!(tree.spend_info.internal_key(), x_only_key(9));
+        let address = tree.address(BitcoinNetwork::Mainnet);
+        assert_eq!(address.address_type(), Some(AddressType::P2tr));
+        assert_eq!(address.script_pubkey(), tree.script_pubkey());
+        for (index, leaf) in tree.leaves.iter().enumerate() {
+            let spend = TapLeafSpend::new(&tree, index).expect("Expected a leaf");
+            assert!(
+                spend.control_block.verify_taproot_commitment(
+                    &Secp256k1::verification_only(),
+                    tree.spend_info.output_key().to_x_only_public_key(),
+                    leaf
+                ),
+                "leaf {index} should be committed to"
+            );
+            assert_eq!(
+                TapLeafHash::from_script(&spend.script, LeafVersion::TapScript),
+                TapLeafHash::from_script(leaf, LeafVersion::TapScript)
+            );
+        }
+        assert!(TapLeafSpend::new(&tree, 3).is_none());
+    }
+
+    #[test]
+    fn test_taproot_tree_defaults_to_caller_internal_key() {
+        let terms = parse_taproot_tree(&tree(None, &[(1, 0)])).expect("Expected a valid tree");
+        let tree = taproot_tree(terms, x_only_key(1));
+
+        assert_eq!(tree.spend_info.internal_key(), x_only_key(1));
+    }
+
+    #[test]
+    fn test_taproot_spend_witness() {
+        let terms = parse_taproot_tree(&tree(Some(9), &[(1, 1), (2, 1)])).unwrap();
+        let tree = taproot_tree(terms, x_only_key(1));
+        let spend = TapLeafSpend::new(&tree, 1).unwrap();
+
+        let witness = spend.witness(&[7; 64]);
+        let elements = witness.iter().collect::<Vec<_>>();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0], [7; 64]);
+        assert_eq!(elements[1], checksig_leaf(2).as_bytes());
+        assert_eq!(elements[2], spend.control_block.serialize());
+    }
+
+    #[test]
+    fn test_parse_taproot_tree_rejects_invalid_trees() {
+        assert_eq!(
+            parse_taproot_tree(&tree(Some(9), &[])).err(),
+            Some("a script tree needs at least one leaf".to_string())
+        );
+        assert_eq!(
+            parse_taproot_tree(&tree(Some(9), &[(1, 1), (2, 2)])).err(),
+            Some("the leaves do not form a complete binary tree".to_string())
+        );
+        let invalid_key = BtcTaprootTree {
+            internal_key: Some("deadbeef".to_string()),
+            ..tree(None, &[(1, 0)])
+        };
+        assert_eq!(
+            parse_taproot_tree(&invalid_key).err(),
+            Some("invalid internal_key: deadbeef".to_string())
+        );
+        let mut invalid_script = tree(None, &[(1, 0)]);
+        invalid_script.leaves[0].script_hex = "xy".to_string();
+        assert!(parse_taproot_tree(&invalid_script)
+            .err()
+            .is_some_and(|msg| msg.starts_with("leaf 0: invalid script_hex")));
+    }
+
+    #[test]
+    fn test_pushes_x_only_key() {
+        assert!(pushes_x_only_key(&checksig_leaf(1), &x_only_key(1)));
+        assert!(!pushes_x_only_key(&checksig_leaf(1), &x_only_key(2)));
+    }
+}
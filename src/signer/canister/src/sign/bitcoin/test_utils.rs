@@ -0,0 +1,29 @@
+//! Keys shared by the unit tests of the Bitcoin modules.
+use std::str::FromStr;
+
+use bitcoin::{
+    secp256k1::{Secp256k1, SecretKey},
+    CompressedPublicKey, XOnlyPublicKey,
+};
+
+/// The public key of the private key 1, that is, the generator point.
+pub fn generator_public_key() -> CompressedPublicKey {
+    CompressedPublicKey::from_str(
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    )
+    .unwrap()
+}
+
+/// The public key of the private key whose 32 bytes are all `seed`.
+pub fn public_key(seed: u8) -> CompressedPublicKey {
+    CompressedPublicKey(secret_key(seed).public_key(&Secp256k1::new()))
+}
+
+/// The x-only public key of the private key whose 32 bytes are all `seed`.
+pub fn x_only_key(seed: u8) -> XOnlyPublicKey {
+    secret_key(seed).x_only_public_key(&Secp256k1::new()).0
+}
+
+fn secret_key(seed: u8) -> SecretKey {
+    SecretKey::from_slice(&[seed; 32]).expect("Invalid secret key")
+}
@@ -1,25 +1,53 @@
 use std::str::FromStr;
 
 use bitcoin::{
-    absolute::LockTime, consensus::serialize, hashes::Hash, script::PushBytesBuf,
-    sighash::SighashCache, transaction::Version, Address, AddressType, Amount, EcdsaSighashType,
-    OutPoint as BitcoinOutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    absolute::LockTime,
+    consensus::serialize,
+    hashes::Hash,
+    psbt::Psbt,
+    script::{Builder, PushBytesBuf},
+    secp256k1::schnorr,
+    sighash::{Prevouts, SighashCache},
+    taproot::{LeafVersion, TapLeafHash},
+    transaction::Version,
+    Address, AddressType, Amount, CompressedPublicKey, EcdsaSighashType,
+    OutPoint as BitcoinOutPoint, ScriptBuf, Sequence, TapSighashType, Transaction, TxIn, TxOut,
+    Txid, Witness,
 };
 use candid::Principal;
-use ic_cdk_bitcoin_canister::{Network, OutPoint as IcCdkOutPoint, Utxo};
-use ic_chain_fusion_signer_api::types::bitcoin::{BtcTxOutput, BuildP2wpkhTxError};
+use ic_cdk_bitcoin_canister::{OutPoint as IcCdkOutPoint, Utxo};
+use ic_chain_fusion_signer_api::types::bitcoin::{
+    BitcoinNetwork, BtcRuneTransfer, BtcSighashType, BtcTxOutput, BuildP2wpkhTxError,
+    SendBtcResponse, SignBtcResponse,
+};
 
 use crate::{
     derivation_path::Schema,
     sign::{
-        bitcoin::bitcoin_utils::transform_network,
-        ecdsa_api::{ecdsa_pubkey_of, get_ecdsa_signature},
+        bitcoin::{
+            bitcoin_utils::{transform_network, transform_sighash_type},
+            chain::{AddressKind, ChainParams},
+            fee_utils::{fee_for_vsize, Fee},
+            htlc::{Htlc, HtlcSpend},
+            taproot::{TapLeafSpend, TaprootTree},
+            vault::Vault,
+        },
+        ecdsa_api::{ecdsa_pubkey_of, get_ecdsa_signatures},
+        schnorr_api::get_bip340_signatures,
     },
 };
 
 const ECDSA_SIG_HASH_TYPE: EcdsaSighashType = EcdsaSighashType::All;
 // Assume that any amount below this threshold is dust.
-const DUST_THRESHOLD: u64 = 1_000;
+pub const DUST_THRESHOLD: u64 = 1_000;
+/// The largest strict-DER ECDSA signature, plus the sighash type byte.
+const MAX_SIGNATURE_WITH_HASHTYPE_BYTES: usize = 73;
+/// The size of a BIP-340 signature with the default sighash type.
+const SCHNORR_SIGNATURE_BYTES: usize = 64;
+/// The size of a compressed public key.
+const COMPRESSED_PUBLIC_KEY_BYTES: usize = 33;
+/// The largest `OP_RETURN` data that nodes relay by default.
+pub const MAX_OP_RETURN_DATA_BYTES: usize = 80;
 
 /// Converts a 64-byte SEC1 compact ECDSA signature (`r || s`) into strict
 /// Bitcoin DER as required by BIP-66.
@@ -60,9 +88,48 @@ fn calculate_remaining_amount(
     }
 }
 
+/// Checks that `OP_RETURN` data fits in an output that nodes relay by default.
+pub fn check_op_return_data(data: &[u8]) -> Result<(), BuildP2wpkhTxError> {
+    if data.len() > MAX_OP_RETURN_DATA_BYTES {
+        return Err(BuildP2wpkhTxError::OpReturnDataTooLarge {
+            data_bytes: data.len() as u64,
+            max_data_bytes: MAX_OP_RETURN_DATA_BYTES as u64,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the data of every `Data` output, see [`check_op_return_data`].
+pub fn check_data_outputs(outputs: &[BtcTxOutput]) -> Result<(), BuildP2wpkhTxError> {
+    outputs
+        .iter()
+        .filter_map(BtcTxOutput::data)
+        .try_for_each(check_op_return_data)
+}
+
+/// The value that `outputs` send, or `None` if they only carry data, so that there is no sent
+/// value to compare a fee with.
+pub fn sent_satoshis(outputs: &[BtcTxOutput]) -> Option<u64> {
+    outputs
+        .iter()
+        .any(|output| matches!(output, BtcTxOutput::Payment { .. }))
+        .then(|| outputs.iter().map(BtcTxOutput::sent_satoshis).sum())
+}
+
+/// Builds an output that carries `data` in an `OP_RETURN` script and no value.
+fn op_return_output(data: &[u8]) -> Result<TxOut, BuildP2wpkhTxError> {
+    check_op_return_data(data)?;
+    let data = PushBytesBuf::try_from(data.to_vec())
+        .expect("Data within the OP_RETURN limit is a valid push");
+    Ok(TxOut {
+        script_pubkey: ScriptBuf::new_op_return(data),
+        value: Amount::ZERO,
+    })
+}
+
 pub fn build_p2wpkh_transaction(
     source_address: &str,
-    network: Network,
+    network: BitcoinNetwork,
     utxos_to_spend: &[Utxo],
     fee: u64,
     request_outputs: &[BtcTxOutput],
@@ -78,37 +145,67 @@ pub fn build_p2wpkh_transaction(
         return Err(BuildP2wpkhTxError::NotP2WPKHSourceAddress);
     }
 
-    let inputs: Vec<TxIn> = utxos_to_spend
+    build_transaction(
+        &own_address.script_pubkey(),
+        &ChainParams::bitcoin(network),
+        utxos_to_spend,
+        fee,
+        request_outputs,
+    )
+}
+
+/// Unsigned inputs that spend `utxos_to_spend` and signal replace-by-fee.
+fn transaction_inputs(utxos_to_spend: &[Utxo]) -> Vec<TxIn> {
+    utxos_to_spend
         .iter()
         .map(|utxo| TxIn {
             previous_output: BitcoinOutPoint {
                 txid: Txid::from_raw_hash(Hash::from_slice(utxo.outpoint.txid.as_ref()).unwrap()),
                 vout: utxo.outpoint.vout,
             },
-            sequence: Sequence(0xFFFF_FFFF),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
             witness: Witness::new(),
             script_sig: ScriptBuf::new(),
         })
-        .collect();
+        .collect()
+}
+
+/// Builds a transaction on `chain` that spends all of `utxos_to_spend` to the requested outputs, in
+/// order, and returns any change above the dust threshold of the chain to `change_script_pubkey`.
+fn build_transaction(
+    change_script_pubkey: &ScriptBuf,
+    chain: &ChainParams,
+    utxos_to_spend: &[Utxo],
+    fee: u64,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let inputs = transaction_inputs(utxos_to_spend);
 
     let utxos_amount: u64 = utxos_to_spend.iter().map(|u| u.value).sum();
 
     let outputs_result: Result<Vec<TxOut>, BuildP2wpkhTxError> = request_outputs
         .iter()
         .map(|output| {
-            let address = Address::from_str(&output.destination_address).map_err(|_| {
-                BuildP2wpkhTxError::InvalidDestinationAddress {
-                    address: output.destination_address.clone(),
-                }
-            })?; // Convert from ParseError to BuildP2wpkhError
-
-            let address = address
-                .require_network(transform_network(network))
-                .map_err(|_| BuildP2wpkhTxError::WrongBitcoinNetwork)?; // Convert from ParseError to BuildP2wpkhError
+            let (destination_address, sent_satoshis) = match output {
+                BtcTxOutput::Payment {
+                    destination_address,
+                    sent_satoshis,
+                } => (destination_address, *sent_satoshis),
+                BtcTxOutput::Data { data } => return op_return_output(data),
+            };
+            let script_pubkey = chain.script_pubkey(destination_address)?;
+
+            if sent_satoshis < chain.dust_threshold {
+                return Err(BuildP2wpkhTxError::DustOutput {
+                    address: destination_address.clone(),
+                    sent_satoshis,
+                    dust_threshold: chain.dust_threshold,
+                });
+            }
 
             Ok(TxOut {
-                script_pubkey: address.script_pubkey(),
-                value: Amount::from_sat(output.sent_satoshis),
+                script_pubkey,
+                value: Amount::from_sat(sent_satoshis),
             })
         })
         .collect();
@@ -118,9 +215,9 @@ pub fn build_p2wpkh_transaction(
             let sent_amount: u64 = outputs.iter().map(|u| u.value.to_sat()).sum();
             let remaining_amount = calculate_remaining_amount(utxos_amount, sent_amount, fee)?;
 
-            if remaining_amount >= DUST_THRESHOLD {
+            if remaining_amount >= chain.dust_threshold {
                 outputs.push(TxOut {
-                    script_pubkey: own_address.script_pubkey(),
+                    script_pubkey: change_script_pubkey.clone(),
                     value: Amount::from_sat(remaining_amount),
                 });
             }
@@ -136,12 +233,499 @@ pub fn build_p2wpkh_transaction(
     }
 }
 
+/// Computes the size, in virtual bytes, that a P2WPKH transaction will have once signed.
+///
+/// Every input is given a witness with the largest possible signature, so the estimate is at most
+/// one virtual byte per input larger than the signed transaction.
+pub fn estimate_signed_vsize(transaction: &Transaction) -> u64 {
+    vsize_with_witness(transaction, &max_p2wpkh_witness())
+}
+
+/// A P2WPKH witness with the largest possible signature, the size of a signed P2WPKH input's.
+pub fn max_p2wpkh_witness() -> Witness {
+    let mut witness = Witness::new();
+    witness.push([0u8; MAX_SIGNATURE_WITH_HASHTYPE_BYTES]);
+    witness.push([0u8; COMPRESSED_PUBLIC_KEY_BYTES]);
+    witness
+}
+
+/// Computes the size, in virtual bytes, that a P2PKH transaction will have once signed.
+///
+/// Every input is given a `scriptSig` with the largest possible signature, so the estimate is never
+/// smaller than the signed transaction.
+pub fn estimate_signed_p2pkh_vsize(transaction: &Transaction) -> u64 {
+    let script_sig = Builder::new()
+        .push_slice([0u8; MAX_SIGNATURE_WITH_HASHTYPE_BYTES])
+        .push_slice([0u8; COMPRESSED_PUBLIC_KEY_BYTES])
+        .into_script();
+    let mut transaction = transaction.clone();
+    for input in &mut transaction.input {
+        input.script_sig = script_sig.clone();
+    }
+    transaction.vsize() as u64
+}
+
+/// The size, in virtual bytes, of a transaction with the given witness on every input.
+fn vsize_with_witness(transaction: &Transaction, witness: &Witness) -> u64 {
+    let mut transaction = transaction.clone();
+    for input in &mut transaction.input {
+        input.witness = witness.clone();
+    }
+    transaction.vsize() as u64
+}
+
+/// Builds a transaction with `build`, paying the given fee.
+///
+/// For a fee rate, the transaction is first built without a fee, then rebuilt with the fee for
+/// `estimate_signed_vsize` of the last build until that fee covers it.  The size comes from the
+/// actual output scripts, including any `OP_RETURN` data, and the witnesses the inputs will have.
+/// A larger fee can only remove the change output, which shrinks the transaction, so once the fee
+/// consumes the change the transaction pays slightly more than the requested rate.
+fn build_with_fee(
+    fee: Fee,
+    build: impl Fn(u64) -> Result<Transaction, BuildP2wpkhTxError>,
+    estimate_signed_vsize: impl Fn(&Transaction) -> u64,
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let fee_rate = match fee {
+        Fee::Absolute(fee) => return build(fee),
+        Fee::Rate(fee_rate) => fee_rate,
+    };
+    let mut fee = 0;
+    loop {
+        let transaction = build(fee)?;
+        let required_fee = fee_for_vsize(estimate_signed_vsize(&transaction), fee_rate);
+        if fee >= required_fee {
+            return Ok(transaction);
+        }
+        fee = required_fee;
+    }
+}
+
+/// Builds a P2WPKH transaction paying the given fee, see [`build_with_fee`].
+pub fn build_p2wpkh_transaction_with_fee(
+    source_address: &str,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    build_with_fee(
+        fee,
+        |fee| {
+            build_p2wpkh_transaction(
+                source_address,
+                network,
+                utxos_to_spend,
+                fee,
+                request_outputs,
+            )
+        },
+        estimate_signed_vsize,
+    )
+}
+
+/// Builds a P2WPKH transaction that sends the value of all of `utxos_to_spend`, less the given
+/// fee, to the only `Payment` output of `request_outputs`, without a change output.
+///
+/// For a fee rate, the size of the transaction does not depend on the value of its output, so the
+/// fee for the size of the transaction built without a fee is final.
+pub fn build_p2wpkh_sweep_transaction_with_fee(
+    source_address: &str,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let utxos_amount: u64 = utxos_to_spend.iter().map(|utxo| utxo.value).sum();
+    let sweep = |fee: u64| {
+        let sent_satoshis =
+            utxos_amount
+                .checked_sub(fee)
+                .ok_or(BuildP2wpkhTxError::NotEnoughFunds {
+                    required: fee,
+                    available: utxos_amount,
+                })?;
+        let outputs = request_outputs
+            .iter()
+            .map(|output| match output {
+                BtcTxOutput::Payment {
+                    destination_address,
+                    ..
+                } => BtcTxOutput::Payment {
+                    destination_address: destination_address.clone(),
+                    sent_satoshis,
+                },
+                BtcTxOutput::Data { .. } => output.clone(),
+            })
+            .collect::<Vec<_>>();
+        build_p2wpkh_transaction(source_address, network, utxos_to_spend, fee, &outputs)
+    };
+    build_with_fee(fee, sweep, estimate_signed_vsize)
+}
+
+/// Builds a P2WPKH transaction that sends runes with the given runestone, paying the given fee.
+///
+/// Every transfer gets an output of `postage_satoshis` to its destination, in order, followed by
+/// an output of `postage_satoshis` to `source_address` that receives the runes left over, the
+/// runestone, and the change.
+pub fn build_runes_transaction_with_fee(
+    source_address: &str,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    transfers: &[BtcRuneTransfer],
+    postage_satoshis: u64,
+    runestone: &ScriptBuf,
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let mut request_outputs = transfers
+        .iter()
+        .map(|transfer| BtcTxOutput::Payment {
+            destination_address: transfer.destination_address.clone(),
+            sent_satoshis: postage_satoshis,
+        })
+        .collect::<Vec<_>>();
+    request_outputs.push(BtcTxOutput::Payment {
+        destination_address: source_address.to_string(),
+        sent_satoshis: postage_satoshis,
+    });
+    let build = |fee| {
+        let mut transaction = build_p2wpkh_transaction(
+            source_address,
+            network,
+            utxos_to_spend,
+            fee,
+            &request_outputs,
+        )?;
+        transaction.output.insert(
+            request_outputs.len(),
+            TxOut {
+                script_pubkey: runestone.clone(),
+                value: Amount::ZERO,
+            },
+        );
+        Ok(transaction)
+    };
+    build_with_fee(fee, build, estimate_signed_vsize)
+}
+
+/// Computes the size, in virtual bytes, that a vault transaction will have once the signer and the
+/// cosigner have signed every input.
+pub fn estimate_signed_vault_vsize(transaction: &Transaction, vault: &Vault) -> u64 {
+    let mut witness = Witness::new();
+    // The dummy element that `OP_CHECKMULTISIG` pops.
+    witness.push([]);
+    witness.push([0u8; MAX_SIGNATURE_WITH_HASHTYPE_BYTES]);
+    witness.push([0u8; MAX_SIGNATURE_WITH_HASHTYPE_BYTES]);
+    witness.push(vault.witness_script.as_bytes());
+    vsize_with_witness(transaction, &witness)
+}
+
+/// Builds a transaction that returns any change to `change_script_pubkey`, paying the given fee.
+///
+/// For a fee rate, `estimate_signed_vsize` gives the size of the transaction once signed, see
+/// [`build_with_fee`].
+fn build_transaction_with_fee(
+    change_script_pubkey: &ScriptBuf,
+    chain: &ChainParams,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+    estimate_signed_vsize: impl Fn(&Transaction) -> u64,
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    build_with_fee(
+        fee,
+        |fee| {
+            build_transaction(
+                change_script_pubkey,
+                chain,
+                utxos_to_spend,
+                fee,
+                request_outputs,
+            )
+        },
+        estimate_signed_vsize,
+    )
+}
+
+/// Builds a transaction on `chain` that spends the caller's outputs, paying the given fee and
+/// returning any change to `own_script_pubkey`.
+///
+/// For a fee rate, the size of the signed transaction is estimated for the output type of the
+/// chain, see [`ChainParams::estimate_signed_vsize`].
+pub fn build_chain_transaction_with_fee(
+    chain: &ChainParams,
+    own_script_pubkey: &ScriptBuf,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    build_transaction_with_fee(
+        own_script_pubkey,
+        chain,
+        utxos_to_spend,
+        fee,
+        request_outputs,
+        |transaction| chain.estimate_signed_vsize(transaction),
+    )
+}
+
+/// Builds a transaction that spends vault UTXOs on the signer's branch, paying the given fee and
+/// returning any change to the vault.
+///
+/// The signer's branch has no timelock, so the inputs keep `Sequence::ENABLE_RBF_NO_LOCKTIME` and
+/// the lock time is zero: the transaction is valid as soon as it is fully signed, and signals
+/// replace-by-fee.  Only a spend on the recovery branch needs an `nSequence` of at least the
+/// recovery delay.
+pub fn build_vault_transaction_with_fee(
+    vault: &Vault,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    build_transaction_with_fee(
+        &vault.script_pubkey(),
+        &ChainParams::bitcoin(network),
+        utxos_to_spend,
+        fee,
+        request_outputs,
+        |transaction| estimate_signed_vault_vsize(transaction, vault),
+    )
+}
+
+/// Wraps a vault transaction in a PSBT, with the UTXO and the witness script of every input, so
+/// that the signer and the cosigner can add their signatures.
+pub fn vault_psbt(transaction: Transaction, utxos: &[Utxo], vault: &Vault) -> Psbt {
+    let mut psbt =
+        Psbt::from_unsigned_tx(transaction).expect("A transaction without witnesses is unsigned");
+    for (input, psbt_input) in psbt.unsigned_tx.input.iter().zip(&mut psbt.inputs) {
+        psbt_input.witness_utxo = get_input_value(input, utxos).map(|value| TxOut {
+            value,
+            script_pubkey: vault.script_pubkey(),
+        });
+        psbt_input.witness_script = Some(vault.witness_script.clone());
+    }
+    psbt
+}
+
+/// Builds a transaction that sweeps all of `utxos_to_spend` to `destination_address`, paying the
+/// fee out of the swept value.
+///
+/// The inputs keep `Sequence::ENABLE_RBF_NO_LOCKTIME`, which is not final, so that a non-zero
+/// `lock_time` is enforced.
+fn build_sweep_transaction(
+    destination_address: &str,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: u64,
+    lock_time: LockTime,
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let address = Address::from_str(destination_address)
+        .map_err(|_| BuildP2wpkhTxError::InvalidDestinationAddress {
+            address: destination_address.to_string(),
+        })?
+        .require_network(transform_network(network))
+        .map_err(|_| BuildP2wpkhTxError::WrongBitcoinNetwork)?;
+
+    let utxos_amount: u64 = utxos_to_spend.iter().map(|u| u.value).sum();
+    let sent_amount = calculate_remaining_amount(utxos_amount, 0, fee)?;
+    if sent_amount < DUST_THRESHOLD {
+        return Err(BuildP2wpkhTxError::DustOutput {
+            address: destination_address.to_string(),
+            sent_satoshis: sent_amount,
+            dust_threshold: DUST_THRESHOLD,
+        });
+    }
+
+    Ok(Transaction {
+        input: transaction_inputs(utxos_to_spend),
+        output: vec![TxOut {
+            script_pubkey: address.script_pubkey(),
+            value: Amount::from_sat(sent_amount),
+        }],
+        lock_time,
+        version: Version::TWO,
+    })
+}
+
+/// Computes the size, in virtual bytes, that an HTLC transaction will have once every input is
+/// signed.
+pub fn estimate_signed_htlc_vsize(
+    transaction: &Transaction,
+    htlc: &Htlc,
+    spend: &HtlcSpend<'_>,
+) -> u64 {
+    vsize_with_witness(
+        transaction,
+        &spend.witness(htlc, &[0u8; MAX_SIGNATURE_WITH_HASHTYPE_BYTES]),
+    )
+}
+
+/// Builds a transaction that sweeps HTLC UTXOs to `destination_address` on the branch of `spend`,
+/// paying the given fee.
+pub fn build_htlc_transaction_with_fee(
+    htlc: &Htlc,
+    spend: &HtlcSpend<'_>,
+    destination_address: &str,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    let lock_time = spend.lock_time(htlc);
+    build_with_fee(
+        fee,
+        |fee| build_sweep_transaction(destination_address, network, utxos_to_spend, fee, lock_time),
+        |transaction| estimate_signed_htlc_vsize(transaction, htlc, spend),
+    )
+}
+
+/// Signs every input of an HTLC transaction on the branch of `spend`, with the sighash type `All`.
+///
+/// The sighashes of all inputs are computed first and signed concurrently.
+pub async fn sign_htlc_transaction(
+    principal: &Principal,
+    mut transaction: Transaction,
+    utxos: &[Utxo],
+    htlc: &Htlc,
+    spend: &HtlcSpend<'_>,
+) -> Result<SignedTransaction, String> {
+    let derivation_path = Schema::Btc.derivation_path(principal);
+    let mut cache = SighashCache::new(&transaction);
+    let sighashes = (0..transaction.input.len())
+        .map(|index| {
+            let value = get_input_value(&transaction.input[index], utxos)
+                .expect("input value not found in passed utxos");
+            cache
+                .p2wsh_signature_hash(index, &htlc.witness_script, value, ECDSA_SIG_HASH_TYPE)
+                .map(|sighash| sighash.as_byte_array().to_vec())
+                .map_err(|e| format!("input {index}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let signatures = get_ecdsa_signatures(derivation_path, sighashes).await?;
+    for (input, signature) in transaction.input.iter_mut().zip(signatures) {
+        let mut sig_with_hashtype = sec1_to_der(&signature);
+        sig_with_hashtype.push(
+            u8::try_from(ECDSA_SIG_HASH_TYPE.to_u32()).expect("Error converting the sighash type"),
+        );
+        input.witness = spend.witness(htlc, &sig_with_hashtype);
+    }
+
+    Ok(SignedTransaction::new(&transaction, utxos))
+}
+
+/// Computes the size, in virtual bytes, of a transaction that spends `leaf` on every input.
+///
+/// A BIP-340 signature with the default sighash type has a fixed size, so the estimate is exact.
+pub fn estimate_signed_taproot_vsize(transaction: &Transaction, leaf: &TapLeafSpend) -> u64 {
+    vsize_with_witness(transaction, &leaf.witness(&[0u8; SCHNORR_SIGNATURE_BYTES]))
+}
+
+/// Computes the size, in virtual bytes, of a transaction that spends a single output of `tree` on
+/// the script path of `leaf` to a single output.
+///
+/// The output is assumed to be a P2TR output, the largest standard output of an address, so the
+/// estimate is exact for a P2TR destination and larger for any other.
+pub fn estimate_signed_reveal_vsize(tree: &TaprootTree, leaf: &TapLeafSpend) -> u64 {
+    let transaction = Transaction {
+        input: vec![TxIn::default()],
+        output: vec![TxOut {
+            script_pubkey: tree.script_pubkey(),
+            value: Amount::ZERO,
+        }],
+        lock_time: LockTime::ZERO,
+        version: Version::TWO,
+    };
+    estimate_signed_taproot_vsize(&transaction, leaf)
+}
+
+/// Builds a transaction that spends taproot UTXOs on the script path of `leaf`, paying the given
+/// fee and returning any change to the taproot output.
+pub fn build_taproot_transaction_with_fee(
+    tree: &TaprootTree,
+    leaf: &TapLeafSpend,
+    network: BitcoinNetwork,
+    utxos_to_spend: &[Utxo],
+    fee: Fee,
+    request_outputs: &[BtcTxOutput],
+) -> Result<Transaction, BuildP2wpkhTxError> {
+    build_transaction_with_fee(
+        &tree.script_pubkey(),
+        &ChainParams::bitcoin(network),
+        utxos_to_spend,
+        fee,
+        request_outputs,
+        |transaction| estimate_signed_taproot_vsize(transaction, leaf),
+    )
+}
+
+/// Signs every input of a transaction that spends taproot UTXOs on the script path of `leaf`.
+///
+/// Each input is signed with the caller's untweaked Schnorr key over its
+/// [BIP-341](https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki) script-path sighash,
+/// with the default sighash type, so the signatures have no sighash type byte.  The sighashes of
+/// all inputs are computed first and signed concurrently.
+pub async fn sign_taproot_script_path_transaction(
+    principal: &Principal,
+    mut transaction: Transaction,
+    utxos: &[Utxo],
+    tree: &TaprootTree,
+    leaf: &TapLeafSpend,
+) -> Result<SignedTransaction, String> {
+    let derivation_path = Schema::Schnorr.derivation_path(principal);
+    let script_pubkey = tree.script_pubkey();
+    // BIP-341 sighashes commit to the amounts and scripts of all inputs.
+    let prevouts = transaction
+        .input
+        .iter()
+        .map(|input| {
+            let value =
+                get_input_value(input, utxos).expect("input value not found in passed utxos");
+            TxOut {
+                value,
+                script_pubkey: script_pubkey.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let leaf_hash = TapLeafHash::from_script(&leaf.script, LeafVersion::TapScript);
+    let mut cache = SighashCache::new(&transaction);
+    let sighashes = (0..prevouts.len())
+        .map(|index| {
+            cache
+                .taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    leaf_hash,
+                    TapSighashType::Default,
+                )
+                .map(|sighash| sighash.as_byte_array().to_vec())
+                .map_err(|e| format!("input {index}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let signatures = get_bip340_signatures(derivation_path, sighashes).await?;
+    for (input, signature) in transaction.input.iter_mut().zip(signatures) {
+        let signature = schnorr::Signature::from_slice(&signature)
+            .map_err(|e| format!("malformed signature: {e}"))?;
+        input.witness = leaf.witness(signature.as_ref());
+    }
+
+    Ok(SignedTransaction::new(&transaction, utxos))
+}
+
+/// Marks every input as final, so that the transaction does not signal BIP-125 replace-by-fee.
+pub fn disable_rbf(transaction: &mut Transaction) {
+    for input in &mut transaction.input {
+        input.sequence = Sequence::MAX;
+    }
+}
+
 fn is_same_outpoint(txin_outpoint: &BitcoinOutPoint, utxo_outpout: &IcCdkOutPoint) -> bool {
     txin_outpoint.vout == utxo_outpout.vout
         && txin_outpoint.txid.as_byte_array()[..] == utxo_outpout.txid.as_ref()[..]
 }
 
-fn get_input_value(input: &TxIn, outputs: &[Utxo]) -> Option<Amount> {
+pub fn get_input_value(input: &TxIn, outputs: &[Utxo]) -> Option<Amount> {
     // The `previous_output` field in `TxIn` contains the `OutPoint`, which includes
     // the TXID and the output vout that this input is spending from.
     outputs
@@ -150,64 +734,333 @@ fn get_input_value(input: &TxIn, outputs: &[Utxo]) -> Option<Amount> {
         .map(|output| Amount::from_sat(output.value))
 }
 
+/// The fee paid by a transaction: the value of its inputs minus the value of its outputs.
+pub fn transaction_fee(transaction: &Transaction, utxos: &[Utxo]) -> u64 {
+    let inputs_amount: u64 = transaction
+        .input
+        .iter()
+        .filter_map(|input| get_input_value(input, utxos))
+        .map(Amount::to_sat)
+        .sum();
+    let outputs_amount: u64 = transaction
+        .output
+        .iter()
+        .map(|output| output.value.to_sat())
+        .sum();
+    inputs_amount.saturating_sub(outputs_amount)
+}
+
+/// The size of the `OP_RETURN` outputs' scripts, in bytes, including the opcodes that frame the
+/// data.
+pub fn op_return_data_bytes(transaction: &Transaction) -> u64 {
+    transaction
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey.is_op_return())
+        .map(|output| output.script_pubkey.len() as u64)
+        .sum()
+}
+
+/// Checks that no input is to be signed with `None` or `NonePlusAnyoneCanPay`.
+///
+/// Those sighash types commit to no outputs, so anyone who sees the signed input could redirect
+/// its value.  The check does not need the transaction, so that it can run before charging.
+pub fn check_sighash_types(
+    sighash_types: Option<&[BtcSighashType]>,
+) -> Result<(), BuildP2wpkhTxError> {
+    let input_index = sighash_types
+        .unwrap_or_default()
+        .iter()
+        .position(|sighash_type| {
+            matches!(
+                sighash_type,
+                BtcSighashType::None | BtcSighashType::NonePlusAnyoneCanPay
+            )
+        });
+    match input_index {
+        Some(input_index) => Err(BuildP2wpkhTxError::SighashNoneNotAllowed {
+            input_index: input_index as u64,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The sighash type of each input of a transaction, as requested or `All` by default.
+///
+/// An input signed with `Single` commits to the output with the same index, so that output has to
+/// exist.  Without it, BIP-143 signs no outputs at all and anyone could redirect the funds.  For
+/// the same reason, `None` types are rejected, see [`check_sighash_types`].
+pub fn input_sighash_types(
+    transaction: &Transaction,
+    sighash_types: Option<&[BtcSighashType]>,
+) -> Result<Vec<EcdsaSighashType>, BuildP2wpkhTxError> {
+    check_sighash_types(sighash_types)?;
+    let Some(sighash_types) = sighash_types else {
+        return Ok(vec![ECDSA_SIG_HASH_TYPE; transaction.input.len()]);
+    };
+    if sighash_types.len() != transaction.input.len() {
+        return Err(BuildP2wpkhTxError::WrongNumberOfSighashTypes {
+            inputs: transaction.input.len() as u64,
+            sighash_types: sighash_types.len() as u64,
+        });
+    }
+    sighash_types
+        .iter()
+        .enumerate()
+        .map(|(input_index, sighash_type)| {
+            let sighash_type = transform_sighash_type(*sighash_type);
+            let is_single = matches!(
+                sighash_type,
+                EcdsaSighashType::Single | EcdsaSighashType::SinglePlusAnyoneCanPay
+            );
+            if is_single && input_index >= transaction.output.len() {
+                return Err(BuildP2wpkhTxError::SighashSingleWithoutOutput {
+                    input_index: input_index as u64,
+                });
+            }
+            Ok(sighash_type)
+        })
+        .collect()
+}
+
 pub struct SignedTransaction {
     pub signed_transaction_bytes: Vec<u8>,
     pub txid: String,
+    /// The fee paid by the transaction, in satoshi.
+    pub fee_satoshis: u64,
+    /// The size of the signed transaction, in virtual bytes.
+    pub vsize: u64,
+    /// The output that returns the change to the caller, if any.
+    pub change_output_index: Option<u32>,
+}
+
+impl SignedTransaction {
+    /// Describes a transaction whose inputs are all signed.  The transaction has no change output
+    /// unless one is set with [`SignedTransaction::with_change_output_index`].
+    pub fn new(transaction: &Transaction, utxos: &[Utxo]) -> Self {
+        SignedTransaction {
+            signed_transaction_bytes: serialize(transaction),
+            txid: transaction.compute_txid().to_string(),
+            fee_satoshis: transaction_fee(transaction, utxos),
+            vsize: transaction.vsize() as u64,
+            change_output_index: None,
+        }
+    }
+
+    /// Sets the output that returns the change to the caller, see [`change_output_index`].
+    #[must_use]
+    pub fn with_change_output_index(mut self, change_output_index: Option<u32>) -> Self {
+        self.change_output_index = change_output_index;
+        self
+    }
+
+    /// The effective fee rate of the transaction, in millisatoshi per virtual byte.
+    pub fn fee_rate_millisat_per_vbyte(&self) -> u64 {
+        self.fee_satoshis * 1000 / self.vsize.max(1)
+    }
+}
+
+impl From<SignedTransaction> for SignBtcResponse {
+    fn from(signed_transaction: SignedTransaction) -> Self {
+        SignBtcResponse {
+            signed_transaction_hex: hex::encode(&signed_transaction.signed_transaction_bytes),
+            fee_rate_millisat_per_vbyte: signed_transaction.fee_rate_millisat_per_vbyte(),
+            fee_satoshis: signed_transaction.fee_satoshis,
+            vsize: signed_transaction.vsize,
+            change_output_index: signed_transaction.change_output_index,
+            txid: signed_transaction.txid,
+        }
+    }
+}
+
+impl From<SignedTransaction> for SendBtcResponse {
+    fn from(signed_transaction: SignedTransaction) -> Self {
+        SendBtcResponse {
+            signed_transaction_hex: hex::encode(&signed_transaction.signed_transaction_bytes),
+            fee_rate_millisat_per_vbyte: signed_transaction.fee_rate_millisat_per_vbyte(),
+            fee_satoshis: signed_transaction.fee_satoshis,
+            vsize: signed_transaction.vsize,
+            change_output_index: signed_transaction.change_output_index,
+            txid: signed_transaction.txid,
+        }
+    }
+}
+
+/// The index of the change output of a transaction built by this module.
+///
+/// The builders put the change, if there is any, after the `n_request_outputs` outputs that the
+/// request determines, including any `OP_RETURN` output.
+pub fn change_output_index(transaction: &Transaction, n_request_outputs: usize) -> Option<u32> {
+    (transaction.output.len() > n_request_outputs).then(|| {
+        u32::try_from(transaction.output.len() - 1).expect("A transaction has few outputs")
+    })
 }
 
+/// Signs every input of a P2WPKH transaction with the sighash type given for it in
+/// `sighash_types`, see [`input_sighash_types`].
+///
+/// The sighashes of all inputs are computed first and signed concurrently, so the latency is
+/// roughly that of a single signature; the witnesses are assembled once all signatures are in.
 pub async fn btc_sign_transaction(
     principal: &Principal,
     mut transaction: Transaction,
     utxos: &[Utxo],
+    sighash_types: &[EcdsaSighashType],
     source_address: String,
-    network: Network,
+    network: BitcoinNetwork,
 ) -> Result<SignedTransaction, String> {
     let derivation_path = Schema::Btc.derivation_path(principal);
-    let txclone = transaction.clone();
     let user_public_key = ecdsa_pubkey_of(derivation_path.clone()).await?;
-    let own_address = Address::from_str(&source_address)
-        .unwrap()
-        .require_network(transform_network(network))
+    let sighashes = p2wpkh_sighashes(&transaction, utxos, sighash_types, &source_address, network);
+
+    let signatures = get_ecdsa_signatures(derivation_path, sighashes).await?;
+    add_p2wpkh_witnesses(
+        &mut transaction,
+        signatures,
+        sighash_types,
+        &user_public_key,
+    );
+
+    Ok(SignedTransaction::new(&transaction, utxos))
+}
+
+/// Computes the sighash of every input of a P2WPKH transaction that spends from
+/// `source_address`, with the sighash type given for it in `sighash_types`.
+pub fn p2wpkh_sighashes(
+    transaction: &Transaction,
+    utxos: &[Utxo],
+    sighash_types: &[EcdsaSighashType],
+    source_address: &str,
+    network: BitcoinNetwork,
+) -> Vec<Vec<u8>> {
+    let script_pubkey = ChainParams::bitcoin(network)
+        .script_pubkey(source_address)
         .expect("Network check failed");
-    for (index, input) in transaction.input.iter_mut().enumerate() {
-        let value = get_input_value(input, utxos).expect("input value not found in passed utxos");
-        let sighash = SighashCache::new(&txclone)
-            .p2wpkh_signature_hash(
-                index,
-                &own_address.script_pubkey(),
-                value,
-                ECDSA_SIG_HASH_TYPE,
-            )
-            .unwrap();
+    p2wpkh_script_sighashes(transaction, utxos, sighash_types, &script_pubkey)
+}
 
-        let signature =
-            get_ecdsa_signature(derivation_path.clone(), sighash.as_byte_array().to_vec()).await?;
+/// Computes the sighash of every input of a P2WPKH transaction that spends outputs with
+/// `script_pubkey`, on any chain with segregated witness.
+fn p2wpkh_script_sighashes(
+    transaction: &Transaction,
+    utxos: &[Utxo],
+    sighash_types: &[EcdsaSighashType],
+    script_pubkey: &ScriptBuf,
+) -> Vec<Vec<u8>> {
+    let mut cache = SighashCache::new(transaction);
+    (0..transaction.input.len())
+        .map(|index| {
+            let value = get_input_value(&transaction.input[index], utxos)
+                .expect("input value not found in passed utxos");
+            cache
+                .p2wpkh_signature_hash(index, script_pubkey, value, sighash_types[index])
+                .unwrap()
+                .as_byte_array()
+                .to_vec()
+        })
+        .collect()
+}
 
+/// Sets the witness of every input of a P2WPKH transaction from its SEC1 signature, in the order
+/// of the inputs, and the public key of the source address.
+pub fn add_p2wpkh_witnesses(
+    transaction: &mut Transaction,
+    signatures: Vec<Vec<u8>>,
+    sighash_types: &[EcdsaSighashType],
+    public_key: &[u8],
+) {
+    for ((input, signature), &sighash_type) in transaction
+        .input
+        .iter_mut()
+        .zip(signatures)
+        .zip(sighash_types)
+    {
         // Convert signature to DER.
         let der_signature = sec1_to_der(&signature);
 
         let mut sig_with_hashtype: Vec<u8> = der_signature;
-        sig_with_hashtype.push(
-            u8::try_from(ECDSA_SIG_HASH_TYPE.to_u32())
-                .expect("Error converting ECDSA_SIG_HASH_TYPE"),
-        );
+        sig_with_hashtype
+            .push(u8::try_from(sighash_type.to_u32()).expect("Error converting the sighash type"));
 
         let sig_with_hashtype_push_bytes = PushBytesBuf::try_from(sig_with_hashtype).unwrap();
-        let own_public_key_push_bytes = PushBytesBuf::try_from(user_public_key.clone()).unwrap();
+        let own_public_key_push_bytes = PushBytesBuf::try_from(public_key.to_vec()).unwrap();
         let mut witness = Witness::new();
         witness.push(sig_with_hashtype_push_bytes.as_bytes());
         witness.push(own_public_key_push_bytes.as_bytes());
         input.witness = witness;
     }
+}
 
-    let signed_transaction_bytes = serialize(&transaction);
+/// Computes the legacy sighash, with sighash type `All`, of every input of a P2PKH transaction
+/// that spends outputs with `script_pubkey`.
+pub fn p2pkh_sighashes(transaction: &Transaction, script_pubkey: &ScriptBuf) -> Vec<Vec<u8>> {
+    let cache = SighashCache::new(transaction);
+    (0..transaction.input.len())
+        .map(|index| {
+            cache
+                .legacy_signature_hash(index, script_pubkey, ECDSA_SIG_HASH_TYPE.to_u32())
+                .expect("Every input index is in range")
+                .as_byte_array()
+                .to_vec()
+        })
+        .collect()
+}
 
-    let txid = transaction.compute_txid().to_string();
+/// Sets the `scriptSig` of every input of a P2PKH transaction from its SEC1 signature, in the
+/// order of the inputs, and the public key of the source address.
+pub fn add_p2pkh_script_sigs(
+    transaction: &mut Transaction,
+    signatures: Vec<Vec<u8>>,
+    public_key: &[u8],
+) {
+    for (input, signature) in transaction.input.iter_mut().zip(signatures) {
+        let mut sig_with_hashtype = sec1_to_der(&signature);
+        sig_with_hashtype.push(
+            u8::try_from(ECDSA_SIG_HASH_TYPE.to_u32()).expect("Error converting the sighash type"),
+        );
+        input.script_sig = Builder::new()
+            .push_slice(PushBytesBuf::try_from(sig_with_hashtype).unwrap())
+            .push_slice(PushBytesBuf::try_from(public_key.to_vec()).unwrap())
+            .into_script();
+    }
+}
 
-    Ok(SignedTransaction {
-        signed_transaction_bytes,
-        txid,
-    })
+/// Signs every input of a transaction on `chain` that spends the outputs of `public_key`, the
+/// principal's key for the chain, with sighash type `All`.
+///
+/// P2WPKH inputs get a witness over their BIP-143 sighash and P2PKH inputs a `scriptSig` over
+/// their legacy sighash.  As for Bitcoin, all inputs are signed concurrently.
+pub async fn sign_chain_transaction(
+    principal: &Principal,
+    chain: &ChainParams,
+    public_key: &CompressedPublicKey,
+    mut transaction: Transaction,
+    utxos: &[Utxo],
+) -> Result<SignedTransaction, String> {
+    let derivation_path = chain.schema.derivation_path(principal);
+    let user_public_key = public_key.to_bytes();
+    let own_script_pubkey = chain.own_script_pubkey(public_key);
+    match chain.address_kind {
+        AddressKind::P2wpkh => {
+            let sighash_types = vec![ECDSA_SIG_HASH_TYPE; transaction.input.len()];
+            let sighashes =
+                p2wpkh_script_sighashes(&transaction, utxos, &sighash_types, &own_script_pubkey);
+            let signatures = get_ecdsa_signatures(derivation_path, sighashes).await?;
+            add_p2wpkh_witnesses(
+                &mut transaction,
+                signatures,
+                &sighash_types,
+                &user_public_key,
+            );
+        }
+        AddressKind::P2pkh => {
+            let sighashes = p2pkh_sighashes(&transaction, &own_script_pubkey);
+            let signatures = get_ecdsa_signatures(derivation_path, sighashes).await?;
+            add_p2pkh_script_sigs(&mut transaction, signatures, &user_public_key);
+        }
+    }
+
+    Ok(SignedTransaction::new(&transaction, utxos))
 }
 
 #[cfg(test)]
@@ -215,12 +1068,45 @@ mod tests {
     use std::str::FromStr;
 
     use bitcoin::{
-        hashes::Hash, OutPoint as BitcoinOutPoint, ScriptBuf, Sequence, TxIn, Txid, Witness,
+        absolute::LockTime,
+        hashes::{sha256, Hash},
+        opcodes::all::OP_CHECKSIG,
+        script::Builder,
+        secp256k1::{Keypair, Message, Secp256k1, SecretKey},
+        sighash::{Prevouts, SighashCache},
+        taproot::{LeafVersion, TapLeafHash},
+        transaction::Version,
+        Address, CompressedPublicKey, EcdsaSighashType, OutPoint as BitcoinOutPoint, ScriptBuf,
+        Sequence, TapSighashType, TxIn, TxOut, Txid, Witness,
+    };
+    use ic_cdk_bitcoin_canister::{OutPoint as IcCdkOutPoint, Txid as BtcIfTxid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::{
+        BitcoinNetwork, BtcHtlcRole, BtcInscription, BtcRuneId, BtcRuneTransfer, BtcSighashType,
+        BtcTapLeaf, BtcTaprootTree, BtcTxOutput, BuildP2wpkhTxError,
     };
-    use ic_cdk_bitcoin_canister::{Network, OutPoint as IcCdkOutPoint, Txid as BtcIfTxid, Utxo};
-    use ic_chain_fusion_signer_api::types::bitcoin::{BtcTxOutput, BuildP2wpkhTxError};
 
-    use super::{build_p2wpkh_transaction, get_input_value, sec1_to_der, DUST_THRESHOLD};
+    use super::{
+        add_p2pkh_script_sigs, build_chain_transaction_with_fee, build_htlc_transaction_with_fee,
+        build_p2wpkh_sweep_transaction_with_fee, build_p2wpkh_transaction,
+        build_p2wpkh_transaction_with_fee, build_runes_transaction_with_fee,
+        build_taproot_transaction_with_fee, build_vault_transaction_with_fee, change_output_index,
+        disable_rbf, estimate_signed_htlc_vsize, estimate_signed_p2pkh_vsize,
+        estimate_signed_reveal_vsize, estimate_signed_taproot_vsize, estimate_signed_vault_vsize,
+        estimate_signed_vsize, get_input_value, input_sighash_types, op_return_data_bytes,
+        p2pkh_sighashes, sec1_to_der, sent_satoshis, transaction_fee, vault_psbt, DUST_THRESHOLD,
+        MAX_OP_RETURN_DATA_BYTES,
+    };
+    use crate::sign::bitcoin::{
+        chain::ChainParams,
+        fee_utils::{fee_for_vsize, Fee},
+        htlc::{htlc, Htlc, HtlcSpend, HtlcTerms},
+        inscription::inscription_tree,
+        psbt::inputs_to_sign,
+        runes::{Edict, Runestone},
+        taproot::{parse_taproot_tree, taproot_tree, TapLeafSpend},
+        test_utils::public_key,
+        vault::{vault, Vault, VaultPolicy},
+    };
 
     const TXID1: &str = "36f3a7fcb6b5ebd9fa4041928da89cd423662f9c5c12e41c80e07a6559d178ef";
     const TXID2: &str = "d3f71b58d539fd97d2122f112d52dadb6a479ad3c47464978b3b0ce0046c1b50";
@@ -347,10 +1233,10 @@ mod tests {
 
         let result = build_p2wpkh_transaction(
             source_address,
-            Network::Mainnet,
+            BitcoinNetwork::Mainnet,
             &utxos,
             tx_fee,
-            &vec![BtcTxOutput {
+            &vec![BtcTxOutput::Payment {
                 destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
                 sent_satoshis: first_mock.utxo.value,
             }],
@@ -372,7 +1258,8 @@ mod tests {
     fn test_build_p2wpkh_transaction_invalid_source_address() {
         let invalid_address = "invalid_address";
 
-        let result = build_p2wpkh_transaction(invalid_address, Network::Mainnet, &[], 10, &vec![]);
+        let result =
+            build_p2wpkh_transaction(invalid_address, BitcoinNetwork::Mainnet, &[], 10, &vec![]);
 
         match result {
             Err(BuildP2wpkhTxError::InvalidSourceAddress { address }) => {
@@ -388,7 +1275,7 @@ mod tests {
 
         let result = build_p2wpkh_transaction(
             source_address,
-            Network::Testnet, // Incorrect network for the address
+            BitcoinNetwork::Testnet, // Incorrect network for the address
             &[],
             10,
             &vec![],
@@ -407,10 +1294,10 @@ mod tests {
 
         let result = build_p2wpkh_transaction(
             source_address,
-            Network::Mainnet,
+            BitcoinNetwork::Mainnet,
             &[],
             10,
-            &vec![BtcTxOutput {
+            &vec![BtcTxOutput::Payment {
                 destination_address: invalid_address.clone(),
                 sent_satoshis: 1000,
             }],
@@ -425,15 +1312,46 @@ mod tests {
     }
 
     #[test]
-    fn test_build_p2wpkh_transaction_not_p2wpkh_source_address() {
-        let source_address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"; // This is a legacy P2PKH address, not P2WPKH
-
-        let result = build_p2wpkh_transaction(source_address, Network::Mainnet, &[], 10, &vec![]);
+    fn test_build_p2wpkh_transaction_dust_output() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let destination_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string();
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
 
-        match result {
-            // Expect this error:
-            Err(BuildP2wpkhTxError::NotP2WPKHSourceAddress) => {}
-            // Anything else is wrong:
+        let result = build_p2wpkh_transaction(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            10,
+            &vec![BtcTxOutput::Payment {
+                destination_address: destination_address.clone(),
+                sent_satoshis: DUST_THRESHOLD - 1,
+            }],
+        );
+
+        assert_eq!(
+            result,
+            Err(BuildP2wpkhTxError::DustOutput {
+                address: destination_address,
+                sent_satoshis: DUST_THRESHOLD - 1,
+                dust_threshold: DUST_THRESHOLD,
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_not_p2wpkh_source_address() {
+        let source_address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"; // This is a legacy P2PKH address, not P2WPKH
+
+        let result =
+            build_p2wpkh_transaction(source_address, BitcoinNetwork::Mainnet, &[], 10, &vec![]);
+
+        match result {
+            // Expect this error:
+            Err(BuildP2wpkhTxError::NotP2WPKHSourceAddress) => {}
+            // Anything else is wrong:
             _ => panic!("Expected NotP2WPKHSourceAddress error"),
         }
     }
@@ -453,14 +1371,14 @@ mod tests {
         // Leave some amount to be sent to the source address.
         let amount_sent = utxos_amount - tx_fee - remaining;
 
-        let request_outputs = vec![BtcTxOutput {
+        let request_outputs = vec![BtcTxOutput::Payment {
             destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
             sent_satoshis: amount_sent,
         }];
 
         let result = build_p2wpkh_transaction(
             source_address,
-            Network::Mainnet,
+            BitcoinNetwork::Mainnet,
             &utxos,
             tx_fee,
             &request_outputs,
@@ -482,6 +1400,664 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_build_p2wpkh_transaction_with_op_return_data() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let data = [0xab; MAX_OP_RETURN_DATA_BYTES];
+        let request_outputs = vec![
+            BtcTxOutput::Data {
+                data: data.to_vec(),
+            },
+            BtcTxOutput::Payment {
+                destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+                sent_satoshis: 5_000,
+            },
+            BtcTxOutput::Data {
+                data: b"memo".to_vec(),
+            },
+        ];
+
+        let tx = build_p2wpkh_transaction(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            400,
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        // The requested outputs in order, then the change.
+        assert_eq!(tx.output.len(), 4);
+        let data_output = &tx.output[0];
+        assert!(data_output.script_pubkey.is_op_return());
+        assert_eq!(data_output.value.to_sat(), 0);
+        assert!(data_output.script_pubkey.as_bytes().ends_with(&data));
+        assert_eq!(tx.output[1].value.to_sat(), 5_000);
+        let memo_output = &tx.output[2];
+        assert!(memo_output.script_pubkey.is_op_return());
+        assert!(memo_output.script_pubkey.as_bytes().ends_with(b"memo"));
+        assert_eq!(
+            op_return_data_bytes(&tx),
+            (data_output.script_pubkey.len() + memo_output.script_pubkey.len()) as u64
+        );
+    }
+
+    #[test]
+    fn test_sent_satoshis_is_none_without_payments() {
+        let data = BtcTxOutput::Data {
+            data: b"memo".to_vec(),
+        };
+        let payment = BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        };
+
+        assert_eq!(sent_satoshis(&[]), None);
+        assert_eq!(sent_satoshis(std::slice::from_ref(&data)), None);
+        assert_eq!(
+            sent_satoshis(&[data, payment.clone(), payment]),
+            Some(10_000)
+        );
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_op_return_data_too_large() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let request_outputs = vec![BtcTxOutput::Data {
+            data: vec![0xab; MAX_OP_RETURN_DATA_BYTES + 1],
+        }];
+
+        let result = build_p2wpkh_transaction(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            400,
+            &request_outputs,
+        );
+
+        assert_eq!(
+            result,
+            Err(BuildP2wpkhTxError::OpReturnDataTooLarge {
+                data_bytes: MAX_OP_RETURN_DATA_BYTES as u64 + 1,
+                max_data_bytes: MAX_OP_RETURN_DATA_BYTES as u64,
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_with_fee_rate_pays_for_op_return_data() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_000; // 2 sat/vbyte
+        let payment = BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        };
+        let fee_with = |data: Option<&[u8]>| {
+            let mut request_outputs = vec![payment.clone()];
+            request_outputs.extend(data.map(|data| BtcTxOutput::Data {
+                data: data.to_vec(),
+            }));
+            let tx = build_p2wpkh_transaction_with_fee(
+                source_address,
+                BitcoinNetwork::Mainnet,
+                &utxos,
+                Fee::Rate(fee_rate),
+                &request_outputs,
+            )
+            .expect("Expected successful transaction");
+            utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>()
+        };
+
+        // An 80-byte payload adds an output of 8 value bytes, a 1-byte script length and an
+        // 83-byte script: 92 virtual bytes.
+        let data = [0xab; MAX_OP_RETURN_DATA_BYTES];
+        assert_eq!(fee_with(Some(&data)) - fee_with(None), 92 * 2);
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_signals_rbf_unless_disabled() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+
+        let mut tx = build_p2wpkh_transaction(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            400,
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+        assert!(tx.is_explicitly_rbf());
+
+        disable_rbf(&mut tx);
+        assert!(!tx.is_explicitly_rbf());
+        assert!(tx.input.iter().all(|input| input.sequence == Sequence::MAX));
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_with_fee_rate_pays_for_signed_size() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_500; // 2.5 sat/vbyte
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+
+        let tx = build_p2wpkh_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.output.len(), 2);
+        let fee = utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+        assert_eq!(fee, fee_for_vsize(estimate_signed_vsize(&tx), fee_rate));
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_with_fee_rate_pays_for_output_scripts() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_000; // 2 sat/vbyte
+        let fee_to = |destination_address: &str| {
+            let tx = build_p2wpkh_transaction_with_fee(
+                source_address,
+                BitcoinNetwork::Mainnet,
+                &utxos,
+                Fee::Rate(fee_rate),
+                &[BtcTxOutput::Payment {
+                    destination_address: destination_address.to_string(),
+                    sent_satoshis: 5_000,
+                }],
+            )
+            .expect("Expected successful transaction");
+            assert_eq!(tx.output.len(), 2);
+            utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>()
+        };
+
+        // A P2TR script has a 32-byte program where a P2WPKH script has a 20-byte one.
+        let p2tr_address = "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr";
+        assert_eq!(fee_to(p2tr_address) - fee_to(source_address), 12 * 2);
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_with_fee_rate_drops_change_the_fee_consumes() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_000; // 2 sat/vbyte
+                              // Without a fee, the change of 1,500 satoshi is above the dust threshold; the fee leaves
+                              // less than the dust threshold.
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: utxos_amount - DUST_THRESHOLD - 500,
+        }];
+
+        let tx = build_p2wpkh_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(change_output_index(&tx, 1), None);
+        let fee = utxos_amount - tx.output[0].value.to_sat();
+        assert_eq!(fee, DUST_THRESHOLD + 500);
+        assert!(fee >= fee_for_vsize(estimate_signed_vsize(&tx), fee_rate));
+    }
+
+    #[test]
+    fn test_change_output_index_is_after_the_request_outputs() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let request_outputs = vec![
+            BtcTxOutput::Payment {
+                destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+                sent_satoshis: 5_000,
+            },
+            BtcTxOutput::Data {
+                data: b"memo".to_vec(),
+            },
+        ];
+
+        let tx = build_p2wpkh_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Absolute(1_000),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        // The request output, the `OP_RETURN` output, then the change.
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(change_output_index(&tx, 2), Some(2));
+        assert_eq!(tx.output[2].value.to_sat(), 4_000);
+    }
+
+    #[test]
+    fn test_build_p2wpkh_transaction_with_absolute_fee() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+
+        let tx = build_p2wpkh_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Absolute(700),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        let fee = utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+        assert_eq!(fee, 700);
+    }
+
+    #[test]
+    fn test_build_p2wpkh_sweep_transaction_has_no_change() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let destination_address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_500; // 2.5 sat/vbyte
+                              // The value of the only payment output is ignored.
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: destination_address.to_string(),
+            sent_satoshis: 0,
+        }];
+
+        let tx = build_p2wpkh_sweep_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.input.len(), utxos.len());
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(
+            tx.output[0].script_pubkey,
+            Address::from_str(destination_address)
+                .unwrap()
+                .assume_checked()
+                .script_pubkey()
+        );
+        let fee = utxos_amount - tx.output[0].value.to_sat();
+        assert_eq!(fee, fee_for_vsize(estimate_signed_vsize(&tx), fee_rate));
+
+        let tx = build_p2wpkh_sweep_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Absolute(700),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value.to_sat(), utxos_amount - 700);
+    }
+
+    #[test]
+    fn test_build_p2wpkh_sweep_transaction_rejects_dust_and_missing_funds() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: source_address.to_string(),
+            sent_satoshis: 0,
+        }];
+        let sweep = |fee| {
+            build_p2wpkh_sweep_transaction_with_fee(
+                source_address,
+                BitcoinNetwork::Mainnet,
+                &utxos,
+                Fee::Absolute(fee),
+                &request_outputs,
+            )
+        };
+
+        assert_eq!(
+            sweep(utxos_amount - DUST_THRESHOLD + 1),
+            Err(BuildP2wpkhTxError::DustOutput {
+                address: source_address.to_string(),
+                sent_satoshis: DUST_THRESHOLD - 1,
+                dust_threshold: DUST_THRESHOLD,
+            })
+        );
+        assert_eq!(
+            sweep(utxos_amount + 1),
+            Err(BuildP2wpkhTxError::NotEnoughFunds {
+                required: utxos_amount + 1,
+                available: utxos_amount,
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_runes_transaction_keeps_runes_apart_from_change() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let destination_address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_000; // 2 sat/vbyte
+        let transfers = vec![BtcRuneTransfer {
+            rune_id: BtcRuneId {
+                block: 840_000,
+                tx: 3,
+            },
+            amount: 10,
+            destination_address: destination_address.to_string(),
+        }];
+        let runestone = Runestone {
+            edicts: vec![Edict {
+                id: transfers[0].rune_id,
+                amount: 10,
+                output: 0,
+            }],
+            etching: None,
+            pointer: Some(1),
+        }
+        .script_pubkey()
+        .unwrap();
+
+        let tx = build_runes_transaction_with_fee(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &transfers,
+            DUST_THRESHOLD,
+            &runestone,
+        )
+        .expect("Expected successful transaction");
+
+        let own_script = Address::from_str(source_address)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        let destination_script = Address::from_str(destination_address)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        assert_eq!(tx.input.len(), utxos.len());
+        assert_eq!(tx.output.len(), 4);
+        assert_eq!(tx.output[0].script_pubkey, destination_script);
+        assert_eq!(tx.output[0].value.to_sat(), DUST_THRESHOLD);
+        assert_eq!(tx.output[1].script_pubkey, own_script);
+        assert_eq!(tx.output[1].value.to_sat(), DUST_THRESHOLD);
+        assert_eq!(tx.output[2].script_pubkey, runestone);
+        assert_eq!(tx.output[2].value.to_sat(), 0);
+        assert_eq!(tx.output[3].script_pubkey, own_script);
+        let fee = utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+        assert_eq!(fee, fee_for_vsize(estimate_signed_vsize(&tx), fee_rate));
+    }
+
+    #[test]
+    fn test_estimate_signed_vsize_of_one_input_two_outputs() {
+        let source_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let utxos = vec![get_mock_utxos()[3].utxo.clone()];
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 1_000,
+        }];
+        let tx = build_p2wpkh_transaction(
+            source_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            0,
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+        assert_eq!(tx.output.len(), 2);
+
+        // A signed 1-input, 2-output P2WPKH transaction is 141 vbytes with a 72-byte signature.
+        assert_eq!(estimate_signed_vsize(&tx), 141);
+    }
+
+    /// A vault of key 1, with cosigner key 2 and recovery key 3, and the signer key 1.
+    fn test_vault() -> (Vault, CompressedPublicKey) {
+        let policy = VaultPolicy {
+            cosigner: public_key(2),
+            recovery: public_key(3),
+            recovery_delay_blocks: 144,
+        };
+        let vault = vault(public_key(1), &policy).expect("Expected a valid vault");
+        (vault, public_key(1))
+    }
+
+    #[test]
+    fn test_build_vault_transaction_with_fee_rate_returns_change_to_vault() {
+        let (vault, _) = test_vault();
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let fee_rate = 2_500; // 2.5 sat/vbyte
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+
+        let tx = build_vault_transaction_with_fee(
+            &vault,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[1].script_pubkey, vault.script_pubkey());
+        let fee = utxos_amount - tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+        assert_eq!(
+            fee,
+            fee_for_vsize(estimate_signed_vault_vsize(&tx, &vault), fee_rate)
+        );
+        // The signer's branch has no timelock.
+        assert_eq!(tx.version, Version::TWO);
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+        assert!(tx
+            .input
+            .iter()
+            .all(|input| input.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+    }
+
+    #[test]
+    fn test_vault_psbt_can_be_signed_by_signer() {
+        let (vault, signer) = test_vault();
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+        let tx = build_vault_transaction_with_fee(
+            &vault,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Absolute(500),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        let psbt = vault_psbt(tx, &utxos, &vault);
+
+        for (psbt_input, utxo) in psbt.inputs.iter().zip(&utxos) {
+            let witness_utxo = psbt_input.witness_utxo.as_ref().unwrap();
+            assert_eq!(witness_utxo.value.to_sat(), utxo.value);
+            assert_eq!(witness_utxo.script_pubkey, vault.script_pubkey());
+            assert_eq!(
+                psbt_input.witness_script,
+                Some(vault.witness_script.clone())
+            );
+        }
+        let inputs = inputs_to_sign(&psbt, &signer).expect("Expected inputs to sign");
+        assert_eq!(inputs.len(), utxos.len());
+    }
+
+    /// A transaction with four inputs, a payment and a change output.
+    fn build_test_transaction() -> bitcoin::Transaction {
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+        build_p2wpkh_transaction(
+            "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh",
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            400,
+            &request_outputs,
+        )
+        .expect("Expected successful transaction")
+    }
+
+    #[test]
+    fn test_input_sighash_types_default_to_all() {
+        let tx = build_test_transaction();
+
+        assert_eq!(
+            input_sighash_types(&tx, None),
+            Ok(vec![EcdsaSighashType::All; tx.input.len()])
+        );
+    }
+
+    #[test]
+    fn test_input_sighash_types_are_per_input() {
+        let tx = build_test_transaction();
+
+        let sighash_types = [
+            BtcSighashType::SinglePlusAnyoneCanPay,
+            BtcSighashType::AllPlusAnyoneCanPay,
+            BtcSighashType::All,
+            BtcSighashType::All,
+        ];
+        assert_eq!(
+            input_sighash_types(&tx, Some(&sighash_types)),
+            Ok(vec![
+                EcdsaSighashType::SinglePlusAnyoneCanPay,
+                EcdsaSighashType::AllPlusAnyoneCanPay,
+                EcdsaSighashType::All,
+                EcdsaSighashType::All,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_input_sighash_types_rejects_none() {
+        let tx = build_test_transaction();
+
+        for none in [BtcSighashType::None, BtcSighashType::NonePlusAnyoneCanPay] {
+            let sighash_types = [
+                BtcSighashType::All,
+                BtcSighashType::All,
+                none,
+                BtcSighashType::All,
+            ];
+            assert_eq!(
+                input_sighash_types(&tx, Some(&sighash_types)),
+                Err(BuildP2wpkhTxError::SighashNoneNotAllowed { input_index: 2 })
+            );
+        }
+    }
+
+    #[test]
+    fn test_input_sighash_types_rejects_wrong_number_of_types() {
+        let tx = build_test_transaction();
+
+        assert_eq!(
+            input_sighash_types(&tx, Some(&[BtcSighashType::All])),
+            Err(BuildP2wpkhTxError::WrongNumberOfSighashTypes {
+                inputs: 4,
+                sighash_types: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_input_sighash_types_rejects_single_without_output() {
+        let tx = build_test_transaction();
+
+        // The first two inputs have matching outputs; the third does not.
+        let sighash_types = [
+            BtcSighashType::Single,
+            BtcSighashType::Single,
+            BtcSighashType::SinglePlusAnyoneCanPay,
+            BtcSighashType::All,
+        ];
+        assert_eq!(
+            input_sighash_types(&tx, Some(&sighash_types)),
+            Err(BuildP2wpkhTxError::SighashSingleWithoutOutput { input_index: 2 })
+        );
+    }
+
     /// Builds a 64-byte compact signature whose `r` and `s` are valid scalars
     /// modulo the secp256k1 curve order. `r_top_byte` and `s_top_byte` choose
     /// the first byte of each scalar so the test can exercise the various
@@ -559,4 +2135,277 @@ mod tests {
             "Bitcoin must accept DER produced from a normal compact signature"
         );
     }
+
+    fn test_htlc() -> Htlc {
+        let terms = HtlcTerms {
+            counterparty: public_key(2),
+            payment_hash: sha256::Hash::hash(&[7; 32]),
+            timeout: LockTime::from_height(800_000).unwrap(),
+            caller_role: BtcHtlcRole::Sender,
+        };
+        htlc(public_key(1), &terms).expect("Expected a valid HTLC")
+    }
+
+    #[test]
+    fn test_build_htlc_transaction_sweeps_utxos() {
+        let htlc = test_htlc();
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let utxos_amount: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+        let destination_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+        let fee_rate = 2_500; // 2.5 sat/vbyte
+
+        let claim = HtlcSpend::Claim { preimage: &[7; 32] };
+        let tx = build_htlc_transaction_with_fee(
+            &htlc,
+            &claim,
+            destination_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.input.len(), utxos.len());
+        assert_eq!(tx.output.len(), 1);
+        let fee = utxos_amount - tx.output[0].value.to_sat();
+        assert_eq!(
+            fee,
+            fee_for_vsize(estimate_signed_htlc_vsize(&tx, &htlc, &claim), fee_rate)
+        );
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+
+        // A refund is locked until the timeout, and its inputs are not final so that the lock
+        // time is enforced.
+        let tx = build_htlc_transaction_with_fee(
+            &htlc,
+            &HtlcSpend::Refund,
+            destination_address,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Absolute(500),
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.output[0].value.to_sat(), utxos_amount - 500);
+        assert_eq!(tx.lock_time, htlc.timeout);
+        assert!(tx.input.iter().all(|input| !input.sequence.is_final()));
+    }
+
+    #[test]
+    fn test_build_htlc_transaction_rejects_dust() {
+        let htlc = test_htlc();
+        let utxos = vec![get_mock_utxos()[1].utxo.clone()];
+        let destination_address = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+
+        assert_eq!(
+            build_htlc_transaction_with_fee(
+                &htlc,
+                &HtlcSpend::Refund,
+                destination_address,
+                BitcoinNetwork::Mainnet,
+                &utxos,
+                Fee::Absolute(1_500),
+            ),
+            Err(BuildP2wpkhTxError::DustOutput {
+                address: destination_address.to_string(),
+                sent_satoshis: 500,
+                dust_threshold: DUST_THRESHOLD,
+            })
+        );
+    }
+
+    #[test]
+    fn test_estimate_signed_reveal_vsize_of_p2tr_destination() {
+        let secp = Secp256k1::new();
+        let (signer, _) = Keypair::from_seckey_slice(&secp, &[1; 32])
+            .expect("Invalid secret key")
+            .x_only_public_key();
+        let inscription = BtcInscription {
+            content_type: "text/plain;charset=utf-8".to_string(),
+            body: vec![b'a'; 1_000],
+        };
+        let (tree, leaf) = inscription_tree(signer, &inscription);
+        let commit_utxo = get_mock_utxos()[3].utxo.clone();
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: tree.address(BitcoinNetwork::Mainnet).to_string(),
+            sent_satoshis: DUST_THRESHOLD,
+        }];
+
+        let tx = build_taproot_transaction_with_fee(
+            &tree,
+            &leaf,
+            BitcoinNetwork::Mainnet,
+            std::slice::from_ref(&commit_utxo),
+            Fee::Absolute(commit_utxo.value - DUST_THRESHOLD),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(
+            estimate_signed_reveal_vsize(&tree, &leaf),
+            estimate_signed_taproot_vsize(&tx, &leaf)
+        );
+    }
+
+    #[test]
+    fn test_taproot_script_path_spend_is_signed_as_estimated() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[1; 32]).expect("Invalid secret key");
+        let (signer, _) = keypair.x_only_public_key();
+        let leaf_hex = |key| {
+            hex::encode(
+                Builder::new()
+                    .push_x_only_key(&key)
+                    .push_opcode(OP_CHECKSIG)
+                    .into_script()
+                    .as_bytes(),
+            )
+        };
+        let other = Keypair::from_seckey_slice(&secp, &[2; 32])
+            .unwrap()
+            .x_only_public_key()
+            .0;
+        let terms = parse_taproot_tree(&BtcTaprootTree {
+            internal_key: Some(hex::encode(other.serialize())),
+            leaves: vec![
+                BtcTapLeaf {
+                    script_hex: leaf_hex(other),
+                    depth: 1,
+                },
+                BtcTapLeaf {
+                    script_hex: leaf_hex(signer),
+                    depth: 1,
+                },
+            ],
+        })
+        .expect("Expected a valid tree");
+        let tree = taproot_tree(terms, signer);
+        let leaf = TapLeafSpend::new(&tree, 1).expect("Expected a leaf");
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| wrapper.utxo.clone())
+            .collect();
+        let fee_rate = 2_500; // 2.5 sat/vbyte
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".to_string(),
+            sent_satoshis: 5_000,
+        }];
+
+        let mut tx = build_taproot_transaction_with_fee(
+            &tree,
+            &leaf,
+            BitcoinNetwork::Mainnet,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+        assert_eq!(tx.output[1].script_pubkey, tree.script_pubkey());
+        let estimated_vsize = estimate_signed_taproot_vsize(&tx, &leaf);
+
+        let prevouts: Vec<TxOut> = utxos
+            .iter()
+            .map(|utxo| TxOut {
+                value: bitcoin::Amount::from_sat(utxo.value),
+                script_pubkey: tree.script_pubkey(),
+            })
+            .collect();
+        let leaf_hash = TapLeafHash::from_script(&leaf.script, LeafVersion::TapScript);
+        let unsigned = tx.clone();
+        let mut cache = SighashCache::new(&unsigned);
+        for (index, input) in tx.input.iter_mut().enumerate() {
+            let sighash = cache
+                .taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    leaf_hash,
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let message = Message::from_digest(sighash.to_byte_array());
+            let signature = secp.sign_schnorr_no_aux_rand(&message, &keypair);
+            secp.verify_schnorr(&signature, &message, &signer)
+                .expect("Expected a valid signature");
+            input.witness = leaf.witness(signature.as_ref());
+        }
+
+        assert_eq!(tx.vsize() as u64, estimated_vsize);
+    }
+
+    #[test]
+    fn test_dogecoin_transaction_is_signed_with_legacy_sighash_as_estimated() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).expect("Invalid secret key");
+        let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+        let chain = ChainParams::dogecoin(BitcoinNetwork::Mainnet);
+        let own_script_pubkey = chain.own_script_pubkey(&public_key);
+        let utxos: Vec<Utxo> = get_mock_utxos()
+            .iter()
+            .map(|wrapper| Utxo {
+                value: wrapper.utxo.value * 1_000,
+                ..wrapper.utxo.clone()
+            })
+            .collect();
+        let fee_rate = chain.default_fee_rate;
+        let request_outputs = vec![BtcTxOutput::Payment {
+            destination_address: chain.address(&public_key),
+            sent_satoshis: chain.dust_threshold,
+        }];
+
+        let mut tx = build_chain_transaction_with_fee(
+            &chain,
+            &own_script_pubkey,
+            &utxos,
+            Fee::Rate(fee_rate),
+            &request_outputs,
+        )
+        .expect("Expected successful transaction");
+        assert_eq!(tx.output[1].script_pubkey, own_script_pubkey);
+        let estimated_vsize = estimate_signed_p2pkh_vsize(&tx);
+
+        let sighashes = p2pkh_sighashes(&tx, &own_script_pubkey);
+        let signatures = sighashes
+            .iter()
+            .map(|sighash| {
+                let message = Message::from_digest_slice(sighash).unwrap();
+                secp.sign_ecdsa(&message, &secret_key)
+                    .serialize_compact()
+                    .to_vec()
+            })
+            .collect();
+        add_p2pkh_script_sigs(&mut tx, signatures, &public_key.to_bytes());
+
+        let cache = SighashCache::new(&tx);
+        for (index, input) in tx.input.iter().enumerate() {
+            let pushes: Vec<Vec<u8>> = input
+                .script_sig
+                .instructions()
+                .map(|push| push.unwrap().push_bytes().unwrap().as_bytes().to_vec())
+                .collect();
+            assert_eq!(pushes[1], public_key.to_bytes());
+            let signature = bitcoin::ecdsa::Signature::from_slice(&pushes[0]).unwrap();
+            assert_eq!(signature.sighash_type, EcdsaSighashType::All);
+            let sighash = cache
+                .legacy_signature_hash(index, &own_script_pubkey, EcdsaSighashType::All.to_u32())
+                .unwrap();
+            secp.verify_ecdsa(
+                &Message::from_digest(sighash.to_byte_array()),
+                &signature.signature,
+                &public_key.0,
+            )
+            .expect("Expected a valid signature");
+        }
+
+        let vsize = tx.vsize() as u64;
+        // A DER signature is shorter than the largest one if `r` or `s` has its high bit clear.
+        assert!(vsize <= estimated_vsize && estimated_vsize <= vsize + 2 * tx.input.len() as u64);
+        assert_eq!(
+            transaction_fee(&tx, &utxos),
+            fee_for_vsize(estimated_vsize, fee_rate)
+        );
+    }
 }
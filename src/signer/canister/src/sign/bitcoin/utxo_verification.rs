@@ -0,0 +1,83 @@
+//! Cross-checks of the UTXOs that a caller supplies against the UTXO set of the Bitcoin canister.
+use ic_cdk_bitcoin_canister::Utxo;
+use ic_chain_fusion_signer_api::types::bitcoin::SendBtcError;
+
+use crate::sign::bitcoin::bitcoin_utils::display_txid;
+
+/// Checks that every supplied UTXO is one of the `known` UTXOs, with the same value.
+///
+/// The number of confirmations is not checked here: `known` holds the UTXOs that
+/// `bitcoin_get_utxos` returned for `min_confirmations`, so an input with fewer confirmations is
+/// reported as `UnknownUtxo`.
+pub fn verify_utxos(supplied: &[Utxo], known: &[Utxo]) -> Result<(), SendBtcError> {
+    for utxo in supplied {
+        let known_utxo = known
+            .iter()
+            .find(|known_utxo| known_utxo.outpoint == utxo.outpoint)
+            .ok_or_else(|| SendBtcError::UnknownUtxo {
+                txid: display_txid(&utxo.outpoint.txid),
+                vout: utxo.outpoint.vout,
+            })?;
+        if known_utxo.value != utxo.value {
+            return Err(SendBtcError::ValueMismatch {
+                txid: display_txid(&utxo.outpoint.txid),
+                vout: utxo.outpoint.vout,
+                supplied_satoshis: utxo.value,
+                actual_satoshis: known_utxo.value,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_cdk_bitcoin_canister::{OutPoint, Txid, Utxo};
+    use ic_chain_fusion_signer_api::types::bitcoin::SendBtcError;
+
+    use super::verify_utxos;
+
+    fn utxo(txid_byte: u8, vout: u32, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint {
+                txid: Txid::from([txid_byte; 32]),
+                vout,
+            },
+            value,
+            height: 100,
+        }
+    }
+
+    #[test]
+    fn test_verify_utxos_accepts_known_utxos() {
+        let known = [utxo(1, 0, 10_000), utxo(1, 1, 20_000), utxo(2, 0, 30_000)];
+
+        assert!(verify_utxos(&[utxo(2, 0, 30_000), utxo(1, 1, 20_000)], &known).is_ok());
+        assert!(verify_utxos(&[], &known).is_ok());
+    }
+
+    #[test]
+    fn test_verify_utxos_rejects_unknown_outpoint() {
+        let known = [utxo(1, 0, 10_000)];
+
+        assert!(matches!(
+            verify_utxos(&[utxo(1, 0, 10_000), utxo(1, 1, 10_000)], &known),
+            Err(SendBtcError::UnknownUtxo { txid, vout: 1 }) if txid == "01".repeat(32)
+        ));
+    }
+
+    #[test]
+    fn test_verify_utxos_rejects_wrong_value() {
+        let known = [utxo(1, 0, 10_000)];
+
+        assert!(matches!(
+            verify_utxos(&[utxo(1, 0, 50_000)], &known),
+            Err(SendBtcError::ValueMismatch {
+                vout: 0,
+                supplied_satoshis: 50_000,
+                actual_satoshis: 10_000,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Timelocked P2WSH vaults: the caller's key spends together with a cosigner's key, and a recovery
+//! key spends alone after a relative timelock.
+use bitcoin::{
+    opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIGVERIFY, OP_CSV, OP_ENDIF, OP_IFDUP, OP_NOTIF},
+    script::Builder,
+    Address, CompressedPublicKey, PublicKey, ScriptBuf,
+};
+use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcVaultPolicy};
+
+use crate::sign::bitcoin::bitcoin_utils::{parse_compressed_public_key, transform_network};
+
+/// The keys and the delay of a vault, before the caller's own key is known.
+#[derive(Debug, Eq, PartialEq)]
+pub struct VaultPolicy {
+    pub cosigner: CompressedPublicKey,
+    pub recovery: CompressedPublicKey,
+    pub recovery_delay_blocks: u16,
+}
+
+/// A vault of the caller's key.
+pub struct Vault {
+    pub witness_script: ScriptBuf,
+}
+
+impl Vault {
+    pub fn address(&self, network: BitcoinNetwork) -> Address {
+        Address::p2wsh(&self.witness_script, transform_network(network))
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2wsh(&self.witness_script.wscript_hash())
+    }
+}
+
+fn parse_public_key(name: &str, public_key: &str) -> Result<CompressedPublicKey, String> {
+    parse_compressed_public_key(public_key).ok_or_else(|| format!("invalid {name}: {public_key}"))
+}
+
+/// Parses the keys of a vault policy and checks them and the delay.
+pub fn parse_vault_policy(policy: &BtcVaultPolicy) -> Result<VaultPolicy, String> {
+    if policy.recovery_delay_blocks == 0 {
+        return Err("recovery_delay_blocks must not be zero".to_string());
+    }
+    let cosigner = parse_public_key("cosigner_public_key", &policy.cosigner_public_key)?;
+    let recovery = parse_public_key("recovery_public_key", &policy.recovery_public_key)?;
+    if cosigner == recovery {
+        return Err("the cosigner and recovery keys must differ".to_string());
+    }
+    Ok(VaultPolicy {
+        cosigner,
+        recovery,
+        recovery_delay_blocks: policy.recovery_delay_blocks,
+    })
+}
+
+/// Builds the witness script of the vault of `signer`.
+///
+/// The policy `or(and(pk(signer),pk(cosigner)),and(pk(recovery),older(N)))` compiles to the
+/// miniscript `or_d(multi(2,signer,cosigner),and_v(v:pk(recovery),older(N)))`:
+///
+/// ```text
+/// 2 <signer> <cosigner> 2 CHECKMULTISIG IFDUP NOTIF
+///     <recovery> CHECKSIGVERIFY <N> CHECKSEQUENCEVERIFY
+/// ENDIF
+/// ```
+///
+/// The signer's branch is satisfied by `<> <signer_sig> <cosigner_sig>` and has no timelock.  The
+/// recovery branch is satisfied by `<recovery_sig> <> <> <>`, in a version 2 transaction whose
+/// input has an `nSequence` of at least `N` blocks (BIP-68).
+pub fn vault(signer: CompressedPublicKey, policy: &VaultPolicy) -> Result<Vault, String> {
+    if signer == policy.cosigner || signer == policy.recovery {
+        return Err("the caller's key must differ from the cosigner and recovery keys".to_string());
+    }
+    let witness_script = Builder::new()
+        .push_int(2)
+        .push_key(&PublicKey::from(signer))
+        .push_key(&PublicKey::from(policy.cosigner))
+        .push_int(2)
+        .push_opcode(OP_CHECKMULTISIG)
+        .push_opcode(OP_IFDUP)
+        .push_opcode(OP_NOTIF)
+        .push_key(&PublicKey::from(policy.recovery))
+        .push_opcode(OP_CHECKSIGVERIFY)
+        .push_int(i64::from(policy.recovery_delay_blocks))
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_ENDIF)
+        .into_script();
+    Ok(Vault { witness_script })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        opcodes::all::{OP_CHECKMULTISIG, OP_CSV, OP_ENDIF},
+        script::Instruction,
+        AddressType,
+    };
+    use ic_chain_fusion_signer_api::types::bitcoin::{BitcoinNetwork, BtcVaultPolicy};
+
+    use super::{parse_vault_policy, vault};
+    use crate::sign::bitcoin::test_utils::public_key;
+
+    fn policy(cosigner: u8, recovery: u8, recovery_delay_blocks: u16) -> BtcVaultPolicy {
+        BtcVaultPolicy {
+            cosigner_public_key: hex::encode(public_key(cosigner).to_bytes()),
+            recovery_public_key: hex::encode(public_key(recovery).to_bytes()),
+            recovery_delay_blocks,
+        }
+    }
+
+    #[test]
+    fn test_vault_witness_script() {
+        let policy = parse_vault_policy(&policy(2, 3, 144)).expect("Expected a valid policy");
+        let vault = vault(public_key(1), &policy).expect("Expected a valid vault");
+
+        let instructions: Vec<Instruction> = vault
+            .witness_script
+            .instructions()
+            .collect::<Result<_, _>>()
+            .expect("Expected a valid script");
+        assert_eq!(instructions.len(), 12);
+        assert_eq!(instructions[0].script_num(), Some(2));
+        assert_eq!(
+            instructions[1]
+                .push_bytes()
+                .map(|bytes| bytes.as_bytes().to_vec()),
+            Some(public_key(1).to_bytes().to_vec()),
+            "the signer's key should come first"
+        );
+        assert_eq!(instructions[4].opcode(), Some(OP_CHECKMULTISIG));
+        assert_eq!(
+            instructions[7]
+                .push_bytes()
+                .map(|bytes| bytes.as_bytes().to_vec()),
+            Some(public_key(3).to_bytes().to_vec()),
+            "the recovery key should guard the timelocked branch"
+        );
+        assert_eq!(
+            instructions[9].script_num(),
+            Some(144),
+            "delay should be 144"
+        );
+        assert_eq!(instructions[10].opcode(), Some(OP_CSV));
+        assert_eq!(instructions[11].opcode(), Some(OP_ENDIF));
+
+        let address = vault.address(BitcoinNetwork::Mainnet);
+        assert_eq!(address.address_type(), Some(AddressType::P2wsh));
+        assert_eq!(address.script_pubkey(), vault.script_pubkey());
+    }
+
+    #[test]
+    fn test_vault_depends_on_delay() {
+        let short = parse_vault_policy(&policy(2, 3, 6)).unwrap();
+        let long = parse_vault_policy(&policy(2, 3, 1000)).unwrap();
+
+        assert_ne!(
+            vault(public_key(1), &short).unwrap().witness_script,
+            vault(public_key(1), &long).unwrap().witness_script
+        );
+    }
+
+    #[test]
+    fn test_parse_vault_policy_rejects_invalid_policies() {
+        assert_eq!(
+            parse_vault_policy(&policy(2, 3, 0)).err(),
+            Some("recovery_delay_blocks must not be zero".to_string())
+        );
+        assert_eq!(
+            parse_vault_policy(&policy(2, 2, 6)).err(),
+            Some("the cosigner and recovery keys must differ".to_string())
+        );
+        let invalid = BtcVaultPolicy {
+            recovery_public_key: "02deadbeef".to_string(),
+            ..policy(2, 3, 6)
+        };
+        assert_eq!(
+            parse_vault_policy(&invalid).err(),
+            Some("invalid recovery_public_key: 02deadbeef".to_string())
+        );
+    }
+
+    #[test]
+    fn test_vault_rejects_signer_key_in_policy() {
+        let policy = parse_vault_policy(&policy(2, 3, 6)).unwrap();
+
+        assert!(vault(public_key(2), &policy).is_err());
+        assert!(vault(public_key(3), &policy).is_err());
+    }
+}
@@ -1,14 +1,33 @@
+use ic_cdk::call::{CallErrorExt, CallFailed};
 use ic_cdk_management_canister::{
-    ecdsa_public_key, sign_with_ecdsa, EcdsaCurve, EcdsaKeyId, EcdsaPublicKeyArgs,
+    ecdsa_public_key, sign_with_ecdsa, EcdsaCurve, EcdsaKeyId, EcdsaPublicKeyArgs, SignCallError,
     SignWithEcdsaArgs,
 };
 
-use crate::state::read_config;
+use crate::{
+    sign::fan_out::{max_concurrent_signatures, sign_concurrently},
+    state::read_config,
+};
+
+/// A failed `sign_with_ecdsa` call.
+#[derive(Debug)]
+pub struct SignatureError {
+    pub msg: String,
+    /// Whether the same call may succeed later, e.g. once the subnet is less busy or the canister
+    /// has been topped up.
+    pub transient: bool,
+}
+
+impl From<SignatureError> for String {
+    fn from(err: SignatureError) -> Self {
+        err.msg
+    }
+}
 
 pub async fn get_ecdsa_signature(
     derivation_path: Vec<Vec<u8>>,
     message_hash: Vec<u8>,
-) -> Result<Vec<u8>, String> {
+) -> Result<Vec<u8>, SignatureError> {
     let key_name = read_config(|s| s.ecdsa_key_name.clone());
     let key_id = EcdsaKeyId {
         curve: EcdsaCurve::Secp256k1,
@@ -21,11 +40,42 @@ pub async fn get_ecdsa_signature(
         key_id,
     })
     .await
-    .map_err(|err| format!("{err:?}"))?;
+    .map_err(|err| SignatureError {
+        transient: is_transient(&err),
+        msg: format!("{err:?}"),
+    })?;
 
     Ok(res.signature)
 }
 
+/// Whether a failed call may succeed if it is made again later: the call could not be made or was
+/// rejected for a transient reason, or the canister was short of cycles.
+fn is_transient(err: &SignCallError) -> bool {
+    match err {
+        SignCallError::CallFailed(err) => {
+            err.is_immediately_retryable()
+                || matches!(err, CallFailed::InsufficientLiquidCycleBalance(_))
+        }
+        SignCallError::SignCostError(_) | SignCallError::CandidDecodeFailed(_) => false,
+    }
+}
+
+/// Signs every message hash with the same key, issuing the calls concurrently up to
+/// `Config::max_concurrent_signatures`.
+///
+/// Returns the signatures in the order of the message hashes.
+pub async fn get_ecdsa_signatures(
+    derivation_path: Vec<Vec<u8>>,
+    message_hashes: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, SignatureError> {
+    sign_concurrently(
+        message_hashes,
+        max_concurrent_signatures(),
+        |message_hash| get_ecdsa_signature(derivation_path.clone(), message_hash),
+    )
+    .await
+}
+
 /// Computes the public key of the specified principal.
 pub async fn ecdsa_pubkey_of(derivation_path: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
     let key_name = read_config(|s| s.ecdsa_key_name.clone());
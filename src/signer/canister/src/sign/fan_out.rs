@@ -0,0 +1,129 @@
+//! Runs the threshold signature calls of a multi-input transaction concurrently.
+//!
+//! Every `sign_with_ecdsa` or `sign_with_schnorr` call takes a few consensus rounds, so signing the
+//! inputs one after the other makes the latency grow with the number of inputs.  Issuing the calls
+//! together makes it roughly that of a single signature, up to the configured fan-out.
+use std::future::Future;
+
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::state::read_config;
+
+/// The number of signatures requested at once if `Config::max_concurrent_signatures` is not set.
+pub const DEFAULT_MAX_CONCURRENT_SIGNATURES: u32 = 10;
+
+/// The number of signatures that may be requested at once, at least 1.
+pub fn max_concurrent_signatures() -> usize {
+    let max = read_config(|config| config.max_concurrent_signatures)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_SIGNATURES);
+    usize::try_from(max).unwrap_or(usize::MAX).max(1)
+}
+
+/// Runs `sign` on every message with at most `fan_out` calls in flight, and returns the results in
+/// the order of the messages.
+///
+/// Stops at the first error; the calls in flight are dropped.
+pub async fn sign_concurrently<T, E, F, Fut>(
+    messages: Vec<Vec<u8>>,
+    fan_out: usize,
+    sign: F,
+) -> Result<Vec<T>, E>
+where
+    F: FnMut(Vec<u8>) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    stream::iter(messages)
+        .map(sign)
+        .buffered(fan_out.max(1))
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::executor::block_on;
+
+    use super::sign_concurrently;
+
+    /// Returns `Pending` once, so that the other calls in flight get polled in the meantime.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_sign_concurrently_bounds_calls_in_flight_and_keeps_order() {
+        let in_flight = Cell::new(0);
+        let max_in_flight = Cell::new(0);
+        let messages = (0..20).map(|i| vec![i]).collect::<Vec<_>>();
+
+        let signatures = block_on(sign_concurrently(messages, 4, |message| {
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                in_flight.set(in_flight.get() + 1);
+                max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+                YieldOnce(false).await;
+                in_flight.set(in_flight.get() - 1);
+                Ok::<_, String>(message[0])
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(signatures, (0..20).collect::<Vec<u8>>());
+        assert_eq!(max_in_flight.get(), 4);
+    }
+
+    #[test]
+    fn test_sign_concurrently_fails_on_the_first_error() {
+        let messages = (0..5).map(|i| vec![i]).collect::<Vec<_>>();
+
+        let result = block_on(sign_concurrently(messages, 2, |message| async move {
+            if message[0] == 3 {
+                Err(format!("input {}", message[0]))
+            } else {
+                Ok(message[0])
+            }
+        }));
+
+        assert_eq!(result, Err("input 3".to_string()));
+    }
+
+    #[test]
+    fn test_sign_concurrently_signs_sequentially_without_fan_out() {
+        let in_flight = Cell::new(0);
+        let max_in_flight = Cell::new(0);
+        let messages = (0..3).map(|i| vec![i]).collect::<Vec<_>>();
+
+        block_on(sign_concurrently(messages, 0, |message| {
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                in_flight.set(in_flight.get() + 1);
+                max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+                YieldOnce(false).await;
+                in_flight.set(in_flight.get() - 1);
+                Ok::<_, String>(message)
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(max_in_flight.get(), 1);
+    }
+}
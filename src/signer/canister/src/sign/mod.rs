@@ -1,4 +1,7 @@
 pub mod bitcoin;
 pub mod ecdsa_api;
 pub mod eth;
+pub mod fan_out;
 pub mod generic;
+pub mod schnorr_api;
+pub mod sol;
@@ -0,0 +1,99 @@
+use ic_cdk_management_canister::{
+    schnorr_public_key, sign_with_schnorr, SchnorrAlgorithm, SchnorrKeyId, SchnorrPublicKeyArgs,
+    SignWithSchnorrArgs,
+};
+
+use crate::{
+    sign::fan_out::{max_concurrent_signatures, sign_concurrently},
+    state::read_config,
+};
+
+/// The BIP-340 Schnorr key, which has the same name as the ECDSA key.
+fn bip340_key_id() -> SchnorrKeyId {
+    SchnorrKeyId {
+        algorithm: SchnorrAlgorithm::Bip340secp256k1,
+        name: read_config(|s| s.ecdsa_key_name.clone()),
+    }
+}
+
+/// The Ed25519 key, which has the same name as the ECDSA key.
+fn ed25519_key_id() -> SchnorrKeyId {
+    SchnorrKeyId {
+        algorithm: SchnorrAlgorithm::Ed25519,
+        name: read_config(|s| s.ecdsa_key_name.clone()),
+    }
+}
+
+/// Signs a message with the untweaked BIP-340 key, as needed for taproot script-path spends.
+pub async fn get_bip340_signature(
+    derivation_path: Vec<Vec<u8>>,
+    message: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let res = sign_with_schnorr(&SignWithSchnorrArgs {
+        message,
+        derivation_path,
+        key_id: bip340_key_id(),
+        aux: None,
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+
+    Ok(res.signature)
+}
+
+/// Signs every message with the untweaked BIP-340 key, issuing the calls concurrently up to
+/// `Config::max_concurrent_signatures`.
+///
+/// Returns the signatures in the order of the messages.
+pub async fn get_bip340_signatures(
+    derivation_path: Vec<Vec<u8>>,
+    messages: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, String> {
+    sign_concurrently(messages, max_concurrent_signatures(), |message| {
+        get_bip340_signature(derivation_path.clone(), message)
+    })
+    .await
+}
+
+/// Computes the BIP-340 public key of the specified derivation path, in SEC1 compressed form.
+pub async fn bip340_pubkey_of(derivation_path: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let response = schnorr_public_key(&SchnorrPublicKeyArgs {
+        canister_id: None,
+        derivation_path,
+        key_id: bip340_key_id(),
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+
+    Ok(response.public_key)
+}
+
+/// Signs a message with the Ed25519 key.
+pub async fn get_ed25519_signature(
+    derivation_path: Vec<Vec<u8>>,
+    message: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let res = sign_with_schnorr(&SignWithSchnorrArgs {
+        message,
+        derivation_path,
+        key_id: ed25519_key_id(),
+        aux: None,
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+
+    Ok(res.signature)
+}
+
+/// Computes the 32-byte Ed25519 public key of the specified derivation path.
+pub async fn ed25519_pubkey_of(derivation_path: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let response = schnorr_public_key(&SchnorrPublicKeyArgs {
+        canister_id: None,
+        derivation_path,
+        key_id: ed25519_key_id(),
+    })
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+
+    Ok(response.public_key)
+}
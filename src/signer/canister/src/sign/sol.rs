@@ -0,0 +1,336 @@
+//! Solana addresses and transaction signing with the caller's Ed25519 key.
+//!
+//! Messages are parsed only as far as needed to find their required signers and to reject
+//! malformed input; the Solana runtime validates everything else.
+use bitcoin::base58;
+use candid::Principal;
+use ic_chain_fusion_signer_api::types::sol::{SolSignTransactionError, SolSignTransactionResponse};
+
+use crate::{
+    derivation_path::Schema,
+    sign::schnorr_api::{ed25519_pubkey_of, get_ed25519_signature},
+};
+
+/// The length of an Ed25519 public key, which is also the length of a Solana account address.
+const PUBKEY_LEN: usize = 32;
+/// The length of an Ed25519 signature.
+const SIGNATURE_LEN: usize = 64;
+/// The length of the recent blockhash in a message.
+const BLOCKHASH_LEN: usize = 32;
+/// Set in the first byte of a versioned message.  The first byte of a legacy message is its
+/// number of required signatures, which never has this bit set.
+const VERSION_PREFIX_MASK: u8 = 0x80;
+
+/// Converts an Ed25519 public key to a Solana address.
+pub fn pubkey_to_address(pubkey: &[u8]) -> String {
+    base58::encode(pubkey)
+}
+
+/// Returns the Solana address of the principal.
+pub async fn sol_address(principal: &Principal) -> Result<String, String> {
+    let pubkey = ed25519_pubkey_of(Schema::Sol.derivation_path(principal)).await?;
+    Ok(pubkey_to_address(&pubkey))
+}
+
+/// Signs a message that has already been checked with [`required_signers`], and returns the
+/// transaction in wire format with the principal's signature in place.
+pub async fn sign_transaction(
+    principal: &Principal,
+    message: Vec<u8>,
+    signers: &[[u8; PUBKEY_LEN]],
+) -> Result<SolSignTransactionResponse, SolSignTransactionError> {
+    let derivation_path = Schema::Sol.derivation_path(principal);
+    let pubkey = ed25519_pubkey_of(derivation_path.clone())
+        .await
+        .map_err(SolSignTransactionError::SigningError)?;
+    let Some(index) = signers.iter().position(|signer| signer[..] == pubkey[..]) else {
+        return Err(SolSignTransactionError::NotASigner {
+            address: pubkey_to_address(&pubkey),
+        });
+    };
+    let signature = get_ed25519_signature(derivation_path, message.clone())
+        .await
+        .map_err(SolSignTransactionError::SigningError)?;
+    let transaction = wire_transaction(&message, signers.len(), index, &signature);
+    Ok(SolSignTransactionResponse {
+        signed_transaction_hex: hex::encode(transaction),
+        signature: base58::encode(&signature),
+    })
+}
+
+/// Returns the required signers of a serialized legacy or v0 message, in signature order.
+///
+/// # Errors
+/// - If the message is not a well-formed legacy or v0 message.
+pub fn required_signers(message: &[u8]) -> Result<Vec<[u8; PUBKEY_LEN]>, String> {
+    let mut reader = Reader::new(message);
+    let first = reader.u8()?;
+    let versioned = first & VERSION_PREFIX_MASK != 0;
+    let num_required_signatures = if versioned {
+        let version = first & !VERSION_PREFIX_MASK;
+        if version != 0 {
+            return Err(format!("Unsupported message version: {version}"));
+        }
+        reader.u8()?
+    } else {
+        first
+    };
+    let num_readonly_signed = reader.u8()?;
+    let num_readonly_unsigned = reader.u8()?;
+
+    let num_keys = usize::from(reader.compact_u16()?);
+    let mut keys = Vec::with_capacity(num_keys);
+    for _ in 0..num_keys {
+        keys.push(reader.pubkey()?);
+    }
+    if num_required_signatures == 0 {
+        return Err("The message has no required signers".to_string());
+    }
+    if num_readonly_signed >= num_required_signatures {
+        return Err("The fee payer must be writable".to_string());
+    }
+    if usize::from(num_required_signatures) + usize::from(num_readonly_unsigned) > num_keys {
+        return Err(format!(
+            "The header describes more accounts than the {num_keys} account keys"
+        ));
+    }
+
+    reader.bytes(BLOCKHASH_LEN)?;
+    for _ in 0..reader.compact_u16()? {
+        // Program ID index, account indices and instruction data.
+        reader.u8()?;
+        reader.short_vec()?;
+        reader.short_vec()?;
+    }
+    if versioned {
+        for _ in 0..reader.compact_u16()? {
+            // Lookup table address, writable indices and readonly indices.
+            reader.pubkey()?;
+            reader.short_vec()?;
+            reader.short_vec()?;
+        }
+    }
+    reader.finish()?;
+
+    keys.truncate(usize::from(num_required_signatures));
+    Ok(keys)
+}
+
+/// Serializes a transaction: the signatures, as a compact-u16 length-prefixed array, followed by
+/// the message.  The signature at `index` is `signature`; all others are zero.
+pub fn wire_transaction(
+    message: &[u8],
+    num_signatures: usize,
+    index: usize,
+    signature: &[u8],
+) -> Vec<u8> {
+    let mut transaction = Vec::with_capacity(3 + num_signatures * SIGNATURE_LEN + message.len());
+    encode_compact_u16(
+        u16::try_from(num_signatures).expect("a message has at most 255 signers"),
+        &mut transaction,
+    );
+    for i in 0..num_signatures {
+        if i == index {
+            transaction.extend_from_slice(signature);
+        } else {
+            transaction.extend_from_slice(&[0; SIGNATURE_LEN]);
+        }
+    }
+    transaction.extend_from_slice(message);
+    transaction
+}
+
+/// Appends the Solana compact-u16 encoding of `value`: 7 bits per byte, least significant first,
+/// with the high bit set on every byte but the last.
+fn encode_compact_u16(mut value: u16, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Reads the fields of a serialized message.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("The message is truncated at byte {}", self.position))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn pubkey(&mut self) -> Result<[u8; PUBKEY_LEN], String> {
+        Ok(self
+            .bytes(PUBKEY_LEN)?
+            .try_into()
+            .expect("the slice has the length of a public key"))
+    }
+
+    /// Reads a compact-u16, rejecting encodings that are not the shortest.
+    fn compact_u16(&mut self) -> Result<u16, String> {
+        let mut value: u32 = 0;
+        for i in 0..3 {
+            let byte = self.u8()?;
+            value |= u32::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                if byte == 0 && i > 0 {
+                    return Err("Non-canonical compact-u16 length".to_string());
+                }
+                return u16::try_from(value)
+                    .map_err(|_| "Compact-u16 length overflows u16".to_string());
+            }
+        }
+        Err("Compact-u16 length is longer than 3 bytes".to_string())
+    }
+
+    /// Reads a compact-u16 length-prefixed array of bytes.
+    fn short_vec(&mut self) -> Result<&'a [u8], String> {
+        let len = self.compact_u16()?;
+        self.bytes(usize::from(len))
+    }
+
+    fn finish(self) -> Result<(), String> {
+        if self.position == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(format!(
+                "The message has {} trailing bytes",
+                self.bytes.len() - self.position
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEE_PAYER: [u8; PUBKEY_LEN] = [1; PUBKEY_LEN];
+    const COSIGNER: [u8; PUBKEY_LEN] = [2; PUBKEY_LEN];
+    const RECIPIENT: [u8; PUBKEY_LEN] = [3; PUBKEY_LEN];
+    const SYSTEM_PROGRAM: [u8; PUBKEY_LEN] = [0; PUBKEY_LEN];
+
+    /// A transfer from the fee payer, co-signed by a second account.
+    fn message(versioned: bool) -> Vec<u8> {
+        let mut message = Vec::new();
+        if versioned {
+            message.push(VERSION_PREFIX_MASK);
+        }
+        // Two signers, both writable; the system program is readonly.
+        message.extend_from_slice(&[2, 0, 1]);
+        message.push(4);
+        for key in [FEE_PAYER, COSIGNER, RECIPIENT, SYSTEM_PROGRAM] {
+            message.extend_from_slice(&key);
+        }
+        message.extend_from_slice(&[9; BLOCKHASH_LEN]);
+        // One instruction: program 3, accounts [0, 2], 12 bytes of data.
+        message.extend_from_slice(&[1, 3, 2, 0, 2, 12]);
+        message.extend_from_slice(&[2, 0, 0, 0, 0xe8, 3, 0, 0, 0, 0, 0, 0]);
+        if versioned {
+            // One address lookup table with one writable and no readonly index.
+            message.push(1);
+            message.extend_from_slice(&[7; PUBKEY_LEN]);
+            message.extend_from_slice(&[1, 5, 0]);
+        }
+        message
+    }
+
+    #[test]
+    fn address_is_base58_of_the_public_key() {
+        assert_eq!(
+            pubkey_to_address(&SYSTEM_PROGRAM),
+            "11111111111111111111111111111111"
+        );
+    }
+
+    #[test]
+    fn finds_the_required_signers_of_legacy_and_v0_messages() {
+        for versioned in [false, true] {
+            assert_eq!(
+                required_signers(&message(versioned)),
+                Ok(vec![FEE_PAYER, COSIGNER]),
+                "versioned: {versioned}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let mut unsupported_version = message(true);
+        unsupported_version[0] = VERSION_PREFIX_MASK | 1;
+        let mut trailing = message(false);
+        trailing.push(0);
+        let mut truncated = message(true);
+        truncated.pop();
+        let mut no_signers = message(false);
+        no_signers[0] = 0;
+        let mut readonly_fee_payer = message(false);
+        readonly_fee_payer[1] = 2;
+        let mut too_many_signers = message(false);
+        too_many_signers[0] = 5;
+        for (name, message) in [
+            ("empty", vec![]),
+            ("unsupported version", unsupported_version),
+            ("trailing bytes", trailing),
+            ("truncated", truncated),
+            ("no signers", no_signers),
+            ("readonly fee payer", readonly_fee_payer),
+            ("too many signers", too_many_signers),
+        ] {
+            assert!(required_signers(&message).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn compact_u16_round_trips_and_rejects_non_canonical_encodings() {
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u16::MAX] {
+            let mut encoded = Vec::new();
+            encode_compact_u16(value, &mut encoded);
+            let mut reader = Reader::new(&encoded);
+            assert_eq!(reader.compact_u16(), Ok(value));
+            assert_eq!(reader.finish(), Ok(()));
+        }
+        for encoded in [
+            &[0x80, 0x00][..],
+            &[0x80, 0x80, 0x80, 0x01],
+            &[0xff, 0xff, 0x04],
+        ] {
+            assert!(Reader::new(encoded).compact_u16().is_err(), "{encoded:?}");
+        }
+    }
+
+    #[test]
+    fn wire_transaction_places_the_signature_at_the_signer_index() {
+        let message = message(false);
+        let signature = [5; SIGNATURE_LEN];
+        let transaction = wire_transaction(&message, 2, 1, &signature);
+        assert_eq!(transaction[0], 2);
+        assert_eq!(transaction[1..=SIGNATURE_LEN], [0; SIGNATURE_LEN]);
+        assert_eq!(
+            transaction[1 + SIGNATURE_LEN..=2 * SIGNATURE_LEN],
+            signature
+        );
+        assert_eq!(transaction[1 + 2 * SIGNATURE_LEN..], message[..]);
+    }
+}
@@ -8,9 +8,12 @@ use ic_stable_structures::{
     DefaultMemoryImpl,
 };
 
-use crate::types::{Candid, ConfigCell};
+use crate::types::{Candid, ConfigCell, SignedTransactions, SigningJobs, UtxoReservations};
 
 const CONFIG_MEMORY_ID: MemoryId = MemoryId::new(0);
+const UTXO_RESERVATIONS_MEMORY_ID: MemoryId = MemoryId::new(1);
+const SIGNED_TRANSACTIONS_MEMORY_ID: MemoryId = MemoryId::new(2);
+const SIGNING_JOBS_MEMORY_ID: MemoryId = MemoryId::new(3);
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
@@ -20,6 +23,9 @@ thread_local! {
     static STATE: RefCell<State> = RefCell::new(
         MEMORY_MANAGER.with(|mm| State {
             config: ConfigCell::init(mm.borrow().get(CONFIG_MEMORY_ID), None),
+            utxo_reservations: UtxoReservations::init(mm.borrow().get(UTXO_RESERVATIONS_MEMORY_ID)),
+            signed_transactions: SignedTransactions::init(mm.borrow().get(SIGNED_TRANSACTIONS_MEMORY_ID)),
+            signing_jobs: SigningJobs::init(mm.borrow().get(SIGNING_JOBS_MEMORY_ID)),
         })
     );
 }
@@ -48,6 +54,12 @@ pub fn read_config<R>(f: impl FnOnce(&Config) -> R) -> R {
 
 pub struct State {
     pub config: ConfigCell,
+    /// The UTXOs spent by transactions in flight or sent recently.
+    pub utxo_reservations: UtxoReservations,
+    /// The transactions signed for callers recently.
+    pub signed_transactions: SignedTransactions,
+    /// The jobs started by `btc_caller_sign_job` and `btc_caller_send_job`.
+    pub signing_jobs: SigningJobs,
 }
 
 pub fn set_config(arg: InitArg) {
@@ -57,6 +69,11 @@ pub fn set_config(arg: InitArg) {
     });
 }
 
+/// Charges callers for the paid endpoints.
+///
+/// Endpoints check everything that is cheap to check before calling `deduct`: the arguments, the
+/// network, and the reservation of the inputs.  A request that is bound to fail thus returns its
+/// typed error without deducting payment from the caller.
 pub static PAYMENT_GUARD: LazyLock<PaymentGuard<5>> = LazyLock::new(|| PaymentGuard {
     supported: [
         VendorPaymentConfig::AttachedCycles,
@@ -1,11 +1,107 @@
-use candid::{CandidType, Deserialize};
-use ic_chain_fusion_signer_api::types::Config;
-use ic_stable_structures::{memory_manager::VirtualMemory, Cell as StableCell, DefaultMemoryImpl};
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk_bitcoin_canister::Utxo;
+use ic_chain_fusion_signer_api::types::{
+    bitcoin::{BitcoinNetwork, BtcSignedTransactionStatus, SignBtcResponse},
+    Config,
+};
+use ic_stable_structures::{
+    memory_manager::VirtualMemory, Cell as StableCell, DefaultMemoryImpl, StableBTreeMap,
+};
 
 pub type VMem = VirtualMemory<DefaultMemoryImpl>;
 pub type ConfigCell = StableCell<Option<Candid<Config>>, VMem>;
+pub type UtxoReservations<M = VMem> =
+    StableBTreeMap<UtxoReservationKey, Candid<UtxoReservation>, M>;
+pub type SignedTransactions<M = VMem> =
+    StableBTreeMap<SignedTransactionKey, Candid<SignedTransactionRecord>, M>;
+pub type SigningJobs<M = VMem> = StableBTreeMap<u64, Candid<SigningJob>, M>;
 
 #[derive(Default)]
 pub struct Candid<T>(pub T)
 where
     T: CandidType + for<'de> Deserialize<'de>;
+
+/// A UTXO reserved by a caller.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct UtxoReservationKey {
+    pub principal: Principal,
+    /// The txid of the outpoint, in the byte order of `bitcoin_get_utxos`.
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+/// The transaction that a reserved UTXO is spent by.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct UtxoReservation {
+    /// The txid of the transaction, or `None` while it is being signed.
+    pub spending_txid: Option<String>,
+    /// When the reservation expires, in nanoseconds since the Unix epoch.
+    pub expires_at_ns: u64,
+}
+
+/// A transaction signed for a caller.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct SignedTransactionKey {
+    pub principal: Principal,
+    /// The txid of the transaction, in internal byte order.
+    pub txid: [u8; 32],
+}
+
+/// A signed transaction, kept so that it can be broadcast again without signing it again.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SignedTransactionRecord {
+    pub network: BitcoinNetwork,
+    pub signed_transaction_bytes: Vec<u8>,
+    pub fee_satoshis: u64,
+    pub fee_rate_millisat_per_vbyte: u64,
+    /// The size of the transaction, in virtual bytes.
+    pub vsize: u64,
+    /// The output that returns the change to the caller, if any.
+    pub change_output_index: Option<u32>,
+    pub status: BtcSignedTransactionStatus,
+    /// When the transaction was signed, in nanoseconds since the Unix epoch.
+    pub signed_at_ns: u64,
+}
+
+/// A P2WPKH transaction whose inputs are signed in batches by timers.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SigningJob {
+    pub principal: Principal,
+    pub network: BitcoinNetwork,
+    /// Whether the transaction is sent once it is signed.
+    pub send: bool,
+    pub utxos: Vec<Utxo>,
+    /// The transaction, without witnesses.
+    pub unsigned_transaction_bytes: Vec<u8>,
+    /// The output that returns the change to the caller, if any.
+    pub change_output_index: Option<u32>,
+    /// The sighash type of every input, in consensus encoding.
+    pub sighash_types: Vec<u32>,
+    /// The sighash of every input.
+    pub sighashes: Vec<Vec<u8>>,
+    /// The signatures of the first inputs, in the order of the inputs.
+    pub signatures: Vec<Vec<u8>>,
+    /// The SEC1 public key of the caller's address.
+    pub public_key: Vec<u8>,
+    pub status: SigningJobStatus,
+    /// When the job was started, in nanoseconds since the Unix epoch.
+    pub created_at_ns: u64,
+    /// When the job finished, in nanoseconds since the Unix epoch, or `None` while it is signing.
+    pub finished_at_ns: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum SigningJobStatus {
+    Signing,
+    /// The transaction is signed and, if the job sends it, sent.
+    Completed(SignBtcResponse),
+    /// Signing an input failed.
+    Failed {
+        msg: String,
+    },
+    /// The transaction is signed, but `bitcoin_send_transaction` rejected it.
+    BroadcastFailed {
+        response: SignBtcResponse,
+        msg: String,
+    },
+}
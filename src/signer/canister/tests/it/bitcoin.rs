@@ -5,10 +5,24 @@ use crate::{
     canister::{
         cycles_ledger::{self, ApproveArgs},
         signer::{
-            BitcoinAddressType, BtcSignPrehashError, BtcSignPrehashRequest, BtcSignPrehashResponse,
-            BtcTxOutput, GetAddressError, GetAddressRequest, GetAddressResponse, GetBalanceRequest,
-            GetBalanceResponse, Network, OutPoint, PaymentType, SendBtcError, SendBtcRequest,
-            SignBtcResponse, Utxo,
+            BitcoinAddressType, BtcAddressRequest, BtcBumpFeeError, BtcBumpFeeMode,
+            BtcBumpFeeRequest, BtcBumpFeeResponse, BtcConsolidateRequest,
+            BtcDecodeTransactionError, BtcDecodeTransactionRequest, BtcDepositToCkbtcRequest,
+            BtcDepositToCkbtcResponse, BtcDescriptorResponse, BtcFeePriority, BtcFeeRate, BtcHtlc,
+            BtcHtlcAddressRequest, BtcHtlcAddressResponse, BtcHtlcClaimRequest, BtcHtlcError,
+            BtcHtlcRefundRequest, BtcHtlcRole, BtcInscription, BtcInscriptionCommitRequest,
+            BtcInscriptionCommitResponse, BtcInscriptionError, BtcInscriptionRevealRequest,
+            BtcJobResponse, BtcJobStatus, BtcJobStatusError, BtcJobStatusRequest,
+            BtcMultisigAddressError, BtcMultisigAddressRequest, BtcMultisigAddressResponse,
+            BtcRebroadcastError, BtcRebroadcastRequest, BtcRuneEtching, BtcRuneId, BtcRuneTransfer,
+            BtcSendRunesError, BtcSendRunesRequest, BtcSighashType, BtcSignPrehashError,
+            BtcSignPrehashRequest, BtcSignPrehashResponse, BtcSignPsbtError, BtcSignPsbtRequest,
+            BtcSignPsbtResponse, BtcSignedTransactionStatus, BtcTapLeaf, BtcTaprootAddressRequest,
+            BtcTaprootAddressResponse, BtcTaprootError, BtcTaprootSignRequest, BtcTaprootTree,
+            BtcTxOutput, BtcUtxoVerification, BtcVaultPolicy, BtcVaultSignRequest,
+            BuildP2wpkhTxError, CkbtcUpdateBalance, GetAddressError, GetAddressRequest,
+            GetAddressResponse, GetBalanceRequest, GetBalanceResponse, Network, OutPoint,
+            PaymentType, SendBtcError, SendBtcRequest, SendBtcResponse, SignBtcResponse, Utxo,
         },
     },
     utils::{
@@ -70,7 +84,7 @@ mod address {
     use super::*;
 
     /// A standard btc_caller_address() call, including payment.
-    fn paid_caller_address(
+    pub(super) fn paid_caller_address(
         test_env: &TestSetup,
         caller: Principal,
         request: &GetAddressRequest,
@@ -138,6 +152,31 @@ mod address {
         );
     }
 
+    #[test]
+    fn test_caller_btc_address_testnet4_and_signet_match_testnet() {
+        let test_env = TestSetup::default();
+
+        for network in [Network::Testnet4, Network::Signet] {
+            let response = paid_caller_address(
+                &test_env,
+                test_env.user,
+                &GetAddressRequest {
+                    network,
+                    address_type: BitcoinAddressType::P2WPKH,
+                },
+            )
+            .expect("Failed to call btc address.")
+            .expect("Failed to get successul btc address response");
+
+            assert_eq!(
+                response,
+                GetAddressResponse {
+                    address: CALLER_BTC_ADDRESS_TESTNET.to_string()
+                }
+            );
+        }
+    }
+
     #[test]
     fn test_caller_btc_address_regtest() {
         let test_env = TestSetup::default();
@@ -211,12 +250,168 @@ mod address {
 
         assert_ne!(testnet_address, regtest_address);
     }
+
+    /// A standard btc_address() call, including payment.
+    fn paid_btc_address(
+        test_env: &TestSetup,
+        caller: Principal,
+        principal: Option<Principal>,
+    ) -> Result<Result<GetAddressResponse, GetAddressError>, String> {
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = SignerMethods::BtcAddress.fee() + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env.signer.btc_address(
+            caller,
+            &BtcAddressRequest {
+                principal,
+                network: Network::Mainnet,
+                address_type: BitcoinAddressType::P2WPKH,
+            },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+    }
+
+    #[test]
+    fn test_btc_address_of() {
+        let test_env = TestSetup::default();
+
+        let own_address = paid_btc_address(&test_env, test_env.user, None)
+            .expect("Failed to call btc_address")
+            .expect("Failed to get btc address");
+        let address_of_user = paid_btc_address(&test_env, test_env.user, Some(test_env.user))
+            .expect("Failed to call btc_address")
+            .expect("Failed to get btc address");
+        let address_of_user2 = paid_btc_address(&test_env, test_env.user, Some(test_env.user2))
+            .expect("Failed to call btc_address")
+            .expect("Failed to get btc address");
+
+        assert_eq!(own_address.address, CALLER_BTC_ADDRESS_MAINNET);
+        assert_eq!(address_of_user, own_address);
+        assert_ne!(address_of_user2, own_address);
+    }
+
+    #[test]
+    fn test_cannot_call_btc_address_of_for_anonymous() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_address(
+            test_env.user,
+            &BtcAddressRequest {
+                principal: Some(Principal::anonymous()),
+                network: Network::Mainnet,
+                address_type: BitcoinAddressType::P2WPKH,
+            },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+        assert!(response
+            .unwrap_err()
+            .contains("Anonymous principal is not authorized"));
+    }
+}
+
+mod descriptor {
+    use super::*;
+
+    /// A standard btc_descriptor() call, including payment.
+    fn paid_descriptor(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &GetAddressRequest,
+    ) -> Result<Result<BtcDescriptorResponse, GetAddressError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = SignerMethods::BtcDescriptor.fee() + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_descriptor(caller, request, &Some(payment_type))
+    }
+
+    #[test]
+    fn test_btc_descriptor_of_caller_address() {
+        let test_env = TestSetup::default();
+
+        let response = paid_descriptor(
+            &test_env,
+            test_env.user,
+            &GetAddressRequest {
+                network: Network::Mainnet,
+                address_type: BitcoinAddressType::P2WPKH,
+            },
+        )
+        .expect("Failed to call btc_descriptor")
+        .expect("Failed to get btc descriptor");
+
+        assert_eq!(response.address, CALLER_BTC_ADDRESS_MAINNET);
+        let (descriptor, checksum) = response
+            .descriptor
+            .split_once('#')
+            .expect("Expected a checksum");
+        assert!(
+            descriptor.starts_with("wpkh(") && descriptor.ends_with(')'),
+            "unexpected descriptor: {descriptor}"
+        );
+        // A compressed public key is 33 bytes.
+        assert_eq!(descriptor.len(), "wpkh()".len() + 66);
+        assert_eq!(checksum.len(), 8);
+    }
+
+    #[test]
+    fn test_btc_descriptor_of_vault_matches_vault_address() {
+        let test_env = TestSetup::default();
+        let request = GetAddressRequest {
+            network: Network::Regtest,
+            address_type: BitcoinAddressType::P2WSHVault(BtcVaultPolicy {
+                cosigner_public_key:
+                    "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".to_string(),
+                recovery_public_key:
+                    "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5".to_string(),
+                recovery_delay_blocks: 144,
+            }),
+        };
+
+        let vault_address = address::paid_caller_address(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_address")
+            .expect("Failed to get vault address");
+        let response = paid_descriptor(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_descriptor")
+            .expect("Failed to get vault descriptor");
+
+        assert_eq!(response.address, vault_address.address);
+        assert!(
+            response.descriptor.starts_with("wsh(or_d(multi(2,"),
+            "unexpected descriptor: {}",
+            response.descriptor
+        );
+        assert!(response.descriptor.contains(
+            ",0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798),and_v(v:pk("
+        ));
+        assert!(response.descriptor.contains("),older(144))))#"));
+    }
 }
 
 mod caller_sign {
     use super::*;
 
-    fn make_test_send_request(network: Network) -> SendBtcRequest {
+    pub(super) fn make_test_send_request(network: Network) -> SendBtcRequest {
         SendBtcRequest {
             network,
             address_type: BitcoinAddressType::P2WPKH,
@@ -233,15 +428,21 @@ mod caller_sign {
                 },
             }],
             fee_satoshis: Some(1000),
-            outputs: vec![BtcTxOutput {
+            fee_rate: None,
+            outputs: vec![BtcTxOutput::Payment {
                 destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
                 sent_satoshis: 50_000,
             }],
+            allow_high_fee: None,
+            disable_rbf: None,
+            sighash_types: None,
+            verify_utxos: None,
+            send_all: None,
         }
     }
 
     /// A standard btc_caller_sign() call, including payment.
-    fn paid_caller_sign(
+    pub(super) fn paid_caller_sign(
         test_env: &TestSetup,
         caller: Principal,
         request: &SendBtcRequest,
@@ -252,7 +453,14 @@ mod caller_sign {
             subaccount: None,
         };
         let n_inputs = request.utxos_to_spend.len() as u64;
-        let amount: u128 = SignerMethods::BtcCallerSign.btc_fee_for_inputs(n_inputs) + LEDGER_FEE;
+        let verification_fee = if request.verify_utxos.is_some() {
+            SignerMethods::BtcCallerSign.btc_utxo_verification_fee()
+        } else {
+            0
+        };
+        let amount: u128 = SignerMethods::BtcCallerSign.btc_fee_for_inputs(n_inputs)
+            + verification_fee
+            + LEDGER_FEE;
         test_env
             .ledger
             .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
@@ -296,113 +504,2566 @@ mod caller_sign {
         assert!(!response.signed_transaction_hex.is_empty());
         assert!(!response.txid.is_empty());
         assert!(hex::decode(&response.signed_transaction_hex).is_ok());
+        assert_eq!(response.fee_satoshis, 1000);
     }
-}
 
-mod sign_prehash {
-    use super::*;
+    #[test]
+    fn test_btc_caller_sign_describes_the_signed_transaction() {
+        let test_env = TestSetup::default();
 
-    // An arbitrary 32-byte digest (hex), as `btc_sign_prehash` expects.
-    const PREHASH_HEX: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let response = paid_caller_sign(
+            &test_env,
+            test_env.user,
+            &make_test_send_request(Network::Regtest),
+        )
+        .expect("Failed to call btc_caller_sign")
+        .expect("Failed to get successful sign response");
 
-    /// A standard btc_sign_prehash() call, including payment.
-    fn paid_sign_prehash(
-        test_env: &TestSetup,
-        caller: Principal,
-        request: &BtcSignPrehashRequest,
-    ) -> Result<Result<BtcSignPrehashResponse, BtcSignPrehashError>, String> {
-        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
-        let payment_recipient = cycles_ledger::Account {
-            owner: test_env.signer.canister_id(),
-            subaccount: None,
+        let transaction: bitcoin::Transaction = bitcoin::consensus::deserialize(
+            &hex::decode(&response.signed_transaction_hex).expect("Invalid hex"),
+        )
+        .expect("Invalid transaction");
+        assert_eq!(response.vsize, transaction.vsize() as u64);
+        assert_eq!(
+            response.fee_rate_millisat_per_vbyte,
+            response.fee_satoshis * 1000 / response.vsize
+        );
+        // The requested output, then the change: 100_000 - 50_000 - 1000.
+        assert_eq!(response.change_output_index, Some(1));
+        assert_eq!(transaction.output[1].value.to_sat(), 49_000);
+    }
+
+    #[test]
+    fn test_btc_caller_sign_send_all_has_no_change_output() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            send_all: Some(true),
+            ..make_test_send_request(Network::Regtest)
         };
-        let amount: u128 = SignerMethods::BtcSignPrehash.fee() + LEDGER_FEE;
-        test_env
-            .ledger
-            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
-            .expect("Failed to call ledger canister")
-            .expect("Failed to approve payment");
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to sign");
 
-        test_env
-            .signer
-            .btc_sign_prehash(caller, request, &Some(payment_type))
+        let transaction: bitcoin::Transaction = bitcoin::consensus::deserialize(
+            &hex::decode(&response.signed_transaction_hex).expect("Invalid hex"),
+        )
+        .expect("Invalid transaction");
+        assert_eq!(transaction.output.len(), 1);
+        assert_eq!(transaction.output[0].value.to_sat(), 100_000 - 1000);
+        assert_eq!(response.fee_satoshis, 1000);
+        assert_eq!(response.change_output_index, None);
     }
 
     #[test]
-    fn can_btc_sign_prehash() {
+    fn test_btc_caller_sign_send_all_needs_a_single_output() {
         let test_env = TestSetup::default();
 
-        let request = BtcSignPrehashRequest {
-            hash: PREHASH_HEX.to_string(),
+        let mut request = SendBtcRequest {
+            send_all: Some(true),
+            ..make_test_send_request(Network::Regtest)
         };
-        let response = paid_sign_prehash(&test_env, test_env.user, &request)
-            .expect("Failed to reach signer canister")
-            .expect("Failed to sign");
+        request.outputs.push(request.outputs[0].clone());
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
 
-        // Raw 64-byte `r || s` ECDSA signature => 128 hex chars. The recovery id is left to the
-        // caller, who recovers it against the BTC public key (the key match is what makes this
-        // usable for arbitrary message / PSBT signing).
-        assert_eq!(response.signature.len(), 128);
-        assert!(hex::decode(&response.signature).is_ok());
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::SendAllWithoutSingleOutput { outputs: 2 }
+            ))
+        );
     }
 
     #[test]
-    fn cannot_btc_sign_prehash_if_hash_is_not_hex() {
+    fn test_btc_caller_sign_rejects_unverified_utxos() {
         let test_env = TestSetup::default();
 
-        let request = BtcSignPrehashRequest {
-            hash: "not a hex string".to_string(),
+        // The test UTXO is not in the UTXO set of the Bitcoin canister.
+        let request = SendBtcRequest {
+            verify_utxos: Some(BtcUtxoVerification {
+                min_confirmations: None,
+            }),
+            ..make_test_send_request(Network::Regtest)
         };
-        let response = paid_sign_prehash(&test_env, test_env.user, &request)
-            .expect("Failed to reach signer canister");
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
 
-        // Malformed input must surface as the typed error (no trap).
-        match response {
-            Err(BtcSignPrehashError::InvalidHash { msg }) => {
-                assert!(
-                    msg.contains("failed to decode hex"),
-                    "unexpected msg: {msg}"
-                );
-            }
-            other => panic!("expected InvalidHash error, got {other:?}"),
-        }
+        assert_eq!(
+            response,
+            Err(SendBtcError::UnknownUtxo {
+                txid: "ef78d159657ae0801ce4125c9c2f6623d49ca88d924140fad9ebb5b6fca7f336"
+                    .to_string(),
+                vout: 0,
+            })
+        );
     }
 
     #[test]
-    fn cannot_btc_sign_prehash_if_hash_is_not_32_bytes() {
+    fn test_btc_caller_sign_with_explicit_fee_rate() {
         let test_env = TestSetup::default();
 
-        // Valid hex, but only 31 bytes.
-        let request = BtcSignPrehashRequest {
-            hash: "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcd".to_string(),
+        let request = SendBtcRequest {
+            fee_satoshis: None,
+            fee_rate: Some(BtcFeeRate::SatPerVbyte(10)),
+            ..make_test_send_request(Network::Regtest)
         };
-        let response = paid_sign_prehash(&test_env, test_env.user, &request)
-            .expect("Failed to reach signer canister");
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
 
-        match response {
-            Err(BtcSignPrehashError::InvalidHash { msg }) => {
-                assert!(msg.contains("32-byte digest"), "unexpected msg: {msg}");
-            }
-            other => panic!("expected InvalidHash error, got {other:?}"),
-        }
+        // The fee is computed from the worst-case signature size, so the effective rate of the
+        // signed transaction is at least the requested rate, and at most ~1 vbyte per input more.
+        assert!(response.fee_rate_millisat_per_vbyte >= 10_000);
+        assert!(response.fee_rate_millisat_per_vbyte < 10_100);
     }
 
     #[test]
-    fn test_anonymous_cannot_btc_sign_prehash() {
+    fn test_btc_caller_sign_with_fee_priority() {
         let test_env = TestSetup::default();
 
-        let request = BtcSignPrehashRequest {
-            hash: PREHASH_HEX.to_string(),
+        let request = SendBtcRequest {
+            fee_satoshis: None,
+            fee_rate: Some(BtcFeeRate::Priority(BtcFeePriority::High)),
+            ..make_test_send_request(Network::Regtest)
         };
-        let result = test_env.signer.btc_sign_prehash(
-            Principal::anonymous(),
-            &request,
-            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        // The regtest network has no fee percentiles, so the default of 2 sat/vbyte applies.
+        assert!(response.fee_rate_millisat_per_vbyte >= 2_000);
+        assert!(response.fee_satoshis > 0);
+    }
+
+    #[test]
+    fn test_btc_caller_sign_rejects_conflicting_fee_arguments() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            fee_satoshis: Some(1000),
+            fee_rate: Some(BtcFeeRate::SatPerVbyte(10)),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::ConflictingFeeArguments
+            ))
         );
+    }
 
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .contains("Anonymous caller not authorized"));
+    #[test]
+    fn test_btc_caller_sign_rejects_fee_above_share_of_sent_value() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            fee_satoshis: Some(40_000),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::FeeTooHighForSentValue {
+                    fee_satoshis: 40_000,
+                    sent_satoshis: 50_000,
+                    max_fee_percent_of_sent: 50,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_sign_rejects_fee_rate_far_above_network() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            fee_satoshis: None,
+            fee_rate: Some(BtcFeeRate::SatPerVbyte(100)),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
+
+        // The regtest network has no fee percentiles, so the limit is ten times 2 sat/vbyte.
+        assert!(matches!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::FeeRateTooHigh {
+                    max_fee_rate_millisat_per_vbyte: 20_000,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_btc_caller_sign_allows_high_fee_when_requested() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            fee_satoshis: Some(40_000),
+            allow_high_fee: Some(true),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        assert_eq!(response.fee_satoshis, 40_000);
+    }
+
+    #[test]
+    fn test_btc_caller_sign_rejects_dust_output() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            outputs: vec![BtcTxOutput::Payment {
+                destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                sent_satoshis: 500,
+            }],
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::DustOutput {
+                    address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                    sent_satoshis: 500,
+                    dust_threshold: 1_000,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_sign_signals_rbf_unless_disabled() {
+        let test_env = TestSetup::default();
+
+        let decode = |response: SignBtcResponse| -> bitcoin::Transaction {
+            bitcoin::consensus::deserialize(&hex::decode(response.signed_transaction_hex).unwrap())
+                .expect("Failed to decode signed transaction")
+        };
+
+        let response = paid_caller_sign(
+            &test_env,
+            test_env.user,
+            &make_test_send_request(Network::Regtest),
+        )
+        .expect("Failed to call btc_caller_sign")
+        .expect("Failed to get successful sign response");
+        assert!(decode(response).is_explicitly_rbf());
+
+        let request = SendBtcRequest {
+            disable_rbf: Some(true),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+        assert!(!decode(response).is_explicitly_rbf());
+    }
+
+    #[test]
+    fn test_btc_caller_sign_embeds_op_return_data() {
+        let test_env = TestSetup::default();
+
+        let data = b"document hash".to_vec();
+        let mut request = make_test_send_request(Network::Regtest);
+        request.outputs.insert(
+            0,
+            BtcTxOutput::Data {
+                data: serde_bytes::ByteBuf::from(data.clone()),
+            },
+        );
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        let transaction: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(response.signed_transaction_hex).unwrap())
+                .expect("Failed to decode signed transaction");
+        // The outputs are in the requested order.
+        let data_output = &transaction.output[0];
+        assert!(data_output.script_pubkey.is_op_return());
+        assert_eq!(data_output.value.to_sat(), 0);
+        assert_eq!(transaction.output[1].value.to_sat(), 50_000);
+        assert!(data_output.script_pubkey.as_bytes().ends_with(&data));
+    }
+
+    #[test]
+    fn test_btc_caller_sign_accepts_op_return_data_without_payments() {
+        let test_env = TestSetup::default();
+
+        // No value is sent, so the fee cannot be compared with the sent value.
+        let request = SendBtcRequest {
+            outputs: vec![BtcTxOutput::Data {
+                data: serde_bytes::ByteBuf::from(b"document hash".to_vec()),
+            }],
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        let transaction: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(response.signed_transaction_hex).unwrap())
+                .expect("Failed to decode signed transaction");
+        assert_eq!(
+            transaction.output.len(),
+            2,
+            "Expected the data and the change"
+        );
+        assert!(transaction.output[0].script_pubkey.is_op_return());
+        assert_eq!(transaction.output[1].value.to_sat(), 99_000);
+    }
+
+    #[test]
+    fn test_btc_caller_sign_uses_requested_sighash_type() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            sighash_types: Some(vec![BtcSighashType::AllPlusAnyoneCanPay]),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        let transaction: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(response.signed_transaction_hex).unwrap())
+                .expect("Failed to decode signed transaction");
+        let signature = transaction.input[0]
+            .witness
+            .nth(0)
+            .expect("Expected a signature in the witness");
+        assert_eq!(
+            signature.last(),
+            Some(&(bitcoin::EcdsaSighashType::AllPlusAnyoneCanPay as u8))
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_sign_rejects_wrong_number_of_sighash_types() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            sighash_types: Some(vec![BtcSighashType::All, BtcSighashType::All]),
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::WrongNumberOfSighashTypes {
+                    inputs: 1,
+                    sighash_types: 2,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_sign_on_signet_uses_default_fee_rate() {
+        let test_env = TestSetup::default();
+
+        // There is no Bitcoin canister for Signet, so the fee priority uses the default rate.
+        let request = SendBtcRequest {
+            fee_satoshis: None,
+            outputs: vec![BtcTxOutput::Payment {
+                destination_address: CALLER_BTC_ADDRESS_TESTNET.to_string(),
+                sent_satoshis: 50_000,
+            }],
+            ..make_test_send_request(Network::Signet)
+        };
+        let response = paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        assert!(hex::decode(&response.signed_transaction_hex).is_ok());
+        assert!(response.fee_rate_millisat_per_vbyte >= 2000);
+    }
+
+    #[test]
+    fn test_btc_caller_send_rejects_signet_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the network is checked before charging.
+        let response = test_env
+            .signer
+            .btc_caller_send(
+                test_env.user,
+                &make_test_send_request(Network::Signet),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_send");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::NoBitcoinCanister {
+                network: Network::Signet
+            })
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_send_rejects_sighash_none_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the sighash types are checked before charging.
+        for sighash_type in [BtcSighashType::None, BtcSighashType::NonePlusAnyoneCanPay] {
+            let request = SendBtcRequest {
+                sighash_types: Some(vec![sighash_type]),
+                ..make_test_send_request(Network::Regtest)
+            };
+            let response = test_env
+                .signer
+                .btc_caller_send(
+                    test_env.user,
+                    &request,
+                    &Some(PaymentType::CallerPaysIcrc2Cycles),
+                )
+                .expect("Failed to call btc_caller_send");
+
+            assert_eq!(
+                response,
+                Err(SendBtcError::BuildP2wpkhError(
+                    BuildP2wpkhTxError::SighashNoneNotAllowed { input_index: 0 }
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_btc_caller_sign_rejects_vault_without_payment() {
+        let test_env = TestSetup::default();
+        let request = SendBtcRequest {
+            address_type: BitcoinAddressType::P2WSHVault(BtcVaultPolicy {
+                recovery_public_key: "02".repeat(33),
+                recovery_delay_blocks: 144,
+                cosigner_public_key: "03".repeat(33),
+            }),
+            ..make_test_send_request(Network::Regtest)
+        };
+
+        // No payment is approved: the address type is checked before charging.
+        let response = test_env
+            .signer
+            .btc_caller_sign(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_sign");
+
+        assert!(matches!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::NotP2WPKHSourceAddress
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_btc_caller_send_rejects_large_op_return_data_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the data is checked before charging.
+        let request = SendBtcRequest {
+            outputs: vec![BtcTxOutput::Data {
+                data: serde_bytes::ByteBuf::from(vec![0; 81]),
+            }],
+            ..make_test_send_request(Network::Regtest)
+        };
+        let response = test_env
+            .signer
+            .btc_caller_send(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_send");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::OpReturnDataTooLarge {
+                    data_bytes: 81,
+                    max_data_bytes: 80,
+                }
+            ))
+        );
+    }
+}
+
+mod pending_transactions {
+    use super::*;
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_pending_transactions() {
+        let test_env = TestSetup::default();
+
+        let response = test_env
+            .signer
+            .btc_pending_transactions(Principal::anonymous());
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn test_unsent_transaction_releases_its_inputs() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved, so the transaction is never sent.
+        let send = || {
+            test_env
+                .signer
+                .btc_caller_send(
+                    test_env.user,
+                    &caller_sign::make_test_send_request(Network::Regtest),
+                    &Some(PaymentType::CallerPaysIcrc2Cycles),
+                )
+                .expect("Failed to call btc_caller_send")
+        };
+
+        assert!(matches!(send(), Err(SendBtcError::PaymentError(_))));
+        // The inputs were released, so the same inputs are not reported as reserved.
+        assert!(matches!(send(), Err(SendBtcError::PaymentError(_))));
+        let pending = test_env
+            .signer
+            .btc_pending_transactions(test_env.user)
+            .expect("Failed to call btc_pending_transactions");
+        assert_eq!(pending.reserved_utxos, vec![]);
+    }
+}
+
+mod consolidate {
+    use super::*;
+
+    fn make_test_consolidate_request() -> BtcConsolidateRequest {
+        BtcConsolidateRequest {
+            network: Network::Regtest,
+            utxos: Some(caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend),
+            min_confirmations: None,
+            max_inputs: None,
+            destination_address: None,
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_consolidate() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_consolidate(
+            Principal::anonymous(),
+            &make_test_consolidate_request(),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn test_btc_consolidate_rejects_too_many_inputs_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the number of inputs is checked before charging.
+        let request = BtcConsolidateRequest {
+            utxos: None,
+            max_inputs: Some(101),
+            ..make_test_consolidate_request()
+        };
+        let response = test_env
+            .signer
+            .btc_consolidate(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_consolidate");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::TooManyInputs {
+                    inputs: 101,
+                    max_inputs: 100,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unpaid_consolidation_releases_its_inputs() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved, so the transaction is never sent.
+        let response = test_env
+            .signer
+            .btc_consolidate(
+                test_env.user,
+                &make_test_consolidate_request(),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_consolidate");
+
+        assert!(matches!(response, Err(SendBtcError::PaymentError(_))));
+        let pending = test_env
+            .signer
+            .btc_pending_transactions(test_env.user)
+            .expect("Failed to call btc_pending_transactions");
+        assert_eq!(pending.reserved_utxos, vec![]);
+    }
+}
+
+mod deposit_to_ckbtc {
+    use super::*;
+    use crate::canister::ckbtc_minter;
+
+    /// The deposit address of every account at the stand-in ckBTC minter.
+    const DEPOSIT_ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn make_test_deposit_request() -> BtcDepositToCkbtcRequest {
+        BtcDepositToCkbtcRequest {
+            network: Network::Regtest,
+            owner: None,
+            subaccount: None,
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            deposit_satoshis: 50_000,
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+            send_all: None,
+            update_balance: Some(true),
+        }
+    }
+
+    /// A standard btc_deposit_to_ckbtc() call, including payment.
+    fn paid_deposit_to_ckbtc(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcDepositToCkbtcRequest,
+    ) -> Result<Result<BtcDepositToCkbtcResponse, SendBtcError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        let amount: u128 =
+            SignerMethods::BtcDepositToCkbtc.btc_fee_for_tx(n_inputs, 2) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_deposit_to_ckbtc(caller, request, &Some(payment_type))
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_deposit_to_ckbtc() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_deposit_to_ckbtc(
+            Principal::anonymous(),
+            &make_test_deposit_request(),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn test_btc_deposit_to_ckbtc_rejects_invalid_subaccount_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the account is checked before charging.
+        let request = BtcDepositToCkbtcRequest {
+            subaccount: Some(serde_bytes::ByteBuf::from(vec![1; 31])),
+            ..make_test_deposit_request()
+        };
+        let response = test_env
+            .signer
+            .btc_deposit_to_ckbtc(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_deposit_to_ckbtc");
+
+        assert_eq!(
+            response,
+            Err(SendBtcError::CkbtcMinterError {
+                msg: "the subaccount must be 32 bytes, got 31".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unpaid_deposit_does_not_call_the_minter() {
+        let test_env = TestSetup::default();
+
+        let response = test_env
+            .signer
+            .btc_deposit_to_ckbtc(
+                test_env.user,
+                &make_test_deposit_request(),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_deposit_to_ckbtc");
+
+        assert!(matches!(response, Err(SendBtcError::PaymentError(_))));
+        let calls = test_env
+            .ckbtc_minter
+            .get_btc_address_calls(test_env.user)
+            .expect("Failed to call get_btc_address_calls");
+        assert_eq!(calls, vec![]);
+    }
+
+    #[test]
+    fn test_btc_deposit_to_ckbtc_sends_to_the_deposit_address_of_the_caller() {
+        let test_env = TestSetup::default();
+
+        let response =
+            paid_deposit_to_ckbtc(&test_env, test_env.user, &make_test_deposit_request())
+                .expect("Failed to call btc_deposit_to_ckbtc")
+                .expect("Failed to deposit");
+
+        assert_eq!(response.deposit_address, DEPOSIT_ADDRESS);
+        assert_eq!(response.fee_satoshis, 1000);
+        assert!(!response.txid.is_empty());
+        // The transaction was only just sent, so the minter has nothing to mint yet.
+        assert_eq!(
+            response.update_balance,
+            Some(CkbtcUpdateBalance::NoNewUtxos {
+                required_confirmations: 6,
+                current_confirmations: None,
+            })
+        );
+        let calls = test_env
+            .ckbtc_minter
+            .get_btc_address_calls(test_env.user)
+            .expect("Failed to call get_btc_address_calls");
+        assert_eq!(
+            calls,
+            vec![ckbtc_minter::Account {
+                owner: Some(test_env.user),
+                subaccount: None,
+            }]
+        );
+    }
+}
+
+mod signed_transactions {
+    use super::*;
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_list_signed_transactions() {
+        let test_env = TestSetup::default();
+
+        let response = test_env
+            .signer
+            .btc_list_signed_transactions(Principal::anonymous());
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn test_btc_caller_sign_records_the_signed_transaction() {
+        let test_env = TestSetup::default();
+
+        let signed = caller_sign::paid_caller_sign(
+            &test_env,
+            test_env.user,
+            &caller_sign::make_test_send_request(Network::Regtest),
+        )
+        .expect("Failed to call btc_caller_sign")
+        .expect("Failed to sign");
+
+        let listed = test_env
+            .signer
+            .btc_list_signed_transactions(test_env.user)
+            .expect("Failed to call btc_list_signed_transactions")
+            .transactions;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].txid, signed.txid);
+        assert_eq!(
+            listed[0].signed_transaction_hex,
+            signed.signed_transaction_hex
+        );
+        assert_eq!(listed[0].fee_satoshis, signed.fee_satoshis);
+        assert_eq!(listed[0].network, Network::Regtest);
+        assert_eq!(listed[0].status, BtcSignedTransactionStatus::Signed);
+
+        // Other callers cannot see the transaction.
+        let other_listed = test_env
+            .signer
+            .btc_list_signed_transactions(Principal::from_slice(&[1, 2, 3]))
+            .expect("Failed to call btc_list_signed_transactions")
+            .transactions;
+        assert_eq!(other_listed, vec![]);
+    }
+
+    #[test]
+    fn test_btc_rebroadcast_rejects_unknown_transaction_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the unknown txid must be rejected before charging.
+        let response = test_env
+            .signer
+            .btc_rebroadcast(
+                test_env.user,
+                &BtcRebroadcastRequest {
+                    txid: "ab".repeat(32),
+                },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_rebroadcast");
+
+        assert_eq!(response, Err(BtcRebroadcastError::UnknownTransaction));
+    }
+}
+
+mod decode_transaction {
+    use super::*;
+
+    #[test]
+    fn test_btc_decode_transaction_reviews_a_signed_transaction() {
+        let test_env = TestSetup::default();
+        let request = caller_sign::make_test_send_request(Network::Regtest);
+        let signed = caller_sign::paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to sign");
+
+        // The query is free: no payment is approved.
+        let decoded = test_env
+            .signer
+            .btc_decode_transaction(
+                test_env.user,
+                &BtcDecodeTransactionRequest {
+                    network: Network::Regtest,
+                    transaction_hex: signed.signed_transaction_hex.clone(),
+                    utxos: Some(request.utxos_to_spend.clone()),
+                    own_address: Some(CALLER_BTC_ADDRESS_REGTEST.to_string()),
+                },
+            )
+            .expect("Failed to call btc_decode_transaction")
+            .expect("Failed to decode the transaction");
+
+        assert_eq!(decoded.txid, signed.txid);
+        assert!(!decoded.is_psbt);
+        assert!(decoded.inputs.iter().all(|input| input.is_signed));
+        assert_eq!(decoded.fee_satoshis, Some(signed.fee_satoshis));
+        assert_eq!(
+            decoded.fee_rate_millisat_per_vbyte,
+            Some(signed.fee_rate_millisat_per_vbyte)
+        );
+        assert!(decoded.signals_rbf);
+        assert_eq!(decoded.outputs[0].value, 50_000);
+        assert_eq!(
+            decoded.outputs[0].address.as_deref(),
+            Some(CALLER_BTC_ADDRESS_REGTEST)
+        );
+        assert!(decoded.outputs.iter().all(|output| output.pays_caller));
+    }
+
+    #[test]
+    fn test_btc_decode_transaction_rejects_invalid_hex() {
+        let test_env = TestSetup::default();
+
+        let response = test_env
+            .signer
+            .btc_decode_transaction(
+                test_env.user,
+                &BtcDecodeTransactionRequest {
+                    network: Network::Regtest,
+                    transaction_hex: "not hex".to_string(),
+                    utxos: None,
+                    own_address: None,
+                },
+            )
+            .expect("Failed to call btc_decode_transaction");
+
+        assert!(matches!(
+            response,
+            Err(BtcDecodeTransactionError::InvalidTransaction { .. })
+        ));
+    }
+}
+
+mod signing_jobs {
+    use super::*;
+
+    /// A standard btc_caller_sign_job() call, including payment.
+    fn paid_caller_sign_job(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &SendBtcRequest,
+    ) -> Result<Result<BtcJobResponse, SendBtcError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        let amount: u128 = SignerMethods::BtcCallerSign.btc_fee_for_inputs(n_inputs) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_caller_sign_job(caller, request, &Some(payment_type))
+    }
+
+    fn job_status(
+        test_env: &TestSetup,
+        caller: Principal,
+        job_id: u64,
+    ) -> Result<BtcJobStatus, BtcJobStatusError> {
+        test_env
+            .signer
+            .btc_job_status(caller, &BtcJobStatusRequest { job_id })
+            .expect("Failed to call btc_job_status")
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_caller_sign_job() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_caller_sign_job(
+            Principal::anonymous(),
+            &caller_sign::make_test_send_request(Network::Regtest),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    fn test_btc_job_status_rejects_unknown_job() {
+        let test_env = TestSetup::default();
+
+        assert_eq!(
+            job_status(&test_env, test_env.user, 0),
+            Err(BtcJobStatusError::UnknownJob)
+        );
+    }
+
+    #[test]
+    fn test_btc_caller_send_job_rejects_signet_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the network is checked before charging.
+        let response = test_env
+            .signer
+            .btc_caller_send_job(
+                test_env.user,
+                &caller_sign::make_test_send_request(Network::Signet),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_send_job");
+
+        assert!(matches!(
+            response,
+            Err(SendBtcError::NoBitcoinCanister {
+                network: Network::Signet
+            })
+        ));
+    }
+
+    #[test]
+    fn test_btc_caller_send_job_rejects_vault_without_payment() {
+        let test_env = TestSetup::default();
+        let mut request = caller_sign::make_test_send_request(Network::Regtest);
+        request.address_type = BitcoinAddressType::P2WSHVault(BtcVaultPolicy {
+            recovery_public_key: "02".repeat(33),
+            recovery_delay_blocks: 144,
+            cosigner_public_key: "03".repeat(33),
+        });
+
+        // No payment is approved: the request must be rejected before charging.
+        let response = test_env
+            .signer
+            .btc_caller_send_job(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_send_job");
+
+        assert!(matches!(
+            response,
+            Err(SendBtcError::BuildP2wpkhError(
+                BuildP2wpkhTxError::NotP2WPKHSourceAddress
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_btc_caller_sign_job_signs_in_timers() {
+        let test_env = TestSetup::default();
+        let request = caller_sign::make_test_send_request(Network::Regtest);
+
+        let job_id = paid_caller_sign_job(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign_job")
+            .expect("Failed to start the job")
+            .job_id;
+        // Other callers cannot see the job.
+        assert_eq!(
+            job_status(&test_env, Principal::from_slice(&[1, 2, 3]), job_id),
+            Err(BtcJobStatusError::UnknownJob)
+        );
+
+        let mut status = job_status(&test_env, test_env.user, job_id).expect("Unknown job");
+        for _ in 0..50 {
+            if !matches!(status, BtcJobStatus::Signing { .. }) {
+                break;
+            }
+            test_env.pic.tick();
+            status = job_status(&test_env, test_env.user, job_id).expect("Unknown job");
+        }
+        let BtcJobStatus::Completed(signed_by_job) = status else {
+            panic!("The job did not complete: {status:?}");
+        };
+
+        // The job signs the same transaction as `btc_caller_sign`.
+        let signed = caller_sign::paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to sign");
+        assert_eq!(signed_by_job.txid, signed.txid);
+        assert_eq!(signed_by_job.fee_satoshis, signed.fee_satoshis);
+    }
+}
+
+mod sign_prehash {
+    use super::*;
+
+    // An arbitrary 32-byte digest (hex), as `btc_sign_prehash` expects.
+    const PREHASH_HEX: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    /// A standard btc_sign_prehash() call, including payment.
+    fn paid_sign_prehash(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcSignPrehashRequest,
+    ) -> Result<Result<BtcSignPrehashResponse, BtcSignPrehashError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = SignerMethods::BtcSignPrehash.fee() + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_sign_prehash(caller, request, &Some(payment_type))
+    }
+
+    #[test]
+    fn can_btc_sign_prehash() {
+        let test_env = TestSetup::default();
+
+        let request = BtcSignPrehashRequest {
+            hash: PREHASH_HEX.to_string(),
+        };
+        let response = paid_sign_prehash(&test_env, test_env.user, &request)
+            .expect("Failed to reach signer canister")
+            .expect("Failed to sign");
+
+        // Raw 64-byte `r || s` ECDSA signature => 128 hex chars. The recovery id is left to the
+        // caller, who recovers it against the BTC public key (the key match is what makes this
+        // usable for arbitrary message / PSBT signing).
+        assert_eq!(response.signature.len(), 128);
+        assert!(hex::decode(&response.signature).is_ok());
+    }
+
+    #[test]
+    fn cannot_btc_sign_prehash_if_hash_is_not_hex() {
+        let test_env = TestSetup::default();
+
+        let request = BtcSignPrehashRequest {
+            hash: "not a hex string".to_string(),
+        };
+        let response = paid_sign_prehash(&test_env, test_env.user, &request)
+            .expect("Failed to reach signer canister");
+
+        // Malformed input must surface as the typed error (no trap).
+        match response {
+            Err(BtcSignPrehashError::InvalidHash { msg }) => {
+                assert!(
+                    msg.contains("failed to decode hex"),
+                    "unexpected msg: {msg}"
+                );
+            }
+            other => panic!("expected InvalidHash error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn cannot_btc_sign_prehash_if_hash_is_not_32_bytes() {
+        let test_env = TestSetup::default();
+
+        // Valid hex, but only 31 bytes.
+        let request = BtcSignPrehashRequest {
+            hash: "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcd".to_string(),
+        };
+        let response = paid_sign_prehash(&test_env, test_env.user, &request)
+            .expect("Failed to reach signer canister");
+
+        match response {
+            Err(BtcSignPrehashError::InvalidHash { msg }) => {
+                assert!(msg.contains("32-byte digest"), "unexpected msg: {msg}");
+            }
+            other => panic!("expected InvalidHash error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_anonymous_cannot_btc_sign_prehash() {
+        let test_env = TestSetup::default();
+
+        let request = BtcSignPrehashRequest {
+            hash: PREHASH_HEX.to_string(),
+        };
+        let result = test_env.signer.btc_sign_prehash(
+            Principal::anonymous(),
+            &request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .contains("Anonymous caller not authorized"));
+    }
+}
+
+mod bump_fee {
+    use super::*;
+
+    fn make_test_bump_fee_request(signed_transaction_hex: String) -> BtcBumpFeeRequest {
+        BtcBumpFeeRequest {
+            network: Network::Regtest,
+            address_type: BitcoinAddressType::P2WPKH,
+            signed_transaction_hex: Some(signed_transaction_hex),
+            txid: None,
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            fee_rate: BtcFeeRate::SatPerVbyte(10),
+            mode: BtcBumpFeeMode::Rbf,
+            allow_high_fee: None,
+        }
+    }
+
+    /// A standard btc_bump_fee() call, including payment for a replacement of the given shape.
+    fn paid_bump_fee(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcBumpFeeRequest,
+        n_inputs: u64,
+        n_outputs: u64,
+    ) -> Result<Result<BtcBumpFeeResponse, BtcBumpFeeError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 =
+            SignerMethods::BtcBumpFee.btc_fee_for_tx(n_inputs, n_outputs) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_bump_fee(caller, request, &Some(payment_type))
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_bump_fee() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_bump_fee(
+            Principal::anonymous(),
+            &make_test_bump_fee_request("00".to_string()),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+        assert_eq!(
+            response.unwrap_err(),
+            "Update call error. RejectionCode: CanisterReject, Error: Update call error. RejectionCode: CanisterReject, Error: Anonymous caller not authorized.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_btc_bump_fee_rejects_invalid_transaction_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the transaction is decoded before charging.
+        let response = test_env
+            .signer
+            .btc_bump_fee(
+                test_env.user,
+                &make_test_bump_fee_request("not a transaction".to_string()),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_bump_fee");
+
+        match response {
+            Err(BtcBumpFeeError::InvalidTransaction { msg }) => {
+                assert!(
+                    msg.contains("failed to decode hex"),
+                    "unexpected msg: {msg}"
+                );
+            }
+            other => panic!("expected InvalidTransaction error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_btc_bump_fee_rejects_non_replaceable_transaction() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            disable_rbf: Some(true),
+            ..caller_sign::make_test_send_request(Network::Regtest)
+        };
+        let signed = caller_sign::paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        let response = paid_bump_fee(
+            &test_env,
+            test_env.user,
+            &make_test_bump_fee_request(signed.signed_transaction_hex),
+            1,
+            2,
+        )
+        .expect("Failed to call btc_bump_fee");
+
+        assert_eq!(response, Err(BtcBumpFeeError::NotReplaceable));
+    }
+
+    #[test]
+    fn test_btc_bump_fee_finds_the_original_transaction_by_txid() {
+        let test_env = TestSetup::default();
+
+        let request = SendBtcRequest {
+            disable_rbf: Some(true),
+            ..caller_sign::make_test_send_request(Network::Regtest)
+        };
+        let signed = caller_sign::paid_caller_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_caller_sign")
+            .expect("Failed to get successful sign response");
+
+        // The stored transaction is found and decoded: it does not signal replace-by-fee.
+        let response = paid_bump_fee(
+            &test_env,
+            test_env.user,
+            &BtcBumpFeeRequest {
+                signed_transaction_hex: None,
+                txid: Some(signed.txid.clone()),
+                ..make_test_bump_fee_request(String::new())
+            },
+            1,
+            2,
+        )
+        .expect("Failed to call btc_bump_fee");
+        assert_eq!(response, Err(BtcBumpFeeError::NotReplaceable));
+
+        // Another caller has no transaction with this txid, and is not charged.
+        let response = test_env
+            .signer
+            .btc_bump_fee(
+                test_env.user2,
+                &BtcBumpFeeRequest {
+                    signed_transaction_hex: None,
+                    txid: Some(signed.txid),
+                    ..make_test_bump_fee_request(String::new())
+                },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_bump_fee");
+        assert_eq!(response, Err(BtcBumpFeeError::UnknownTransaction));
+    }
+}
+
+mod multisig {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, psbt::Psbt, transaction::Version, Amount, OutPoint,
+        PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    use super::*;
+
+    /// A compressed public key of a cosigner outside the signer canister.
+    const COSIGNER_PUBLIC_KEY: &str =
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn make_test_multisig_request() -> BtcMultisigAddressRequest {
+        BtcMultisigAddressRequest {
+            network: Network::Regtest,
+            cosigner_public_keys: vec![COSIGNER_PUBLIC_KEY.to_string()],
+            threshold: 2,
+        }
+    }
+
+    /// A standard btc_multisig_address() call, including payment.
+    fn paid_multisig_address(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcMultisigAddressRequest,
+    ) -> Result<Result<BtcMultisigAddressResponse, BtcMultisigAddressError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = SignerMethods::BtcMultisigAddress.fee() + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_multisig_address(caller, request, &Some(payment_type))
+    }
+
+    /// A standard btc_sign_psbt() call, including payment for the given number of inputs.
+    fn paid_sign_psbt(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcSignPsbtRequest,
+        n_inputs: u64,
+    ) -> Result<Result<BtcSignPsbtResponse, BtcSignPsbtError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = SignerMethods::BtcSignPsbt.btc_fee_for_inputs(n_inputs) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_sign_psbt(caller, request, &Some(payment_type))
+    }
+
+    /// A PSBT that spends a P2WSH output with the given witness script.
+    fn make_test_psbt(witness_script: ScriptBuf) -> Psbt {
+        let transaction = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: 0,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(transaction).expect("Expected an unsigned tx");
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script);
+        psbt
+    }
+
+    #[test]
+    fn test_btc_multisig_address_includes_caller_key() {
+        let test_env = TestSetup::default();
+
+        let response =
+            paid_multisig_address(&test_env, test_env.user, &make_test_multisig_request())
+                .expect("Failed to call btc_multisig_address")
+                .expect("Failed to get multisig address");
+
+        assert!(
+            response.address.starts_with("bcrt1q"),
+            "unexpected address: {}",
+            response.address
+        );
+        assert_eq!(response.public_keys.len(), 2);
+        assert!(response
+            .public_keys
+            .contains(&COSIGNER_PUBLIC_KEY.to_string()));
+        // The caller's key is the one that the canister added.
+        assert!(response
+            .public_keys
+            .iter()
+            .any(|public_key| public_key != COSIGNER_PUBLIC_KEY));
+    }
+
+    #[test]
+    fn test_btc_multisig_address_rejects_invalid_threshold_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the threshold is checked before charging.
+        let request = BtcMultisigAddressRequest {
+            threshold: 3,
+            ..make_test_multisig_request()
+        };
+        let response = test_env
+            .signer
+            .btc_multisig_address(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_multisig_address");
+
+        assert_eq!(
+            response,
+            Err(BtcMultisigAddressError::InvalidThreshold {
+                threshold: 3,
+                public_keys: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_btc_sign_psbt_adds_caller_signature() {
+        let test_env = TestSetup::default();
+        let account =
+            paid_multisig_address(&test_env, test_env.user, &make_test_multisig_request())
+                .expect("Failed to call btc_multisig_address")
+                .expect("Failed to get multisig address");
+        let witness_script = ScriptBuf::from_bytes(
+            hex::decode(&account.witness_script_hex).expect("Invalid witness script hex"),
+        );
+
+        let request = BtcSignPsbtRequest {
+            psbt_hex: hex::encode(make_test_psbt(witness_script).serialize()),
+        };
+        let response = paid_sign_psbt(&test_env, test_env.user, &request, 1)
+            .expect("Failed to call btc_sign_psbt")
+            .expect("Failed to sign PSBT");
+
+        assert_eq!(response.signed_inputs, vec![0]);
+        let psbt = Psbt::deserialize(&hex::decode(&response.psbt_hex).expect("Invalid PSBT hex"))
+            .expect("Invalid PSBT");
+        let signers: Vec<String> = psbt.inputs[0]
+            .partial_sigs
+            .keys()
+            .map(PublicKey::to_string)
+            .collect();
+        assert_eq!(signers.len(), 1);
+        assert!(account.public_keys.contains(&signers[0]));
+        assert_ne!(signers[0], COSIGNER_PUBLIC_KEY);
+    }
+
+    #[test]
+    fn test_btc_sign_psbt_rejects_psbt_without_caller_key() {
+        let test_env = TestSetup::default();
+
+        // The input can only be spent by the cosigner.
+        let cosigner_public_key =
+            PublicKey::from_str(COSIGNER_PUBLIC_KEY).expect("Invalid cosigner public key");
+        let witness_script = ScriptBuf::new_p2pk(&cosigner_public_key);
+        let request = BtcSignPsbtRequest {
+            psbt_hex: hex::encode(make_test_psbt(witness_script).serialize()),
+        };
+        let response = paid_sign_psbt(&test_env, test_env.user, &request, 1)
+            .expect("Failed to call btc_sign_psbt");
+
+        assert_eq!(response, Err(BtcSignPsbtError::NoInputsToSign));
+    }
+
+    #[test]
+    fn test_btc_sign_psbt_rejects_invalid_psbt_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the PSBT is decoded before charging.
+        let response = test_env
+            .signer
+            .btc_sign_psbt(
+                test_env.user,
+                &BtcSignPsbtRequest {
+                    psbt_hex: "deadbeef".to_string(),
+                },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_sign_psbt");
+
+        match response {
+            Err(BtcSignPsbtError::InvalidPsbt { msg }) => {
+                assert!(
+                    msg.contains("failed to decode PSBT"),
+                    "unexpected msg: {msg}"
+                );
+            }
+            other => panic!("expected InvalidPsbt error, got {other:?}"),
+        }
+    }
+}
+
+mod vault {
+    use std::str::FromStr;
+
+    use bitcoin::psbt::Psbt;
+
+    use super::*;
+
+    fn make_test_vault_policy() -> BtcVaultPolicy {
+        BtcVaultPolicy {
+            cosigner_public_key:
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".to_string(),
+            recovery_public_key:
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5".to_string(),
+            recovery_delay_blocks: 144,
+        }
+    }
+
+    fn vault_address(test_env: &TestSetup) -> String {
+        address::paid_caller_address(
+            test_env,
+            test_env.user,
+            &GetAddressRequest {
+                network: Network::Regtest,
+                address_type: BitcoinAddressType::P2WSHVault(make_test_vault_policy()),
+            },
+        )
+        .expect("Failed to call btc_caller_address")
+        .expect("Failed to get vault address")
+        .address
+    }
+
+    /// A standard btc_vault_sign() call, including payment.
+    fn paid_vault_sign(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcVaultSignRequest,
+    ) -> Result<Result<BtcSignPsbtResponse, SendBtcError>, String> {
+        let payment_type = PaymentType::CallerPaysIcrc2Cycles;
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        let amount: u128 = SignerMethods::BtcVaultSign.btc_fee_for_inputs(n_inputs) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_vault_sign(caller, request, &Some(payment_type))
+    }
+
+    #[test]
+    fn test_caller_vault_address_is_p2wsh() {
+        let test_env = TestSetup::default();
+
+        let address = vault_address(&test_env);
+
+        // A P2WSH address commits to a 32-byte script hash, so it is longer than the P2WPKH one.
+        assert!(
+            address.starts_with("bcrt1q"),
+            "unexpected address: {address}"
+        );
+        assert_eq!(address.len(), 64);
+        assert_ne!(address, CALLER_BTC_ADDRESS_REGTEST);
+    }
+
+    #[test]
+    fn test_caller_vault_address_rejects_invalid_policy_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the policy is checked before charging.
+        let policy = BtcVaultPolicy {
+            recovery_delay_blocks: 0,
+            ..make_test_vault_policy()
+        };
+        let response = test_env
+            .signer
+            .btc_caller_address(
+                test_env.user,
+                &GetAddressRequest {
+                    network: Network::Regtest,
+                    address_type: BitcoinAddressType::P2WSHVault(policy),
+                },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_caller_address");
+
+        assert_eq!(
+            response,
+            Err(GetAddressError::InvalidVaultPolicy {
+                msg: "recovery_delay_blocks must not be zero".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_btc_vault_sign_returns_psbt_signed_by_caller() {
+        let test_env = TestSetup::default();
+        let address = vault_address(&test_env);
+
+        let request = BtcVaultSignRequest {
+            network: Network::Regtest,
+            vault: make_test_vault_policy(),
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            outputs: vec![BtcTxOutput::Payment {
+                destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                sent_satoshis: 50_000,
+            }],
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        };
+        let response = paid_vault_sign(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_vault_sign")
+            .expect("Failed to sign vault transaction");
+
+        assert_eq!(response.signed_inputs, vec![0]);
+        let psbt = Psbt::deserialize(&hex::decode(&response.psbt_hex).expect("Invalid PSBT hex"))
+            .expect("Invalid PSBT");
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+        // The change goes back to the vault.
+        let change = &psbt.unsigned_tx.output[1];
+        let vault_script_pubkey = bitcoin::Address::from_str(&address)
+            .expect("Invalid vault address")
+            .assume_checked()
+            .script_pubkey();
+        assert_eq!(change.script_pubkey, vault_script_pubkey);
+        assert_eq!(change.value.to_sat(), 100_000 - 50_000 - 1000);
+    }
+}
+
+mod htlc {
+    use bitcoin::{
+        absolute::LockTime,
+        consensus::deserialize,
+        hashes::{sha256, Hash},
+        Transaction,
+    };
+
+    use super::*;
+
+    const PREIMAGE: [u8; 32] = [7; 32];
+    const TIMEOUT_BLOCK_HEIGHT: u32 = 1000;
+
+    fn make_test_htlc(caller_role: BtcHtlcRole) -> BtcHtlc {
+        BtcHtlc {
+            counterparty_public_key:
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".to_string(),
+            payment_hash: hex::encode(sha256::Hash::hash(&PREIMAGE).to_byte_array()),
+            timeout_block_height: TIMEOUT_BLOCK_HEIGHT,
+            caller_role,
+        }
+    }
+
+    fn make_test_claim_request(preimage: &[u8]) -> BtcHtlcClaimRequest {
+        BtcHtlcClaimRequest {
+            network: Network::Regtest,
+            htlc: make_test_htlc(BtcHtlcRole::Recipient),
+            preimage: hex::encode(preimage),
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    fn approve_payment(test_env: &TestSetup, caller: Principal, fee: u128) {
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = fee + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+    }
+
+    /// A standard btc_htlc_address() call, including payment.
+    fn paid_htlc_address(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcHtlcAddressRequest,
+    ) -> Result<Result<BtcHtlcAddressResponse, BtcHtlcError>, String> {
+        approve_payment(test_env, caller, SignerMethods::BtcHtlcAddress.fee());
+        test_env
+            .signer
+            .btc_htlc_address(caller, request, &Some(PaymentType::CallerPaysIcrc2Cycles))
+    }
+
+    /// A standard btc_htlc_claim() call, including payment.
+    fn paid_htlc_claim(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcHtlcClaimRequest,
+    ) -> Result<Result<SignBtcResponse, BtcHtlcError>, String> {
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        approve_payment(
+            test_env,
+            caller,
+            SignerMethods::BtcHtlcClaim.btc_fee_for_inputs(n_inputs),
+        );
+        test_env
+            .signer
+            .btc_htlc_claim(caller, request, &Some(PaymentType::CallerPaysIcrc2Cycles))
+    }
+
+    /// A standard btc_htlc_refund() call, including payment.
+    fn paid_htlc_refund(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcHtlcRefundRequest,
+    ) -> Result<Result<SignBtcResponse, BtcHtlcError>, String> {
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        approve_payment(
+            test_env,
+            caller,
+            SignerMethods::BtcHtlcRefund.btc_fee_for_inputs(n_inputs),
+        );
+        test_env
+            .signer
+            .btc_htlc_refund(caller, request, &Some(PaymentType::CallerPaysIcrc2Cycles))
+    }
+
+    #[test]
+    fn test_htlc_address_is_p2wsh_and_depends_on_role() {
+        let test_env = TestSetup::default();
+
+        let htlc_address = |caller_role| {
+            paid_htlc_address(
+                &test_env,
+                test_env.user,
+                &BtcHtlcAddressRequest {
+                    network: Network::Regtest,
+                    htlc: make_test_htlc(caller_role),
+                },
+            )
+            .expect("Failed to call btc_htlc_address")
+            .expect("Failed to get HTLC address")
+        };
+        let recipient = htlc_address(BtcHtlcRole::Recipient);
+        let sender = htlc_address(BtcHtlcRole::Sender);
+
+        assert!(
+            recipient.address.starts_with("bcrt1q"),
+            "unexpected address: {}",
+            recipient.address
+        );
+        assert_eq!(recipient.address.len(), 64);
+        // The caller's key guards the claim path as the recipient, and the refund path as the
+        // sender.
+        assert_ne!(recipient.address, sender.address);
+        assert_ne!(recipient.witness_script_hex, sender.witness_script_hex);
+    }
+
+    #[test]
+    fn test_htlc_claim_rejects_invalid_spends_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the spend conditions are checked before charging.
+        let claim = |request: &BtcHtlcClaimRequest| {
+            test_env
+                .signer
+                .btc_htlc_claim(
+                    test_env.user,
+                    request,
+                    &Some(PaymentType::CallerPaysIcrc2Cycles),
+                )
+                .expect("Failed to call btc_htlc_claim")
+                .map(|response| response.txid)
+        };
+
+        assert_eq!(
+            claim(&make_test_claim_request(&[8; 32])),
+            Err(BtcHtlcError::PreimageMismatch)
+        );
+        let as_sender = BtcHtlcClaimRequest {
+            htlc: make_test_htlc(BtcHtlcRole::Sender),
+            ..make_test_claim_request(&PREIMAGE)
+        };
+        assert_eq!(claim(&as_sender), Err(BtcHtlcError::WrongCallerRole));
+        let invalid_timeout = BtcHtlcClaimRequest {
+            htlc: BtcHtlc {
+                timeout_block_height: 0,
+                ..make_test_htlc(BtcHtlcRole::Recipient)
+            },
+            ..make_test_claim_request(&PREIMAGE)
+        };
+        assert_eq!(
+            claim(&invalid_timeout),
+            Err(BtcHtlcError::InvalidHtlc {
+                msg: "timeout_block_height must not be zero".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_htlc_claim_reveals_preimage_in_witness() {
+        let test_env = TestSetup::default();
+
+        let response = paid_htlc_claim(
+            &test_env,
+            test_env.user,
+            &make_test_claim_request(&PREIMAGE),
+        )
+        .expect("Failed to call btc_htlc_claim")
+        .expect("Failed to sign claim transaction");
+
+        let transaction: Transaction = deserialize(
+            &hex::decode(&response.signed_transaction_hex).expect("Invalid transaction hex"),
+        )
+        .expect("Invalid transaction");
+        assert_eq!(transaction.compute_txid().to_string(), response.txid);
+        assert_eq!(transaction.lock_time, LockTime::ZERO);
+        assert_eq!(response.fee_satoshis, 1000);
+        assert_eq!(transaction.output.len(), 1);
+        assert_eq!(transaction.output[0].value.to_sat(), 100_000 - 1000);
+        // <signature> <preimage> 1 <witness script>
+        let witness: Vec<&[u8]> = transaction.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness[1], &PREIMAGE[..]);
+        assert_eq!(witness[2], &[1][..]);
+    }
+
+    #[test]
+    fn test_htlc_refund_before_timeout_is_rejected() {
+        let test_env = TestSetup::default();
+
+        let request = BtcHtlcRefundRequest {
+            network: Network::Regtest,
+            htlc: make_test_htlc(BtcHtlcRole::Sender),
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        };
+        let response = paid_htlc_refund(&test_env, test_env.user, &request)
+            .expect("Failed to call btc_htlc_refund");
+
+        // The regtest chain has no blocks beyond genesis.
+        assert!(
+            matches!(
+                response,
+                Err(BtcHtlcError::TimeoutNotReached {
+                    timeout_block_height: TIMEOUT_BLOCK_HEIGHT,
+                    ..
+                })
+            ),
+            "unexpected response: {response:?}"
+        );
+    }
+}
+
+mod taproot {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        consensus::deserialize,
+        hashes::Hash,
+        secp256k1::{schnorr, Message, Secp256k1, XOnlyPublicKey},
+        sighash::{Prevouts, SighashCache},
+        taproot::{LeafVersion, TapLeafHash},
+        Address, Amount, ScriptBuf, TapSighashType, Transaction, TxOut,
+    };
+
+    use super::*;
+
+    /// `OP_TRUE`, a leaf that anyone can spend.
+    const ANYONE_CAN_SPEND_LEAF: &str = "51";
+    /// The x-only key of the generator point, which nobody can sign for in practice.
+    const UNSPENDABLE_INTERNAL_KEY: &str =
+        "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    /// A leaf that the key signs alone: `<key> OP_CHECKSIG`.
+    fn checksig_leaf(x_only_public_key: &str) -> String {
+        format!("20{x_only_public_key}ac")
+    }
+
+    /// A tree with an anyone-can-spend leaf and a leaf of the given key.
+    fn make_test_tree(x_only_public_key: &str) -> BtcTaprootTree {
+        BtcTaprootTree {
+            internal_key: Some(UNSPENDABLE_INTERNAL_KEY.to_string()),
+            leaves: vec![
+                BtcTapLeaf {
+                    script_hex: ANYONE_CAN_SPEND_LEAF.to_string(),
+                    depth: 1,
+                },
+                BtcTapLeaf {
+                    script_hex: checksig_leaf(x_only_public_key),
+                    depth: 1,
+                },
+            ],
+        }
+    }
+
+    fn make_test_sign_request(tree: BtcTaprootTree, leaf_index: u32) -> BtcTaprootSignRequest {
+        BtcTaprootSignRequest {
+            network: Network::Regtest,
+            tree,
+            leaf_index,
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            outputs: vec![BtcTxOutput::Payment {
+                destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                sent_satoshis: 50_000,
+            }],
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    fn approve_payment(test_env: &TestSetup, caller: Principal, fee: u128) {
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = fee + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+    }
+
+    /// A standard btc_taproot_address() call, including payment.
+    fn paid_taproot_address(
+        test_env: &TestSetup,
+        caller: Principal,
+        tree: BtcTaprootTree,
+    ) -> BtcTaprootAddressResponse {
+        approve_payment(test_env, caller, SignerMethods::BtcTaprootAddress.fee());
+        test_env
+            .signer
+            .btc_taproot_address(
+                caller,
+                &BtcTaprootAddressRequest {
+                    network: Network::Regtest,
+                    tree,
+                },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_taproot_address")
+            .expect("Failed to get taproot address")
+    }
+
+    /// A standard btc_taproot_sign() call, including payment.
+    fn paid_taproot_sign(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcTaprootSignRequest,
+    ) -> Result<Result<SignBtcResponse, BtcTaprootError>, String> {
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        approve_payment(
+            test_env,
+            caller,
+            SignerMethods::BtcTaprootSign.btc_fee_for_inputs(n_inputs),
+        );
+        test_env
+            .signer
+            .btc_taproot_sign(caller, request, &Some(PaymentType::CallerPaysIcrc2Cycles))
+    }
+
+    /// The caller's x-only Schnorr key, as returned with any taproot address.
+    fn caller_public_key(test_env: &TestSetup) -> String {
+        paid_taproot_address(
+            test_env,
+            test_env.user,
+            make_test_tree(UNSPENDABLE_INTERNAL_KEY),
+        )
+        .caller_public_key
+    }
+
+    #[test]
+    fn test_taproot_address_defaults_to_caller_internal_key() {
+        let test_env = TestSetup::default();
+
+        let response = paid_taproot_address(
+            &test_env,
+            test_env.user,
+            BtcTaprootTree {
+                internal_key: None,
+                leaves: vec![BtcTapLeaf {
+                    script_hex: ANYONE_CAN_SPEND_LEAF.to_string(),
+                    depth: 0,
+                }],
+            },
+        );
+
+        assert!(
+            response.address.starts_with("bcrt1p"),
+            "unexpected address: {}",
+            response.address
+        );
+        assert_eq!(response.internal_key, response.caller_public_key);
+        assert_eq!(response.control_blocks.len(), 1);
+        // `OP_TRUE` has no descriptor of its own, so only the output key is described.
+        assert!(
+            response.descriptor.starts_with("rawtr("),
+            "unexpected descriptor: {}",
+            response.descriptor
+        );
+        // A single leaf is the root of the tree.
+        assert_eq!(
+            response.merkle_root,
+            hex::encode(
+                TapLeafHash::from_script(
+                    &ScriptBuf::from_hex(ANYONE_CAN_SPEND_LEAF).unwrap(),
+                    LeafVersion::TapScript
+                )
+                .to_byte_array()
+            )
+        );
+    }
+
+    #[test]
+    fn test_taproot_sign_rejects_invalid_spends_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the tree and the leaf are checked before charging.
+        let sign = |request: &BtcTaprootSignRequest| {
+            test_env
+                .signer
+                .btc_taproot_sign(
+                    test_env.user,
+                    request,
+                    &Some(PaymentType::CallerPaysIcrc2Cycles),
+                )
+                .expect("Failed to call btc_taproot_sign")
+                .map(|response| response.txid)
+        };
+
+        let tree = make_test_tree(UNSPENDABLE_INTERNAL_KEY);
+        assert_eq!(
+            sign(&make_test_sign_request(tree.clone(), 2)),
+            Err(BtcTaprootError::LeafNotFound {
+                leaf_index: 2,
+                leaves: 2
+            })
+        );
+        let incomplete_tree = BtcTaprootTree {
+            leaves: tree.leaves[..1].to_vec(),
+            ..tree
+        };
+        assert_eq!(
+            sign(&make_test_sign_request(incomplete_tree, 0)),
+            Err(BtcTaprootError::InvalidTaprootTree {
+                msg: "the leaves do not form a complete binary tree".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_taproot_sign_rejects_leaf_without_caller_key() {
+        let test_env = TestSetup::default();
+        let tree = make_test_tree(&caller_public_key(&test_env));
+
+        let response =
+            paid_taproot_sign(&test_env, test_env.user, &make_test_sign_request(tree, 0))
+                .expect("Failed to call btc_taproot_sign");
+
+        assert_eq!(response, Err(BtcTaprootError::LeafWithoutCallerKey));
+    }
+
+    #[test]
+    fn test_taproot_sign_produces_valid_script_path_signature() {
+        let test_env = TestSetup::default();
+        let caller_public_key = caller_public_key(&test_env);
+        let tree = make_test_tree(&caller_public_key);
+        let taproot_address = paid_taproot_address(&test_env, test_env.user, tree.clone());
+
+        let response =
+            paid_taproot_sign(&test_env, test_env.user, &make_test_sign_request(tree, 1))
+                .expect("Failed to call btc_taproot_sign")
+                .expect("Failed to sign taproot transaction");
+
+        let transaction: Transaction = deserialize(
+            &hex::decode(&response.signed_transaction_hex).expect("Invalid transaction hex"),
+        )
+        .expect("Invalid transaction");
+        assert_eq!(transaction.compute_txid().to_string(), response.txid);
+        assert_eq!(response.fee_satoshis, 1000);
+        let taproot_script_pubkey = Address::from_str(&taproot_address.address)
+            .expect("Invalid taproot address")
+            .assume_checked()
+            .script_pubkey();
+        // The change is returned to the taproot output.
+        assert_eq!(transaction.output.len(), 2);
+        assert_eq!(transaction.output[1].script_pubkey, taproot_script_pubkey);
+
+        // <signature> <leaf script> <control block>
+        let witness: Vec<&[u8]> = transaction.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 3);
+        let leaf_script = ScriptBuf::from_hex(&checksig_leaf(&caller_public_key)).unwrap();
+        assert_eq!(witness[1], leaf_script.as_bytes());
+        assert_eq!(hex::encode(witness[2]), taproot_address.control_blocks[1]);
+
+        let prevouts = [TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: taproot_script_pubkey,
+        }];
+        let sighash = SighashCache::new(&transaction)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .expect("Failed to compute sighash");
+        let signature = schnorr::Signature::from_slice(witness[0]).expect("Invalid signature");
+        let public_key = XOnlyPublicKey::from_str(&caller_public_key).expect("Invalid public key");
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature,
+                &Message::from_digest(sighash.to_byte_array()),
+                &public_key,
+            )
+            .expect("The signature should verify against the caller's key");
+    }
+}
+
+mod runes {
+    use super::*;
+
+    fn make_test_send_runes_request(transfers: Vec<BtcRuneTransfer>) -> BtcSendRunesRequest {
+        BtcSendRunesRequest {
+            network: Network::Regtest,
+            rune_utxos: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            fee_utxos: vec![],
+            transfers,
+            etching: None,
+            postage_satoshis: None,
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    fn transfer(block: u64, tx: u32, amount: u128) -> BtcRuneTransfer {
+        BtcRuneTransfer {
+            rune_id: BtcRuneId { block, tx },
+            amount: amount.into(),
+            destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+        }
+    }
+
+    /// A standard btc_send_runes() call, including payment.
+    fn paid_send_runes(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcSendRunesRequest,
+        runestone_bytes: u64,
+    ) -> Result<Result<SendBtcResponse, BtcSendRunesError>, String> {
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let n_inputs = (request.rune_utxos.len() + request.fee_utxos.len()) as u64;
+        let n_outputs = request.transfers.len() as u64 + 3;
+        let amount: u128 = SignerMethods::BtcSendRunes.btc_fee_for_tx_with_data(
+            n_inputs,
+            n_outputs,
+            runestone_bytes,
+        ) + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+
+        test_env
+            .signer
+            .btc_send_runes(caller, request, &Some(PaymentType::CallerPaysIcrc2Cycles))
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_send_runes() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_send_runes(
+            Principal::anonymous(),
+            &make_test_send_runes_request(vec![transfer(840_000, 3, 10)]),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+        assert_eq!(
+            response.unwrap_err(),
+            "Update call error. RejectionCode: CanisterReject, Error: Update call error. RejectionCode: CanisterReject, Error: Anonymous caller not authorized.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_btc_send_runes_rejects_invalid_runestones_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the runestone is checked before charging.
+        let send = |request: &BtcSendRunesRequest| {
+            test_env
+                .signer
+                .btc_send_runes(
+                    test_env.user,
+                    request,
+                    &Some(PaymentType::CallerPaysIcrc2Cycles),
+                )
+                .expect("Failed to call btc_send_runes")
+        };
+
+        assert_eq!(
+            send(&make_test_send_runes_request(vec![])),
+            Err(BtcSendRunesError::InvalidRunestone {
+                msg: "a runestone needs a transfer or an etching".to_string()
+            })
+        );
+        assert_eq!(
+            send(&make_test_send_runes_request(vec![transfer(0, 0, 10)])),
+            Err(BtcSendRunesError::InvalidRunestone {
+                msg: "transfer 0: rune 0:0 can only be sent with an etching".to_string()
+            })
+        );
+        let etching = BtcSendRunesRequest {
+            etching: Some(BtcRuneEtching {
+                divisibility: Some(39),
+                symbol: None,
+                premine: None,
+            }),
+            ..make_test_send_runes_request(vec![])
+        };
+        assert_eq!(
+            send(&etching),
+            Err(BtcSendRunesError::InvalidRunestone {
+                msg: "divisibility must be at most 38".to_string()
+            })
+        );
+        // Ten edicts with the largest amounts do not fit in an `OP_RETURN` output.
+        let transfers = (0..10).map(|tx| transfer(840_000, tx, u128::MAX)).collect();
+        assert!(matches!(
+            send(&make_test_send_runes_request(transfers)),
+            Err(BtcSendRunesError::BuildP2wpkhError(
+                BuildP2wpkhTxError::OpReturnDataTooLarge {
+                    max_data_bytes: 79,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_btc_send_runes_rejects_signet_without_payment() {
+        let test_env = TestSetup::default();
+        let request = BtcSendRunesRequest {
+            network: Network::Signet,
+            ..make_test_send_runes_request(vec![transfer(840_000, 3, 10)])
+        };
+
+        // No payment is approved: the network is checked before charging.
+        let response = test_env
+            .signer
+            .btc_send_runes(
+                test_env.user,
+                &request,
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_send_runes");
+
+        assert_eq!(
+            response,
+            Err(BtcSendRunesError::NoBitcoinCanister {
+                network: Network::Signet
+            })
+        );
+    }
+
+    #[test]
+    fn test_unsent_btc_send_runes_releases_its_inputs() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved, so the transaction is never sent.
+        let response = test_env
+            .signer
+            .btc_send_runes(
+                test_env.user,
+                &make_test_send_runes_request(vec![transfer(840_000, 3, 10)]),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_send_runes");
+
+        assert!(matches!(response, Err(BtcSendRunesError::PaymentError(_))));
+        let pending = test_env
+            .signer
+            .btc_pending_transactions(test_env.user)
+            .expect("Failed to call btc_pending_transactions");
+        assert_eq!(pending.reserved_utxos, vec![]);
+    }
+
+    #[test]
+    fn test_btc_send_runes_rejects_dust_postage() {
+        let test_env = TestSetup::default();
+
+        let request = BtcSendRunesRequest {
+            postage_satoshis: Some(500),
+            ..make_test_send_runes_request(vec![transfer(840_000, 3, 10)])
+        };
+        // OP_RETURN OP_13 and a push of the 9-byte payload.
+        let response = paid_send_runes(&test_env, test_env.user, &request, 12)
+            .expect("Failed to call btc_send_runes");
+
+        assert_eq!(
+            response,
+            Err(BtcSendRunesError::BuildP2wpkhError(
+                BuildP2wpkhTxError::DustOutput {
+                    address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                    sent_satoshis: 500,
+                    dust_threshold: 1_000,
+                }
+            ))
+        );
+    }
+}
+
+mod inscription {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        consensus::deserialize, hashes::Hash, script::Instruction, Address, ScriptBuf, Transaction,
+    };
+
+    use super::*;
+
+    fn make_test_inscription() -> BtcInscription {
+        BtcInscription {
+            content_type: "text/plain;charset=utf-8".to_string(),
+            body: serde_bytes::ByteBuf::from(b"Hello, chain fusion!".to_vec()),
+        }
+    }
+
+    fn make_test_commit_request(inscription: BtcInscription) -> BtcInscriptionCommitRequest {
+        BtcInscriptionCommitRequest {
+            network: Network::Regtest,
+            inscription,
+            utxos_to_spend: caller_sign::make_test_send_request(Network::Regtest).utxos_to_spend,
+            postage_satoshis: None,
+            reveal_fee_rate: Some(BtcFeeRate::SatPerVbyte(10)),
+            fee_satoshis: Some(1000),
+            fee_rate: None,
+            allow_high_fee: None,
+        }
+    }
+
+    fn approve_payment(test_env: &TestSetup, caller: Principal, fee: u128) {
+        let payment_recipient = cycles_ledger::Account {
+            owner: test_env.signer.canister_id(),
+            subaccount: None,
+        };
+        let amount: u128 = fee + LEDGER_FEE;
+        test_env
+            .ledger
+            .icrc2_approve(caller, &ApproveArgs::new(payment_recipient, amount.into()))
+            .expect("Failed to call ledger canister")
+            .expect("Failed to approve payment");
+    }
+
+    /// A standard btc_inscription_commit() call, including payment.
+    fn paid_inscription_commit(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcInscriptionCommitRequest,
+    ) -> Result<Result<BtcInscriptionCommitResponse, BtcInscriptionError>, String> {
+        let n_inputs = request.utxos_to_spend.len() as u64;
+        approve_payment(
+            test_env,
+            caller,
+            SignerMethods::BtcInscriptionCommit.btc_fee_for_inputs(n_inputs),
+        );
+        test_env.signer.btc_inscription_commit(
+            caller,
+            request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+    }
+
+    /// A standard btc_inscription_reveal() call, including payment.
+    fn paid_inscription_reveal(
+        test_env: &TestSetup,
+        caller: Principal,
+        request: &BtcInscriptionRevealRequest,
+    ) -> Result<Result<SignBtcResponse, BtcInscriptionError>, String> {
+        approve_payment(test_env, caller, SignerMethods::BtcInscriptionReveal.fee());
+        test_env.signer.btc_inscription_reveal(
+            caller,
+            request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+    }
+
+    fn decode_transaction(signed_transaction_hex: &str) -> Transaction {
+        deserialize(&hex::decode(signed_transaction_hex).expect("Invalid transaction hex"))
+            .expect("Invalid transaction")
+    }
+
+    #[test]
+    fn test_anonymous_cannot_call_btc_inscription_commit() {
+        let test_env = TestSetup::default();
+
+        let response = test_env.signer.btc_inscription_commit(
+            Principal::anonymous(),
+            &make_test_commit_request(make_test_inscription()),
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        );
+
+        assert!(response.is_err());
+        assert_eq!(
+            response.unwrap_err(),
+            "Update call error. RejectionCode: CanisterReject, Error: Update call error. RejectionCode: CanisterReject, Error: Anonymous caller not authorized.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_btc_inscription_commit_rejects_invalid_inscription_without_payment() {
+        let test_env = TestSetup::default();
+
+        // No payment is approved: the inscription is checked before charging.
+        let response = test_env
+            .signer
+            .btc_inscription_commit(
+                test_env.user,
+                &make_test_commit_request(BtcInscription {
+                    content_type: String::new(),
+                    ..make_test_inscription()
+                }),
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call btc_inscription_commit");
+
+        assert_eq!(
+            response,
+            Err(BtcInscriptionError::InvalidInscription {
+                msg: "content_type must not be empty".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_btc_inscription_commit_and_reveal() {
+        let test_env = TestSetup::default();
+        let inscription = make_test_inscription();
+
+        let commit = paid_inscription_commit(
+            &test_env,
+            test_env.user,
+            &make_test_commit_request(inscription.clone()),
+        )
+        .expect("Failed to call btc_inscription_commit")
+        .expect("Failed to sign commit transaction");
+
+        assert!(
+            commit.commit_address.starts_with("bcrt1p"),
+            "unexpected address: {}",
+            commit.commit_address
+        );
+        let commit_transaction = decode_transaction(&commit.transaction.signed_transaction_hex);
+        assert_eq!(
+            commit_transaction.compute_txid().to_string(),
+            commit.transaction.txid
+        );
+        assert_eq!(commit.transaction.fee_satoshis, 1000);
+        let commit_output = &commit_transaction.output[commit.commit_vout as usize];
+        assert_eq!(commit_output.value.to_sat(), commit.commit_satoshis);
+        assert_eq!(
+            commit_output.script_pubkey,
+            Address::from_str(&commit.commit_address)
+                .expect("Invalid commit address")
+                .assume_checked()
+                .script_pubkey()
+        );
+        // The postage and the reveal fee.
+        assert!(commit.commit_satoshis > 1_000);
+
+        let reveal = paid_inscription_reveal(
+            &test_env,
+            test_env.user,
+            &BtcInscriptionRevealRequest {
+                network: Network::Regtest,
+                inscription,
+                commit_utxo: Utxo {
+                    height: 0,
+                    value: commit.commit_satoshis,
+                    outpoint: OutPoint {
+                        txid: serde_bytes::ByteBuf::from(
+                            commit_transaction.compute_txid().to_byte_array().to_vec(),
+                        ),
+                        vout: commit.commit_vout,
+                    },
+                },
+                destination_address: CALLER_BTC_ADDRESS_REGTEST.to_string(),
+                postage_satoshis: None,
+                allow_high_fee: None,
+            },
+        )
+        .expect("Failed to call btc_inscription_reveal")
+        .expect("Failed to sign reveal transaction");
+
+        let reveal_transaction = decode_transaction(&reveal.signed_transaction_hex);
+        assert_eq!(reveal_transaction.compute_txid().to_string(), reveal.txid);
+        assert_eq!(reveal.fee_satoshis, commit.commit_satoshis - 1_000);
+        assert_eq!(reveal_transaction.output.len(), 1);
+        assert_eq!(reveal_transaction.output[0].value.to_sat(), 1_000);
+        // <signature> <envelope leaf> <control block>
+        let witness: Vec<&[u8]> = reveal_transaction.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 3);
+        let leaf = ScriptBuf::from_bytes(witness[1].to_vec());
+        let pushes = leaf
+            .instructions()
+            .filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes().to_vec()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(pushes.contains(&b"ord".to_vec()));
+        assert!(pushes.contains(&b"Hello, chain fusion!".to_vec()));
     }
 }
@@ -0,0 +1,45 @@
+//! Bindings to the stand-in ckBTC minter canister, `src/test_ckbtc_minter`.
+#![allow(dead_code, unused_imports)]
+use std::sync::Arc;
+
+use candid::{self, CandidType, Deserialize, Principal};
+use pocket_ic::PocketIc;
+
+use crate::utils::pic_canister::{PicCanister, PicCanisterTrait};
+
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct Account {
+    pub(crate) owner: Option<Principal>,
+    pub(crate) subaccount: Option<serde_bytes::ByteBuf>,
+}
+
+pub struct CkbtcMinterPic {
+    pub pic: Arc<PocketIc>,
+    pub canister_id: Principal,
+}
+
+impl From<PicCanister> for CkbtcMinterPic {
+    fn from(pic: PicCanister) -> Self {
+        Self {
+            pic: pic.pic(),
+            canister_id: pic.canister_id(),
+        }
+    }
+}
+
+impl PicCanisterTrait for CkbtcMinterPic {
+    /// The shared PocketIc instance.
+    fn pic(&self) -> Arc<PocketIc> {
+        self.pic.clone()
+    }
+    /// The ID of this canister.
+    fn canister_id(&self) -> Principal {
+        self.canister_id.clone()
+    }
+}
+
+impl CkbtcMinterPic {
+    pub fn get_btc_address_calls(&self, caller: Principal) -> Result<Vec<Account>, String> {
+        self.query(caller, "get_btc_address_calls", ())
+    }
+}
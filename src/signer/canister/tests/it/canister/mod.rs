@@ -1,5 +1,6 @@
 //! Pocket-ic bindings to canisters.
 pub mod bitcoin;
+pub mod ckbtc_minter;
 pub mod cycles_depositor;
 pub mod cycles_ledger;
 pub mod signer;
@@ -9,12 +9,45 @@ use crate::utils::pic_canister::{PicCanister, PicCanisterTrait};
 
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct InitArg {
+    /// Limits on the fees of Bitcoin transactions.  Default: see `BtcFeeLimits::default()`.
+    pub(crate) btc_fee_limits: Option<BtcFeeLimits>,
     pub(crate) ecdsa_key_name: String,
     /// Root of trust for checking canister signatures.
     pub(crate) ic_root_key_der: Option<serde_bytes::ByteBuf>,
+    /// The ckBTC minter that `btc_deposit_to_ckbtc` deposits to.  Default: the mainnet ckBTC
+    /// minter, `mqygn-kiaaa-aaaar-qaadq-cai`.
+    pub(crate) ckbtc_minter: Option<Principal>,
+    /// The number of threshold signatures requested at once when signing the inputs of a
+    /// transaction.  Default: 10.
+    pub(crate) max_concurrent_signatures: Option<u32>,
+    /// Network fee percentiles used for the Bitcoin fee priority levels.  Default: 25, 50, 75.
+    /// The install or upgrade fails if they are not ordered or above 100.
+    pub(crate) btc_fee_percentiles: Option<BtcFeePercentiles>,
     /// Payment canister ID.
     pub(crate) cycles_ledger: Option<Principal>,
 }
+/// The network fee percentiles (0-100) that the fee priority levels map to, with
+/// `low <= medium <= high`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcFeePercentiles {
+    pub(crate) low: u8,
+    pub(crate) high: u8,
+    pub(crate) medium: u8,
+}
+/// Limits that protect callers against paying unreasonable Bitcoin fees.
+///
+/// A transaction that exceeds any of these limits is rejected, unless the request sets
+/// `allow_high_fee`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcFeeLimits {
+    /// The largest fee rate, as a multiple of the current network fee rate at the `High`
+    /// priority level.
+    pub(crate) max_fee_rate_multiple: u64,
+    /// The largest fee, as a percentage of the value sent to the (non-change) outputs.
+    pub(crate) max_fee_percent_of_sent: u64,
+    /// The largest fee, in satoshi.
+    pub(crate) max_fee_satoshis: u64,
+}
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum Arg {
     Upgrade,
@@ -25,16 +58,40 @@ pub(crate) enum Network {
     /// Bitcoin Mainnet.
     #[serde(rename = "mainnet")]
     Mainnet,
+    /// Bitcoin Testnet4.  Served by the Bitcoin canister's test network.
+    #[serde(rename = "testnet4")]
+    Testnet4,
     /// Bitcoin Regtest.
     #[serde(rename = "regtest")]
     Regtest,
-    /// Bitcoin Testnet4.
+    /// Bitcoin Signet.  There is no Bitcoin canister for Signet.
+    #[serde(rename = "signet")]
+    Signet,
+    /// Bitcoin Testnet4, under the name of the Bitcoin canister's test network.
     #[serde(rename = "testnet")]
     Testnet,
 }
+/// A vault that the caller's key spends together with a cosigner's key, or that a recovery
+/// key spends alone once a vault output is `recovery_delay_blocks` deep.
+///
+/// The witness script implements the policy
+/// `or(and(pk(signer),pk(cosigner)),and(pk(recovery),older(recovery_delay_blocks)))`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcVaultPolicy {
+    /// Hex-encoded compressed public key that spends alone after the delay.
+    pub(crate) recovery_public_key: String,
+    /// The relative timelock of the recovery path, in blocks.  Must not be zero.
+    pub(crate) recovery_delay_blocks: u16,
+    /// Hex-encoded compressed public key that spends together with the caller's key.
+    pub(crate) cosigner_public_key: String,
+}
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum BitcoinAddressType {
     P2WPKH,
+    /// A P2WSH vault of the caller's key, see [`BtcVaultPolicy`].
+    ///
+    /// Only the address endpoints support vaults; spend from them with `btc_vault_sign`.
+    P2WSHVault(BtcVaultPolicy),
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct GetAddressRequest {
@@ -42,6 +99,13 @@ pub(crate) struct GetAddressRequest {
     pub(crate) address_type: BitcoinAddressType,
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcAddressRequest {
+    /// The principal owning the Bitcoin address.  Default: The caller.
+    pub(crate) principal: Option<Principal>,
+    pub(crate) network: Network,
+    pub(crate) address_type: BitcoinAddressType,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct Account {
     pub(crate) owner: Principal,
     pub(crate) subaccount: Option<serde_bytes::ByteBuf>,
@@ -165,7 +229,13 @@ pub(crate) enum PaymentError {
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum GetAddressError {
-    InternalError { msg: String },
+    InternalError {
+        msg: String,
+    },
+    /// The keys or the delay of a `P2WSHVault` are invalid.
+    InvalidVaultPolicy {
+        msg: String,
+    },
     PaymentError(PaymentError),
 }
 pub(crate) type Result_ = std::result::Result<GetAddressResponse, GetAddressError>;
@@ -196,44 +266,1091 @@ pub(crate) struct Utxo {
     pub(crate) value: u64,
     pub(crate) outpoint: OutPoint,
 }
+/// An output of a transaction.  Outputs appear in the transaction in the order requested,
+/// followed by the change.
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
-pub(crate) struct BtcTxOutput {
-    pub(crate) destination_address: String,
-    pub(crate) sent_satoshis: u64,
+pub(crate) enum BtcTxOutput {
+    /// Embeds `data`, at most 80 bytes, in an `OP_RETURN` output that carries no value.
+    Data { data: serde_bytes::ByteBuf },
+    /// Sends `sent_satoshis` to `destination_address`.
+    Payment {
+        destination_address: String,
+        sent_satoshis: u64,
+    },
+}
+/// How urgently a transaction should be confirmed.
+///
+/// Each level maps to a percentile of the current network fee rates, as set in
+/// `Config::btc_fee_percentiles`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcFeePriority {
+    Low,
+    High,
+    Medium,
+}
+/// The fee rate at which a transaction is built.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcFeeRate {
+    /// The fee rate paid by recent transactions at the given priority level.
+    Priority(BtcFeePriority),
+    /// An explicit fee rate, in satoshi per virtual byte.
+    SatPerVbyte(u64),
+}
+/// Cross-checks the inputs of a transaction against the UTXOs that `bitcoin_get_utxos` reports
+/// for the caller's address, before any input is signed.
+///
+/// Only the first page of UTXOs is fetched, so an address with many UTXOs may have to spend its
+/// older ones without verification.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcUtxoVerification {
+    /// The minimum number of confirmations of every input.  Default: the default of
+    /// `bitcoin_get_utxos`, one confirmation.
+    pub(crate) min_confirmations: Option<u32>,
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct SendBtcRequest {
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// Marks the transaction as final instead of signalling BIP-125 replace-by-fee.  Default:
+    /// `false`.
+    pub(crate) disable_rbf: Option<bool>,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
     pub(crate) fee_satoshis: Option<u64>,
     pub(crate) network: Network,
     pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// Checks every input against the UTXO set of the Bitcoin canister before signing.
+    /// Default: the inputs are signed as supplied.
+    pub(crate) verify_utxos: Option<BtcUtxoVerification>,
     pub(crate) address_type: BitcoinAddressType,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    /// The sighash type of each input, in the order of `utxos_to_spend`.  `None` and
+    /// `NonePlusAnyoneCanPay` are rejected.  Default: `All` for every input.
+    pub(crate) sighash_types: Option<Vec<BtcSighashType>>,
     pub(crate) outputs: Vec<BtcTxOutput>,
+    /// Sends the value of all inputs, less the fee, to the only `Payment` output in `outputs`,
+    /// whose `sent_satoshis` is ignored, without a change output.  Default: `false`.
+    pub(crate) send_all: Option<bool>,
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct SendBtcResponse {
+    /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+    pub(crate) fee_rate_millisat_per_vbyte: u64,
+    /// The fee paid by the transaction, in satoshi.
+    pub(crate) fee_satoshis: u64,
+    /// The size of the signed transaction, in virtual bytes.
+    pub(crate) vsize: u64,
     pub(crate) txid: String,
+    /// The output that returns the change to the caller, or `None` if the transaction has no
+    /// change output.
+    pub(crate) change_output_index: Option<u32>,
+    pub(crate) signed_transaction_hex: String,
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum BuildP2wpkhTxError {
-    NotEnoughFunds { available: u64, required: u64 },
+    /// `send_all` is set, but `outputs` does not have exactly one `Payment` output.
+    SendAllWithoutSingleOutput {
+        outputs: u64,
+    },
+    /// An input would be signed with `None` or `NonePlusAnyoneCanPay`, which commit to no
+    /// outputs.
+    SighashNoneNotAllowed {
+        input_index: u64,
+    },
+    /// The transaction would spend more inputs than the method allows.
+    TooManyInputs {
+        inputs: u64,
+        max_inputs: u64,
+    },
+    /// The fee rate exceeds `BtcFeeLimits::max_fee_rate_multiple` times the network fee rate.
+    FeeRateTooHigh {
+        fee_rate_millisat_per_vbyte: u64,
+        max_fee_rate_millisat_per_vbyte: u64,
+    },
+    NotEnoughFunds {
+        available: u64,
+        required: u64,
+    },
     WrongBitcoinNetwork,
     NotP2WPKHSourceAddress,
+    /// An output sends less than the dust threshold.
+    DustOutput {
+        sent_satoshis: u64,
+        dust_threshold: u64,
+        address: String,
+    },
+    /// The fee exceeds `BtcFeeLimits::max_fee_percent_of_sent` of the sent value.
+    FeeTooHighForSentValue {
+        sent_satoshis: u64,
+        max_fee_percent_of_sent: u64,
+        fee_satoshis: u64,
+    },
     InvalidDestinationAddress(GetAddressResponse),
+    /// The fee exceeds `BtcFeeLimits::max_fee_satoshis`.
+    FeeTooHigh {
+        fee_satoshis: u64,
+        max_fee_satoshis: u64,
+    },
+    /// An input signed with `Single` has no output with the same index.
+    SighashSingleWithoutOutput {
+        input_index: u64,
+    },
+    /// `sighash_types` does not have one entry per input.
+    WrongNumberOfSighashTypes {
+        inputs: u64,
+        sighash_types: u64,
+    },
     InvalidSourceAddress(GetAddressResponse),
+    /// The `OP_RETURN` data exceeds the size that nodes relay by default.
+    OpReturnDataTooLarge {
+        max_data_bytes: u64,
+        data_bytes: u64,
+    },
+    /// Both `fee_satoshis` and `fee_rate` were set.
+    ConflictingFeeArguments,
 }
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum SendBtcError {
+    /// The value supplied for an input differs from the value of the unspent output.
+    ValueMismatch {
+        txid: String,
+        vout: u32,
+        actual_satoshis: u64,
+        supplied_satoshis: u64,
+    },
+    /// The ckBTC account is invalid, or the ckBTC minter did not return its deposit address.
+    CkbtcMinterError {
+        msg: String,
+    },
     BuildP2wpkhError(BuildP2wpkhTxError),
-    InternalError { msg: String },
+    /// An input is not an unspent output of the caller's address with enough confirmations,
+    /// according to `bitcoin_get_utxos`.
+    UnknownUtxo {
+        txid: String,
+        vout: u32,
+    },
+    /// The request needs the Bitcoin canister, which does not serve `network`.  Transactions
+    /// for such a network can be signed with `btc_caller_sign` and broadcast elsewhere.
+    NoBitcoinCanister {
+        network: Network,
+    },
+    /// The transaction was signed, but `bitcoin_send_transaction` rejected it.  It can be
+    /// retried with `btc_rebroadcast`.
+    BroadcastFailed {
+        msg: String,
+        txid: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    /// The keys or the delay of the vault are invalid.
+    InvalidVaultPolicy {
+        msg: String,
+    },
     PaymentError(PaymentError),
+    /// An input is spent by another transaction of the caller that is in flight or was sent
+    /// recently.
+    UtxoReserved {
+        txid: String,
+        vout: u32,
+        /// The transaction that spends the input, or `None` while it is being signed.
+        spending_txid: Option<String>,
+        /// When the reservation expires, in nanoseconds since the Unix epoch.
+        expires_at_ns: u64,
+    },
 }
 pub(crate) type Result2 = std::result::Result<SendBtcResponse, SendBtcError>;
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct SignBtcResponse {
+    /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+    pub(crate) fee_rate_millisat_per_vbyte: u64,
+    /// The fee paid by the transaction, in satoshi.
+    pub(crate) fee_satoshis: u64,
+    /// The size of the signed transaction, in virtual bytes.
+    pub(crate) vsize: u64,
     pub(crate) txid: String,
+    /// The output that returns the change to the caller, or `None` if the transaction has no
+    /// change output.
+    pub(crate) change_output_index: Option<u32>,
     pub(crate) signed_transaction_hex: String,
 }
 pub(crate) type Result3 = std::result::Result<SignBtcResponse, SendBtcError>;
+/// How the fee of a stuck transaction is increased.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcBumpFeeMode {
+    /// Replace-by-fee: a replacement spends the same inputs, paying the higher fee out of the
+    /// change output.
+    Rbf,
+    /// Child-pays-for-parent: a new transaction spends the change output of the original,
+    /// paying a fee that brings the two transactions to the requested fee rate.
+    Cpfp,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcBumpFeeRequest {
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    pub(crate) mode: BtcBumpFeeMode,
+    /// The txid of the original transaction, if the signer signed it for the caller in the
+    /// last week; see `btc_list_signed_transactions`.  Cannot be combined with
+    /// `signed_transaction_hex`.
+    pub(crate) txid: Option<String>,
+    pub(crate) network: Network,
+    /// The UTXOs spent by the original transaction.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    pub(crate) address_type: BitcoinAddressType,
+    /// The new fee rate.  For `Cpfp`, the fee rate of the original and the new transaction
+    /// together.
+    pub(crate) fee_rate: BtcFeeRate,
+    /// The original transaction, hex-encoded, as signed by the caller.  Cannot be combined with
+    /// `txid`.
+    pub(crate) signed_transaction_hex: Option<String>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcBumpFeeResponse {
+    /// The effective fee rate of the new transaction, in millisatoshi per virtual byte.
+    pub(crate) fee_rate_millisat_per_vbyte: u64,
+    /// The fee paid by the new transaction, in satoshi.
+    pub(crate) fee_satoshis: u64,
+    pub(crate) txid: String,
+    /// The replacement (`Rbf`) or child (`Cpfp`) transaction, as sent to the network.
+    pub(crate) signed_transaction_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcBumpFeeError {
+    /// An input of the original transaction is not in `utxos_to_spend`.
+    MissingInputUtxo {
+        txid: String,
+        vout: u32,
+    },
+    /// The original transaction has no change output to the caller's address.
+    NoChangeOutput,
+    BuildP2wpkhError(BuildP2wpkhTxError),
+    /// The original transaction could not be decoded.
+    InvalidTransaction {
+        msg: String,
+    },
+    /// The request needs the Bitcoin canister, which does not serve `network`.
+    NoBitcoinCanister {
+        network: Network,
+    },
+    /// `bitcoin_send_transaction` rejected the new transaction.  It is kept, and can be sent
+    /// again with `btc_rebroadcast`.
+    BroadcastFailed {
+        msg: String,
+        txid: String,
+    },
+    /// The replacement must pay the original fee plus 1 satoshi per virtual byte of its own
+    /// size.
+    ReplacementFeeTooLow {
+        fee_satoshis: u64,
+        min_fee_satoshis: u64,
+    },
+    /// The original transaction does not signal BIP-125 replace-by-fee.
+    NotReplaceable,
+    InternalError {
+        msg: String,
+    },
+    /// The caller has no signed transaction with `txid`, or it has expired.
+    UnknownTransaction,
+    PaymentError(PaymentError),
+    /// The change output that a `Cpfp` child spends is spent by another transaction of the
+    /// caller that is in flight or was sent recently, e.g. an earlier child.
+    UtxoReserved {
+        txid: String,
+        vout: u32,
+        /// The transaction that spends the output, or `None` while it is being signed.
+        spending_txid: Option<String>,
+        /// When the reservation expires, in nanoseconds since the Unix epoch.
+        expires_at_ns: u64,
+    },
+}
+pub(crate) type Result12 = std::result::Result<BtcBumpFeeResponse, BtcBumpFeeError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcMultisigAddressRequest {
+    /// The number of signatures required to spend.
+    pub(crate) threshold: u8,
+    pub(crate) network: Network,
+    /// Hex-encoded compressed public keys of the other cosigners.  The caller's key is added
+    /// to these.
+    pub(crate) cosigner_public_keys: Vec<String>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcMultisigAddressResponse {
+    /// The hex-encoded witness script, as needed by the cosigners to spend.
+    pub(crate) witness_script_hex: String,
+    /// The P2WSH address of the multisig account.
+    pub(crate) address: String,
+    /// All public keys of the account, hex-encoded, in the order of the witness script.
+    pub(crate) public_keys: Vec<String>,
+}
+/// A UTXO of the caller that a `btc_caller_send` transaction in flight, or sent recently,
+/// spends.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcReservedUtxo {
+    pub(crate) txid: String,
+    pub(crate) vout: u32,
+    /// The transaction that spends the UTXO, or `None` while it is being signed.
+    pub(crate) spending_txid: Option<String>,
+    /// When the reservation expires, in nanoseconds since the Unix epoch.
+    pub(crate) expires_at_ns: u64,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcPendingTransactionsResponse {
+    pub(crate) reserved_utxos: Vec<BtcReservedUtxo>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcMultisigAddressError {
+    /// The account has more public keys than a standard multisig script allows.
+    TooManyPublicKeys {
+        max_public_keys: u64,
+        public_keys: u64,
+    },
+    /// The threshold is zero or exceeds the number of public keys.
+    InvalidThreshold {
+        threshold: u8,
+        public_keys: u64,
+    },
+    /// A cosigner public key is not a hex-encoded compressed public key.
+    InvalidPublicKey {
+        public_key: String,
+    },
+    /// The same public key appears more than once.
+    DuplicatePublicKey {
+        public_key: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+}
+pub(crate) type Result13 = std::result::Result<BtcMultisigAddressResponse, BtcMultisigAddressError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcSignPsbtRequest {
+    /// The hex-encoded [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki)
+    /// PSBT to sign.
+    ///
+    /// Inputs to be signed need a `witness_utxo` and a `witness_script` that contains the
+    /// caller's public key.  Other inputs are left untouched.
+    pub(crate) psbt_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcSignPsbtResponse {
+    /// The indices of the inputs that were signed.
+    pub(crate) signed_inputs: Vec<u32>,
+    /// The hex-encoded PSBT with the caller's partial signatures added.  It is not finalized.
+    pub(crate) psbt_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcSignPsbtError {
+    /// An input to sign has the sighash type `None` or `NonePlusAnyoneCanPay`, which commit to
+    /// no outputs.
+    SighashNoneNotAllowed {
+        input_index: u64,
+    },
+    /// No input has a witness script that contains the caller's public key.
+    NoInputsToSign,
+    /// The PSBT could not be decoded, or an input is inconsistent.
+    InvalidPsbt {
+        msg: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+}
+pub(crate) type Result14 = std::result::Result<BtcSignPsbtResponse, BtcSignPsbtError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcVaultSignRequest {
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    pub(crate) vault: BtcVaultPolicy,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    pub(crate) network: Network,
+    /// UTXOs of the vault address.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    pub(crate) outputs: Vec<BtcTxOutput>,
+}
+pub(crate) type Result15 = std::result::Result<BtcSignPsbtResponse, SendBtcError>;
+/// The side of an HTLC that the caller is on.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcHtlcRole {
+    /// The caller is refunded after the timeout; the counterparty claims the HTLC with the
+    /// preimage.
+    Sender,
+    /// The caller claims the HTLC with the preimage; the counterparty is refunded after the
+    /// timeout.
+    Recipient,
+}
+/// A hash time-locked contract between the caller and a counterparty, as used in atomic
+/// swaps.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcHtlc {
+    /// Hex-encoded SHA-256 hash of the 32-byte preimage that claims the HTLC.
+    pub(crate) payment_hash: String,
+    /// The block height from which the sender can be refunded.
+    pub(crate) timeout_block_height: u32,
+    /// Hex-encoded compressed public key of the counterparty.
+    pub(crate) counterparty_public_key: String,
+    pub(crate) caller_role: BtcHtlcRole,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcHtlcAddressRequest {
+    pub(crate) htlc: BtcHtlc,
+    pub(crate) network: Network,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcHtlcAddressResponse {
+    /// The hex-encoded witness script, as needed by the counterparty to spend.
+    pub(crate) witness_script_hex: String,
+    /// The P2WSH address of the HTLC.
+    pub(crate) address: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcHtlcError {
+    BuildP2wpkhError(BuildP2wpkhTxError),
+    /// The chain has not reached the timeout of the HTLC yet.
+    TimeoutNotReached {
+        tip_height: u32,
+        timeout_block_height: u32,
+    },
+    /// The request needs the Bitcoin canister, which does not serve `network`.
+    NoBitcoinCanister {
+        network: Network,
+    },
+    /// The counterparty key, the payment hash or the timeout of the HTLC is invalid.
+    InvalidHtlc {
+        msg: String,
+    },
+    /// The preimage is not 32 bytes long or does not hash to the payment hash.
+    PreimageMismatch,
+    /// Only the recipient can claim the HTLC, and only the sender can be refunded.
+    WrongCallerRole,
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+}
+pub(crate) type Result16 = std::result::Result<BtcHtlcAddressResponse, BtcHtlcError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcHtlcClaimRequest {
+    /// Receives the value of the UTXOs, minus the fee.
+    pub(crate) destination_address: String,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    pub(crate) htlc: BtcHtlc,
+    pub(crate) network: Network,
+    /// UTXOs of the HTLC address.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    /// The hex-encoded 32-byte preimage of `payment_hash`.
+    pub(crate) preimage: String,
+}
+pub(crate) type Result17 = std::result::Result<SignBtcResponse, BtcHtlcError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcHtlcRefundRequest {
+    /// Receives the value of the UTXOs, minus the fee.
+    pub(crate) destination_address: String,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    pub(crate) htlc: BtcHtlc,
+    pub(crate) network: Network,
+    /// UTXOs of the HTLC address.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+}
+/// A leaf of a taproot script tree.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcTapLeaf {
+    /// The hex-encoded tapscript, with leaf version `0xc0`.
+    pub(crate) script_hex: String,
+    /// The depth of the leaf in the tree, from 0 for a single leaf to 128.
+    pub(crate) depth: u8,
+}
+/// A taproot output whose internal key is tweaked with the Merkle root of a script tree.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcTaprootTree {
+    /// Hex-encoded 32-byte x-only internal key.  Default: the caller's Schnorr key.
+    pub(crate) internal_key: Option<String>,
+    /// The leaves in depth-first order, left to right.  Their depths must describe a complete
+    /// binary tree, e.g. `[0]`, `[1, 1]` or `[1, 2, 2]`.
+    pub(crate) leaves: Vec<BtcTapLeaf>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcTaprootAddressRequest {
+    pub(crate) tree: BtcTaprootTree,
+    pub(crate) network: Network,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcTaprootAddressResponse {
+    /// The hex-encoded x-only internal key.
+    pub(crate) internal_key: String,
+    /// The output descriptor, with its BIP-380 checksum: `tr(<internal key>,{...})` if every leaf
+    /// is `<key> OP_CHECKSIG`, and otherwise `rawtr(<output key>)`, which a wallet can watch but
+    /// not spend.
+    pub(crate) descriptor: String,
+    /// The hex-encoded control block of each leaf, in the order of `leaves`.
+    pub(crate) control_blocks: Vec<String>,
+    /// The hex-encoded Merkle root of the script tree.
+    pub(crate) merkle_root: String,
+    /// The P2TR address of the output.
+    pub(crate) address: String,
+    /// The hex-encoded x-only public key of the caller's Schnorr key, as used in tapscript
+    /// leaves.
+    pub(crate) caller_public_key: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcTaprootError {
+    BuildP2wpkhError(BuildP2wpkhTxError),
+    /// The script of the leaf to spend does not contain the caller's key.
+    LeafWithoutCallerKey,
+    /// The internal key or a leaf is invalid, or the leaves do not form a complete tree.
+    InvalidTaprootTree {
+        msg: String,
+    },
+    /// `leaf_index` is not the index of a leaf.
+    LeafNotFound {
+        leaf_index: u32,
+        leaves: u64,
+    },
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+}
+pub(crate) type Result18 = std::result::Result<BtcTaprootAddressResponse, BtcTaprootError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcTaprootSignRequest {
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    pub(crate) tree: BtcTaprootTree,
+    /// The index in `tree.leaves` of the leaf to spend.  Its script must contain the caller's
+    /// key and be satisfied by the caller's signature alone.
+    pub(crate) leaf_index: u32,
+    pub(crate) network: Network,
+    /// UTXOs of the taproot address.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    pub(crate) outputs: Vec<BtcTxOutput>,
+}
+pub(crate) type Result19 = std::result::Result<SignBtcResponse, BtcTaprootError>;
+/// The ID of a rune: the block height of its etching transaction and the index of that
+/// transaction in the block, usually written `BLOCK:TX`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcRuneId {
+    pub(crate) tx: u32,
+    pub(crate) block: u64,
+}
+/// Units of a rune to send to an address.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcRuneTransfer {
+    pub(crate) destination_address: String,
+    /// The amount, in the smallest unit of the rune.  `0` sends all remaining units.
+    pub(crate) amount: candid::Nat,
+    /// The rune to send.  `0:0` is the rune etched by the same transaction.
+    pub(crate) rune_id: BtcRuneId,
+}
+/// A new rune without a name, for which the protocol reserves a name.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcRuneEtching {
+    /// Units minted by the etching.  Units not sent with a transfer are returned to the
+    /// caller.  Default: 0.
+    pub(crate) premine: Option<candid::Nat>,
+    /// The number of decimal places of the rune, at most 38.  Default: 0.
+    pub(crate) divisibility: Option<u8>,
+    /// The currency symbol of the rune, a single character.  Default: `¤`.
+    pub(crate) symbol: Option<String>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcSendRunesRequest {
+    /// A rune to etch in the same transaction.
+    pub(crate) etching: Option<BtcRuneEtching>,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// An absolute fee, in satoshi.  Cannot be combined with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    /// The transfers, each to its own output.  Runes that the inputs hold beyond the transfers
+    /// are returned to the caller in a separate output, never in the change output of the fee
+    /// UTXOs.
+    pub(crate) transfers: Vec<BtcRuneTransfer>,
+    pub(crate) network: Network,
+    /// The fee rate; the fee is computed from the size of the built transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    /// UTXOs of the caller's address that pay the postage and the fee.
+    pub(crate) fee_utxos: Vec<Utxo>,
+    /// UTXOs of the caller's address that hold the runes to send.
+    pub(crate) rune_utxos: Vec<Utxo>,
+    /// The value of every output that receives runes, in satoshi.  Default and minimum: the
+    /// dust threshold, 1,000 satoshi.
+    pub(crate) postage_satoshis: Option<u64>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcSendRunesError {
+    /// The transfers or the etching cannot be encoded in a valid runestone.
+    InvalidRunestone {
+        msg: String,
+    },
+    BuildP2wpkhError(BuildP2wpkhTxError),
+    /// The request needs the Bitcoin canister, which does not serve `network`.
+    NoBitcoinCanister {
+        network: Network,
+    },
+    /// The transaction was signed, but `bitcoin_send_transaction` rejected it.  It can be
+    /// retried with `btc_rebroadcast`.
+    BroadcastFailed {
+        msg: String,
+        txid: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+    /// An input is spent by another transaction of the caller that is in flight or was sent
+    /// recently.
+    UtxoReserved {
+        txid: String,
+        vout: u32,
+        /// The transaction that spends the input, or `None` while it is being signed.
+        spending_txid: Option<String>,
+        /// When the reservation expires, in nanoseconds since the Unix epoch.
+        expires_at_ns: u64,
+    },
+}
+pub(crate) type Result20 = std::result::Result<SendBtcResponse, BtcSendRunesError>;
+/// Content to inscribe, revealed in the witness of an input.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcInscription {
+    /// The content.  Split into pushes of at most 520 bytes.
+    pub(crate) body: serde_bytes::ByteBuf,
+    /// The MIME type of the body, e.g. `text/plain;charset=utf-8`.
+    pub(crate) content_type: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcInscriptionCommitRequest {
+    /// The fee rate of the reveal transaction.  Default: `Priority(Medium)`.
+    pub(crate) reveal_fee_rate: Option<BtcFeeRate>,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// An absolute fee for the commit transaction, in satoshi.  Cannot be combined with
+    /// `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    pub(crate) network: Network,
+    /// UTXOs of the caller's P2WPKH address that fund the commit output and the fee.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate of the commit transaction; the fee is computed from the size of the built
+    /// transaction.
+    ///
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    pub(crate) inscription: BtcInscription,
+    /// The value of the output that will hold the inscription, in satoshi.  The commit output
+    /// holds this plus the fee of the reveal transaction.  Default: the dust threshold, 1,000
+    /// satoshi.
+    pub(crate) postage_satoshis: Option<u64>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcInscriptionCommitResponse {
+    /// The value of the commit output, in satoshi: the postage plus the reveal fee.
+    pub(crate) commit_satoshis: u64,
+    /// The signed commit transaction, which is not broadcast.
+    pub(crate) transaction: SignBtcResponse,
+    /// The P2TR address of the commit output, whose script tree has a single leaf with the
+    /// inscription envelope.
+    pub(crate) commit_address: String,
+    /// The index of the commit output in the commit transaction.
+    pub(crate) commit_vout: u32,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcInscriptionError {
+    BuildP2wpkhError(BuildP2wpkhTxError),
+    /// The content type or the body cannot be inscribed.
+    InvalidInscription {
+        msg: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    PaymentError(PaymentError),
+    /// An input of the commit transaction is spent by another transaction of the caller that
+    /// is in flight or was sent recently.
+    UtxoReserved {
+        txid: String,
+        vout: u32,
+        /// The transaction that spends the input, or `None` while it is being signed.
+        spending_txid: Option<String>,
+        /// When the reservation expires, in nanoseconds since the Unix epoch.
+        expires_at_ns: u64,
+    },
+}
+pub(crate) type Result21 = std::result::Result<BtcInscriptionCommitResponse, BtcInscriptionError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcInscriptionRevealRequest {
+    /// Receives the inscription.
+    pub(crate) destination_address: String,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    pub(crate) network: Network,
+    /// The inscription of the commit, unchanged.
+    pub(crate) inscription: BtcInscription,
+    /// The value of the output that receives the inscription, in satoshi.  The rest of the
+    /// commit output pays the fee.  Default: the dust threshold, 1,000 satoshi.
+    pub(crate) postage_satoshis: Option<u64>,
+    /// The commit output.
+    pub(crate) commit_utxo: Utxo,
+}
+pub(crate) type Result22 = std::result::Result<SignBtcResponse, BtcInscriptionError>;
+/// Whether a signed transaction has been broadcast.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcSignedTransactionStatus {
+    /// Accepted by `bitcoin_send_transaction`.
+    Sent,
+    /// Rejected by `bitcoin_send_transaction`; it can be retried with `btc_rebroadcast`.
+    BroadcastFailed { msg: String },
+    /// Signed by `btc_caller_sign`, which does not broadcast.
+    Signed,
+}
+/// A transaction signed for the caller, as listed by `btc_list_signed_transactions`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcSignedTransaction {
+    pub(crate) status: BtcSignedTransactionStatus,
+    /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+    pub(crate) fee_rate_millisat_per_vbyte: u64,
+    /// The fee paid by the transaction, in satoshi.
+    pub(crate) fee_satoshis: u64,
+    pub(crate) txid: String,
+    /// When the transaction was signed, in nanoseconds since the Unix epoch.
+    pub(crate) signed_at_ns: u64,
+    pub(crate) network: Network,
+    pub(crate) signed_transaction_hex: String,
+    /// When the transaction is removed from the journal, in nanoseconds since the Unix epoch.
+    pub(crate) expires_at_ns: u64,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcListSignedTransactionsResponse {
+    /// The caller's signed transactions, most recently signed first.
+    pub(crate) transactions: Vec<BtcSignedTransaction>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcRebroadcastRequest {
+    /// The txid of a transaction listed by `btc_list_signed_transactions`.
+    pub(crate) txid: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcRebroadcastError {
+    /// The request needs the Bitcoin canister, which does not serve `network`.
+    NoBitcoinCanister {
+        network: Network,
+    },
+    /// `bitcoin_send_transaction` rejected the transaction.
+    BroadcastFailed {
+        msg: String,
+    },
+    InternalError {
+        msg: String,
+    },
+    /// The caller has no signed transaction with this txid, or it has expired.
+    UnknownTransaction,
+    PaymentError(PaymentError),
+}
+pub(crate) type Result23 = std::result::Result<SendBtcResponse, BtcRebroadcastError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcJobResponse {
+    /// Identifies the job in `btc_job_status`.
+    pub(crate) job_id: u64,
+}
+pub(crate) type Result24 = std::result::Result<BtcJobResponse, SendBtcError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcJobStatusRequest {
+    pub(crate) job_id: u64,
+}
+/// The progress of a job started by `btc_caller_sign_job` or `btc_caller_send_job`.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcJobStatus {
+    /// The inputs are being signed, in batches.
+    Signing {
+        signed_inputs: u64,
+        total_inputs: u64,
+    },
+    Failed(SendBtcError),
+    /// The transaction is signed and, for `btc_caller_send_job`, sent.
+    Completed(SignBtcResponse),
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcJobStatusError {
+    /// The caller has no job with this id, or it has expired.
+    UnknownJob,
+}
+pub(crate) type Result25 = std::result::Result<BtcJobStatus, BtcJobStatusError>;
+/// Merges UTXOs of the caller's P2WPKH address into a single output.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcConsolidateRequest {
+    /// Receives the merged output.  Default: the caller's P2WPKH address.
+    pub(crate) destination_address: Option<String>,
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    pub(crate) network: Network,
+    /// Default: `Priority(Low)`.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    /// The most UTXOs to merge, if `utxos` is not set; the fee is charged for this many
+    /// inputs.  At most 100.  Default: 50.
+    pub(crate) max_inputs: Option<u32>,
+    /// The UTXOs to merge.  Default: the caller's unreserved UTXOs with `min_confirmations`,
+    /// smallest first, skipping those worth less than the fee of spending them, at most
+    /// `max_inputs` of them.
+    pub(crate) utxos: Option<Vec<Utxo>>,
+    /// The minimum number of confirmations of the merged UTXOs, if `utxos` is not set.
+    /// Default: the default of `bitcoin_get_utxos`, one confirmation.
+    pub(crate) min_confirmations: Option<u32>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct UtxoAddressRequest {
+    pub(crate) network: Network,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct UtxoAddressResponse {
+    /// A base58 P2PKH address for Dogecoin, and a bech32 P2WPKH address for Litecoin.
+    pub(crate) address: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum UtxoAddressError {
+    InternalError { msg: String },
+    PaymentError(PaymentError),
+}
+pub(crate) type Result26 = std::result::Result<UtxoAddressResponse, UtxoAddressError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct UtxoTxOutput {
+    pub(crate) destination_address: String,
+    pub(crate) value: u64,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct UtxoSignRequest {
+    /// An absolute fee.  Cannot be combined with `fee_rate_per_vbyte`.
+    pub(crate) fee: Option<u64>,
+    pub(crate) network: Network,
+    /// UTXOs of the caller's address, as returned by `doge_caller_address` or
+    /// `ltc_caller_address`.  All of them are spent.
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// The fee rate, per virtual byte of the signed transaction.
+    ///
+    /// Default: 1,000 koinu for Dogecoin and 10 litoshi for Litecoin, unless `fee` is set.
+    pub(crate) fee_rate_per_vbyte: Option<u64>,
+    pub(crate) outputs: Vec<UtxoTxOutput>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct UtxoSignResponse {
+    /// The fee paid by the transaction.
+    pub(crate) fee: u64,
+    /// The size of the signed transaction, in virtual bytes.
+    pub(crate) vsize: u64,
+    pub(crate) txid: String,
+    pub(crate) signed_transaction_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum UtxoSignError {
+    InternalError { msg: String },
+    PaymentError(PaymentError),
+    BuildError(BuildP2wpkhTxError),
+}
+pub(crate) type Result27 = std::result::Result<UtxoSignResponse, UtxoSignError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDecodeTransactionRequest {
+    /// A hex-encoded raw transaction, signed or not, or a hex-encoded
+    /// [BIP-174](https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki) PSBT.
+    pub(crate) transaction_hex: String,
+    pub(crate) network: Network,
+    /// The outputs that the transaction spends, to compute its fee.  PSBT inputs also take
+    /// their value from their `witness_utxo` or `non_witness_utxo`.
+    pub(crate) utxos: Option<Vec<Utxo>>,
+    /// The caller's address, as returned by `btc_caller_address`, to find the outputs that pay
+    /// back to the caller.  A query cannot derive the caller's key, so the address is taken as
+    /// given.
+    pub(crate) own_address: Option<String>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDecodedInput {
+    /// The value of the spent output, in satoshi, if it is known.
+    pub(crate) value: Option<u64>,
+    pub(crate) txid: String,
+    pub(crate) vout: u32,
+    /// Whether the input has a witness or a `scriptSig`.
+    pub(crate) is_signed: bool,
+    pub(crate) sequence: u32,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDecodedOutput {
+    pub(crate) value: u64,
+    pub(crate) script_pubkey_hex: String,
+    /// The address of the output on the requested network, or `None` for scripts without an
+    /// address, such as `OP_RETURN` outputs.
+    pub(crate) address: Option<String>,
+    /// Whether the output pays to `own_address`.  The address is supplied by the caller and is
+    /// not checked against the caller's key, so this is only as reliable as `own_address`.
+    pub(crate) pays_caller: bool,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDecodeTransactionResponse {
+    /// Whether `transaction_hex` is a PSBT.
+    pub(crate) is_psbt: bool,
+    /// The fee rate, in millisatoshi per virtual byte, if the fee is known.
+    pub(crate) fee_rate_millisat_per_vbyte: Option<u64>,
+    /// The fee paid by the transaction, in satoshi, if the values of all inputs are known.
+    pub(crate) fee_satoshis: Option<u64>,
+    /// The size of the transaction, in virtual bytes.  Unsigned inputs are counted as P2WPKH
+    /// inputs with the largest possible signature.
+    pub(crate) vsize: u64,
+    pub(crate) txid: String,
+    /// Whether an input signals replaceability, see BIP-125.
+    pub(crate) signals_rbf: bool,
+    pub(crate) inputs: Vec<BtcDecodedInput>,
+    pub(crate) outputs: Vec<BtcDecodedOutput>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcDecodeTransactionError {
+    /// `transaction_hex` is neither a transaction nor a PSBT.
+    InvalidTransaction { msg: String },
+    /// The outputs are worth more than the inputs, so `utxos` or the PSBT carry wrong values.
+    OutputsExceedInputs {
+        inputs_value: u64,
+        outputs_value: u64,
+    },
+    /// `own_address` is not an address of the requested network.
+    InvalidOwnAddress { address: String },
+}
+pub(crate) type Result28 =
+    std::result::Result<BtcDecodeTransactionResponse, BtcDecodeTransactionError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDescriptorResponse {
+    /// The output descriptor, with its BIP-380 checksum.
+    pub(crate) descriptor: String,
+    /// The address of the descriptor on the requested network.
+    pub(crate) address: String,
+}
+pub(crate) type Result29 = std::result::Result<BtcDescriptorResponse, GetAddressError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDepositToCkbtcRequest {
+    /// Skips the checks against `Config::btc_fee_limits`.  Default: `false`.
+    pub(crate) allow_high_fee: Option<bool>,
+    /// The owner of the ckBTC account to credit.  Default: the caller.
+    pub(crate) owner: Option<Principal>,
+    /// The fee, in satoshi.  Conflicts with `fee_rate`.
+    pub(crate) fee_satoshis: Option<u64>,
+    /// The 32-byte subaccount of the ckBTC account to credit.  Default: the default
+    /// subaccount.
+    pub(crate) subaccount: Option<serde_bytes::ByteBuf>,
+    /// The network of the ckBTC minter set in `Config::ckbtc_minter`.
+    pub(crate) network: Network,
+    pub(crate) utxos_to_spend: Vec<Utxo>,
+    /// Default: `Priority(Medium)`, unless `fee_satoshis` is set.
+    pub(crate) fee_rate: Option<BtcFeeRate>,
+    /// Calls the minter's `update_balance` for the account once the transaction is sent.
+    /// Default: `false`.
+    pub(crate) update_balance: Option<bool>,
+    /// The value sent to the deposit address, in satoshi.  Ignored with `send_all`.
+    pub(crate) deposit_satoshis: u64,
+    /// Deposits the value of all inputs, less the fee, without a change output.  Default:
+    /// `false`.
+    pub(crate) send_all: Option<bool>,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum CkbtcUpdateBalance {
+    /// The minter could not be called, or returned an error.
+    Failed { msg: String },
+    /// The minter processed new UTXOs of the deposit address.
+    Updated {
+        /// The ckBTC minted for them, in satoshi, after the minter's fees.
+        minted_satoshis: u64,
+        /// The value of those that were too small, were rejected by the Bitcoin check, or
+        /// are still to be minted, in satoshi.
+        unminted_satoshis: u64,
+    },
+    /// The minter has not seen a new UTXO with enough confirmations yet.  A deposit that was
+    /// just sent always ends up here; call the minter's `update_balance` again once the
+    /// transaction has `required_confirmations`.
+    NoNewUtxos {
+        required_confirmations: u32,
+        current_confirmations: Option<u32>,
+    },
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct BtcDepositToCkbtcResponse {
+    /// The effective fee rate of the signed transaction, in millisatoshi per virtual byte.
+    pub(crate) fee_rate_millisat_per_vbyte: u64,
+    /// The minter's deposit address of the ckBTC account.
+    pub(crate) deposit_address: String,
+    /// The fee paid by the transaction, in satoshi.
+    pub(crate) fee_satoshis: u64,
+    pub(crate) txid: String,
+    /// The answer of `update_balance`, if the request set `update_balance`.
+    pub(crate) update_balance: Option<CkbtcUpdateBalance>,
+}
+pub(crate) type Result30 = std::result::Result<BtcDepositToCkbtcResponse, SendBtcError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct SolAddressResponse {
+    /// The base58 encoding of the caller's Ed25519 public key.
+    pub(crate) address: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum SolAddressError {
+    /// An inter-canister call error from the threshold signature API.
+    SigningError(String),
+    /// Payment failed.
+    PaymentError(PaymentError),
+}
+pub(crate) type Result31 = std::result::Result<SolAddressResponse, SolAddressError>;
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct SolSignTransactionRequest {
+    /// The hex-encoded serialized message of a legacy or v0 transaction.
+    ///
+    /// The caller's address, as returned by `sol_address`, must be one of the required signers.
+    pub(crate) message_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) struct SolSignTransactionResponse {
+    /// The caller's base58-encoded signature.  If the caller is the fee payer, this is the
+    /// transaction ID.
+    pub(crate) signature: String,
+    /// The hex-encoded transaction in wire format, ready for `sendTransaction` once every
+    /// signature is present.
+    ///
+    /// The signatures of other required signers are left as zeros, for them to fill in.
+    pub(crate) signed_transaction_hex: String,
+}
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum SolSignTransactionError {
+    /// The message was not valid hex or not a legacy or v0 Solana message.
+    InvalidMessage { msg: String },
+    /// The caller's address is not one of the required signers of the message.  The fee is
+    /// charged all the same.
+    NotASigner { address: String },
+    /// An inter-canister call error from the threshold signature API.
+    SigningError(String),
+    /// Payment failed.
+    PaymentError(PaymentError),
+}
+pub(crate) type Result32 = std::result::Result<SolSignTransactionResponse, SolSignTransactionError>;
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct BtcSignPrehashRequest {
     /// Hex-encoded 32-byte digest to sign under the caller's Bitcoin key.
@@ -246,6 +1363,25 @@ pub(crate) struct BtcSignPrehashResponse {
     /// key.
     pub(crate) signature: String,
 }
+/// The parts of a transaction that the signature of an input commits to.
+///
+/// See [BIP-143](https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki) for the
+/// exact semantics.
+#[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub(crate) enum BtcSighashType {
+    /// All inputs and outputs.
+    All,
+    /// This input and all outputs.
+    AllPlusAnyoneCanPay,
+    /// All inputs and no outputs.  Rejected: anyone could redirect the funds.
+    None,
+    /// This input and no outputs.  Rejected: anyone could redirect the funds.
+    NonePlusAnyoneCanPay,
+    /// This input and the output with the same index.
+    SinglePlusAnyoneCanPay,
+    /// All inputs and the output with the same index as the input.
+    Single,
+}
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) enum BtcSignPrehashError {
     /// The supplied hash was not valid hex or was not a 32-byte digest.
@@ -258,9 +1394,19 @@ pub(crate) enum BtcSignPrehashError {
 pub(crate) type Result4 = std::result::Result<BtcSignPrehashResponse, BtcSignPrehashError>;
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub(crate) struct Config {
+    /// Limits on the fees of Bitcoin transactions.  Default: see `BtcFeeLimits::default()`.
+    pub(crate) btc_fee_limits: Option<BtcFeeLimits>,
     pub(crate) ecdsa_key_name: String,
     /// Root of trust for checking canister signatures.
     pub(crate) ic_root_key_raw: Option<serde_bytes::ByteBuf>,
+    /// The ckBTC minter that `btc_deposit_to_ckbtc` deposits to.  Default: the mainnet ckBTC
+    /// minter, `mqygn-kiaaa-aaaar-qaadq-cai`.
+    pub(crate) ckbtc_minter: Option<Principal>,
+    /// The number of threshold signatures requested at once when signing the inputs of a
+    /// transaction.  Default: 10.
+    pub(crate) max_concurrent_signatures: Option<u32>,
+    /// Network fee percentiles used for the Bitcoin fee priority levels.  Default: 25, 50, 75.
+    pub(crate) btc_fee_percentiles: Option<BtcFeePercentiles>,
     /// Payment canister ID.
     pub(crate) cycles_ledger: Principal,
 }
@@ -516,6 +1662,22 @@ impl PicCanisterTrait for SignerPic {
 }
 
 impl SignerPic {
+    pub fn btc_address(
+        &self,
+        caller: Principal,
+        arg0: &BtcAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result_, String> {
+        self.update(caller, "btc_address", (arg0, arg1))
+    }
+    pub fn btc_bump_fee(
+        &self,
+        caller: Principal,
+        arg0: &BtcBumpFeeRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result12, String> {
+        self.update(caller, "btc_bump_fee", (arg0, arg1))
+    }
     pub fn btc_caller_address(
         &self,
         caller: Principal,
@@ -540,6 +1702,14 @@ impl SignerPic {
     ) -> Result<Result2, String> {
         self.update(caller, "btc_caller_send", (arg0, arg1))
     }
+    pub fn btc_caller_send_job(
+        &self,
+        caller: Principal,
+        arg0: &SendBtcRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result24, String> {
+        self.update(caller, "btc_caller_send_job", (arg0, arg1))
+    }
     pub fn btc_caller_sign(
         &self,
         caller: Principal,
@@ -548,6 +1718,128 @@ impl SignerPic {
     ) -> Result<Result3, String> {
         self.update(caller, "btc_caller_sign", (arg0, arg1))
     }
+    pub fn btc_caller_sign_job(
+        &self,
+        caller: Principal,
+        arg0: &SendBtcRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result24, String> {
+        self.update(caller, "btc_caller_sign_job", (arg0, arg1))
+    }
+    pub fn btc_consolidate(
+        &self,
+        caller: Principal,
+        arg0: &BtcConsolidateRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result2, String> {
+        self.update(caller, "btc_consolidate", (arg0, arg1))
+    }
+    pub fn btc_decode_transaction(
+        &self,
+        caller: Principal,
+        arg0: &BtcDecodeTransactionRequest,
+    ) -> Result<Result28, String> {
+        self.update(caller, "btc_decode_transaction", (arg0,))
+    }
+    pub fn btc_deposit_to_ckbtc(
+        &self,
+        caller: Principal,
+        arg0: &BtcDepositToCkbtcRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result30, String> {
+        self.update(caller, "btc_deposit_to_ckbtc", (arg0, arg1))
+    }
+    pub fn btc_descriptor(
+        &self,
+        caller: Principal,
+        arg0: &GetAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result29, String> {
+        self.update(caller, "btc_descriptor", (arg0, arg1))
+    }
+    pub fn btc_htlc_address(
+        &self,
+        caller: Principal,
+        arg0: &BtcHtlcAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result16, String> {
+        self.update(caller, "btc_htlc_address", (arg0, arg1))
+    }
+    pub fn btc_htlc_claim(
+        &self,
+        caller: Principal,
+        arg0: &BtcHtlcClaimRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result17, String> {
+        self.update(caller, "btc_htlc_claim", (arg0, arg1))
+    }
+    pub fn btc_htlc_refund(
+        &self,
+        caller: Principal,
+        arg0: &BtcHtlcRefundRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result17, String> {
+        self.update(caller, "btc_htlc_refund", (arg0, arg1))
+    }
+    pub fn btc_inscription_commit(
+        &self,
+        caller: Principal,
+        arg0: &BtcInscriptionCommitRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result21, String> {
+        self.update(caller, "btc_inscription_commit", (arg0, arg1))
+    }
+    pub fn btc_inscription_reveal(
+        &self,
+        caller: Principal,
+        arg0: &BtcInscriptionRevealRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result22, String> {
+        self.update(caller, "btc_inscription_reveal", (arg0, arg1))
+    }
+    pub fn btc_job_status(
+        &self,
+        caller: Principal,
+        arg0: &BtcJobStatusRequest,
+    ) -> Result<Result25, String> {
+        self.update(caller, "btc_job_status", (arg0,))
+    }
+    pub fn btc_list_signed_transactions(
+        &self,
+        caller: Principal,
+    ) -> Result<BtcListSignedTransactionsResponse, String> {
+        self.update(caller, "btc_list_signed_transactions", ())
+    }
+    pub fn btc_multisig_address(
+        &self,
+        caller: Principal,
+        arg0: &BtcMultisigAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result13, String> {
+        self.update(caller, "btc_multisig_address", (arg0, arg1))
+    }
+    pub fn btc_pending_transactions(
+        &self,
+        caller: Principal,
+    ) -> Result<BtcPendingTransactionsResponse, String> {
+        self.update(caller, "btc_pending_transactions", ())
+    }
+    pub fn btc_rebroadcast(
+        &self,
+        caller: Principal,
+        arg0: &BtcRebroadcastRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result23, String> {
+        self.update(caller, "btc_rebroadcast", (arg0, arg1))
+    }
+    pub fn btc_send_runes(
+        &self,
+        caller: Principal,
+        arg0: &BtcSendRunesRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result20, String> {
+        self.update(caller, "btc_send_runes", (arg0, arg1))
+    }
     pub fn btc_sign_prehash(
         &self,
         caller: Principal,
@@ -556,9 +1848,57 @@ impl SignerPic {
     ) -> Result<Result4, String> {
         self.update(caller, "btc_sign_prehash", (arg0, arg1))
     }
+    pub fn btc_sign_psbt(
+        &self,
+        caller: Principal,
+        arg0: &BtcSignPsbtRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result14, String> {
+        self.update(caller, "btc_sign_psbt", (arg0, arg1))
+    }
+    pub fn btc_taproot_address(
+        &self,
+        caller: Principal,
+        arg0: &BtcTaprootAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result18, String> {
+        self.update(caller, "btc_taproot_address", (arg0, arg1))
+    }
+    pub fn btc_taproot_sign(
+        &self,
+        caller: Principal,
+        arg0: &BtcTaprootSignRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result19, String> {
+        self.update(caller, "btc_taproot_sign", (arg0, arg1))
+    }
+    pub fn btc_vault_sign(
+        &self,
+        caller: Principal,
+        arg0: &BtcVaultSignRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result15, String> {
+        self.update(caller, "btc_vault_sign", (arg0, arg1))
+    }
     pub fn config(&self, caller: Principal) -> Result<Config, String> {
         self.update(caller, "config", ())
     }
+    pub fn doge_caller_address(
+        &self,
+        caller: Principal,
+        arg0: &UtxoAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result26, String> {
+        self.update(caller, "doge_caller_address", (arg0, arg1))
+    }
+    pub fn doge_caller_sign(
+        &self,
+        caller: Principal,
+        arg0: &UtxoSignRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result27, String> {
+        self.update(caller, "doge_caller_sign", (arg0, arg1))
+    }
     pub fn eth_address(
         &self,
         caller: Principal,
@@ -624,6 +1964,22 @@ impl SignerPic {
     ) -> Result<HttpResponse, String> {
         self.update(caller, "http_request", (arg0,))
     }
+    pub fn ltc_caller_address(
+        &self,
+        caller: Principal,
+        arg0: &UtxoAddressRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result26, String> {
+        self.update(caller, "ltc_caller_address", (arg0, arg1))
+    }
+    pub fn ltc_caller_sign(
+        &self,
+        caller: Principal,
+        arg0: &UtxoSignRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result27, String> {
+        self.update(caller, "ltc_caller_sign", (arg0, arg1))
+    }
     pub fn schnorr_public_key(
         &self,
         caller: Principal,
@@ -640,4 +1996,19 @@ impl SignerPic {
     ) -> Result<Result11, String> {
         self.update(caller, "schnorr_sign", (arg0, arg1))
     }
+    pub fn sol_address(
+        &self,
+        caller: Principal,
+        arg0: &Option<PaymentType>,
+    ) -> Result<Result31, String> {
+        self.update(caller, "sol_address", (arg0,))
+    }
+    pub fn sol_sign_transaction(
+        &self,
+        caller: Principal,
+        arg0: &SolSignTransactionRequest,
+        arg1: &Option<PaymentType>,
+    ) -> Result<Result32, String> {
+        self.update(caller, "sol_sign_transaction", (arg0, arg1))
+    }
 }
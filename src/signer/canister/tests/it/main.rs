@@ -2,4 +2,6 @@ mod bitcoin;
 mod canister;
 mod eth;
 mod schnorr;
+mod sol;
 mod utils;
+mod utxo_chains;
@@ -0,0 +1,221 @@
+//! Tests the Solana API.
+use candid::Principal;
+use ed25519_dalek::Verifier;
+use ic_chain_fusion_signer_api::methods::SignerMethods;
+
+use crate::{
+    canister::{
+        cycles_ledger::{self, ApproveArgs},
+        signer::{
+            PaymentType, SolAddressError, SolSignTransactionError, SolSignTransactionRequest,
+            SolSignTransactionResponse,
+        },
+    },
+    utils::{
+        pic_canister::PicCanisterTrait,
+        test_environment::{TestSetup, LEDGER_FEE},
+    },
+};
+
+/// Approves the signer to charge `fee` to the caller.
+fn approve(test_env: &TestSetup, caller: Principal, fee: u128) {
+    let payment_recipient = cycles_ledger::Account {
+        owner: test_env.signer.canister_id(),
+        subaccount: None,
+    };
+    test_env
+        .ledger
+        .icrc2_approve(
+            caller,
+            &ApproveArgs::new(payment_recipient, (fee + LEDGER_FEE).into()),
+        )
+        .expect("Failed to call ledger canister")
+        .expect("Failed to approve payment");
+}
+
+/// A standard sol_address() call, including payment.
+fn paid_sol_address(test_env: &TestSetup, caller: Principal) -> Result<String, SolAddressError> {
+    approve(test_env, caller, SignerMethods::SolAddress.fee());
+    test_env
+        .signer
+        .sol_address(caller, &Some(PaymentType::CallerPaysIcrc2Cycles))
+        .expect("Failed to call sol_address")
+        .map(|response| response.address)
+}
+
+/// A standard sol_sign_transaction() call, including payment.
+fn paid_sol_sign_transaction(
+    test_env: &TestSetup,
+    message: &[u8],
+) -> Result<SolSignTransactionResponse, SolSignTransactionError> {
+    approve(
+        test_env,
+        test_env.user,
+        SignerMethods::SolSignTransaction.fee(),
+    );
+    test_env
+        .signer
+        .sol_sign_transaction(
+            test_env.user,
+            &SolSignTransactionRequest {
+                message_hex: hex::encode(message),
+            },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call sol_sign_transaction")
+}
+
+/// Decodes a Solana address to its 32-byte Ed25519 public key.
+fn address_to_pubkey(address: &str) -> [u8; 32] {
+    bitcoin::base58::decode(address)
+        .expect("The address is not base58")
+        .try_into()
+        .expect("The address is not a 32-byte public key")
+}
+
+/// A message that transfers lamports from the first of `signers`, which all sign, to a recipient.
+fn transfer_message(signers: &[[u8; 32]], versioned: bool) -> Vec<u8> {
+    let mut message = Vec::new();
+    if versioned {
+        message.push(0x80);
+    }
+    let num_signers = u8::try_from(signers.len()).unwrap();
+    // All signers are writable; the system program is readonly.
+    message.extend_from_slice(&[num_signers, 0, 1]);
+    message.push(num_signers + 2);
+    for signer in signers {
+        message.extend_from_slice(signer);
+    }
+    message.extend_from_slice(&[3; 32]);
+    message.extend_from_slice(&[0; 32]);
+    message.extend_from_slice(&[9; 32]);
+    // One system transfer of 1,000 lamports from account 0 to the recipient.
+    let program_index = num_signers + 1;
+    let recipient_index = num_signers;
+    message.extend_from_slice(&[1, program_index, 2, 0, recipient_index, 12]);
+    message.extend_from_slice(&[2, 0, 0, 0, 0xe8, 3, 0, 0, 0, 0, 0, 0]);
+    if versioned {
+        // No address lookup tables.
+        message.push(0);
+    }
+    message
+}
+
+/// Verifies `signature` of `message` by `pubkey`.
+fn verify(pubkey: &[u8; 32], message: &[u8], signature: &[u8]) {
+    let verifying_key =
+        ed25519_dalek::VerifyingKey::from_bytes(pubkey).expect("Invalid Ed25519 public key");
+    let signature =
+        ed25519_dalek::Signature::try_from(signature).expect("Invalid Ed25519 signature");
+    verifying_key
+        .verify(message, &signature)
+        .expect("The signature does not verify");
+}
+
+#[test]
+fn sol_address_is_the_base58_ed25519_key_of_the_caller() {
+    let test_env = TestSetup::default();
+    let address = paid_sol_address(&test_env, test_env.user).expect("Failed to get address");
+    address_to_pubkey(&address);
+    assert_eq!(
+        paid_sol_address(&test_env, test_env.user),
+        Ok(address.clone()),
+        "The address should be stable"
+    );
+    assert_ne!(
+        paid_sol_address(&test_env, test_env.user2),
+        Ok(address),
+        "Users should have different addresses"
+    );
+}
+
+#[test]
+fn anonymous_user_cannot_use_sol_methods() {
+    let test_env = TestSetup::default();
+    assert!(test_env
+        .signer
+        .sol_address(Principal::anonymous(), &None)
+        .is_err());
+    assert!(test_env
+        .signer
+        .sol_sign_transaction(
+            Principal::anonymous(),
+            &SolSignTransactionRequest {
+                message_hex: hex::encode(transfer_message(&[[1; 32]], false)),
+            },
+            &None,
+        )
+        .is_err());
+}
+
+#[test]
+fn sol_sign_transaction_signs_as_the_fee_payer() {
+    let test_env = TestSetup::default();
+    let pubkey = address_to_pubkey(&paid_sol_address(&test_env, test_env.user).unwrap());
+    for versioned in [false, true] {
+        let message = transfer_message(&[pubkey], versioned);
+        let response =
+            paid_sol_sign_transaction(&test_env, &message).expect("Failed to sign transaction");
+        let transaction = hex::decode(&response.signed_transaction_hex).unwrap();
+        assert_eq!(transaction[0], 1, "There should be one signature");
+        assert_eq!(transaction[65..], message[..]);
+        assert_eq!(
+            bitcoin::base58::encode(&transaction[1..65]),
+            response.signature
+        );
+        verify(&pubkey, &message, &transaction[1..65]);
+    }
+}
+
+#[test]
+fn sol_sign_transaction_leaves_other_signatures_empty() {
+    let test_env = TestSetup::default();
+    let pubkey = address_to_pubkey(&paid_sol_address(&test_env, test_env.user).unwrap());
+    let message = transfer_message(&[[1; 32], pubkey], true);
+    let response =
+        paid_sol_sign_transaction(&test_env, &message).expect("Failed to sign transaction");
+    let transaction = hex::decode(&response.signed_transaction_hex).unwrap();
+    assert_eq!(transaction[0], 2, "There should be two signatures");
+    assert_eq!(transaction[1..65], [0; 64]);
+    verify(&pubkey, &message, &transaction[65..129]);
+    assert_eq!(transaction[129..], message[..]);
+}
+
+#[test]
+fn sol_sign_transaction_requires_the_caller_to_sign() {
+    let test_env = TestSetup::default();
+    let address = paid_sol_address(&test_env, test_env.user).unwrap();
+    let pubkey = address_to_pubkey(&address);
+    // The caller's key is an account of the message, but not a signer.
+    let mut message = transfer_message(&[[1; 32]], false);
+    message[4 + 32..4 + 64].copy_from_slice(&pubkey);
+    assert_eq!(
+        paid_sol_sign_transaction(&test_env, &message),
+        Err(SolSignTransactionError::NotASigner { address })
+    );
+}
+
+#[test]
+fn sol_sign_transaction_rejects_invalid_messages_before_charging() {
+    let test_env = TestSetup::default();
+    let mut trailing = transfer_message(&[[1; 32]], false);
+    trailing.push(0);
+    for message_hex in ["not hex".to_string(), hex::encode(trailing)] {
+        // No payment is approved: the message is rejected before the caller is charged.
+        let response = test_env
+            .signer
+            .sol_sign_transaction(
+                test_env.user,
+                &SolSignTransactionRequest { message_hex },
+                &Some(PaymentType::CallerPaysIcrc2Cycles),
+            )
+            .expect("Failed to call sol_sign_transaction");
+        assert!(
+            matches!(
+                response,
+                Err(SolSignTransactionError::InvalidMessage { .. })
+            ),
+            "{response:?}"
+        );
+    }
+}
@@ -8,6 +8,7 @@ use super::mock::CALLER;
 use crate::{
     canister::{
         bitcoin::{self, BitcoinPic},
+        ckbtc_minter::CkbtcMinterPic,
         cycles_depositor::{self, CyclesDepositorPic},
         cycles_ledger::{
             Account, ApproveArgs, CyclesLedgerPic, InitArgs as LedgerInitArgs, LedgerArgs,
@@ -43,6 +44,8 @@ pub struct TestSetup {
     pub cycles_depositor: CyclesDepositorPic,
     /// Bitcoin canister
     pub bitcoin_canister: BitcoinPic,
+    /// Stand-in for the ckBTC minter, which `btc_deposit_to_ckbtc` deposits to.
+    pub ckbtc_minter: CkbtcMinterPic,
 }
 impl Default for TestSetup {
     fn default() -> Self {
@@ -74,6 +77,11 @@ impl Default for TestSetup {
                 )
                 .deploy_to(pic.clone()),
         );
+        let ckbtc_minter = CkbtcMinterPic::from(
+            PicCanisterBuilder::default()
+                .with_wasm(&cargo_wasm_path("test_ckbtc_minter"))
+                .deploy_to(pic.clone()),
+        );
         let signer = SignerPic::from(
             PicCanisterBuilder::default()
                 .with_wasm(&cargo_wasm_path("signer"))
@@ -82,6 +90,10 @@ impl Default for TestSetup {
                         ecdsa_key_name: format!("test_key_1"),
                         ic_root_key_der: None,
                         cycles_ledger: None,
+                        btc_fee_percentiles: None,
+                        btc_fee_limits: None,
+                        max_concurrent_signatures: None,
+                        ckbtc_minter: Some(ckbtc_minter.canister_id),
                     }))
                     .unwrap(),
                 )
@@ -157,6 +169,7 @@ impl Default for TestSetup {
             unauthorized_user,
             cycles_depositor,
             bitcoin_canister,
+            ckbtc_minter,
         };
         ans.fund_user(Self::USER_INITIAL_BALANCE);
         ans
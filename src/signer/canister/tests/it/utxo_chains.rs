@@ -0,0 +1,335 @@
+use bitcoin::{
+    hashes::Hash,
+    secp256k1::{Message, Secp256k1},
+    sighash::SighashCache,
+    CompressedPublicKey, EcdsaSighashType, ScriptBuf, Transaction,
+};
+use candid::Principal;
+use ic_chain_fusion_signer_api::methods::SignerMethods;
+
+use crate::{
+    canister::{
+        cycles_ledger::{self, ApproveArgs},
+        signer::{
+            BitcoinAddressType, BuildP2wpkhTxError, GetAddressRequest, Network, OutPoint,
+            PaymentType, Utxo, UtxoAddressError, UtxoAddressRequest, UtxoAddressResponse,
+            UtxoSignError, UtxoSignRequest, UtxoSignResponse, UtxoTxOutput,
+        },
+    },
+    utils::{
+        pic_canister::PicCanisterTrait,
+        test_environment::{TestSetup, LEDGER_FEE},
+    },
+};
+
+/// Approves the signer to charge `fee` to the caller.
+fn approve(test_env: &TestSetup, caller: Principal, fee: u128) {
+    let payment_recipient = cycles_ledger::Account {
+        owner: test_env.signer.canister_id(),
+        subaccount: None,
+    };
+    test_env
+        .ledger
+        .icrc2_approve(
+            caller,
+            &ApproveArgs::new(payment_recipient, (fee + LEDGER_FEE).into()),
+        )
+        .expect("Failed to call ledger canister")
+        .expect("Failed to approve payment");
+}
+
+/// A standard doge_caller_address() call, including payment.
+fn paid_doge_caller_address(
+    test_env: &TestSetup,
+    network: Network,
+) -> Result<UtxoAddressResponse, UtxoAddressError> {
+    approve(
+        test_env,
+        test_env.user,
+        SignerMethods::DogeCallerAddress.fee(),
+    );
+    test_env
+        .signer
+        .doge_caller_address(
+            test_env.user,
+            &UtxoAddressRequest { network },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call doge_caller_address")
+}
+
+/// A standard ltc_caller_address() call, including payment.
+fn paid_ltc_caller_address(
+    test_env: &TestSetup,
+    network: Network,
+) -> Result<UtxoAddressResponse, UtxoAddressError> {
+    approve(
+        test_env,
+        test_env.user,
+        SignerMethods::LtcCallerAddress.fee(),
+    );
+    test_env
+        .signer
+        .ltc_caller_address(
+            test_env.user,
+            &UtxoAddressRequest { network },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call ltc_caller_address")
+}
+
+/// A standard doge_caller_sign() call, including payment.
+fn paid_doge_caller_sign(
+    test_env: &TestSetup,
+    request: &UtxoSignRequest,
+) -> Result<UtxoSignResponse, UtxoSignError> {
+    let n_inputs = request.utxos_to_spend.len() as u64;
+    approve(
+        test_env,
+        test_env.user,
+        SignerMethods::DogeCallerSign.btc_fee_for_inputs(n_inputs),
+    );
+    test_env
+        .signer
+        .doge_caller_sign(
+            test_env.user,
+            request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call doge_caller_sign")
+}
+
+/// A standard ltc_caller_sign() call, including payment.
+fn paid_ltc_caller_sign(
+    test_env: &TestSetup,
+    request: &UtxoSignRequest,
+) -> Result<UtxoSignResponse, UtxoSignError> {
+    let n_inputs = request.utxos_to_spend.len() as u64;
+    approve(
+        test_env,
+        test_env.user,
+        SignerMethods::LtcCallerSign.btc_fee_for_inputs(n_inputs),
+    );
+    test_env
+        .signer
+        .ltc_caller_sign(
+            test_env.user,
+            request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call ltc_caller_sign")
+}
+
+/// A request that spends one UTXO of 10 coins to `destination_address`.
+fn make_test_sign_request(destination_address: String, value: u64) -> UtxoSignRequest {
+    UtxoSignRequest {
+        network: Network::Regtest,
+        utxos_to_spend: vec![Utxo {
+            height: 100,
+            value: 1_000_000_000,
+            outpoint: OutPoint {
+                txid: serde_bytes::ByteBuf::from(vec![0x36; 32]),
+                vout: 0,
+            },
+        }],
+        outputs: vec![UtxoTxOutput {
+            destination_address,
+            value,
+        }],
+        fee: None,
+        fee_rate_per_vbyte: None,
+    }
+}
+
+fn decode(response: &UtxoSignResponse) -> Transaction {
+    bitcoin::consensus::deserialize(&hex::decode(&response.signed_transaction_hex).unwrap())
+        .expect("Expected a valid transaction")
+}
+
+#[test]
+fn test_addresses_use_their_own_keys_and_prefixes() {
+    let test_env = TestSetup::default();
+
+    let doge_mainnet = paid_doge_caller_address(&test_env, Network::Mainnet)
+        .expect("Failed to get the Dogecoin address")
+        .address;
+    let doge_regtest = paid_doge_caller_address(&test_env, Network::Regtest)
+        .expect("Failed to get the Dogecoin address")
+        .address;
+    let ltc_mainnet = paid_ltc_caller_address(&test_env, Network::Mainnet)
+        .expect("Failed to get the Litecoin address")
+        .address;
+    let ltc_regtest = paid_ltc_caller_address(&test_env, Network::Regtest)
+        .expect("Failed to get the Litecoin address")
+        .address;
+
+    assert!(doge_mainnet.starts_with('D'), "{doge_mainnet}");
+    assert!(doge_regtest.starts_with(['m', 'n']), "{doge_regtest}");
+    assert!(ltc_mainnet.starts_with("ltc1q"), "{ltc_mainnet}");
+    assert!(ltc_regtest.starts_with("rltc1q"), "{ltc_regtest}");
+
+    // The Litecoin key is not the Bitcoin key: the witness programs differ.
+    approve(
+        &test_env,
+        test_env.user,
+        SignerMethods::BtcCallerAddress.fee(),
+    );
+    let btc_regtest = test_env
+        .signer
+        .btc_caller_address(
+            test_env.user,
+            &GetAddressRequest {
+                network: Network::Regtest,
+                address_type: BitcoinAddressType::P2WPKH,
+            },
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call btc_caller_address")
+        .expect("Failed to get the Bitcoin address")
+        .address;
+    assert_ne!(
+        btc_regtest.trim_start_matches("bcrt1q")[..32],
+        ltc_regtest.trim_start_matches("rltc1q")[..32]
+    );
+}
+
+#[test]
+fn test_doge_caller_sign_signs_p2pkh_inputs_with_legacy_sighash() {
+    let test_env = TestSetup::default();
+    let own_address = paid_doge_caller_address(&test_env, Network::Regtest)
+        .expect("Failed to get the Dogecoin address")
+        .address;
+
+    let response = paid_doge_caller_sign(
+        &test_env,
+        &make_test_sign_request(own_address.clone(), 100_000_000),
+    )
+    .expect("Failed to sign");
+    let transaction = decode(&response);
+
+    assert_eq!(response.txid, transaction.compute_txid().to_string());
+    assert_eq!(transaction.output.len(), 2);
+    assert_eq!(
+        transaction.output[0].script_pubkey,
+        transaction.output[1].script_pubkey
+    );
+    // The default fee rate of 1,000 koinu per byte.
+    assert_eq!(response.vsize, transaction.vsize() as u64);
+    assert!(response.fee >= response.vsize * 1_000 && response.fee <= (response.vsize + 2) * 1_000);
+
+    let input = &transaction.input[0];
+    assert!(input.witness.is_empty());
+    let pushes: Vec<Vec<u8>> = input
+        .script_sig
+        .instructions()
+        .map(|push| push.unwrap().push_bytes().unwrap().as_bytes().to_vec())
+        .collect();
+    let public_key = CompressedPublicKey::from_slice(&pushes[1]).unwrap();
+    let own_script_pubkey = ScriptBuf::new_p2pkh(&public_key.pubkey_hash());
+    assert_eq!(transaction.output[1].script_pubkey, own_script_pubkey);
+    let signature = bitcoin::ecdsa::Signature::from_slice(&pushes[0]).unwrap();
+    assert_eq!(signature.sighash_type, EcdsaSighashType::All);
+    let sighash = SighashCache::new(&transaction)
+        .legacy_signature_hash(0, &own_script_pubkey, EcdsaSighashType::All.to_u32())
+        .unwrap();
+    Secp256k1::verification_only()
+        .verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &signature.signature,
+            &public_key.0,
+        )
+        .expect("Expected a valid signature");
+}
+
+#[test]
+fn test_doge_caller_sign_rejects_dust_output() {
+    let test_env = TestSetup::default();
+    let own_address = paid_doge_caller_address(&test_env, Network::Regtest)
+        .expect("Failed to get the Dogecoin address")
+        .address;
+
+    let response = paid_doge_caller_sign(
+        &test_env,
+        &make_test_sign_request(own_address.clone(), 999_999),
+    );
+
+    assert_eq!(
+        response,
+        Err(UtxoSignError::BuildError(BuildP2wpkhTxError::DustOutput {
+            address: own_address,
+            sent_satoshis: 999_999,
+            dust_threshold: 1_000_000,
+        }))
+    );
+}
+
+#[test]
+fn test_ltc_caller_sign_signs_p2wpkh_inputs() {
+    let test_env = TestSetup::default();
+    let own_address = paid_ltc_caller_address(&test_env, Network::Regtest)
+        .expect("Failed to get the Litecoin address")
+        .address;
+
+    let request = UtxoSignRequest {
+        fee: Some(5_000),
+        ..make_test_sign_request(own_address, 100_000_000)
+    };
+    let response = paid_ltc_caller_sign(&test_env, &request).expect("Failed to sign");
+    let transaction = decode(&response);
+
+    assert_eq!(response.fee, 5_000);
+    assert_eq!(response.txid, transaction.compute_txid().to_string());
+    assert!(transaction.input[0].script_sig.is_empty());
+    assert_eq!(transaction.input[0].witness.len(), 2);
+    assert!(transaction.output[1].script_pubkey.is_p2wpkh());
+}
+
+#[test]
+fn test_ltc_caller_sign_rejects_address_of_another_network() {
+    let test_env = TestSetup::default();
+    let mainnet_address = paid_ltc_caller_address(&test_env, Network::Mainnet)
+        .expect("Failed to get the Litecoin address")
+        .address;
+
+    let response = paid_ltc_caller_sign(
+        &test_env,
+        &make_test_sign_request(mainnet_address, 100_000_000),
+    );
+
+    assert_eq!(
+        response,
+        Err(UtxoSignError::BuildError(
+            BuildP2wpkhTxError::WrongBitcoinNetwork
+        ))
+    );
+}
+
+#[test]
+fn test_conflicting_fee_arguments_are_rejected_without_charge() {
+    let test_env = TestSetup::default();
+
+    let request = UtxoSignRequest {
+        fee: Some(5_000),
+        fee_rate_per_vbyte: Some(10),
+        ..make_test_sign_request(
+            "rltc1qw508d6qejxtdg4y5r3zarvary0c5xw7k693xs3".to_string(),
+            1_000,
+        )
+    };
+    // No payment is approved: the request is rejected before charging.
+    let response = test_env
+        .signer
+        .ltc_caller_sign(
+            test_env.user,
+            &request,
+            &Some(PaymentType::CallerPaysIcrc2Cycles),
+        )
+        .expect("Failed to call ltc_caller_sign");
+
+    assert_eq!(
+        response,
+        Err(UtxoSignError::BuildError(
+            BuildP2wpkhTxError::ConflictingFeeArguments
+        ))
+    );
+}
@@ -0,0 +1,57 @@
+//! A stand-in for the ckBTC minter, for the signer's integration tests.
+//!
+//! Implements just the parts of the minter's API that `btc_deposit_to_ckbtc` calls: every account
+//! gets the same regtest deposit address, and `update_balance` never finds new UTXOs.
+use std::cell::RefCell;
+
+use candid::{CandidType, Deserialize, Principal};
+
+/// The deposit address of every account.
+const DEPOSIT_ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+struct Account {
+    owner: Option<Principal>,
+    subaccount: Option<Vec<u8>>,
+}
+
+/// Declared for the type of `update_balance`, which never reports a UTXO.
+#[derive(CandidType, Deserialize, Debug)]
+#[allow(dead_code)]
+enum UtxoStatus {
+    Checked { value: u64 },
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum UpdateBalanceError {
+    NoNewUtxos {
+        current_confirmations: Option<u32>,
+        required_confirmations: u32,
+    },
+}
+
+thread_local! {
+    /// The accounts of the `get_btc_address` calls, in order.
+    static ACCOUNTS: RefCell<Vec<Account>> = const { RefCell::new(Vec::new()) };
+}
+
+#[ic_cdk::update]
+fn get_btc_address(account: Account) -> String {
+    ACCOUNTS.with_borrow_mut(|accounts| accounts.push(account));
+    DEPOSIT_ADDRESS.to_string()
+}
+
+#[ic_cdk::update]
+#[allow(clippy::needless_pass_by_value)]
+fn update_balance(_account: Account) -> Result<Vec<UtxoStatus>, UpdateBalanceError> {
+    Err(UpdateBalanceError::NoNewUtxos {
+        current_confirmations: None,
+        required_confirmations: 6,
+    })
+}
+
+/// The accounts that `get_btc_address` was called for.
+#[ic_cdk::query]
+fn get_btc_address_calls() -> Vec<Account> {
+    ACCOUNTS.with_borrow(Clone::clone)
+}